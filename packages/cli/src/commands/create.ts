@@ -1,7 +1,7 @@
 import type { HeadersConfig } from '@wvb/config';
 import { Command, Option } from 'clipanion';
 import { isNotNil } from 'es-toolkit';
-import { isBoolean } from 'typanion';
+import { cascade, isBoolean, isInteger, isNumber } from 'typanion';
 import { defaultOutDir, defaultOutFile, resolveConfig } from '../config.js';
 import { create } from '../operations/create.js';
 import { BaseCommand } from './base.js';
@@ -39,6 +39,15 @@ If extension not set, will automatically add extension (\`.wvb\`)`,
 For example, \`--header '*.html' 'cache-control' 'max-age=3600'\` will set \`cache-control: max-age=3600\` for all files with extension \`.html\`.`,
     arity: 3,
   });
+  readonly dedup = Option.String('--dedup', {
+    validator: isBoolean(),
+    tolerateBoolean: true,
+    description: 'Store the data of identical files once. [Default: false]',
+  });
+  readonly threads = Option.String('--threads', {
+    validator: cascade(isNumber(), [isInteger()]),
+    description: 'Number of threads compressing files, or 0 to use every available core. [Default: 1]',
+  });
   readonly write = Option.String('--write', true, {
     tolerateBoolean: true,
     validator: isBoolean(),
@@ -78,6 +87,7 @@ Set this to \`false\` (or pass "--no-write") just for simulating operation.
       return 1;
     }
     const overwrite = this.overwrite ?? config.create?.overwrite ?? true;
+    const dedup = this.dedup ?? config.create?.dedup ?? false;
     await create({
       dir,
       outFile,
@@ -87,6 +97,8 @@ Set this to \`false\` (or pass "--no-write") just for simulating operation.
         config.create?.headers,
         this.headers != null ? this.intoHeaderConfig(this.headers) : undefined,
       ].filter(isNotNil),
+      dedup,
+      threads: this.threads ?? config.create?.threads,
       write: this.write,
       overwrite,
       cwd: config.root,
@@ -18,6 +18,8 @@ export interface CreateParams {
   outFile: string;
   ignores?: IgnoreConfig[];
   headers?: HeadersConfig[];
+  dedup?: boolean;
+  threads?: number;
   write?: boolean;
   overwrite?: boolean;
   cwd?: string;
@@ -32,6 +34,8 @@ export async function create(params: CreateParams): Promise<Bundle> {
     outDir = '.wvb',
     ignores,
     headers,
+    dedup = false,
+    threads,
     write = true,
     overwrite = true,
     cwd,
@@ -76,7 +80,11 @@ export async function create(params: CreateParams): Promise<Bundle> {
   const outFile = path.join(outDir, withWVBExtension(outFileInput));
   const outFilepath = toAbsolutePath(outFile, cwd);
 
-  const bundle = builder.build();
+  const bundle = builder.build({ dedup, threads });
+  const deduplicatedSize = bundle.descriptor().index().deduplicatedSize();
+  if (deduplicatedSize > 0n) {
+    logger?.info(`Deduplicated: ${c.bytes(formatByteLength(Number(deduplicatedSize)))}`);
+  }
   if (!write) {
     return bundle;
   }
@@ -13,6 +13,16 @@ export interface CreateConfig {
    * @default true
    */
   overwrite?: boolean;
+  /**
+   * Store the data of identical files once, shared by every path.
+   * @default false
+   */
+  dedup?: boolean;
+  /**
+   * Number of threads compressing files, or `0` to use every available core.
+   * @default 1
+   */
+  threads?: number;
   /**
    * Ignore patterns which exclude files from the bundle.
    */
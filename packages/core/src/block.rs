@@ -0,0 +1,319 @@
+use crate::codec::Codec;
+use crate::index::IndexEntry;
+use std::ops::Range;
+
+/// Block size of LZ4 entries in `V2` bundles when no block size is set, see
+/// [`BundleBuilderOptions::block_size`](crate::BundleBuilderOptions::block_size).
+pub(crate) const DEFAULT_BLOCK_SIZE: u64 = 1024 * 1024;
+
+/// Block table of an entry split into independently compressed blocks.
+///
+/// Large entries can be stored as a sequence of blocks, each holding `block_size` bytes of the
+/// original data (the last block may be shorter) compressed on its own. Reading a byte range of
+/// such an entry only reads and decompresses the blocks the range touches.
+///
+/// # Example
+///
+/// ```
+/// use wvb::EntryBlocks;
+///
+/// let blocks = EntryBlocks::new(1024, vec![300, 280, 12]);
+/// assert_eq!(blocks.block_size(), 1024);
+/// assert_eq!(blocks.len(), 3);
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct EntryBlocks {
+  block_size: u64,
+  lens: Vec<u64>,
+}
+
+impl EntryBlocks {
+  /// Creates a block table.
+  ///
+  /// # Arguments
+  ///
+  /// * `block_size` - Size of the original data held by each block
+  /// * `lens` - Length of every block after compression, in order
+  pub fn new(block_size: u64, lens: Vec<u64>) -> Self {
+    Self { block_size, lens }
+  }
+
+  /// Returns the size of the original data held by each block.
+  pub fn block_size(&self) -> u64 {
+    self.block_size
+  }
+
+  /// Returns the length of every block after compression.
+  pub fn lens(&self) -> &[u64] {
+    &self.lens
+  }
+
+  /// Returns the number of blocks.
+  pub fn len(&self) -> usize {
+    self.lens.len()
+  }
+
+  /// Returns `true` if there are no blocks.
+  pub fn is_empty(&self) -> bool {
+    self.lens.is_empty()
+  }
+
+  /// Returns the offset of the given block in the stored entry data.
+  fn offset_of(&self, block: usize) -> u64 {
+    sum_lens(&self.lens[..block])
+  }
+
+  /// Returns the size of the original data held by the given block.
+  pub(crate) fn content_length_of(&self, block: usize, content_length: u64) -> u64 {
+    let start = (block as u64).saturating_mul(self.block_size);
+    self.block_size.min(content_length.saturating_sub(start))
+  }
+}
+
+/// Sums block lengths, saturating on lengths read from a corrupt index.
+fn sum_lens(lens: &[u64]) -> u64 {
+  lens.iter().fold(0, |sum, len| sum.saturating_add(*len))
+}
+
+/// Checks that the blocks of an entry add up to its stored data.
+pub(crate) fn check_blocks(entry: &IndexEntry, blocks: &EntryBlocks) -> crate::Result<()> {
+  if sum_lens(&blocks.lens) != entry.len() {
+    return Err(crate::Error::InvalidEntryBlocks);
+  }
+  Ok(())
+}
+
+/// Compresses data as a sequence of blocks with the given codec.
+///
+/// Returns the compressed blocks concatenated, and the block table.
+pub(crate) fn compress_blocks(
+  codec: Codec,
+  data: &[u8],
+  block_size: u64,
+) -> crate::Result<(Vec<u8>, EntryBlocks)> {
+  let mut compressed = Vec::new();
+  let mut lens = Vec::new();
+  for block in data.chunks(block_size as usize) {
+    let bytes = codec.compress(block)?;
+    lens.push(bytes.len() as u64);
+    compressed.extend(bytes);
+  }
+  Ok((compressed, EntryBlocks::new(block_size, lens)))
+}
+
+/// Decompresses the stored data of an entry.
+pub(crate) fn decompress_entry(entry: &IndexEntry, buf: &[u8]) -> crate::Result<Vec<u8>> {
+  match entry.blocks() {
+    Some(blocks) if entry.codec() != Codec::None => {
+      decompress_blocks(entry, blocks, 0..blocks.len(), buf)
+    }
+    _ => entry.codec().decompress(buf, entry.content_length()),
+  }
+}
+
+fn decompress_blocks(
+  entry: &IndexEntry,
+  blocks: &EntryBlocks,
+  range: Range<usize>,
+  buf: &[u8],
+) -> crate::Result<Vec<u8>> {
+  check_blocks(entry, blocks)?;
+  let mut data = Vec::new();
+  let mut offset = 0usize;
+  for block in range {
+    let len = blocks.lens[block] as usize;
+    let block_buf = offset
+      .checked_add(len)
+      .and_then(|end| buf.get(offset..end))
+      .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::UnexpectedEof))?;
+    let content_length = blocks.content_length_of(block, entry.content_length());
+    data.extend(entry.codec().decompress(block_buf, content_length)?);
+    offset += len;
+  }
+  Ok(data)
+}
+
+/// Part of the stored data of an entry needed to read a byte range of its original data.
+pub(crate) struct EntrySpan {
+  /// Offset of the part in the stored entry data.
+  pub(crate) offset: u64,
+  /// Length of the part.
+  pub(crate) len: u64,
+  kind: SpanKind,
+  range: Range<u64>,
+}
+
+enum SpanKind {
+  /// Stored without compression, the part is the range itself.
+  Stored,
+  /// The whole entry is decompressed.
+  Whole,
+  /// Only the given blocks are decompressed.
+  Blocks(Range<usize>),
+}
+
+impl EntrySpan {
+  /// Resolves the part of the stored data of an entry which holds the given range.
+  ///
+  /// The range is clamped to the original data, and for stored entries to the stored data too.
+  pub(crate) fn new(entry: &IndexEntry, range: Range<u64>) -> Self {
+    if entry.codec() == Codec::None {
+      let end = range.end.min(entry.content_length()).min(entry.len());
+      let start = range.start.min(end);
+      let range = start..end;
+      return Self {
+        offset: start,
+        len: end - start,
+        kind: SpanKind::Stored,
+        range,
+      };
+    }
+    let end = range.end.min(entry.content_length());
+    let start = range.start.min(end);
+    let range = start..end;
+    match entry.blocks() {
+      Some(blocks)
+        if !range.is_empty()
+          && blocks.block_size > 0
+          && start / blocks.block_size < blocks.len() as u64 =>
+      {
+        let first = (start / blocks.block_size) as usize;
+        let last = ((end - 1) / blocks.block_size) as usize;
+        let last = last.min(blocks.len() - 1);
+        let offset = blocks.offset_of(first);
+        let len = sum_lens(&blocks.lens[first..=last]);
+        Self {
+          offset,
+          len,
+          kind: SpanKind::Blocks(first..last + 1),
+          range,
+        }
+      }
+      _ => Self {
+        offset: 0,
+        len: entry.len(),
+        kind: SpanKind::Whole,
+        range,
+      },
+    }
+  }
+
+  /// Decodes the range from the part of the stored data read at this span.
+  pub(crate) fn decode(self, entry: &IndexEntry, buf: &[u8]) -> crate::Result<Vec<u8>> {
+    let range_len = (self.range.end - self.range.start) as usize;
+    let (data, skip) = match self.kind {
+      SpanKind::Stored => return Ok(buf.to_vec()),
+      SpanKind::Whole => (decompress_entry(entry, buf)?, self.range.start as usize),
+      SpanKind::Blocks(blocks_range) => {
+        let blocks = entry.blocks().unwrap();
+        let skip = self.range.start - blocks_range.start as u64 * blocks.block_size;
+        let data = decompress_blocks(entry, blocks, blocks_range, buf)?;
+        (data, skip as usize)
+      }
+    };
+    let end = (skip + range_len).min(data.len());
+    Ok(data[skip.min(end)..end].to_vec())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::CODECS;
+
+  fn data() -> Vec<u8> {
+    (0..10_000u32).map(|x| (x % 251) as u8).collect()
+  }
+
+  fn blocked_entry(codec: Codec, block_size: u64) -> (IndexEntry, Vec<u8>) {
+    let data = data();
+    let (compressed, blocks) = compress_blocks(codec, &data, block_size).unwrap();
+    let mut entry = IndexEntry::new(0, compressed.len() as u64, "video/mp4", data.len() as u64);
+    entry.codec = codec;
+    entry.blocks = Some(blocks);
+    (entry, compressed)
+  }
+
+  #[test]
+  fn decompress() {
+    for &codec in CODECS.iter().filter(|&&x| x != Codec::None) {
+      let (entry, compressed) = blocked_entry(codec, 1024);
+      assert_eq!(entry.blocks().unwrap().len(), 10);
+      assert_eq!(decompress_entry(&entry, &compressed).unwrap(), data());
+    }
+  }
+
+  #[cfg(feature = "codec-zstd")]
+  #[test]
+  fn span() {
+    let data = data();
+    let (entry, compressed) = blocked_entry(Codec::Zstd, 1024);
+    for range in [
+      0..1,
+      0..1024,
+      1000..1100,
+      2048..4096,
+      9990..10_000,
+      5000..20_000,
+    ] {
+      let span = EntrySpan::new(&entry, range.clone());
+      let buf = &compressed[span.offset as usize..(span.offset + span.len) as usize];
+      let end = range.end.min(data.len() as u64) as usize;
+      assert_eq!(
+        span.decode(&entry, buf).unwrap(),
+        &data[range.start as usize..end],
+        "range: {range:?}"
+      );
+    }
+    // only the touched blocks are read
+    let span = EntrySpan::new(&entry, 1000..1100);
+    let blocks = entry.blocks().unwrap();
+    assert_eq!(span.offset, 0);
+    assert_eq!(span.len, blocks.lens()[0] + blocks.lens()[1]);
+  }
+
+  #[test]
+  fn span_without_blocks() {
+    let data = data();
+    let compressed = Codec::Lz4.compress(&data).unwrap();
+    let entry = IndexEntry::new(0, compressed.len() as u64, "video/mp4", data.len() as u64);
+    let span = EntrySpan::new(&entry, 10..20);
+    assert_eq!((span.offset, span.len), (0, compressed.len() as u64));
+    assert_eq!(span.decode(&entry, &compressed).unwrap(), &data[10..20]);
+
+    let mut stored = IndexEntry::new(0, data.len() as u64, "video/mp4", data.len() as u64);
+    stored.codec = Codec::None;
+    let span = EntrySpan::new(&stored, 10..20);
+    assert_eq!((span.offset, span.len), (10, 10));
+    assert_eq!(span.decode(&stored, &data[10..20]).unwrap(), &data[10..20]);
+  }
+
+  #[test]
+  fn span_of_short_stored_entry() {
+    // a stored entry shorter than its content length, read from a corrupt index
+    let mut stored = IndexEntry::new(0, 100, "video/mp4", 10_000);
+    stored.codec = Codec::None;
+    let span = EntrySpan::new(&stored, 50..500);
+    assert_eq!((span.offset, span.len), (50, 50));
+    let span = EntrySpan::new(&stored, 200..500);
+    assert_eq!((span.offset, span.len), (100, 0));
+  }
+
+  #[test]
+  fn invalid_blocks() {
+    let (mut entry, compressed) = blocked_entry(Codec::Lz4, 1024);
+    let mut lens = entry.blocks().unwrap().lens().to_vec();
+    lens[0] -= 1;
+    entry.blocks = Some(EntryBlocks::new(1024, lens));
+    assert!(matches!(
+      decompress_entry(&entry, &compressed),
+      Err(crate::Error::InvalidEntryBlocks)
+    ));
+    let span = EntrySpan::new(&entry, 2048..4096);
+    let buf = &compressed[span.offset as usize..(span.offset + span.len) as usize];
+    assert!(matches!(
+      span.decode(&entry, buf),
+      Err(crate::Error::InvalidEntryBlocks)
+    ));
+  }
+}
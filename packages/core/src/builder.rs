@@ -1,37 +1,55 @@
+use crate::block::{compress_blocks, EntryBlocks, DEFAULT_BLOCK_SIZE};
 use crate::checksum::{make_checksum, CHECKSUM_LEN};
+use crate::codec::Codec;
+use crate::digest::{DigestAlgorithm, EntryDigest};
+use crate::encoding::ContentEncoding;
 use crate::header::HeaderWriterOptions;
-use crate::index::{Index, IndexEntry, IndexWriterOptions};
+#[cfg(feature = "header-rules")]
+use crate::header_rules::HeaderRules;
+use crate::index::{EntryVariant, Index, IndexEntry, IndexWriterOptions};
+use crate::metadata::BundleMetadata;
+use crate::mime::{path_extension, MimeType};
 use crate::version::Version;
 use crate::{Bundle, BundleDescriptor, Header, IndexWriter, Writer};
 use http::HeaderMap;
-use lz4_flex::compress_prepend_size;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct BundleEntry {
-  compressed: Vec<u8>,
+  data: Vec<u8>,
   content_type: String,
-  content_length: u64,
   pub headers: Option<HeaderMap>,
 }
 
 impl BundleEntry {
   pub fn new(data: &[u8], content_type: impl Into<String>, headers: Option<HeaderMap>) -> Self {
-    let compressed = compress_prepend_size(data);
     Self {
-      compressed,
+      data: data.to_vec(),
       content_type: content_type.into(),
-      content_length: data.len() as u64,
       headers,
     }
   }
 
+  /// Returns the original data before compression.
+  ///
+  /// Entries are compressed when the bundle is built, with the codec chosen by
+  /// `BundleBuilderOptions`.
+  pub fn content(&self) -> &[u8] {
+    &self.data
+  }
+
+  /// Returns the original data before compression.
+  ///
+  /// Before codecs were configurable, this returned the LZ4 compressed data. Entries are now
+  /// compressed when the bundle is built, so there is no compressed data to return.
+  #[deprecated(note = "returns the uncompressed data now, use `content` instead")]
   pub fn data(&self) -> &[u8] {
-    &self.compressed
+    &self.data
   }
 
   pub fn is_empty(&self) -> bool {
-    self.compressed.is_empty()
+    self.data.is_empty()
   }
 
   pub fn content_type(&self) -> &str {
@@ -39,19 +57,92 @@ impl BundleEntry {
   }
 
   pub fn content_length(&self) -> u64 {
-    self.content_length
+    self.data.len() as u64
   }
 
+  /// Returns the size of the original data before compression.
+  ///
+  /// Before codecs were configurable, this returned the size of the LZ4 compressed data.
+  #[deprecated(note = "returns the uncompressed size now, use `content_length` instead")]
   pub fn len(&self) -> usize {
-    self.compressed.len()
+    self.data.len()
   }
 }
 
-#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct BundleBuilderOptions {
   pub(crate) header: HeaderWriterOptions,
   pub(crate) index: IndexWriterOptions,
   pub(crate) data_checksum_seed: u32,
+  pub(crate) codec: Codec,
+  pub(crate) content_type_codecs: HashMap<String, Codec>,
+  pub(crate) extension_codecs: HashMap<String, Codec>,
+  pub(crate) store_incompressible: bool,
+  pub(crate) digest: Option<DigestAlgorithm>,
+  pub(crate) precompress: Vec<ContentEncoding>,
+  pub(crate) precompress_content_types: HashSet<String>,
+  pub(crate) block_size: Option<u64>,
+  pub(crate) mime_types: HashMap<String, String>,
+  #[cfg(feature = "header-rules")]
+  pub(crate) header_rules: HeaderRules,
+  pub(crate) dedup: bool,
+  pub(crate) threads: Option<usize>,
+}
+
+/// Content types of formats which are already compressed.
+const COMPRESSED_CONTENT_TYPES: &[&str] = &[
+  "image/png",
+  "image/jpeg",
+  "image/gif",
+  "image/webp",
+  "image/avif",
+  "image/heic",
+  "font/woff",
+  "font/woff2",
+  "video/*",
+  "audio/mpeg",
+  "audio/ogg",
+  "audio/aac",
+  "audio/opus",
+  "audio/webm",
+  "audio/flac",
+  "application/zip",
+  "application/gzip",
+  "application/x-7z-compressed",
+  "application/x-brotli",
+  "application/zstd",
+];
+
+/// File extensions of formats which are already compressed.
+const COMPRESSED_EXTENSIONS: &[&str] = &[
+  "png", "jpg", "jpeg", "gif", "webp", "avif", "heic", "woff", "woff2", "mp4", "m4v", "webm",
+  "mov", "mp3", "m4a", "aac", "ogg", "oga", "opus", "flac", "zip", "gz", "br", "zst", "7z",
+];
+
+/// Content types of text formats which get precompressed variants.
+const PRECOMPRESSED_CONTENT_TYPES: &[&str] = &[
+  "text/*",
+  "application/javascript",
+  "application/json",
+  "application/manifest+json",
+  "application/xml",
+  "application/wasm",
+  "image/svg+xml",
+];
+
+/// Returns the content type without parameters, in lowercase.
+fn content_type_essence(content_type: &str) -> String {
+  content_type
+    .split(';')
+    .next()
+    .unwrap_or_default()
+    .trim()
+    .to_ascii_lowercase()
+}
+
+/// Returns the wildcard of the type of a content type essence (e.g. `image/*`).
+fn content_type_wildcard(essence: &str) -> Option<String> {
+  essence.split_once('/').map(|(ty, _)| format!("{ty}/*"))
 }
 
 impl BundleBuilderOptions {
@@ -73,6 +164,306 @@ impl BundleBuilderOptions {
     self.data_checksum_seed = seed;
     self
   }
+
+  /// Sets the codec for entries which no content type rule matches.
+  ///
+  /// Defaults to [`Codec::Lz4`]. Codecs other than LZ4 require a `V2` bundle.
+  pub fn codec(&mut self, codec: Codec) -> &mut Self {
+    self.codec = codec;
+    self
+  }
+
+  /// Sets the codec for entries of the given content type.
+  ///
+  /// The content type is matched without parameters (e.g. `charset`). A wildcard subtype such
+  /// as `image/*` matches every content type of that type, and an exact match takes precedence
+  /// over a wildcard.
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// use wvb::{BundleBuilderOptions, Codec};
+  ///
+  /// let mut options = BundleBuilderOptions::new();
+  /// options
+  ///   .codec(Codec::Zstd)
+  ///   .content_type_codec("text/html", Codec::Brotli)
+  ///   .content_type_codec("image/*", Codec::None);
+  /// ```
+  pub fn content_type_codec(&mut self, content_type: impl Into<String>, codec: Codec) -> &mut Self {
+    let content_type = content_type.into().to_ascii_lowercase();
+    self.content_type_codecs.insert(content_type, codec);
+    self
+  }
+
+  /// Sets the codec for entries whose path ends with the given file extension.
+  ///
+  /// Extension rules take precedence over content type rules. The extension is matched
+  /// case-insensitively, with or without a leading dot.
+  pub fn extension_codec(&mut self, extension: impl Into<String>, codec: Codec) -> &mut Self {
+    let extension = extension.into();
+    let extension = extension.trim_start_matches('.').to_ascii_lowercase();
+    self.extension_codecs.insert(extension, codec);
+    self
+  }
+
+  /// Stores entries of already compressed formats (images, fonts, media and archives) as-is.
+  ///
+  /// Compressing such entries again wastes CPU and often makes them bigger. This adds
+  /// [`Codec::None`] rules for well-known content types and file extensions, and requires a `V2`
+  /// bundle.
+  pub fn store_compressed_formats(&mut self) -> &mut Self {
+    for content_type in COMPRESSED_CONTENT_TYPES {
+      self.content_type_codec(*content_type, Codec::None);
+    }
+    for extension in COMPRESSED_EXTENSIONS {
+      self.extension_codec(*extension, Codec::None);
+    }
+    self
+  }
+
+  /// Stores an entry as-is when compressing it does not make it smaller.
+  ///
+  /// Only applies to `V2` bundles, since `V1` bundles always use LZ4.
+  pub fn store_incompressible(&mut self, store: bool) -> &mut Self {
+    self.store_incompressible = store;
+    self
+  }
+
+  /// Stores a digest of every entry in the index, computed with the given algorithm.
+  ///
+  /// Digests are computed over the original data and require a `V2` bundle and the `digest`
+  /// feature.
+  pub fn digest(&mut self, algorithm: DigestAlgorithm) -> &mut Self {
+    self.digest = Some(algorithm);
+    self
+  }
+
+  /// Splits entries larger than `block_size` bytes into blocks compressed independently.
+  ///
+  /// Reading a byte range of such an entry (e.g. for HTTP range requests on media) only
+  /// decompresses the blocks the range touches. A size of zero disables blocks. Requires a
+  /// `V2` bundle.
+  ///
+  /// By default, `V2` entries compressed with LZ4 are split into blocks of 1 MiB, since LZ4
+  /// data can't be decompressed incrementally and streamed reads would otherwise buffer the
+  /// whole entry. Entries with other codecs are not split.
+  pub fn block_size(&mut self, block_size: u64) -> &mut Self {
+    self.block_size = Some(block_size);
+    self
+  }
+
+  /// Stores the data of identical entries once, shared by every path.
+  ///
+  /// Entries are identical when their data and the codec and precompressed variants resolved for
+  /// them are the same. The index entries of such paths point at the same data range, which
+  /// [`Index::deduplicated_size`] reports.
+  pub fn dedup(&mut self, dedup: bool) -> &mut Self {
+    self.dedup = dedup;
+    self
+  }
+
+  /// Compresses entries on the given number of threads when building a bundle.
+  ///
+  /// Zero uses the available parallelism of the machine. Entries are compressed on the calling
+  /// thread by default. The output is the same as a serial build, since entries are always laid
+  /// out in the order of their paths.
+  pub fn threads(&mut self, threads: usize) -> &mut Self {
+    self.threads = Some(threads);
+    self
+  }
+
+  /// Stores a precompressed variant of every text entry in the given content encoding.
+  ///
+  /// Text entries are matched by content type: `text/*`, JavaScript, JSON, XML, SVG and
+  /// WebAssembly, plus those added with [`precompress_content_type`](Self::precompress_content_type).
+  /// A variant is only stored when it is smaller than the original data. Requires a `V2` bundle
+  /// and the `precompress` feature.
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// use wvb::{BundleBuilderOptions, ContentEncoding};
+  ///
+  /// let mut options = BundleBuilderOptions::new();
+  /// options
+  ///   .precompress(ContentEncoding::Brotli)
+  ///   .precompress(ContentEncoding::Gzip);
+  /// ```
+  pub fn precompress(&mut self, encoding: ContentEncoding) -> &mut Self {
+    if !self.precompress.contains(&encoding) {
+      self.precompress.push(encoding);
+      self.precompress.sort_unstable();
+    }
+    self
+  }
+
+  /// Adds a content type whose entries get precompressed variants.
+  ///
+  /// Matched like [`content_type_codec`](Self::content_type_codec), including wildcard subtypes.
+  pub fn precompress_content_type(&mut self, content_type: impl Into<String>) -> &mut Self {
+    let content_type = content_type.into().to_ascii_lowercase();
+    self.precompress_content_types.insert(content_type);
+    self
+  }
+
+  /// Sets the content type detected for files with the given extension, overriding the
+  /// built-in table of [`MimeType`].
+  ///
+  /// The extension is matched case-insensitively, with or without the leading `.`.
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// use wvb::BundleBuilderOptions;
+  ///
+  /// let mut options = BundleBuilderOptions::new();
+  /// options
+  ///   .mime_type("js", "application/javascript")
+  ///   .mime_type(".glsl", "text/plain");
+  /// assert_eq!(options.resolve_content_type("/shader.glsl", b""), "text/plain");
+  /// ```
+  pub fn mime_type(
+    &mut self,
+    extension: impl Into<String>,
+    content_type: impl Into<String>,
+  ) -> &mut Self {
+    let extension = extension.into();
+    let extension = extension.trim_start_matches('.').to_ascii_lowercase();
+    self.mime_types.insert(extension, content_type.into());
+    self
+  }
+
+  /// Sets the header rules evaluated for each entry.
+  ///
+  /// Headers of matching rules are stored with the entry in the index, and headers set on the
+  /// [`BundleEntry`] itself take precedence.
+  #[cfg(feature = "header-rules")]
+  pub fn header_rules(&mut self, rules: HeaderRules) -> &mut Self {
+    self.header_rules = rules;
+    self
+  }
+
+  /// Resolves the content type of a file from its path and data.
+  ///
+  /// Overrides set with [`mime_type`](Self::mime_type) take precedence over
+  /// [`MimeType::detect`].
+  pub fn resolve_content_type(&self, path: &str, data: &[u8]) -> String {
+    if let Some(extension) = path_extension(path) {
+      if let Some(content_type) = self.mime_types.get(&extension.to_ascii_lowercase()) {
+        return content_type.clone();
+      }
+    }
+    MimeType::detect(data, path)
+  }
+
+  /// Resolves the encodings of precompressed variants for an entry of the given content type.
+  pub fn resolve_precompress(&self, content_type: &str) -> &[ContentEncoding] {
+    if self.precompress.is_empty() {
+      return &[];
+    }
+    let essence = content_type_essence(content_type);
+    let wildcard = content_type_wildcard(&essence);
+    let matches = |x: &str| x == essence || wildcard.as_deref() == Some(x);
+    if PRECOMPRESSED_CONTENT_TYPES.iter().any(|x| matches(x))
+      || self.precompress_content_types.iter().any(|x| matches(x))
+    {
+      &self.precompress
+    } else {
+      &[]
+    }
+  }
+
+  /// Resolves the codec for an entry of the given path and content type.
+  pub fn resolve_codec(&self, path: &str, content_type: &str) -> Codec {
+    if let Some(extension) = path_extension(path) {
+      if let Some(codec) = self.extension_codecs.get(&extension.to_ascii_lowercase()) {
+        return *codec;
+      }
+    }
+    let essence = content_type_essence(content_type);
+    if let Some(codec) = self.content_type_codecs.get(&essence) {
+      return *codec;
+    }
+    if let Some(wildcard) = content_type_wildcard(&essence) {
+      if let Some(codec) = self.content_type_codecs.get(&wildcard) {
+        return *codec;
+      }
+    }
+    self.codec
+  }
+
+  /// Resolves the block size for entries compressed with the given codec in a `V2` bundle.
+  pub(crate) fn resolve_block_size(&self, codec: Codec) -> Option<u64> {
+    match self.block_size {
+      Some(0) => None,
+      Some(size) => Some(size),
+      None => (codec == Codec::Lz4).then_some(DEFAULT_BLOCK_SIZE),
+    }
+  }
+}
+
+/// Sets every header of `from` in `to`, replacing the values of existing header names.
+pub(crate) fn merge_headers(to: &mut HeaderMap, from: &HeaderMap) {
+  for name in from.keys() {
+    to.remove(name);
+    for value in from.get_all(name) {
+      to.append(name.clone(), value.clone());
+    }
+  }
+}
+
+/// Maps items on up to `threads` scoped threads, keeping their order.
+///
+/// Items are handed out one at a time, so a few large entries don't hold up the others. Returns
+/// the first error in the order of items.
+fn parallel_map<T, R, F>(items: &[T], threads: usize, f: F) -> crate::Result<Vec<R>>
+where
+  T: Sync,
+  R: Send,
+  F: Fn(&T) -> crate::Result<R> + Sync,
+{
+  let threads = threads.min(items.len());
+  if threads <= 1 {
+    return items.iter().map(f).collect();
+  }
+  let next = AtomicUsize::new(0);
+  let mut results = std::thread::scope(|scope| {
+    let workers = (0..threads)
+      .map(|_| {
+        scope.spawn(|| {
+          let mut results = vec![];
+          loop {
+            let i = next.fetch_add(1, Ordering::Relaxed);
+            let Some(item) = items.get(i) else {
+              return results;
+            };
+            results.push((i, f(item)));
+          }
+        })
+      })
+      .collect::<Vec<_>>();
+    workers
+      .into_iter()
+      .flat_map(|x| x.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+      .collect::<Vec<_>>()
+  });
+  results.sort_unstable_by_key(|(i, _)| *i);
+  results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// An entry compressed with the codec resolved at build time.
+pub(crate) struct CompressedEntry<'a> {
+  pub(crate) path: &'a str,
+  pub(crate) entry: &'a BundleEntry,
+  pub(crate) codec: Codec,
+  pub(crate) data: Vec<u8>,
+  pub(crate) digest: Option<EntryDigest>,
+  /// Precompressed variants with the digest of their encoded data.
+  pub(crate) variants: Vec<(ContentEncoding, Vec<u8>, Option<EntryDigest>)>,
+  pub(crate) blocks: Option<EntryBlocks>,
+  /// Position of an identical entry whose data is shared instead of stored again.
+  pub(crate) shared: Option<usize>,
 }
 
 #[derive(Debug, Default)]
@@ -80,6 +471,7 @@ pub struct BundleBuilder {
   entries: HashMap<String, BundleEntry>,
   version: Version,
   options: BundleBuilderOptions,
+  metadata: Option<BundleMetadata>,
 }
 
 impl BundleBuilder {
@@ -119,6 +511,16 @@ impl BundleBuilder {
     self
   }
 
+  pub fn metadata(&self) -> Option<&BundleMetadata> {
+    self.metadata.as_ref()
+  }
+
+  /// Sets the bundle-level metadata stored in the index. Requires a `V2` bundle.
+  pub fn set_metadata(&mut self, metadata: BundleMetadata) -> &mut Self {
+    self.metadata = Some(metadata);
+    self
+  }
+
   pub fn entries(&self) -> &HashMap<String, BundleEntry> {
     &self.entries
   }
@@ -150,48 +552,758 @@ impl BundleBuilder {
   }
 
   pub fn build(&self) -> crate::Result<Bundle> {
-    let index = self.build_index();
-    let header = self.build_header(&index)?;
-    let manifest = BundleDescriptor { header, index };
-    let data = self.build_data();
+    let entries = self.compress_entries()?;
+    let index = self.build_index(&entries);
+    let data = self.build_data(&entries);
+    let header = self.build_header(&index, data.len() as u64)?;
+    let manifest = BundleDescriptor::new(header, index);
     Ok(Bundle {
       descriptor: manifest,
+      data: data.into(),
+    })
+  }
+
+  pub(crate) fn index_writer_options(&self) -> IndexWriterOptions {
+    let mut options = self.options.index;
+    options.version(self.version);
+    options
+  }
+
+  /// Returns entries sorted by path, so the data section is laid out in the same order on every
+  /// build.
+  pub(crate) fn sorted_entries(&self) -> Vec<(&str, &BundleEntry)> {
+    let mut entries = self
+      .entries
+      .iter()
+      .map(|(path, entry)| (path.as_str(), entry))
+      .collect::<Vec<_>>();
+    entries.sort_unstable_by_key(|(path, _)| *path);
+    entries
+  }
+
+  pub(crate) fn compress_entries(&self) -> crate::Result<Vec<CompressedEntry<'_>>> {
+    if self.version == Version::V1 && self.options.digest.is_some() {
+      return Err(crate::Error::UnsupportedDigest(self.version));
+    }
+    if self.version == Version::V1 && self.metadata.is_some() {
+      return Err(crate::Error::UnsupportedMetadata(self.version));
+    }
+    if self.version == Version::V1 && !self.options.precompress.is_empty() {
+      return Err(crate::Error::UnsupportedPrecompression(self.version));
+    }
+    if self.version == Version::V1 && self.options.block_size.is_some_and(|x| x > 0) {
+      return Err(crate::Error::UnsupportedBlocks(self.version));
+    }
+    // codecs and shared data are resolved first, so only distinct entries are compressed
+    let mut pending = Vec::with_capacity(self.entries.len());
+    let mut shared = Vec::with_capacity(self.entries.len());
+    let mut stored = HashMap::new();
+    for (position, (path, entry)) in self.sorted_entries().into_iter().enumerate() {
+      let codec = self.options.resolve_codec(path, entry.content_type());
+      if self.version == Version::V1 && codec != Codec::Lz4 {
+        return Err(crate::Error::UnsupportedCodec {
+          codec,
+          version: self.version,
+        });
+      }
+      let precompress = self.options.resolve_precompress(entry.content_type());
+      if self.options.dedup {
+        let key = (entry.content(), codec, precompress);
+        if let Some(&original) = stored.get(&key) {
+          shared.push((position, path, entry, original));
+          continue;
+        }
+        stored.insert(key, position);
+      }
+      pending.push((position, path, entry, codec, precompress));
+    }
+    let threads = match self.options.threads {
+      Some(0) => std::thread::available_parallelism().map_or(1, |x| x.get()),
+      Some(threads) => threads,
+      None => 1,
+    };
+    let compressed = parallel_map(&pending, threads, |(_, path, entry, codec, precompress)| {
+      self.compress_entry(path, entry, *codec, precompress)
+    })?;
+    let mut entries: Vec<Option<CompressedEntry>> = Vec::with_capacity(self.entries.len());
+    entries.resize_with(self.entries.len(), || None);
+    for ((position, ..), compressed) in pending.iter().zip(compressed) {
+      entries[*position] = Some(compressed);
+    }
+    for (position, path, entry, original) in shared {
+      let original_entry = entries[original].as_ref().unwrap();
+      let compressed = CompressedEntry {
+        path,
+        entry,
+        codec: original_entry.codec,
+        data: vec![],
+        digest: original_entry.digest.clone(),
+        variants: vec![],
+        blocks: original_entry.blocks.clone(),
+        shared: Some(original),
+      };
+      entries[position] = Some(compressed);
+    }
+    Ok(entries.into_iter().map(Option::unwrap).collect())
+  }
+
+  fn compress_entry<'a>(
+    &self,
+    path: &'a str,
+    entry: &'a BundleEntry,
+    mut codec: Codec,
+    precompress: &[ContentEncoding],
+  ) -> crate::Result<CompressedEntry<'a>> {
+    let block_size = |codec: Codec| {
+      self
+        .options
+        .resolve_block_size(codec)
+        .filter(|size| self.version != Version::V1 && entry.content_length() > *size)
+    };
+    let compress = |codec: Codec| match block_size(codec) {
+      Some(size) => compress_blocks(codec, entry.content(), size).map(|(d, b)| (d, Some(b))),
+      None => codec.compress(entry.content()).map(|d| (d, None)),
+    };
+    let (mut data, mut blocks) = compress(codec)?;
+    if self.options.store_incompressible
+      && self.version != Version::V1
+      && codec != Codec::None
+      && data.len() >= entry.content().len()
+    {
+      codec = Codec::None;
+      (data, blocks) = compress(codec)?;
+    }
+    let digest = self
+      .options
+      .digest
+      .map(|algorithm| EntryDigest::try_compute(algorithm, entry.content()))
+      .transpose()?;
+    let mut variants = vec![];
+    for encoding in precompress {
+      let encoded = encoding.encode(entry.content())?;
+      if encoded.len() < entry.content().len() {
+        let digest = self
+          .options
+          .digest
+          .map(|algorithm| EntryDigest::try_compute(algorithm, &encoded))
+          .transpose()?;
+        variants.push((*encoding, encoded, digest));
+      }
+    }
+    Ok(CompressedEntry {
+      path,
+      entry,
+      codec,
       data,
+      digest,
+      variants,
+      blocks,
+      shared: None,
     })
   }
 
-  pub(crate) fn build_header(&self, index: &Index) -> crate::Result<Header> {
+  pub(crate) fn build_header(&self, index: &Index, data_size: u64) -> crate::Result<Header> {
     let index_bytes_size =
-      IndexWriter::new_with_options(&mut vec![], self.options().index).write(index)?;
+      IndexWriter::new_with_options(&mut vec![], self.index_writer_options()).write(index)?;
     let index_size = (index_bytes_size - CHECKSUM_LEN) as u32;
-    let header = Header::new(self.version(), index_size);
+    let mut header = Header::new(self.version(), index_size);
+    if self.version != Version::V1 {
+      header.set_data_size(data_size);
+    }
     Ok(header)
   }
 
-  pub(crate) fn build_index(&self) -> Index {
-    let mut index = Index::new_with_capacity(self.entries().len());
+  pub(crate) fn build_index(&self, entries: &[CompressedEntry]) -> Index {
+    let mut index = Index::new_with_capacity(entries.len());
+    index.metadata.clone_from(&self.metadata);
+    // data range and variants of every entry, in the order of `entries`
+    let mut layouts: Vec<(u64, u64, Vec<EntryVariant>)> = Vec::with_capacity(entries.len());
     let mut offset = 0;
-    for (path, entry) in self.entries() {
-      let len = entry.len() as u64;
-      let mut index_entry =
-        IndexEntry::new(offset, len, entry.content_type(), entry.content_length);
+    for compressed in entries {
+      let layout = match compressed.shared {
+        Some(position) => layouts[position].clone(),
+        None => {
+          let (data_offset, len) = (offset, compressed.data.len() as u64);
+          offset += len + CHECKSUM_LEN as u64;
+          // precompressed variants follow the entry data
+          let mut variants = vec![];
+          for (encoding, encoded, digest) in &compressed.variants {
+            let len = encoded.len() as u64;
+            let mut variant = EntryVariant::new(*encoding, offset, len);
+            variant.digest.clone_from(digest);
+            variants.push(variant);
+            offset += len + CHECKSUM_LEN as u64;
+          }
+          (data_offset, len, variants)
+        }
+      };
+      let entry = compressed.entry;
+      let mut index_entry = IndexEntry::new(
+        layout.0,
+        layout.1,
+        entry.content_type(),
+        entry.content_length(),
+      );
+      index_entry.codec = compressed.codec;
+      index_entry.digest.clone_from(&compressed.digest);
+      index_entry.blocks.clone_from(&compressed.blocks);
+      index_entry.variants.clone_from(&layout.2);
+      #[cfg(feature = "header-rules")]
+      {
+        index_entry.headers = self.options.header_rules.resolve(compressed.path);
+      }
       if let Some(headers) = entry.headers.as_ref() {
-        index_entry.headers.clone_from(headers);
+        merge_headers(&mut index_entry.headers, headers);
       }
-      index.insert_entry(path, index_entry);
-      offset += len;
-      offset += CHECKSUM_LEN as u64;
+      index.insert_entry(compressed.path, index_entry);
+      layouts.push(layout);
     }
     index
   }
 
-  pub(crate) fn build_data(&self) -> Vec<u8> {
+  pub(crate) fn build_data(&self, entries: &[CompressedEntry]) -> Vec<u8> {
     let mut data = vec![];
-    for entry in self.entries().values() {
-      let checksum = make_checksum(self.options.data_checksum_seed, entry.data());
-      data.extend_from_slice(entry.data());
-      data.extend_from_slice(&checksum.to_be_bytes());
+    for compressed in entries.iter().filter(|x| x.shared.is_none()) {
+      let variants = compressed.variants.iter().map(|(_, encoded, _)| encoded);
+      for bytes in std::iter::once(&compressed.data).chain(variants) {
+        let checksum = make_checksum(self.options.data_checksum_seed, bytes);
+        data.extend_from_slice(bytes);
+        data.extend_from_slice(&checksum.to_be_bytes());
+      }
     }
     data
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const INDEX_JS: &str = r#"console.log('Hello World'); console.log('Hello World');"#;
+
+  #[test]
+  fn resolve_content_type() {
+    let mut options = BundleBuilderOptions::new();
+    options
+      .mime_type(".JS", "application/javascript")
+      .mime_type("glsl", "text/plain");
+    assert_eq!(
+      options.resolve_content_type("/app.js", b""),
+      "application/javascript"
+    );
+    assert_eq!(
+      options.resolve_content_type("/shaders/a.GLSL", b""),
+      "text/plain"
+    );
+    assert_eq!(
+      options.resolve_content_type("/app.wasm", b"\0asm"),
+      "application/wasm"
+    );
+    #[cfg(feature = "mime-sniff")]
+    assert_eq!(
+      options.resolve_content_type("/logo", b"\x89PNG\r\n\x1a\n"),
+      "image/png"
+    );
+  }
+
+  #[test]
+  fn resolve_codec() {
+    let mut options = BundleBuilderOptions::new();
+    options
+      .codec(Codec::Zstd)
+      .content_type_codec("text/html", Codec::Brotli)
+      .content_type_codec("image/*", Codec::None)
+      .content_type_codec("image/svg+xml", Codec::Brotli);
+    assert_eq!(
+      options.resolve_codec("/index.html", "text/html"),
+      Codec::Brotli
+    );
+    assert_eq!(
+      options.resolve_codec("/index.html", "Text/HTML; charset=utf-8"),
+      Codec::Brotli
+    );
+    assert_eq!(options.resolve_codec("/logo.png", "image/png"), Codec::None);
+    assert_eq!(
+      options.resolve_codec("/logo.svg", "image/svg+xml"),
+      Codec::Brotli
+    );
+    assert_eq!(
+      options.resolve_codec("/index.js", "text/javascript"),
+      Codec::Zstd
+    );
+  }
+
+  #[test]
+  fn resolve_codec_by_extension() {
+    let mut options = BundleBuilderOptions::new();
+    options
+      .content_type_codec("application/octet-stream", Codec::Zstd)
+      .extension_codec(".WOFF2", Codec::None);
+    assert_eq!(
+      options.resolve_codec("/fonts/inter.woff2", "application/octet-stream"),
+      Codec::None
+    );
+    assert_eq!(
+      options.resolve_codec("/data.bin", "application/octet-stream"),
+      Codec::Zstd
+    );
+    assert_eq!(
+      options.resolve_codec("/assets.woff2/data", "application/octet-stream"),
+      Codec::Zstd
+    );
+  }
+
+  #[test]
+  fn store_compressed_formats() {
+    let mut options = BundleBuilderOptions::new();
+    options.codec(Codec::Zstd).store_compressed_formats();
+    assert_eq!(
+      options.resolve_codec("/intro.mp4", "application/octet-stream"),
+      Codec::None
+    );
+    assert_eq!(options.resolve_codec("/sound", "audio/mpeg"), Codec::None);
+    // uncompressed audio is still compressed
+    assert_eq!(options.resolve_codec("/sound", "audio/wav"), Codec::Zstd);
+    assert_eq!(
+      options.resolve_codec("/index.js", "text/javascript"),
+      Codec::Zstd
+    );
+  }
+
+  #[test]
+  fn metadata() {
+    let mut metadata = BundleMetadata::new("app", "1.0.0");
+    metadata.set_built_at(1_700_000_000);
+    let mut builder = BundleBuilder::new();
+    builder.set_metadata(metadata.clone());
+    assert!(matches!(
+      builder.build(),
+      Err(crate::Error::UnsupportedMetadata(Version::V1))
+    ));
+
+    builder.set_version(Version::V2);
+    let bundle = builder.build().unwrap();
+    assert_eq!(bundle.descriptor().metadata(), Some(&metadata));
+    let mut buf = vec![];
+    crate::BundleWriter::new(&mut buf).write(&bundle).unwrap();
+    let read: Bundle =
+      crate::Reader::read(&mut crate::BundleReader::new(std::io::Cursor::new(&buf))).unwrap();
+    assert_eq!(read.descriptor().metadata(), Some(&metadata));
+  }
+
+  #[test]
+  fn reproducible() {
+    let files = (0..32)
+      .map(|i| (format!("/assets/{i}.js"), format!("console.log({i});")))
+      .collect::<Vec<_>>();
+    let build = |files: &mut dyn Iterator<Item = &(String, String)>| {
+      let mut builder = BundleBuilder::new();
+      for (path, data) in files {
+        let mut headers = HeaderMap::new();
+        headers.insert("cache-control", "max-age=3600".parse().unwrap());
+        headers.insert("x-content-type-options", "nosniff".parse().unwrap());
+        builder.insert_entry(
+          path.as_str(),
+          BundleEntry::new(data.as_bytes(), "text/javascript", Some(headers)),
+        );
+      }
+      let bundle = builder.build().unwrap();
+      let mut buf = vec![];
+      crate::BundleWriter::new(&mut buf).write(&bundle).unwrap();
+      buf
+    };
+    let a = build(&mut files.iter());
+    let b = build(&mut files.iter().rev());
+    assert_eq!(a, b);
+
+    let bundle: Bundle =
+      crate::Reader::read(&mut crate::BundleReader::new(std::io::Cursor::new(&a))).unwrap();
+    let index = bundle.descriptor().index();
+    let mut offsets = index
+      .entries()
+      .iter()
+      .map(|(path, entry)| (entry.offset(), path.as_str()))
+      .collect::<Vec<_>>();
+    offsets.sort();
+    let paths = offsets.iter().map(|(_, path)| *path).collect::<Vec<_>>();
+    let mut sorted = paths.clone();
+    sorted.sort();
+    assert_eq!(paths, sorted);
+  }
+
+  #[cfg(feature = "header-rules")]
+  #[test]
+  fn header_rules() {
+    let mut cache = HeaderMap::new();
+    cache.insert("cache-control", "max-age=31536000".parse().unwrap());
+    let mut csp = HeaderMap::new();
+    csp.insert(
+      "content-security-policy",
+      "default-src 'self'".parse().unwrap(),
+    );
+    let mut rules = HeaderRules::new();
+    rules
+      .add(crate::HeaderRule::new("/assets/**", cache).unwrap())
+      .add(crate::HeaderRule::new("*.html", csp).unwrap());
+    let mut options = BundleBuilderOptions::new();
+    options.header_rules(rules);
+    let mut builder = BundleBuilder::new_with_options(options);
+    let mut no_cache = HeaderMap::new();
+    no_cache.insert("cache-control", "no-cache".parse().unwrap());
+    builder.insert_entry(
+      "/assets/app.js",
+      BundleEntry::new(b"", "text/javascript", None),
+    );
+    builder.insert_entry(
+      "/assets/index.html",
+      BundleEntry::new(b"", "text/html", Some(no_cache)),
+    );
+    builder.insert_entry(
+      "/data.json",
+      BundleEntry::new(b"", "application/json", None),
+    );
+    let bundle = builder.build().unwrap();
+    let index = bundle.descriptor().index();
+    let headers = |path: &str| index.get_entry(path).unwrap().headers().clone();
+    assert_eq!(
+      headers("/assets/app.js")["cache-control"],
+      "max-age=31536000"
+    );
+    let html = headers("/assets/index.html");
+    assert_eq!(html["cache-control"], "no-cache");
+    assert_eq!(html["content-security-policy"], "default-src 'self'");
+    assert!(headers("/data.json").is_empty());
+  }
+
+  #[cfg(all(feature = "codec-zstd", feature = "digest", feature = "precompress"))]
+  #[test]
+  fn dedup() {
+    let build = |dedup: bool| {
+      let mut options = BundleBuilderOptions::new();
+      options
+        .dedup(dedup)
+        .digest(DigestAlgorithm::Sha3_256)
+        .precompress(ContentEncoding::Gzip)
+        .extension_codec("mjs", Codec::Zstd);
+      let mut builder = BundleBuilder::new_with_options(options);
+      builder.set_version(Version::V2);
+      let data = INDEX_JS.repeat(4);
+      for path in ["/en/app.js", "/ko/app.js", "/vendor/app.js", "/app.mjs"] {
+        builder.insert_entry(
+          path,
+          BundleEntry::new(data.as_bytes(), "text/javascript", None),
+        );
+      }
+      builder.insert_entry("/other.js", BundleEntry::new(b"x", "text/javascript", None));
+      builder.build().unwrap()
+    };
+    let bundle = build(true);
+    let index = bundle.descriptor().index();
+    let en = index.get_entry("/en/app.js").unwrap();
+    for path in ["/ko/app.js", "/vendor/app.js"] {
+      let entry = index.get_entry(path).unwrap();
+      assert_eq!((entry.offset(), entry.len()), (en.offset(), en.len()));
+      assert_eq!(entry.variants(), en.variants());
+    }
+    // a different codec is stored separately
+    assert_ne!(index.get_entry("/app.mjs").unwrap().offset(), en.offset());
+    let shared = (en.len() + en.variants()[0].len() + 2 * CHECKSUM_LEN as u64) * 2;
+    assert_eq!(index.deduplicated_size(), shared);
+    assert!(bundle.verify().is_ok());
+
+    let mut buf = vec![];
+    crate::BundleWriter::new(&mut buf).write(&bundle).unwrap();
+    let read: Bundle =
+      crate::Reader::read(&mut crate::BundleReader::new(std::io::Cursor::new(&buf))).unwrap();
+    for path in ["/en/app.js", "/ko/app.js", "/vendor/app.js", "/app.mjs"] {
+      assert_eq!(
+        read.get_data(path).unwrap().unwrap(),
+        INDEX_JS.repeat(4).as_bytes()
+      );
+    }
+
+    let full = build(false);
+    assert_eq!(full.descriptor().index().deduplicated_size(), 0);
+    assert_eq!(full.data.len() as u64 - bundle.data.len() as u64, shared);
+  }
+
+  #[test]
+  fn parallel_map() {
+    let items = (0..100).collect::<Vec<u32>>();
+    let doubled = super::parallel_map(&items, 4, |x| Ok(x * 2)).unwrap();
+    assert_eq!(doubled, items.iter().map(|x| x * 2).collect::<Vec<_>>());
+    let err = super::parallel_map(&items, 4, |x| match x {
+      10 | 20 => Err(crate::Error::EntryAlreadyExists(x.to_string())),
+      x => Ok(*x),
+    });
+    assert!(matches!(err, Err(crate::Error::EntryAlreadyExists(x)) if x == "10"));
+  }
+
+  #[cfg(all(feature = "codec-zstd", feature = "digest", feature = "precompress"))]
+  #[test]
+  fn threads() {
+    let build = |threads: Option<usize>| {
+      let mut options = BundleBuilderOptions::new();
+      options
+        .codec(Codec::Zstd)
+        .content_type_codec("image/*", Codec::None)
+        .digest(DigestAlgorithm::Sha3_256)
+        .precompress(ContentEncoding::Brotli)
+        .block_size(256)
+        .dedup(true);
+      if let Some(threads) = threads {
+        options.threads(threads);
+      }
+      let mut builder = BundleBuilder::new_with_options(options);
+      builder.set_version(Version::V2);
+      for i in 0..200 {
+        let data = INDEX_JS.repeat(i % 7 + 1);
+        builder.insert_entry(
+          format!("/js/{i}.js"),
+          BundleEntry::new(data.as_bytes(), "text/javascript", None),
+        );
+        builder.insert_entry(
+          format!("/img/{i}.png"),
+          BundleEntry::new(&[i as u8; 32], "image/png", None),
+        );
+      }
+      let bundle = builder.build().unwrap();
+      let mut buf = vec![];
+      crate::BundleWriter::new(&mut buf).write(&bundle).unwrap();
+      buf
+    };
+    let serial = build(None);
+    assert_eq!(build(Some(4)), serial);
+    assert_eq!(build(Some(0)), serial);
+  }
+
+  #[test]
+  fn store_incompressible() {
+    // bytes of a sequence that general purpose codecs cannot shrink
+    let data = (0..64u8)
+      .map(|x| x.wrapping_mul(73) ^ 0x5a)
+      .collect::<Vec<_>>();
+    let mut options = BundleBuilderOptions::new();
+    options.store_incompressible(true);
+    let mut builder = BundleBuilder::new_with_options(options);
+    builder.set_version(Version::V2);
+    builder.insert_entry(
+      "/random.bin",
+      BundleEntry::new(&data, "application/octet-stream", None),
+    );
+    builder.insert_entry(
+      "/index.js",
+      BundleEntry::new(INDEX_JS.as_bytes(), "text/javascript", None),
+    );
+    let bundle = builder.build().unwrap();
+    let index = bundle.descriptor().index();
+    let random = index.get_entry("/random.bin").unwrap();
+    assert_eq!(random.codec(), Codec::None);
+    assert_eq!(random.len(), data.len() as u64);
+    assert_eq!(index.get_entry("/index.js").unwrap().codec(), Codec::Lz4);
+    assert_eq!(bundle.get_data("/random.bin").unwrap().unwrap(), data);
+  }
+
+  #[test]
+  fn store_incompressible_ignored_in_v1() {
+    let mut options = BundleBuilderOptions::new();
+    options.store_incompressible(true);
+    let mut builder = BundleBuilder::new_with_options(options);
+    builder.insert_entry(
+      "/a.bin",
+      BundleEntry::new(&[0x01], "application/octet-stream", None),
+    );
+    let bundle = builder.build().unwrap();
+    let entry = bundle.descriptor().index().get_entry("/a.bin").unwrap();
+    assert_eq!(entry.codec(), Codec::Lz4);
+    assert_eq!(bundle.get_data("/a.bin").unwrap().unwrap(), [0x01]);
+  }
+
+  #[cfg(all(feature = "codec-zstd", feature = "codec-brotli"))]
+  #[test]
+  fn build_with_codecs() {
+    let mut options = BundleBuilderOptions::new();
+    options
+      .codec(Codec::Zstd)
+      .content_type_codec("text/html", Codec::Brotli)
+      .content_type_codec("image/png", Codec::None);
+    let mut builder = BundleBuilder::new_with_options(options);
+    builder.set_version(Version::V2);
+    builder.insert_entry(
+      "/index.js",
+      BundleEntry::new(INDEX_JS.as_bytes(), "text/javascript", None),
+    );
+    builder.insert_entry(
+      "/index.html",
+      BundleEntry::new(b"<html></html>", "text/html", None),
+    );
+    builder.insert_entry(
+      "/logo.png",
+      BundleEntry::new(&[0x89, 0x50, 0x4e, 0x47], "image/png", None),
+    );
+    let bundle = builder.build().unwrap();
+    let index = bundle.descriptor().index();
+    assert_eq!(index.get_entry("/index.js").unwrap().codec(), Codec::Zstd);
+    assert_eq!(
+      index.get_entry("/index.html").unwrap().codec(),
+      Codec::Brotli
+    );
+    assert_eq!(index.get_entry("/logo.png").unwrap().codec(), Codec::None);
+    assert_eq!(
+      bundle.get_data("/index.js").unwrap().unwrap(),
+      INDEX_JS.as_bytes()
+    );
+    assert_eq!(
+      bundle.get_data("/index.html").unwrap().unwrap(),
+      b"<html></html>"
+    );
+    assert_eq!(
+      bundle.get_data("/logo.png").unwrap().unwrap(),
+      [0x89, 0x50, 0x4e, 0x47]
+    );
+  }
+
+  #[cfg(feature = "precompress")]
+  #[test]
+  fn precompress() {
+    let mut options = BundleBuilderOptions::new();
+    options
+      .precompress(ContentEncoding::Gzip)
+      .precompress(ContentEncoding::Brotli)
+      .precompress_content_type("application/x-custom");
+    assert_eq!(
+      options.resolve_precompress("text/javascript; charset=utf-8"),
+      [ContentEncoding::Brotli, ContentEncoding::Gzip]
+    );
+    assert_eq!(options.resolve_precompress("application/x-custom").len(), 2);
+    assert!(options.resolve_precompress("image/png").is_empty());
+
+    let js = INDEX_JS.repeat(8);
+    let mut builder = BundleBuilder::new_with_options(options.clone());
+    builder.insert_entry(
+      "/index.js",
+      BundleEntry::new(js.as_bytes(), "text/javascript", None),
+    );
+    assert!(matches!(
+      builder.build(),
+      Err(crate::Error::UnsupportedPrecompression(Version::V1))
+    ));
+
+    builder.set_version(Version::V2);
+    builder.insert_entry("/a.txt", BundleEntry::new(b"a", "text/plain", None));
+    builder.insert_entry(
+      "/logo.png",
+      BundleEntry::new(&[0x89, 0x50, 0x4e, 0x47], "image/png", None),
+    );
+    let bundle = builder.build().unwrap();
+    let index = bundle.descriptor().index();
+    let entry = index.get_entry("/index.js").unwrap();
+    assert_eq!(entry.variants().len(), 2);
+    for encoding in [ContentEncoding::Brotli, ContentEncoding::Gzip] {
+      let variant = bundle.get_variant("/index.js", encoding).unwrap().unwrap();
+      assert_eq!(variant, encoding.encode(js.as_bytes()).unwrap());
+    }
+    // variants which are not smaller than the original are dropped
+    assert!(index.get_entry("/a.txt").unwrap().variants().is_empty());
+    assert!(index.get_entry("/logo.png").unwrap().variants().is_empty());
+    assert_eq!(
+      bundle.get_data("/index.js").unwrap().unwrap(),
+      js.as_bytes()
+    );
+    assert_eq!(bundle.get_data("/a.txt").unwrap().unwrap(), b"a");
+  }
+
+  #[test]
+  fn default_lz4_blocks() {
+    let size = DEFAULT_BLOCK_SIZE as usize;
+    let data = (0..size * 2 + 100)
+      .map(|x| (x % 251) as u8)
+      .collect::<Vec<_>>();
+    let mut builder = BundleBuilder::new();
+    builder.insert_entry("/large.bin", BundleEntry::new(&data, "text/plain", None));
+    builder.insert_entry(
+      "/index.js",
+      BundleEntry::new(INDEX_JS.as_bytes(), "text/javascript", None),
+    );
+    // `V1` has no blocks
+    let bundle = builder.build().unwrap();
+    let entry = bundle.descriptor().index().get_entry("/large.bin").unwrap();
+    assert!(entry.blocks().is_none());
+
+    builder.set_version(Version::V2);
+    let bundle = builder.build().unwrap();
+    let index = bundle.descriptor().index();
+    let blocks = index.get_entry("/large.bin").unwrap().blocks().unwrap();
+    assert_eq!(blocks.block_size(), DEFAULT_BLOCK_SIZE);
+    assert_eq!(blocks.len(), 3);
+    assert!(index.get_entry("/index.js").unwrap().blocks().is_none());
+    assert_eq!(bundle.get_data("/large.bin").unwrap().unwrap(), data);
+
+    let mut options = BundleBuilderOptions::new();
+    options.block_size(0);
+    let mut builder = BundleBuilder::new_with_options(options);
+    builder.insert_entry("/large.bin", BundleEntry::new(&data, "text/plain", None));
+    let bundle = builder.build().unwrap();
+    let entry = bundle.descriptor().index().get_entry("/large.bin").unwrap();
+    assert!(entry.blocks().is_none());
+    builder.set_version(Version::V2);
+    let bundle = builder.build().unwrap();
+    let entry = bundle.descriptor().index().get_entry("/large.bin").unwrap();
+    assert!(entry.blocks().is_none());
+  }
+
+  #[cfg(feature = "codec-zstd")]
+  #[test]
+  fn blocks() {
+    let data = (0..5000u32).map(|x| (x % 251) as u8).collect::<Vec<_>>();
+    let mut options = BundleBuilderOptions::new();
+    options.codec(Codec::Zstd).block_size(1024);
+    let mut builder = BundleBuilder::new_with_options(options);
+    builder.insert_entry("/video.mp4", BundleEntry::new(&data, "video/mp4", None));
+    assert!(matches!(
+      builder.build(),
+      Err(crate::Error::UnsupportedBlocks(Version::V1))
+    ));
+
+    builder.set_version(Version::V2);
+    builder.insert_entry(
+      "/index.js",
+      BundleEntry::new(INDEX_JS.as_bytes(), "text/javascript", None),
+    );
+    let bundle = builder.build().unwrap();
+    let index = bundle.descriptor().index();
+    let video = index.get_entry("/video.mp4").unwrap();
+    let blocks = video.blocks().unwrap();
+    assert_eq!(blocks.block_size(), 1024);
+    assert_eq!(blocks.len(), 5);
+    assert_eq!(blocks.lens().iter().sum::<u64>(), video.len());
+    assert!(index.get_entry("/index.js").unwrap().blocks().is_none());
+    assert_eq!(bundle.get_data("/video.mp4").unwrap().unwrap(), data);
+    assert_eq!(
+      bundle
+        .get_data_range("/video.mp4", 1000..3000)
+        .unwrap()
+        .unwrap(),
+      &data[1000..3000]
+    );
+  }
+
+  #[test]
+  fn v1_only_supports_lz4() {
+    let mut options = BundleBuilderOptions::new();
+    options.codec(Codec::Zstd);
+    let mut builder = BundleBuilder::new_with_options(options);
+    builder.insert_entry(
+      "/index.js",
+      BundleEntry::new(INDEX_JS.as_bytes(), "text/javascript", None),
+    );
+    let err = builder.build().unwrap_err();
+    assert!(matches!(
+      err,
+      crate::Error::UnsupportedCodec {
+        codec: Codec::Zstd,
+        version: Version::V1
+      }
+    ));
+  }
+}
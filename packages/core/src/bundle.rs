@@ -1,19 +1,36 @@
+use crate::block::{decompress_entry, EntrySpan};
 use crate::builder::BundleBuilder;
 use crate::checksum::{parse_checksum, CHECKSUM_LEN};
+use crate::codec::{check_stored_len, Codec};
+use crate::digest::{verify_entry_digest, verify_variant_digest};
+use crate::encoding::ContentEncoding;
+use crate::entry_reader::EntryReader;
 use crate::header::{Header, HeaderReader, HeaderWriter};
-use crate::index::{Index, IndexEntry, IndexReader, IndexWriter};
+use crate::index::{
+  write_index, EntryVariant, Index, IndexEntry, IndexReader, IndexReaderOptions, IndexWriter,
+  IndexWriterOptions,
+};
+use crate::limits::{read_exact_vec, read_to_end_limited, ReaderLimits};
+use crate::metadata::BundleMetadata;
 use crate::reader::Reader;
+use crate::signatures::{parse_signatures, write_signatures, BundleSignature};
+use crate::version::Version;
 use crate::writer::Writer;
-use lz4_flex::decompress_size_prepended;
+use bytes::Bytes;
 use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
 
+#[cfg(feature = "async")]
+use crate::entry_reader::AsyncEntryReader;
+#[cfg(feature = "async")]
+use crate::limits::{async_read_exact_vec, async_read_to_end_limited};
 #[cfg(feature = "async")]
 use crate::{
   AsyncHeaderReader, AsyncHeaderWriter, AsyncIndexReader, AsyncIndexWriter, AsyncReader,
   AsyncWriter,
 };
 #[cfg(feature = "async")]
-use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
 
 /// Bundle metadata including header and index information.
 ///
@@ -44,9 +61,22 @@ use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, As
 pub struct BundleDescriptor {
   pub(crate) header: Header,
   pub(crate) index: Index,
+  pub(crate) signatures: Vec<BundleSignature>,
+  pub(crate) verify_digest: bool,
+  pub(crate) limits: ReaderLimits,
 }
 
 impl BundleDescriptor {
+  pub(crate) fn new(header: Header, index: Index) -> Self {
+    Self {
+      header,
+      index,
+      signatures: vec![],
+      verify_digest: false,
+      limits: ReaderLimits::default(),
+    }
+  }
+
   /// Returns a reference to the bundle header.
   pub fn header(&self) -> &Header {
     &self.header
@@ -57,6 +87,89 @@ impl BundleDescriptor {
     &self.index
   }
 
+  /// Returns the bundle-level metadata, if the bundle has one.
+  pub fn metadata(&self) -> Option<&BundleMetadata> {
+    self.index.metadata.as_ref()
+  }
+
+  /// Returns the signatures embedded in the bundle.
+  pub fn signatures(&self) -> &[BundleSignature] {
+    &self.signatures
+  }
+
+  /// Adds a signature to be embedded when the bundle is written.
+  ///
+  /// The signature must be made over [`BundleDescriptor::signing_message`]. Embedded signatures
+  /// require a `V2` bundle.
+  pub fn add_signature(&mut self, signature: BundleSignature) -> &mut Self {
+    self.signatures.push(signature);
+    self
+  }
+
+  /// Removes all embedded signatures.
+  pub fn clear_signatures(&mut self) -> &mut Self {
+    self.signatures.clear();
+    self
+  }
+
+  /// Returns the message that embedded signatures are made over.
+  ///
+  /// The message is the header without its checksum followed by the encoded index, so it
+  /// authenticates every file through the offsets, lengths and digests in the index. File data
+  /// itself is not part of the message, so it is only authenticated if every entry has a
+  /// digest and the data is checked against it, as `Bundle::verify_signature` does.
+  pub fn signing_message(&self) -> crate::Result<Vec<u8>> {
+    let mut message = vec![];
+    HeaderWriter::new(&mut message).write(&self.header)?;
+    message.truncate(Header::checksum_offset_of(self.header.version()) as usize);
+    message.extend(write_index(&self.index, self.header.version())?);
+    Ok(message)
+  }
+
+  /// Returns `true` if file data is verified against the digest in the index when read.
+  pub fn verify_digest(&self) -> bool {
+    self.verify_digest
+  }
+
+  /// Sets whether file data is verified against the digest in the index when read.
+  ///
+  /// Entries without a digest are read as-is. Reading an entry whose data does not match its
+  /// digest fails with [`Error::DigestMismatch`](crate::Error::DigestMismatch). Precompressed
+  /// variants are verified against their own digests, computed over the encoded data.
+  pub fn set_verify_digest(&mut self, verify: bool) -> &mut Self {
+    self.verify_digest = verify;
+    self
+  }
+
+  /// Returns the limits applied when reading file data.
+  pub fn limits(&self) -> &ReaderLimits {
+    &self.limits
+  }
+
+  /// Sets the limits applied when reading file data.
+  ///
+  /// Descriptors read with [`BundleReader::limits`] keep the limits of the reader.
+  pub fn set_limits(&mut self, limits: ReaderLimits) -> &mut Self {
+    self.limits = limits;
+    self
+  }
+
+  fn data_reader<R: Read + Seek>(&self, reader: R) -> BundleDataReader<R> {
+    BundleDataReader::new(reader, self.header.data_offset())
+      .limits(self.limits)
+      .data_size(self.header.data_size())
+  }
+
+  #[cfg(feature = "async")]
+  fn async_data_reader<R: AsyncRead + AsyncSeek + Unpin>(
+    &self,
+    reader: R,
+  ) -> AsyncBundleDataReader<R> {
+    AsyncBundleDataReader::new(reader, self.header.data_offset())
+      .limits(self.limits)
+      .data_size(self.header.data_size())
+  }
+
   /// Reads file data from the bundle using the provided reader.
   ///
   /// Returns `None` if the path doesn't exist in the bundle.
@@ -66,15 +179,53 @@ impl BundleDescriptor {
   /// * `reader` - A reader positioned at the start of the bundle file
   /// * `path` - File path in the bundle (e.g., "/index.html")
   pub fn get_data<R: Read + Seek>(&self, reader: R, path: &str) -> crate::Result<Option<Vec<u8>>> {
-    if !self.index.contains_path(path) {
+    let Some(entry) = self.index.try_get_entry(path)? else {
       return Ok(None);
-    }
-    let entry = self.index.get_entry(path).unwrap();
-    let mut reader = BundleDataReader::new(reader, self.header.index_end_offset());
+    };
+    let mut reader = self.data_reader(reader).verify_digest(self.verify_digest);
     let data = reader.read_entry_data(entry)?;
     Ok(Some(data))
   }
 
+  /// Opens a reader which streams file data from the bundle, decompressing it as it is read.
+  ///
+  /// Unlike [`get_data`](Self::get_data), the file is not buffered in full, so large files can be
+  /// copied to a socket or hasher with bounded memory. The trailing checksum is checked once the
+  /// stream completes (see [`EntryReader`]).
+  ///
+  /// Returns `None` if the path doesn't exist in the bundle.
+  ///
+  /// # Arguments
+  ///
+  /// * `reader` - A reader positioned at the start of the bundle file
+  /// * `path` - File path in the bundle (e.g., "/index.html")
+  pub fn open_entry<R: Read + Seek>(
+    &self,
+    mut reader: R,
+    path: &str,
+  ) -> crate::Result<Option<EntryReader<R>>> {
+    let Some(entry) = self.index.try_get_entry(path)? else {
+      return Ok(None);
+    };
+    let position = self.entry_position(entry)?;
+    reader.seek(SeekFrom::Start(position))?;
+    let reader = EntryReader::new(reader, entry, self.verify_digest)?;
+    Ok(Some(reader))
+  }
+
+  /// Checks the entry against the limits, and resolves the position of its stored data and
+  /// checksum in the bundle.
+  fn entry_position(&self, entry: &IndexEntry) -> crate::Result<u64> {
+    self.limits.check_entry(entry)?;
+    let len = checked_offset(entry.len(), CHECKSUM_LEN as u64)?;
+    data_position(
+      self.header.data_offset(),
+      self.header.data_size(),
+      entry.offset(),
+      len,
+    )
+  }
+
   /// Reads the checksum of file data from the bundle.
   ///
   /// Returns `None` if the path doesn't exist in the bundle.
@@ -83,15 +234,58 @@ impl BundleDescriptor {
     reader: R,
     path: &str,
   ) -> crate::Result<Option<u32>> {
-    if !self.index.contains_path(path) {
+    let Some(entry) = self.index.try_get_entry(path)? else {
       return Ok(None);
-    }
-    let entry = self.index.get_entry(path).unwrap();
-    let mut reader = BundleDataReader::new(reader, self.header.index_end_offset());
+    };
+    let mut reader = self.data_reader(reader).verify_digest(self.verify_digest);
     let checksum = reader.read_entry_checksum(entry)?;
     Ok(Some(checksum))
   }
 
+  /// Reads a byte range of file data from the bundle.
+  ///
+  /// The range is clamped to the file size. Only the part of the entry holding the range is
+  /// read, and only the blocks it touches are decompressed if the entry is compressed in blocks
+  /// (see [`BundleBuilderOptions::block_size`](crate::BundleBuilderOptions::block_size)).
+  /// Digests are not verified, since they cover the whole file.
+  ///
+  /// Returns `None` if the path doesn't exist in the bundle.
+  pub fn get_data_range<R: Read + Seek>(
+    &self,
+    reader: R,
+    path: &str,
+    range: Range<u64>,
+  ) -> crate::Result<Option<Vec<u8>>> {
+    let Some(entry) = self.index.try_get_entry(path)? else {
+      return Ok(None);
+    };
+    let mut reader = self.data_reader(reader);
+    let data = reader.read_entry_range(entry, range)?;
+    Ok(Some(data))
+  }
+
+  /// Reads the precompressed variant of file data in the given content encoding.
+  ///
+  /// Returns `None` if the path doesn't exist in the bundle or has no such variant. The data is
+  /// returned encoded, as it is sent with a `Content-Encoding` header.
+  pub fn get_variant<R: Read + Seek>(
+    &self,
+    reader: R,
+    path: &str,
+    encoding: ContentEncoding,
+  ) -> crate::Result<Option<Vec<u8>>> {
+    let Some(variant) = self
+      .index
+      .try_get_entry(path)?
+      .and_then(|x| x.variant(encoding))
+    else {
+      return Ok(None);
+    };
+    let mut reader = self.data_reader(reader).verify_digest(self.verify_digest);
+    let data = reader.read_variant_data(variant)?;
+    Ok(Some(data))
+  }
+
   /// Asynchronously reads file data from the bundle.
   ///
   /// Returns `None` if the path doesn't exist in the bundle.
@@ -101,15 +295,76 @@ impl BundleDescriptor {
     reader: R,
     path: &str,
   ) -> crate::Result<Option<Vec<u8>>> {
-    if !self.index.contains_path(path) {
+    let Some(entry) = self.index.try_get_entry(path)? else {
       return Ok(None);
-    }
-    let entry = self.index.get_entry(path).unwrap();
-    let mut reader = AsyncBundleDataReader::new(reader, self.header.index_end_offset());
+    };
+    let mut reader = self
+      .async_data_reader(reader)
+      .verify_digest(self.verify_digest);
     let data = reader.read_entry_data(entry).await?;
     Ok(Some(data))
   }
 
+  /// Asynchronously opens a reader which streams file data from the bundle.
+  ///
+  /// See [`open_entry`](Self::open_entry).
+  #[cfg(feature = "async")]
+  pub async fn async_open_entry<R: AsyncRead + AsyncSeek + Unpin>(
+    &self,
+    mut reader: R,
+    path: &str,
+  ) -> crate::Result<Option<AsyncEntryReader<R>>> {
+    let Some(entry) = self.index.try_get_entry(path)? else {
+      return Ok(None);
+    };
+    let position = self.entry_position(entry)?;
+    reader.seek(SeekFrom::Start(position)).await?;
+    let reader = AsyncEntryReader::new(reader, entry, self.verify_digest)?;
+    Ok(Some(reader))
+  }
+
+  /// Asynchronously reads a byte range of file data from the bundle.
+  ///
+  /// See [`get_data_range`](Self::get_data_range).
+  #[cfg(feature = "async")]
+  pub async fn async_get_data_range<R: AsyncRead + AsyncSeek + Unpin>(
+    &self,
+    reader: R,
+    path: &str,
+    range: Range<u64>,
+  ) -> crate::Result<Option<Vec<u8>>> {
+    let Some(entry) = self.index.try_get_entry(path)? else {
+      return Ok(None);
+    };
+    let mut reader = self.async_data_reader(reader);
+    let data = reader.read_entry_range(entry, range).await?;
+    Ok(Some(data))
+  }
+
+  /// Asynchronously reads the precompressed variant of file data in the given content encoding.
+  ///
+  /// See [`get_variant`](Self::get_variant).
+  #[cfg(feature = "async")]
+  pub async fn async_get_variant<R: AsyncRead + AsyncSeek + Unpin>(
+    &self,
+    reader: R,
+    path: &str,
+    encoding: ContentEncoding,
+  ) -> crate::Result<Option<Vec<u8>>> {
+    let Some(variant) = self
+      .index
+      .try_get_entry(path)?
+      .and_then(|x| x.variant(encoding))
+    else {
+      return Ok(None);
+    };
+    let mut reader = self
+      .async_data_reader(reader)
+      .verify_digest(self.verify_digest);
+    let data = reader.read_variant_data(variant).await?;
+    Ok(Some(data))
+  }
+
   /// Asynchronously reads the checksum of file data from the bundle.
   ///
   /// Returns `None` if the path doesn't exist in the bundle.
@@ -119,11 +374,12 @@ impl BundleDescriptor {
     reader: R,
     path: &str,
   ) -> crate::Result<Option<u32>> {
-    if !self.index.contains_path(path) {
+    let Some(entry) = self.index.try_get_entry(path)? else {
       return Ok(None);
-    }
-    let entry = self.index.get_entry(path).unwrap();
-    let mut reader = AsyncBundleDataReader::new(reader, self.header.index_end_offset());
+    };
+    let mut reader = self
+      .async_data_reader(reader)
+      .verify_digest(self.verify_digest);
     let data = reader.read_entry_checksum(entry).await?;
     Ok(Some(data))
   }
@@ -158,7 +414,7 @@ impl BundleDescriptor {
 #[derive(Debug, PartialEq, Clone)]
 pub struct Bundle {
   pub(crate) descriptor: BundleDescriptor,
-  pub(crate) data: Vec<u8>,
+  pub(crate) data: Bytes,
 }
 
 impl Bundle {
@@ -189,6 +445,36 @@ impl Bundle {
     &self.descriptor
   }
 
+  /// Sets whether file data is verified against the digest in the index when read.
+  ///
+  /// See [`BundleDescriptor::set_verify_digest`].
+  pub fn set_verify_digest(&mut self, verify: bool) -> &mut Self {
+    self.descriptor.set_verify_digest(verify);
+    self
+  }
+
+  /// Sets the limits applied when reading file data.
+  ///
+  /// See [`BundleDescriptor::set_limits`].
+  pub fn set_limits(&mut self, limits: ReaderLimits) -> &mut Self {
+    self.descriptor.set_limits(limits);
+    self
+  }
+
+  pub(crate) fn data_reader(&self) -> BundleDataReader<Cursor<&[u8]>> {
+    BundleDataReader::new(Cursor::new(&self.data[..]), 0)
+      .limits(self.descriptor.limits)
+      .data_size(Some(self.data.len() as u64))
+  }
+
+  /// Adds a signature to be embedded when the bundle is written.
+  ///
+  /// See [`BundleDescriptor::add_signature`].
+  pub fn add_signature(&mut self, signature: BundleSignature) -> &mut Self {
+    self.descriptor.add_signature(signature);
+    self
+  }
+
   /// Retrieves file data by path.
   ///
   /// Returns `None` if the path doesn't exist in the bundle.
@@ -205,63 +491,194 @@ impl Bundle {
   /// assert_eq!(data, b"hello");
   /// ```
   pub fn get_data(&self, path: &str) -> crate::Result<Option<Vec<u8>>> {
-    if !self.descriptor.index.contains_path(path) {
+    let Some(entry) = self.descriptor.index.try_get_entry(path)? else {
       return Ok(None);
-    }
-    let entry = self.descriptor.index.get_entry(path).unwrap();
-    let mut reader = BundleDataReader::new(Cursor::new(&self.data), 0);
+    };
+    let mut reader = self
+      .data_reader()
+      .verify_digest(self.descriptor.verify_digest);
     let data = reader.read_entry_data(entry)?;
     Ok(Some(data))
   }
 
+  /// Retrieves file data by path as [`Bytes`].
+  ///
+  /// Entries stored without compression share the data buffer of the bundle instead of being
+  /// copied. Compressed entries are decompressed into a new buffer.
+  pub fn get_bytes(&self, path: &str) -> crate::Result<Option<Bytes>> {
+    let Some(entry) = self.descriptor.index.try_get_entry(path)? else {
+      return Ok(None);
+    };
+    if entry.codec() != Codec::None {
+      return Ok(self.get_data(path)?.map(Bytes::from));
+    }
+    let len = self.data.len() as u64;
+    let start = data_position(0, Some(len), entry.offset(), entry.len())? as usize;
+    let data = self.data.slice(start..start + entry.len() as usize);
+    check_stored_len(&data, entry.content_length())?;
+    if self.descriptor.verify_digest {
+      verify_entry_digest(entry, &data)?;
+    }
+    Ok(Some(data))
+  }
+
+  /// Retrieves a byte range of file data by path.
+  ///
+  /// See [`BundleDescriptor::get_data_range`].
+  pub fn get_data_range(&self, path: &str, range: Range<u64>) -> crate::Result<Option<Vec<u8>>> {
+    let Some(entry) = self.descriptor.index.try_get_entry(path)? else {
+      return Ok(None);
+    };
+    let mut reader = self.data_reader();
+    let data = reader.read_entry_range(entry, range)?;
+    Ok(Some(data))
+  }
+
+  /// Retrieves the precompressed variant of file data in the given content encoding.
+  ///
+  /// Returns `None` if the path doesn't exist in the bundle or has no such variant.
+  pub fn get_variant(
+    &self,
+    path: &str,
+    encoding: ContentEncoding,
+  ) -> crate::Result<Option<Vec<u8>>> {
+    let Some(variant) = self
+      .descriptor
+      .index
+      .try_get_entry(path)?
+      .and_then(|x| x.variant(encoding))
+    else {
+      return Ok(None);
+    };
+    let mut reader = self
+      .data_reader()
+      .verify_digest(self.descriptor.verify_digest);
+    let data = reader.read_variant_data(variant)?;
+    Ok(Some(data))
+  }
+
   /// Retrieves the checksum of file data by path.
   ///
   /// Returns `None` if the path doesn't exist in the bundle.
   pub fn get_data_checksum(&self, path: &str) -> crate::Result<Option<u32>> {
-    if !self.descriptor.index.contains_path(path) {
+    let Some(entry) = self.descriptor.index.try_get_entry(path)? else {
       return Ok(None);
-    }
-    let entry = self.descriptor.index.get_entry(path).unwrap();
-    let mut reader = BundleDataReader::new(Cursor::new(&self.data), 0);
+    };
+    let mut reader = self
+      .data_reader()
+      .verify_digest(self.descriptor.verify_digest);
     let checksum = reader.read_entry_checksum(entry)?;
     Ok(Some(checksum))
   }
 }
 
-fn read_entry(entry: &IndexEntry) -> (u64, Vec<u8>) {
-  (entry.offset(), vec![0u8; entry.len() as usize])
+fn parse_entry(entry: &IndexEntry, buf: Vec<u8>, verify_digest: bool) -> crate::Result<Vec<u8>> {
+  let data = match entry.codec() {
+    // stored entries are returned as read
+    Codec::None => {
+      check_stored_len(&buf, entry.content_length())?;
+      buf
+    }
+    _ => decompress_entry(entry, &buf)?,
+  };
+  if verify_digest {
+    verify_entry_digest(entry, &data)?;
+  }
+  Ok(data)
 }
 
-fn parse_entry(buf: &[u8]) -> crate::Result<Vec<u8>> {
-  let decompressed = decompress_size_prepended(buf)?;
-  Ok(decompressed)
+/// Resolves the position of `len` bytes at `offset` in the data section, failing with an
+/// unexpected EOF error when they lie beyond it.
+pub(crate) fn data_position(
+  base_offset: u64,
+  data_size: Option<u64>,
+  offset: u64,
+  len: u64,
+) -> crate::Result<u64> {
+  let end = offset.checked_add(len);
+  match (base_offset.checked_add(offset), end) {
+    (Some(position), Some(end)) if data_size.is_none_or(|size| end <= size) => Ok(position),
+    _ => Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into()),
+  }
 }
 
-fn read_entry_checksum(entry: &IndexEntry) -> (u64, [u8; CHECKSUM_LEN]) {
-  (entry.offset() + entry.len(), [0u8; CHECKSUM_LEN])
+/// Returns the offset of `b` bytes after `a`, failing like an out of bounds read on overflow.
+fn checked_offset(a: u64, b: u64) -> crate::Result<u64> {
+  a.checked_add(b)
+    .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into())
 }
 
 pub(crate) struct BundleDataReader<R: Read + Seek> {
   r: R,
   base_offset: u64,
+  verify_digest: bool,
+  limits: ReaderLimits,
+  data_size: Option<u64>,
 }
 
 impl<R: Read + Seek> BundleDataReader<R> {
   pub fn new(r: R, base_offset: u64) -> Self {
-    Self { r, base_offset }
+    Self {
+      r,
+      base_offset,
+      verify_digest: false,
+      limits: ReaderLimits::default(),
+      data_size: None,
+    }
+  }
+
+  pub fn verify_digest(mut self, verify: bool) -> Self {
+    self.verify_digest = verify;
+    self
+  }
+
+  pub fn limits(mut self, limits: ReaderLimits) -> Self {
+    self.limits = limits;
+    self
+  }
+
+  /// Sets the size of the data section, if known, to reject entries lying beyond it.
+  pub fn data_size(mut self, size: Option<u64>) -> Self {
+    self.data_size = size;
+    self
+  }
+
+  fn read_at(&mut self, offset: u64, len: u64) -> crate::Result<Vec<u8>> {
+    let position = data_position(self.base_offset, self.data_size, offset, len)?;
+    self.r.seek(SeekFrom::Start(position))?;
+    read_exact_vec(&mut self.r, len)
   }
 
   pub fn read_entry_data(&mut self, entry: &IndexEntry) -> crate::Result<Vec<u8>> {
-    let (offset, mut buf) = read_entry(entry);
-    self.r.seek(SeekFrom::Start(self.base_offset + offset))?;
-    self.r.read_exact(&mut buf)?;
-    parse_entry(&buf)
+    self.limits.check_entry(entry)?;
+    let buf = self.read_at(entry.offset(), entry.len())?;
+    parse_entry(entry, buf, self.verify_digest)
+  }
+
+  pub fn read_entry_range(
+    &mut self,
+    entry: &IndexEntry,
+    range: Range<u64>,
+  ) -> crate::Result<Vec<u8>> {
+    self.limits.check_entry(entry)?;
+    let span = EntrySpan::new(entry, range);
+    let offset = checked_offset(entry.offset(), span.offset)?;
+    let buf = self.read_at(offset, span.len)?;
+    span.decode(entry, &buf)
+  }
+
+  pub fn read_variant_data(&mut self, variant: &EntryVariant) -> crate::Result<Vec<u8>> {
+    self.limits.check_entry_size(variant.len())?;
+    let data = self.read_at(variant.offset(), variant.len())?;
+    if self.verify_digest {
+      verify_variant_digest(variant, &data)?;
+    }
+    Ok(data)
   }
 
   pub fn read_entry_checksum(&mut self, entry: &IndexEntry) -> crate::Result<u32> {
-    let (offset, mut buf) = read_entry_checksum(entry);
-    self.r.seek(SeekFrom::Start(self.base_offset + offset))?;
-    self.r.read_exact(&mut buf)?;
+    let offset = checked_offset(entry.offset(), entry.len())?;
+    let buf = self.read_at(offset, CHECKSUM_LEN as u64)?;
     Ok(parse_checksum(&buf))
   }
 }
@@ -270,42 +687,96 @@ impl<R: Read + Seek> BundleDataReader<R> {
 pub(crate) struct AsyncBundleDataReader<R: AsyncRead + AsyncSeek + Unpin> {
   r: R,
   base_offset: u64,
+  verify_digest: bool,
+  limits: ReaderLimits,
+  data_size: Option<u64>,
 }
 
 #[cfg(feature = "async")]
 impl<R: AsyncRead + AsyncSeek + Unpin> AsyncBundleDataReader<R> {
   pub fn new(r: R, base_offset: u64) -> Self {
-    Self { r, base_offset }
+    Self {
+      r,
+      base_offset,
+      verify_digest: false,
+      limits: ReaderLimits::default(),
+      data_size: None,
+    }
   }
 
-  pub async fn read_entry_data(&mut self, entry: &IndexEntry) -> crate::Result<Vec<u8>> {
-    let (offset, mut buf) = read_entry(entry);
+  pub fn verify_digest(mut self, verify: bool) -> Self {
+    self.verify_digest = verify;
     self
-      .r
-      .seek(SeekFrom::Start(self.base_offset + offset))
-      .await?;
-    self.r.read_exact(&mut buf).await?;
-    parse_entry(&buf)
   }
 
-  pub async fn read_entry_checksum(&mut self, entry: &IndexEntry) -> crate::Result<u32> {
-    let (offset, mut buf) = read_entry_checksum(entry);
+  pub fn limits(mut self, limits: ReaderLimits) -> Self {
+    self.limits = limits;
     self
-      .r
-      .seek(SeekFrom::Start(self.base_offset + offset))
-      .await?;
-    self.r.read_exact(&mut buf).await?;
+  }
+
+  /// Sets the size of the data section, if known, to reject entries lying beyond it.
+  pub fn data_size(mut self, size: Option<u64>) -> Self {
+    self.data_size = size;
+    self
+  }
+
+  async fn read_at(&mut self, offset: u64, len: u64) -> crate::Result<Vec<u8>> {
+    let position = data_position(self.base_offset, self.data_size, offset, len)?;
+    self.r.seek(SeekFrom::Start(position)).await?;
+    async_read_exact_vec(&mut self.r, len).await
+  }
+
+  pub async fn read_entry_data(&mut self, entry: &IndexEntry) -> crate::Result<Vec<u8>> {
+    self.limits.check_entry(entry)?;
+    let buf = self.read_at(entry.offset(), entry.len()).await?;
+    parse_entry(entry, buf, self.verify_digest)
+  }
+
+  pub async fn read_entry_range(
+    &mut self,
+    entry: &IndexEntry,
+    range: Range<u64>,
+  ) -> crate::Result<Vec<u8>> {
+    self.limits.check_entry(entry)?;
+    let span = EntrySpan::new(entry, range);
+    let offset = checked_offset(entry.offset(), span.offset)?;
+    let buf = self.read_at(offset, span.len).await?;
+    span.decode(entry, &buf)
+  }
+
+  pub async fn read_variant_data(&mut self, variant: &EntryVariant) -> crate::Result<Vec<u8>> {
+    self.limits.check_entry_size(variant.len())?;
+    let data = self.read_at(variant.offset(), variant.len()).await?;
+    if self.verify_digest {
+      verify_variant_digest(variant, &data)?;
+    }
+    Ok(data)
+  }
+
+  pub async fn read_entry_checksum(&mut self, entry: &IndexEntry) -> crate::Result<u32> {
+    let offset = checked_offset(entry.offset(), entry.len())?;
+    let buf = self.read_at(offset, CHECKSUM_LEN as u64).await?;
     Ok(parse_checksum(&buf))
   }
 }
 
 pub struct BundleReader<R: Read + Seek> {
   r: R,
+  limits: ReaderLimits,
 }
 
 impl<R: Read + Seek> BundleReader<R> {
   pub fn new(r: R) -> Self {
-    Self { r }
+    Self {
+      r,
+      limits: ReaderLimits::default(),
+    }
+  }
+
+  /// Sets the limits applied while reading, and to file data read from the read bundle.
+  pub fn limits(mut self, limits: ReaderLimits) -> Self {
+    self.limits = limits;
+    self
   }
 
   pub fn read_header(&mut self) -> crate::Result<Header> {
@@ -315,16 +786,37 @@ impl<R: Read + Seek> BundleReader<R> {
   }
 
   pub fn read_index(&mut self, header: Header) -> crate::Result<Index> {
-    let mut reader = IndexReader::new(&mut self.r, header);
+    let options = IndexReaderOptions::new().limits(self.limits);
+    let mut reader = IndexReader::new_with_options(&mut self.r, header, options);
     let index = reader.read()?;
     Ok(index)
   }
 
+  /// Reads the signatures section after the index, if the bundle has one.
+  pub fn read_signatures(&mut self, header: Header) -> crate::Result<Vec<BundleSignature>> {
+    if header.version() == Version::V1 {
+      return Ok(vec![]);
+    }
+    let offset = header.index_end_offset();
+    let end = self.r.seek(SeekFrom::End(0))?;
+    if end <= offset {
+      return Ok(vec![]);
+    }
+    self.limits.check_total_size(end - offset)?;
+    self.r.seek(SeekFrom::Start(offset))?;
+    let buf = read_exact_vec(&mut self.r, end - offset)?;
+    parse_signatures(&buf)
+  }
+
   pub fn read_data(&mut self, header: Header) -> crate::Result<Vec<u8>> {
-    self.r.seek(SeekFrom::Start(header.index_end_offset()))?;
-    let mut data = vec![];
-    self.r.read_to_end(&mut data)?;
-    Ok(data)
+    self.r.seek(SeekFrom::Start(header.data_offset()))?;
+    match header.data_size() {
+      Some(size) => {
+        self.limits.check_total_size(size)?;
+        read_exact_vec(&mut self.r, size)
+      }
+      None => read_to_end_limited(&mut self.r, self.limits.max_total_size),
+    }
   }
 }
 
@@ -332,7 +824,10 @@ impl<R: Read + Seek> Reader<BundleDescriptor> for BundleReader<R> {
   fn read(&mut self) -> crate::Result<BundleDescriptor> {
     let header = self.read_header()?;
     let index = self.read_index(header)?;
-    Ok(BundleDescriptor { header, index })
+    let mut descriptor = BundleDescriptor::new(header, index);
+    descriptor.limits = self.limits;
+    descriptor.signatures = self.read_signatures(header)?;
+    Ok(descriptor)
   }
 }
 
@@ -341,9 +836,12 @@ impl<R: Read + Seek> Reader<Bundle> for BundleReader<R> {
     let header = self.read_header()?;
     let index = self.read_index(header)?;
     let data = self.read_data(header)?;
+    let mut descriptor = BundleDescriptor::new(header, index);
+    descriptor.limits = self.limits;
+    descriptor.signatures = self.read_signatures(header)?;
     Ok(Bundle {
-      descriptor: BundleDescriptor { header, index },
-      data,
+      descriptor,
+      data: data.into(),
     })
   }
 }
@@ -351,12 +849,22 @@ impl<R: Read + Seek> Reader<Bundle> for BundleReader<R> {
 #[cfg(feature = "async")]
 pub struct AsyncBundleReader<R: AsyncRead + AsyncSeek + Unpin> {
   r: R,
+  limits: ReaderLimits,
 }
 
 #[cfg(feature = "async")]
 impl<R: AsyncRead + AsyncSeek + Unpin> AsyncBundleReader<R> {
   pub fn new(r: R) -> Self {
-    Self { r }
+    Self {
+      r,
+      limits: ReaderLimits::default(),
+    }
+  }
+
+  /// Sets the limits applied while reading, and to file data read from the read bundle.
+  pub fn limits(mut self, limits: ReaderLimits) -> Self {
+    self.limits = limits;
+    self
   }
 
   pub async fn read_header(&mut self) -> crate::Result<Header> {
@@ -366,19 +874,37 @@ impl<R: AsyncRead + AsyncSeek + Unpin> AsyncBundleReader<R> {
   }
 
   pub async fn read_index(&mut self, header: Header) -> crate::Result<Index> {
-    let mut reader = AsyncIndexReader::new(&mut self.r, header);
+    let options = IndexReaderOptions::new().limits(self.limits);
+    let mut reader = AsyncIndexReader::new_with_options(&mut self.r, header, options);
     let index = reader.read().await?;
     Ok(index)
   }
 
+  /// Reads the signatures section after the index, if the bundle has one.
+  pub async fn read_signatures(&mut self, header: Header) -> crate::Result<Vec<BundleSignature>> {
+    if header.version() == Version::V1 {
+      return Ok(vec![]);
+    }
+    let offset = header.index_end_offset();
+    let end = self.r.seek(SeekFrom::End(0)).await?;
+    if end <= offset {
+      return Ok(vec![]);
+    }
+    self.limits.check_total_size(end - offset)?;
+    self.r.seek(SeekFrom::Start(offset)).await?;
+    let buf = async_read_exact_vec(&mut self.r, end - offset).await?;
+    parse_signatures(&buf)
+  }
+
   pub async fn read_data(&mut self, header: Header) -> crate::Result<Vec<u8>> {
-    self
-      .r
-      .seek(SeekFrom::Start(header.index_end_offset()))
-      .await?;
-    let mut data = vec![];
-    self.r.read_to_end(&mut data).await?;
-    Ok(data)
+    self.r.seek(SeekFrom::Start(header.data_offset())).await?;
+    match header.data_size() {
+      Some(size) => {
+        self.limits.check_total_size(size)?;
+        async_read_exact_vec(&mut self.r, size).await
+      }
+      None => async_read_to_end_limited(&mut self.r, self.limits.max_total_size).await,
+    }
   }
 }
 
@@ -387,7 +913,10 @@ impl<R: AsyncRead + AsyncSeek + Unpin> AsyncReader<BundleDescriptor> for AsyncBu
   async fn read(&mut self) -> crate::Result<BundleDescriptor> {
     let header = self.read_header().await?;
     let index = self.read_index(header).await?;
-    Ok(BundleDescriptor { header, index })
+    let mut descriptor = BundleDescriptor::new(header, index);
+    descriptor.limits = self.limits;
+    descriptor.signatures = self.read_signatures(header).await?;
+    Ok(descriptor)
   }
 }
 
@@ -397,13 +926,35 @@ impl<R: AsyncRead + AsyncSeek + Unpin> AsyncReader<Bundle> for AsyncBundleReader
     let header = self.read_header().await?;
     let index = self.read_index(header).await?;
     let data = self.read_data(header).await?;
+    let mut descriptor = BundleDescriptor::new(header, index);
+    descriptor.limits = self.limits;
+    descriptor.signatures = self.read_signatures(header).await?;
     Ok(Bundle {
-      descriptor: BundleDescriptor { header, index },
-      data,
+      descriptor,
+      data: data.into(),
     })
   }
 }
 
+/// Encodes the signatures section written after the index, which is empty without signatures.
+fn signatures_section(bundle: &Bundle) -> crate::Result<Vec<u8>> {
+  let signatures = &bundle.descriptor.signatures;
+  if signatures.is_empty() {
+    return Ok(vec![]);
+  }
+  let version = bundle.descriptor.header.version();
+  if version == Version::V1 {
+    return Err(crate::Error::UnsupportedSignature(version));
+  }
+  write_signatures(signatures)
+}
+
+fn index_writer_options(bundle: &Bundle) -> IndexWriterOptions {
+  let mut options = IndexWriterOptions::new();
+  options.version(bundle.descriptor.header.version());
+  options
+}
+
 pub struct BundleWriter<W: Write> {
   w: W,
 }
@@ -416,12 +967,26 @@ impl<W: Write> BundleWriter<W> {
 
 impl<W: Write> Writer<Bundle> for BundleWriter<W> {
   fn write(&mut self, data: &Bundle) -> crate::Result<usize> {
-    let header_len = HeaderWriter::new(&mut self.w).write(&data.descriptor.header)?;
-    let index_len = IndexWriter::new(&mut self.w).write(&data.descriptor.index)?;
+    let header = &data.descriptor.header;
+    let signatures = signatures_section(data)?;
+    let header_len = HeaderWriter::new(&mut self.w).write(header)?;
+    let index_len = match header.version() {
+      Version::V1 => {
+        let index_len = IndexWriter::new_with_options(&mut self.w, index_writer_options(data))
+          .write(&data.descriptor.index)?;
+        self.w.write_all(&data.data)?;
+        index_len
+      }
+      Version::V2 => {
+        self.w.write_all(&data.data)?;
+        IndexWriter::new_with_options(&mut self.w, index_writer_options(data))
+          .write(&data.descriptor.index)?
+      }
+    };
+    self.w.write_all(&signatures)?;
     let data_len = data.data.len();
-    self.w.write_all(&data.data)?;
     self.w.flush()?;
-    Ok(header_len + index_len + data_len)
+    Ok(header_len + index_len + data_len + signatures.len())
   }
 }
 
@@ -440,24 +1005,35 @@ impl<W: AsyncWrite + Unpin> AsyncBundleWriter<W> {
 #[cfg(feature = "async")]
 impl<W: AsyncWrite + Unpin> AsyncWriter<Bundle> for AsyncBundleWriter<W> {
   async fn write(&mut self, data: &Bundle) -> crate::Result<usize> {
-    let header_len = AsyncHeaderWriter::new(&mut self.w)
-      .write(&data.descriptor.header)
-      .await?;
-    let index_len = AsyncIndexWriter::new(&mut self.w)
-      .write(&data.descriptor.index)
-      .await?;
+    let header = &data.descriptor.header;
+    let signatures = signatures_section(data)?;
+    let header_len = AsyncHeaderWriter::new(&mut self.w).write(header).await?;
+    let index_len = match header.version() {
+      Version::V1 => {
+        let index_len = AsyncIndexWriter::new_with_options(&mut self.w, index_writer_options(data))
+          .write(&data.descriptor.index)
+          .await?;
+        self.w.write_all(&data.data).await?;
+        index_len
+      }
+      Version::V2 => {
+        self.w.write_all(&data.data).await?;
+        AsyncIndexWriter::new_with_options(&mut self.w, index_writer_options(data))
+          .write(&data.descriptor.index)
+          .await?
+      }
+    };
+    self.w.write_all(&signatures).await?;
     let data_len = data.data.len();
-    self.w.write_all(&data.data).await?;
     self.w.flush().await?;
-    Ok(header_len + index_len + data_len)
+    Ok(header_len + index_len + data_len + signatures.len())
   }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
-  use crate::version::Version;
-  use crate::BundleEntry;
+  use crate::{BundleBuilderOptions, BundleEntry};
   use http::{header, HeaderMap};
   use std::io::Cursor;
 
@@ -492,7 +1068,7 @@ mod tests {
 
     let html = descriptor.index.get_entry("/index.html").unwrap();
     assert_eq!(html.content_type(), "text/html");
-    assert_eq!(html.content_length(), INDEX_HTML.as_bytes().len() as u64);
+    assert_eq!(html.content_length(), INDEX_HTML.len() as u64);
     assert_eq!(html.offset(), 0);
     assert_eq!(html.len(), 98);
   }
@@ -528,6 +1104,200 @@ mod tests {
     assert!(bundle.get_data("/not_found.html").unwrap().is_none());
   }
 
+  #[test]
+  fn get_bytes() {
+    let mut options = BundleBuilderOptions::new();
+    options.content_type_codec("text/javascript", Codec::None);
+    let mut builder = BundleBuilder::new_with_options(options);
+    builder.set_version(Version::V2);
+    builder.insert_entry(
+      "/index.html",
+      BundleEntry::new(INDEX_HTML.as_bytes(), "text/html", None),
+    );
+    builder.insert_entry(
+      "/index.js",
+      BundleEntry::new(INDEX_JS.as_bytes(), "text/javascript", None),
+    );
+    let bundle = builder.build().unwrap();
+
+    let js = bundle.get_bytes("/index.js").unwrap().unwrap();
+    assert_eq!(js, INDEX_JS.as_bytes());
+    // stored entries share the data of the bundle
+    assert!(bundle.data.as_ptr_range().contains(&js.as_ptr()));
+    let html = bundle.get_bytes("/index.html").unwrap().unwrap();
+    assert_eq!(html, INDEX_HTML.as_bytes());
+    assert!(bundle.get_bytes("/not_found.html").unwrap().is_none());
+  }
+
+  #[test]
+  fn stored_size_mismatch() {
+    let mut options = BundleBuilderOptions::new();
+    options.codec(Codec::None);
+    let mut builder = BundleBuilder::new_with_options(options);
+    builder.insert_entry(
+      "/index.html",
+      BundleEntry::new(INDEX_HTML.as_bytes(), "text/html", None),
+    );
+    builder.set_version(Version::V2);
+    let mut bundle = builder.build().unwrap();
+    let len = INDEX_HTML.len() as u64;
+    let mut entry = IndexEntry::new(0, len, "text/html", len + 1);
+    entry.codec = Codec::None;
+    bundle.descriptor.index.insert_entry("/index.html", entry);
+    assert!(matches!(
+      bundle.get_data("/index.html"),
+      Err(crate::Error::EntrySizeMismatch { .. })
+    ));
+    assert!(matches!(
+      bundle.get_bytes("/index.html"),
+      Err(crate::Error::EntrySizeMismatch { .. })
+    ));
+  }
+
+  #[cfg(feature = "digest")]
+  #[test]
+  fn verify_digest() {
+    let mut options = BundleBuilderOptions::new();
+    options
+      .codec(Codec::None)
+      .digest(crate::DigestAlgorithm::Sha3_256);
+    let mut builder = BundleBuilder::new_with_options(options);
+    builder.set_version(Version::V2);
+    builder.insert_entry(
+      "/index.html",
+      BundleEntry::new(INDEX_HTML.as_bytes(), "text/html", None),
+    );
+    let bundle = builder.build().unwrap();
+    let entry = bundle
+      .descriptor()
+      .index()
+      .get_entry("/index.html")
+      .unwrap();
+    assert!(entry.digest().unwrap().verify(INDEX_HTML.as_bytes()));
+    let mut data = vec![];
+    BundleWriter::new(Cursor::new(&mut data))
+      .write(&bundle)
+      .unwrap();
+    // tamper the first byte of the stored entry
+    data[Header::V2_END_OFFSET as usize] ^= 0xff;
+
+    let mut descriptor: BundleDescriptor = BundleReader::new(Cursor::new(&data)).read().unwrap();
+    assert!(descriptor
+      .get_data(Cursor::new(&data), "/index.html")
+      .is_ok());
+    descriptor.set_verify_digest(true);
+    assert!(matches!(
+      descriptor.get_data(Cursor::new(&data), "/index.html"),
+      Err(crate::Error::DigestMismatch)
+    ));
+
+    let mut bundle = bundle;
+    bundle.set_verify_digest(true);
+    assert_eq!(
+      bundle.get_data("/index.html").unwrap().unwrap(),
+      INDEX_HTML.as_bytes()
+    );
+  }
+
+  #[test]
+  fn signatures_require_v2() {
+    let mut bundle = BundleBuilder::new().build().unwrap();
+    bundle.add_signature(BundleSignature::new("ed25519", None::<String>, "signature"));
+    assert!(matches!(
+      BundleWriter::new(vec![]).write(&bundle),
+      Err(crate::Error::UnsupportedSignature(Version::V1))
+    ));
+  }
+
+  #[test]
+  fn digest_requires_v2() {
+    let mut options = BundleBuilderOptions::new();
+    options.digest(crate::DigestAlgorithm::Sha3_256);
+    let mut builder = BundleBuilder::new_with_options(options);
+    builder.insert_entry(
+      "/index.html",
+      BundleEntry::new(INDEX_HTML.as_bytes(), "text/html", None),
+    );
+    assert!(matches!(
+      builder.build(),
+      Err(crate::Error::UnsupportedDigest(Version::V1))
+    ));
+  }
+
+  #[cfg(all(feature = "codec-zstd", feature = "codec-brotli"))]
+  #[test]
+  fn read_and_write_v2() {
+    let mut options = BundleBuilderOptions::new();
+    options
+      .codec(Codec::Zstd)
+      .content_type_codec("text/html", Codec::Brotli);
+    let mut builder = BundleBuilder::new_with_options(options);
+    builder.set_version(Version::V2);
+    builder.insert_entry(
+      "/index.html",
+      BundleEntry::new(INDEX_HTML.as_bytes(), "text/html", None),
+    );
+    builder.insert_entry(
+      "/index.js",
+      BundleEntry::new(INDEX_JS.as_bytes(), "text/javascript", None),
+    );
+    let bundle = builder.build().unwrap();
+    let mut data = vec![];
+    BundleWriter::new(Cursor::new(&mut data))
+      .write(&bundle)
+      .unwrap();
+    assert_eq!(data[Header::VERSION_OFFSET as usize], 0x02);
+
+    let descriptor: BundleDescriptor = BundleReader::new(Cursor::new(&data)).read().unwrap();
+    assert_eq!(descriptor.header().version(), Version::V2);
+    let html = descriptor.index().get_entry("/index.html").unwrap();
+    assert_eq!(html.codec(), Codec::Brotli);
+    let js = descriptor.index().get_entry("/index.js").unwrap();
+    assert_eq!(js.codec(), Codec::Zstd);
+    assert_eq!(
+      descriptor
+        .get_data(Cursor::new(&data), "/index.html")
+        .unwrap()
+        .unwrap(),
+      INDEX_HTML.as_bytes()
+    );
+
+    let read_bundle: Bundle = BundleReader::new(Cursor::new(&data)).read().unwrap();
+    assert_eq!(read_bundle, bundle);
+    assert_eq!(
+      read_bundle.get_data("/index.js").unwrap().unwrap(),
+      INDEX_JS.as_bytes()
+    );
+  }
+
+  #[cfg(all(feature = "async", feature = "codec-brotli"))]
+  #[tokio::test]
+  async fn async_get_data_v2() {
+    let mut options = BundleBuilderOptions::new();
+    options.codec(Codec::Brotli);
+    let mut builder = BundleBuilder::new_with_options(options);
+    builder.set_version(Version::V2);
+    builder.insert_entry(
+      "/index.html",
+      BundleEntry::new(INDEX_HTML.as_bytes(), "text/html", None),
+    );
+    let bundle = builder.build().unwrap();
+    let mut data = vec![];
+    AsyncBundleWriter::new(Cursor::new(&mut data))
+      .write(&bundle)
+      .await
+      .unwrap();
+    let descriptor =
+      AsyncReader::<BundleDescriptor>::read(&mut AsyncBundleReader::new(Cursor::new(&data)))
+        .await
+        .unwrap();
+    let html = descriptor
+      .async_get_data(Cursor::new(&data), "/index.html")
+      .await
+      .unwrap();
+    assert_eq!(html.unwrap(), INDEX_HTML.as_bytes());
+  }
+
   #[cfg(feature = "async")]
   #[tokio::test]
   async fn async_get_data() {
@@ -554,4 +1324,43 @@ mod tests {
       .unwrap();
     assert_eq!(html.unwrap(), INDEX_HTML.as_bytes());
   }
+
+  #[cfg(all(feature = "async", feature = "digest", feature = "precompress"))]
+  #[tokio::test]
+  async fn async_get_variant_verify_digest() {
+    let mut options = BundleBuilderOptions::new();
+    options
+      .digest(crate::DigestAlgorithm::Sha3_256)
+      .precompress(ContentEncoding::Gzip);
+    let mut builder = BundleBuilder::new_with_options(options);
+    builder.set_version(Version::V2);
+    builder.insert_entry(
+      "/index.html",
+      BundleEntry::new(INDEX_HTML.repeat(10).as_bytes(), "text/html", None),
+    );
+    let bundle = builder.build().unwrap();
+    let mut data = vec![];
+    BundleWriter::new(Cursor::new(&mut data))
+      .write(&bundle)
+      .unwrap();
+    let variant = bundle
+      .descriptor()
+      .index()
+      .get_entry("/index.html")
+      .unwrap()
+      .variant(ContentEncoding::Gzip)
+      .cloned()
+      .unwrap();
+    let offset = bundle.descriptor().header().data_offset() + variant.offset();
+    data[offset as usize + 4] ^= 0xff;
+
+    let mut descriptor: BundleDescriptor = BundleReader::new(Cursor::new(&data)).read().unwrap();
+    descriptor.set_verify_digest(true);
+    assert!(matches!(
+      descriptor
+        .async_get_variant(Cursor::new(&data), "/index.html", ContentEncoding::Gzip)
+        .await,
+      Err(crate::Error::DigestMismatch)
+    ));
+  }
 }
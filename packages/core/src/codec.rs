@@ -0,0 +1,470 @@
+#[cfg(feature = "codec-brotli")]
+use brotli::{BrotliDecompressStream, BrotliResult, BrotliState, HeapAlloc, HuffmanCode};
+use lz4_flex::compress_prepend_size;
+use std::fmt::{Display, Formatter};
+#[cfg(any(feature = "codec-zstd", feature = "codec-brotli"))]
+use std::io::{Read, Write};
+#[cfg(feature = "codec-zstd")]
+use zstd::stream::raw::Operation;
+
+#[cfg(feature = "codec-zstd")]
+const ZSTD_LEVEL: i32 = 19;
+#[cfg(feature = "codec-brotli")]
+const BROTLI_QUALITY: u32 = 11;
+#[cfg(feature = "codec-brotli")]
+const BROTLI_LG_WINDOW_SIZE: u32 = 22;
+#[cfg(feature = "codec-brotli")]
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
+/// Compression codec applied to a single entry in the data section.
+///
+/// `V1` bundles always use [`Codec::Lz4`]. `V2` bundles record the codec of every entry in the
+/// index, so entries within one bundle can use different codecs.
+///
+/// Zstandard and Brotli need the `codec-zstd` and `codec-brotli` features. Without them, entries
+/// using these codecs fail to compress or decompress with [`Error::FeatureNotEnabled`].
+///
+/// [`Error::FeatureNotEnabled`]: crate::Error::FeatureNotEnabled
+///
+/// # Example
+///
+/// ```
+/// use wvb::Codec;
+///
+/// assert_eq!(Codec::default(), Codec::Lz4);
+/// assert_eq!(Codec::from_id(2).unwrap(), Codec::Zstd);
+/// assert_eq!(Codec::Brotli.id(), 3);
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Default)]
+pub enum Codec {
+  /// Stored as-is without compression
+  None,
+  /// LZ4 block with the decompressed size prepended
+  #[default]
+  Lz4,
+  /// Zstandard frame
+  Zstd,
+  /// Brotli stream
+  Brotli,
+}
+
+impl Codec {
+  /// Returns the identifier of this codec stored in the index.
+  pub const fn id(&self) -> u8 {
+    match self {
+      Codec::None => 0x00,
+      Codec::Lz4 => 0x01,
+      Codec::Zstd => 0x02,
+      Codec::Brotli => 0x03,
+    }
+  }
+
+  /// Resolves a codec from its identifier stored in the index.
+  pub fn from_id(id: u8) -> crate::Result<Self> {
+    match id {
+      0x00 => Ok(Codec::None),
+      0x01 => Ok(Codec::Lz4),
+      0x02 => Ok(Codec::Zstd),
+      0x03 => Ok(Codec::Brotli),
+      _ => Err(crate::Error::InvalidCodec(id)),
+    }
+  }
+
+  /// Compresses the given data with this codec.
+  ///
+  /// The output is identical to feeding the data through the incremental encoder used by
+  /// [`BundleStreamWriter`](crate::BundleStreamWriter).
+  pub fn compress(&self, data: &[u8]) -> crate::Result<Vec<u8>> {
+    let compressed = match self {
+      Codec::None => data.to_vec(),
+      Codec::Lz4 => compress_prepend_size(data),
+      Codec::Zstd | Codec::Brotli => {
+        let mut encoder = self.encoder()?;
+        let mut buf = encoder.update(data)?;
+        buf.extend(encoder.finish()?);
+        buf
+      }
+    };
+    Ok(compressed)
+  }
+
+  /// Decompresses the given data with this codec.
+  ///
+  /// `content_length` is the original size of the data recorded in the index. Decompressing
+  /// fails if the data does not decompress to exactly that size, and never produces more, so
+  /// crafted data can't expand beyond the size recorded in the index.
+  pub fn decompress(&self, data: &[u8], content_length: u64) -> crate::Result<Vec<u8>> {
+    let decompressed = match self {
+      Codec::None => {
+        check_stored_len(data, content_length)?;
+        data.to_vec()
+      }
+      Codec::Lz4 => {
+        let (size, block) = uncompressed_size(data)?;
+        if size as u64 != content_length {
+          return Err(size_differs(content_length, size as u64));
+        }
+        // LZ4 can't expand data more than 255 times, so the block ends before such sizes
+        if size > block.len().saturating_mul(255).saturating_add(16) {
+          return Err(lz4_flex::block::DecompressError::ExpectedAnotherByte.into());
+        }
+        lz4_flex::decompress(block, size)?
+      }
+      #[cfg(feature = "codec-zstd")]
+      Codec::Zstd => read_limited(
+        zstd::stream::read::Decoder::new(data)?,
+        data,
+        content_length,
+      )?,
+      #[cfg(feature = "codec-brotli")]
+      Codec::Brotli => read_limited(
+        brotli::Decompressor::new(data, BROTLI_BUFFER_SIZE),
+        data,
+        content_length,
+      )?,
+      #[allow(unreachable_patterns)]
+      codec => return Err(codec.not_enabled()),
+    };
+    Ok(decompressed)
+  }
+}
+
+/// Returns the size prepended to LZ4 data by `compress_prepend_size`, and the block after it.
+fn uncompressed_size(data: &[u8]) -> crate::Result<(usize, &[u8])> {
+  match data.split_first_chunk::<4>() {
+    Some((size, block)) => Ok((u32::from_le_bytes(*size) as usize, block)),
+    None => Err(lz4_flex::block::DecompressError::ExpectedAnotherByte.into()),
+  }
+}
+
+/// Reads decompressed data of exactly `content_length` bytes.
+#[cfg(any(feature = "codec-zstd", feature = "codec-brotli"))]
+fn read_limited<R: Read>(r: R, data: &[u8], content_length: u64) -> crate::Result<Vec<u8>> {
+  // the capacity is only a hint, bounded by the compressed size
+  let capacity = content_length.min((data.len() as u64).saturating_mul(16));
+  let mut buf = Vec::with_capacity(capacity as usize);
+  r.take(content_length.saturating_add(1))
+    .read_to_end(&mut buf)?;
+  if buf.len() as u64 != content_length {
+    return Err(size_differs(content_length, buf.len() as u64));
+  }
+  Ok(buf)
+}
+
+fn size_differs(expected: u64, actual: u64) -> crate::Error {
+  crate::Error::EntrySizeMismatch { expected, actual }
+}
+
+/// Checks that data stored without compression is exactly `content_length` bytes.
+pub(crate) fn check_stored_len(data: &[u8], content_length: u64) -> crate::Result<()> {
+  if data.len() as u64 != content_length {
+    return Err(size_differs(content_length, data.len() as u64));
+  }
+  Ok(())
+}
+
+impl Codec {
+  /// Creates an encoder which compresses data chunk by chunk.
+  pub(crate) fn encoder(&self) -> crate::Result<CodecEncoder> {
+    let encoder = match self {
+      Codec::None => CodecEncoder::None,
+      Codec::Lz4 => CodecEncoder::Lz4(Vec::new()),
+      #[cfg(feature = "codec-zstd")]
+      Codec::Zstd => CodecEncoder::Zstd(zstd::stream::write::Encoder::new(Vec::new(), ZSTD_LEVEL)?),
+      #[cfg(feature = "codec-brotli")]
+      Codec::Brotli => CodecEncoder::Brotli(Box::new(brotli::CompressorWriter::new(
+        Vec::new(),
+        BROTLI_BUFFER_SIZE,
+        BROTLI_QUALITY,
+        BROTLI_LG_WINDOW_SIZE,
+      ))),
+      #[allow(unreachable_patterns)]
+      codec => return Err(codec.not_enabled()),
+    };
+    Ok(encoder)
+  }
+
+  /// Returns the error of a codec whose feature is not enabled.
+  fn not_enabled(&self) -> crate::Error {
+    let feature = match self {
+      Codec::Zstd => "codec-zstd",
+      Codec::Brotli => "codec-brotli",
+      Codec::None | Codec::Lz4 => unreachable!("{self} is always enabled"),
+    };
+    crate::Error::FeatureNotEnabled(feature)
+  }
+}
+
+/// Incremental encoder of a [`Codec`].
+///
+/// LZ4 entries are stored as a single block with the decompressed size prepended, so the LZ4
+/// encoder buffers its input, a whole entry or one of its blocks, and compresses it on finish.
+pub(crate) enum CodecEncoder {
+  None,
+  Lz4(Vec<u8>),
+  #[cfg(feature = "codec-zstd")]
+  Zstd(zstd::stream::write::Encoder<'static, Vec<u8>>),
+  #[cfg(feature = "codec-brotli")]
+  Brotli(Box<brotli::CompressorWriter<Vec<u8>>>),
+}
+
+impl CodecEncoder {
+  /// Feeds a chunk of data and returns the compressed bytes produced so far.
+  pub(crate) fn update(&mut self, chunk: &[u8]) -> crate::Result<Vec<u8>> {
+    let produced = match self {
+      CodecEncoder::None => chunk.to_vec(),
+      CodecEncoder::Lz4(buf) => {
+        buf.extend_from_slice(chunk);
+        Vec::new()
+      }
+      #[cfg(feature = "codec-zstd")]
+      CodecEncoder::Zstd(w) => {
+        w.write_all(chunk)?;
+        std::mem::take(w.get_mut())
+      }
+      #[cfg(feature = "codec-brotli")]
+      CodecEncoder::Brotli(w) => {
+        w.write_all(chunk)?;
+        std::mem::take(w.get_mut())
+      }
+    };
+    Ok(produced)
+  }
+
+  /// Finishes the stream and returns the remaining compressed bytes.
+  pub(crate) fn finish(self) -> crate::Result<Vec<u8>> {
+    let produced = match self {
+      CodecEncoder::None => Vec::new(),
+      CodecEncoder::Lz4(buf) => compress_prepend_size(&buf),
+      #[cfg(feature = "codec-zstd")]
+      CodecEncoder::Zstd(w) => w.finish()?,
+      #[cfg(feature = "codec-brotli")]
+      CodecEncoder::Brotli(w) => w.into_inner(),
+    };
+    Ok(produced)
+  }
+}
+
+/// Incremental decoder of a [`Codec`], decompressing data as it arrives into bounded output.
+///
+/// LZ4 entries are stored as a single block which can't be decompressed incrementally, so only
+/// Zstandard and Brotli have an incremental decoder.
+pub(crate) enum CodecDecoder {
+  #[cfg(feature = "codec-zstd")]
+  Zstd {
+    decoder: zstd::stream::raw::Decoder<'static>,
+    finished: bool,
+  },
+  #[cfg(feature = "codec-brotli")]
+  Brotli {
+    state: Box<BrotliState<HeapAlloc<u8>, HeapAlloc<u32>, HeapAlloc<HuffmanCode>>>,
+    result: BrotliResult,
+  },
+}
+
+impl CodecDecoder {
+  /// Creates an incremental decoder, or returns `None` if the codec has none.
+  ///
+  /// Codecs whose feature is not enabled have none either, so decoding them fails like
+  /// [`Codec::decompress`].
+  pub(crate) fn new(codec: Codec) -> crate::Result<Option<Self>> {
+    match codec {
+      #[cfg(feature = "codec-zstd")]
+      Codec::Zstd => Ok(Some(CodecDecoder::Zstd {
+        decoder: zstd::stream::raw::Decoder::new()?,
+        finished: false,
+      })),
+      #[cfg(feature = "codec-brotli")]
+      Codec::Brotli => Ok(Some(CodecDecoder::Brotli {
+        state: Box::new(BrotliState::new(
+          HeapAlloc::new(0),
+          HeapAlloc::new(0),
+          HeapAlloc::new(HuffmanCode::default()),
+        )),
+        result: BrotliResult::NeedsMoreInput,
+      })),
+      _ => Ok(None),
+    }
+  }
+
+  /// Decompresses data from `input` into `output`, returning the number of bytes consumed and
+  /// produced.
+  #[cfg_attr(
+    not(any(feature = "codec-zstd", feature = "codec-brotli")),
+    allow(unused_variables)
+  )]
+  pub(crate) fn decode(
+    &mut self,
+    input: &[u8],
+    output: &mut [u8],
+  ) -> crate::Result<(usize, usize)> {
+    match *self {
+      #[cfg(feature = "codec-zstd")]
+      CodecDecoder::Zstd {
+        ref mut decoder,
+        ref mut finished,
+      } => {
+        let status = decoder.run_on_buffers(input, output)?;
+        // the decoder expects the next frame once one ends
+        *finished |= status.remaining == 0;
+        Ok((status.bytes_read, status.bytes_written))
+      }
+      #[cfg(feature = "codec-brotli")]
+      CodecDecoder::Brotli {
+        ref mut state,
+        ref mut result,
+      } => {
+        let mut available_in = input.len();
+        let mut input_offset = 0;
+        let mut available_out = output.len();
+        let mut output_offset = 0;
+        let mut total_out = 0;
+        *result = BrotliDecompressStream(
+          &mut available_in,
+          &mut input_offset,
+          input,
+          &mut available_out,
+          &mut output_offset,
+          output,
+          &mut total_out,
+          state,
+        );
+        if let BrotliResult::ResultFailure = result {
+          return Err(std::io::Error::from(std::io::ErrorKind::InvalidData).into());
+        }
+        Ok((input_offset, output_offset))
+      }
+    }
+  }
+
+  /// Returns `true` once the end of the compressed stream has been decoded.
+  pub(crate) fn is_finished(&self) -> bool {
+    match *self {
+      #[cfg(feature = "codec-zstd")]
+      CodecDecoder::Zstd { finished, .. } => finished,
+      #[cfg(feature = "codec-brotli")]
+      CodecDecoder::Brotli { ref result, .. } => matches!(result, BrotliResult::ResultSuccess),
+    }
+  }
+}
+
+impl Display for Codec {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let s = match self {
+      Self::None => "none",
+      Self::Lz4 => "lz4",
+      Self::Zstd => "zstd",
+      Self::Brotli => "brotli",
+    };
+    f.write_str(s)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::CODECS;
+
+  const DATA: &[u8] = br#"function hello() { console.log('Hello World'); } hello(); hello();"#;
+
+  #[test]
+  fn id() {
+    for codec in [Codec::None, Codec::Lz4, Codec::Zstd, Codec::Brotli] {
+      assert_eq!(Codec::from_id(codec.id()).unwrap(), codec);
+    }
+    assert!(matches!(
+      Codec::from_id(0xff),
+      Err(crate::Error::InvalidCodec(0xff))
+    ));
+  }
+
+  #[test]
+  fn compress_and_decompress() {
+    for &codec in CODECS {
+      let compressed = codec.compress(DATA).unwrap();
+      let decompressed = codec.decompress(&compressed, DATA.len() as u64).unwrap();
+      assert_eq!(decompressed, DATA, "codec: {codec}");
+    }
+  }
+
+  #[test]
+  fn decompress_size_mismatch() {
+    for &codec in CODECS {
+      let compressed = codec.compress(DATA).unwrap();
+      let len = DATA.len() as u64;
+      for content_length in [len - 1, len + 1, u32::MAX as u64] {
+        assert!(
+          codec.decompress(&compressed, content_length).is_err(),
+          "codec: {codec}, content_length: {content_length}"
+        );
+      }
+    }
+    // a crafted LZ4 size prefix
+    let mut crafted = u32::MAX.to_le_bytes().to_vec();
+    crafted.extend([0u8; 8]);
+    assert!(Codec::Lz4.decompress(&crafted, u32::MAX as u64).is_err());
+    assert!(Codec::Lz4.decompress(&[0, 0], 0).is_err());
+  }
+
+  #[test]
+  fn encoder() {
+    for &codec in CODECS {
+      let mut encoder = codec.encoder().unwrap();
+      let mut compressed = vec![];
+      for chunk in DATA.chunks(7) {
+        compressed.extend(encoder.update(chunk).unwrap());
+      }
+      compressed.extend(encoder.finish().unwrap());
+      let decompressed = codec.decompress(&compressed, DATA.len() as u64).unwrap();
+      assert_eq!(decompressed, DATA, "codec: {codec}");
+    }
+  }
+
+  #[cfg(all(feature = "codec-zstd", feature = "codec-brotli"))]
+  #[test]
+  fn decoder() {
+    for codec in [Codec::Zstd, Codec::Brotli] {
+      let compressed = codec.compress(DATA).unwrap();
+      let mut decoder = CodecDecoder::new(codec).unwrap().unwrap();
+      let mut decompressed = vec![];
+      let mut output = [0u8; 5];
+      let mut input = compressed.as_slice();
+      while !decoder.is_finished() {
+        let (consumed, produced) = decoder
+          .decode(&input[..input.len().min(3)], &mut output)
+          .unwrap();
+        decompressed.extend_from_slice(&output[..produced]);
+        input = &input[consumed..];
+      }
+      assert_eq!(decompressed, DATA, "codec: {codec}");
+    }
+    assert!(CodecDecoder::new(Codec::Lz4).unwrap().is_none());
+    assert!(CodecDecoder::new(Codec::None).unwrap().is_none());
+
+    let mut decoder = CodecDecoder::new(Codec::Brotli).unwrap().unwrap();
+    assert!(decoder.decode(&[0xff; 16], &mut [0u8; 16]).is_err());
+  }
+
+  #[test]
+  fn compress_empty() {
+    for &codec in CODECS {
+      let compressed = codec.compress(&[]).unwrap();
+      let decompressed = codec.decompress(&compressed, 0).unwrap();
+      assert!(decompressed.is_empty(), "codec: {codec}");
+    }
+  }
+
+  #[cfg(not(feature = "codec-zstd"))]
+  #[test]
+  fn not_enabled() {
+    assert!(matches!(
+      Codec::Zstd.compress(DATA),
+      Err(crate::Error::FeatureNotEnabled("codec-zstd"))
+    ));
+    assert!(matches!(
+      Codec::Zstd.decompress(DATA, DATA.len() as u64),
+      Err(crate::Error::FeatureNotEnabled("codec-zstd"))
+    ));
+    assert!(CodecDecoder::new(Codec::Zstd).unwrap().is_none());
+  }
+}
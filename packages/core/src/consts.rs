@@ -1,3 +1,4 @@
 pub const EXTENSION: &str = "wvb";
+pub const PATCH_EXTENSION: &str = "wvbp";
 pub const MIME_TYPE: &str = "application/webview-bundle";
 pub const MANIFEST_FILENAME: &str = "manifest.json";
@@ -0,0 +1,492 @@
+//! Comparison of two bundles.
+//!
+//! The diff module reports which paths were added, removed or modified between two bundles,
+//! with the size change of each, so a release can be reviewed before it is published.
+//!
+//! ## Detecting Modified Files
+//!
+//! Entries are compared through the index first, so unchanged files are never decompressed:
+//!
+//! 1. Entries with different content lengths, content types or headers are modified.
+//! 2. If both entries have a digest of the same algorithm, the digests are compared.
+//! 3. If both entries are stored with the same codec, the checksums of the stored data are
+//!    compared.
+//! 4. Otherwise the data of both entries is decompressed and compared.
+//!
+//! Checksums are only comparable between bundles built with the same data checksum seed.
+//!
+//! ## Example
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use wvb::diff::diff_descriptors;
+//! use wvb::{BundleDescriptor, BundleReader, Reader};
+//!
+//! let mut old_file = File::open("app_1.0.0.wvb").unwrap();
+//! let mut new_file = File::open("app_1.1.0.wvb").unwrap();
+//! let old: BundleDescriptor = BundleReader::new(&mut old_file).read().unwrap();
+//! let new: BundleDescriptor = BundleReader::new(&mut new_file).read().unwrap();
+//!
+//! let diff = diff_descriptors(&old, &mut old_file, &new, &mut new_file).unwrap();
+//! for entry in diff.entries() {
+//!     println!("{} {} ({:+} bytes)", entry.change(), entry.path(), entry.size_delta());
+//! }
+//! ```
+
+use crate::{Bundle, BundleDescriptor, Index, IndexEntry};
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Seek};
+
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, AsyncSeek};
+
+/// Differences between two bundles, ordered by path.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct BundleDiff {
+  entries: Vec<EntryDiff>,
+}
+
+impl BundleDiff {
+  /// Returns every changed entry, ordered by path.
+  pub fn entries(&self) -> &[EntryDiff] {
+    &self.entries
+  }
+
+  /// Returns entries only in the new bundle.
+  pub fn added(&self) -> impl Iterator<Item = &EntryDiff> {
+    self.filter(|x| matches!(x, Change::Added))
+  }
+
+  /// Returns entries only in the old bundle.
+  pub fn removed(&self) -> impl Iterator<Item = &EntryDiff> {
+    self.filter(|x| matches!(x, Change::Removed))
+  }
+
+  /// Returns entries in both bundles which differ.
+  pub fn modified(&self) -> impl Iterator<Item = &EntryDiff> {
+    self.filter(|x| matches!(x, Change::Modified(_)))
+  }
+
+  /// Returns `true` if the bundles have the same entries.
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  /// Returns the change of the total size of the original file data, in bytes.
+  pub fn size_delta(&self) -> i64 {
+    self.entries.iter().map(|x| x.size_delta()).sum()
+  }
+
+  /// Returns the change of the total size of the stored file data, in bytes.
+  pub fn stored_size_delta(&self) -> i64 {
+    self.entries.iter().map(|x| x.stored_size_delta()).sum()
+  }
+
+  fn filter(&self, f: impl Fn(&Change) -> bool) -> impl Iterator<Item = &EntryDiff> {
+    self.entries.iter().filter(move |x| f(&x.change))
+  }
+}
+
+/// A changed entry.
+#[derive(Debug, PartialEq, Clone)]
+pub struct EntryDiff {
+  path: String,
+  change: Change,
+  old: Option<IndexEntry>,
+  new: Option<IndexEntry>,
+}
+
+impl EntryDiff {
+  /// Returns the path of the entry.
+  pub fn path(&self) -> &str {
+    &self.path
+  }
+
+  /// Returns how the entry changed.
+  pub fn change(&self) -> &Change {
+    &self.change
+  }
+
+  /// Returns the entry in the old bundle, if any.
+  pub fn old_entry(&self) -> Option<&IndexEntry> {
+    self.old.as_ref()
+  }
+
+  /// Returns the entry in the new bundle, if any.
+  pub fn new_entry(&self) -> Option<&IndexEntry> {
+    self.new.as_ref()
+  }
+
+  /// Returns the change of the size of the original file data, in bytes.
+  pub fn size_delta(&self) -> i64 {
+    delta(&self.old, &self.new, IndexEntry::content_length)
+  }
+
+  /// Returns the change of the size of the stored file data, in bytes.
+  pub fn stored_size_delta(&self) -> i64 {
+    delta(&self.old, &self.new, IndexEntry::len)
+  }
+}
+
+fn delta(old: &Option<IndexEntry>, new: &Option<IndexEntry>, size: fn(&IndexEntry) -> u64) -> i64 {
+  let size = |x: &Option<IndexEntry>| x.as_ref().map(size).unwrap_or_default() as i64;
+  size(new) - size(old)
+}
+
+/// How an entry changed between two bundles.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Change {
+  /// The path is only in the new bundle.
+  Added,
+  /// The path is only in the old bundle.
+  Removed,
+  /// The path is in both bundles, with the given differences.
+  Modified(Modified),
+}
+
+impl Display for Change {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Added => f.write_str("added"),
+      Self::Removed => f.write_str("removed"),
+      Self::Modified(_) => f.write_str("modified"),
+    }
+  }
+}
+
+/// Differences of a modified entry.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Modified {
+  /// The file data differs.
+  pub data: bool,
+  /// The content type differs.
+  pub content_type: bool,
+  /// The HTTP headers differ.
+  pub headers: bool,
+}
+
+impl Modified {
+  fn is_modified(&self) -> bool {
+    self.data || self.content_type || self.headers
+  }
+}
+
+/// How the data of two entries at the same path is compared.
+enum DataCompare {
+  /// Decided from the index alone.
+  Known(bool),
+  /// The checksums of the stored data are compared.
+  Checksum,
+  /// The decompressed data is compared.
+  Data,
+}
+
+impl DataCompare {
+  fn new(old: &IndexEntry, new: &IndexEntry) -> Self {
+    if old.content_length() != new.content_length() {
+      return Self::Known(true);
+    }
+    if let (Some(a), Some(b)) = (old.digest(), new.digest()) {
+      if a.algorithm() == b.algorithm() {
+        return Self::Known(a != b);
+      }
+    }
+    if old.codec() == new.codec() && old.blocks() == new.blocks() {
+      if old.len() != new.len() {
+        return Self::Known(true);
+      }
+      return Self::Checksum;
+    }
+    Self::Data
+  }
+}
+
+/// Entries at the same path in both bundles, pending data comparison.
+struct Pending<'a> {
+  path: &'a str,
+  old: &'a IndexEntry,
+  new: &'a IndexEntry,
+  compare: DataCompare,
+  modified: Modified,
+}
+
+/// Compares the indexes, returning added and removed entries and the entries in both.
+fn compare_indexes<'a>(
+  old: &'a Index,
+  new: &'a Index,
+) -> crate::Result<(Vec<EntryDiff>, Vec<Pending<'a>>)> {
+  let mut changed = vec![];
+  let mut pending = vec![];
+  for (path, entry) in old.try_entries()? {
+    match new.try_get_entry(path)? {
+      Some(new_entry) => pending.push(Pending {
+        path,
+        old: entry,
+        new: new_entry,
+        compare: DataCompare::new(entry, new_entry),
+        modified: Modified {
+          data: false,
+          content_type: entry.content_type() != new_entry.content_type(),
+          headers: entry.headers() != new_entry.headers(),
+        },
+      }),
+      None => changed.push(EntryDiff {
+        path: path.to_string(),
+        change: Change::Removed,
+        old: Some(entry.clone()),
+        new: None,
+      }),
+    }
+  }
+  for (path, entry) in new.try_entries()? {
+    if !old.contains_path(path) {
+      changed.push(EntryDiff {
+        path: path.to_string(),
+        change: Change::Added,
+        old: None,
+        new: Some(entry.clone()),
+      });
+    }
+  }
+  Ok((changed, pending))
+}
+
+fn finish(mut changed: Vec<EntryDiff>, pending: Vec<Pending>) -> BundleDiff {
+  for x in pending {
+    if x.modified.is_modified() {
+      changed.push(EntryDiff {
+        path: x.path.to_string(),
+        change: Change::Modified(x.modified),
+        old: Some(x.old.clone()),
+        new: Some(x.new.clone()),
+      });
+    }
+  }
+  changed.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+  BundleDiff { entries: changed }
+}
+
+/// Compares two bundles in memory.
+pub fn diff_bundles(old: &Bundle, new: &Bundle) -> crate::Result<BundleDiff> {
+  let (changed, mut pending) = compare_indexes(&old.descriptor().index, &new.descriptor().index)?;
+  for x in &mut pending {
+    x.modified.data = match x.compare {
+      DataCompare::Known(modified) => modified,
+      DataCompare::Checksum => old.get_data_checksum(x.path)? != new.get_data_checksum(x.path)?,
+      DataCompare::Data => old.get_data(x.path)? != new.get_data(x.path)?,
+    };
+  }
+  Ok(finish(changed, pending))
+}
+
+/// Compares two bundles, reading file data from the provided readers only when needed.
+pub fn diff_descriptors<R1: Read + Seek, R2: Read + Seek>(
+  old: &BundleDescriptor,
+  mut old_reader: R1,
+  new: &BundleDescriptor,
+  mut new_reader: R2,
+) -> crate::Result<BundleDiff> {
+  let (changed, mut pending) = compare_indexes(&old.index, &new.index)?;
+  for x in &mut pending {
+    x.modified.data = match x.compare {
+      DataCompare::Known(modified) => modified,
+      DataCompare::Checksum => {
+        old.get_data_checksum(&mut old_reader, x.path)?
+          != new.get_data_checksum(&mut new_reader, x.path)?
+      }
+      DataCompare::Data => {
+        old.get_data(&mut old_reader, x.path)? != new.get_data(&mut new_reader, x.path)?
+      }
+    };
+  }
+  Ok(finish(changed, pending))
+}
+
+/// Compares two bundles, reading file data from the provided async readers only when needed.
+///
+/// See [`diff_descriptors`].
+#[cfg(feature = "async")]
+pub async fn async_diff_descriptors<R1, R2>(
+  old: &BundleDescriptor,
+  mut old_reader: R1,
+  new: &BundleDescriptor,
+  mut new_reader: R2,
+) -> crate::Result<BundleDiff>
+where
+  R1: AsyncRead + AsyncSeek + Unpin,
+  R2: AsyncRead + AsyncSeek + Unpin,
+{
+  let (changed, mut pending) = compare_indexes(&old.index, &new.index)?;
+  for x in &mut pending {
+    x.modified.data = match x.compare {
+      DataCompare::Known(modified) => modified,
+      DataCompare::Checksum => {
+        old.async_get_data_checksum(&mut old_reader, x.path).await?
+          != new.async_get_data_checksum(&mut new_reader, x.path).await?
+      }
+      DataCompare::Data => {
+        old.async_get_data(&mut old_reader, x.path).await?
+          != new.async_get_data(&mut new_reader, x.path).await?
+      }
+    };
+  }
+  Ok(finish(changed, pending))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    BundleBuilder, BundleBuilderOptions, BundleEntry, BundleReader, Codec, Reader, Version,
+  };
+  use http::HeaderMap;
+  use std::io::Cursor;
+
+  fn build(entries: &[(&str, &str, &str)], codec: Codec) -> Bundle {
+    let mut options = BundleBuilderOptions::new();
+    options.codec(codec);
+    let mut builder = BundleBuilder::new_with_options(options);
+    builder.set_version(Version::V2);
+    for (path, data, content_type) in entries {
+      builder.insert_entry(
+        *path,
+        BundleEntry::new(data.as_bytes(), *content_type, None),
+      );
+    }
+    builder.build().unwrap()
+  }
+
+  #[test]
+  fn diff() {
+    let old = build(
+      &[
+        ("/index.html", "<html></html>", "text/html"),
+        ("/app.js", "console.log(1);", "text/javascript"),
+        ("/old.css", "body {}", "text/css"),
+        ("/same.txt", "same", "text/plain"),
+        ("/type.txt", "type", "text/plain"),
+      ],
+      Codec::Lz4,
+    );
+    let new = build(
+      &[
+        ("/index.html", "<html><body></body></html>", "text/html"),
+        ("/app.js", "console.log(2);", "text/javascript"),
+        ("/new.css", "main {}", "text/css"),
+        ("/same.txt", "same", "text/plain"),
+        ("/type.txt", "type", "text/markdown"),
+      ],
+      Codec::Lz4,
+    );
+    let diff = diff_bundles(&old, &new).unwrap();
+    let changes = diff
+      .entries()
+      .iter()
+      .map(|x| (x.path(), *x.change()))
+      .collect::<Vec<_>>();
+    let data = Change::Modified(Modified {
+      data: true,
+      ..Default::default()
+    });
+    assert_eq!(
+      changes,
+      [
+        ("/app.js", data),
+        ("/index.html", data),
+        ("/new.css", Change::Added),
+        ("/old.css", Change::Removed),
+        (
+          "/type.txt",
+          Change::Modified(Modified {
+            content_type: true,
+            ..Default::default()
+          })
+        ),
+      ]
+    );
+    assert_eq!(diff.added().count(), 1);
+    assert_eq!(diff.removed().count(), 1);
+    assert_eq!(diff.modified().count(), 3);
+    assert_eq!(diff.entries()[1].size_delta(), 13);
+    assert_eq!(diff.size_delta(), 13);
+    assert!(diff_bundles(&old, &old).unwrap().is_empty());
+  }
+
+  #[test]
+  fn diff_headers() {
+    let old = build(&[("/index.html", "<html></html>", "text/html")], Codec::Lz4);
+    let mut new = old.clone();
+    let mut headers = HeaderMap::new();
+    headers.insert("cache-control", "no-cache".parse().unwrap());
+    new
+      .descriptor
+      .index
+      .get_entry_mut("/index.html")
+      .unwrap()
+      .headers = headers;
+    let diff = diff_bundles(&old, &new).unwrap();
+    assert_eq!(
+      diff.entries()[0].change(),
+      &Change::Modified(Modified {
+        headers: true,
+        ..Default::default()
+      })
+    );
+  }
+
+  #[test]
+  fn diff_codecs() {
+    let entries = [("/index.html", "<html></html>", "text/html")];
+    let diff = diff_bundles(&build(&entries, Codec::Lz4), &build(&entries, Codec::None)).unwrap();
+    assert!(diff.is_empty());
+  }
+
+  #[test]
+  fn diff_descriptors() {
+    let read = |name: &str| {
+      let path = crate::testing::Fixtures::bundles().get_path(name);
+      let buf = std::fs::read(path).unwrap();
+      let descriptor: BundleDescriptor = BundleReader::new(Cursor::new(&buf)).read().unwrap();
+      (descriptor, buf)
+    };
+    let (old, old_buf) = read("remote/app/app_1.0.0.wvb");
+    let (new, new_buf) = read("remote/app/app_1.1.0.wvb");
+    let diff =
+      super::diff_descriptors(&old, Cursor::new(&old_buf), &new, Cursor::new(&new_buf)).unwrap();
+    let bundle_diff = diff_bundles(
+      &BundleReader::new(Cursor::new(&old_buf)).read().unwrap(),
+      &BundleReader::new(Cursor::new(&new_buf)).read().unwrap(),
+    )
+    .unwrap();
+    assert_eq!(diff, bundle_diff);
+    let same =
+      super::diff_descriptors(&old, Cursor::new(&old_buf), &old, Cursor::new(&old_buf)).unwrap();
+    assert!(same.is_empty());
+  }
+
+  #[cfg(feature = "async")]
+  #[tokio::test]
+  async fn async_diff_descriptors() {
+    use crate::{BundleWriter, Writer};
+
+    let old = build(&[("/a.txt", "a", "text/plain")], Codec::Lz4);
+    let new = build(&[("/a.txt", "b", "text/plain")], Codec::Lz4);
+    let write = |bundle: &Bundle| {
+      let mut buf = vec![];
+      BundleWriter::new(Cursor::new(&mut buf))
+        .write(bundle)
+        .unwrap();
+      buf
+    };
+    let (old_buf, new_buf) = (write(&old), write(&new));
+    let diff = super::async_diff_descriptors(
+      old.descriptor(),
+      Cursor::new(&old_buf),
+      new.descriptor(),
+      Cursor::new(&new_buf),
+    )
+    .await
+    .unwrap();
+    assert_eq!(diff.modified().count(), 1);
+  }
+}
@@ -0,0 +1,255 @@
+use crate::index::{EntryVariant, IndexEntry};
+#[cfg(feature = "digest")]
+use sha3::{Digest, Sha3_256, Sha3_384, Sha3_512};
+use std::fmt::{Display, Formatter};
+
+/// Hash algorithm of per-entry digests stored in the index.
+///
+/// Digests are computed over the original (decompressed) file data, so a signature over the
+/// index authenticates every file in the bundle.
+///
+/// Computing and verifying digests needs the `digest` feature. Without it, bundles with digests
+/// are still read, but fail to verify them with [`Error::FeatureNotEnabled`].
+///
+/// [`Error::FeatureNotEnabled`]: crate::Error::FeatureNotEnabled
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Default)]
+pub enum DigestAlgorithm {
+  /// SHA3-256
+  #[default]
+  Sha3_256,
+  /// SHA3-384
+  Sha3_384,
+  /// SHA3-512
+  Sha3_512,
+}
+
+impl DigestAlgorithm {
+  /// Returns the identifier of this algorithm stored in the index.
+  pub const fn id(&self) -> u8 {
+    match self {
+      Self::Sha3_256 => 0x01,
+      Self::Sha3_384 => 0x02,
+      Self::Sha3_512 => 0x03,
+    }
+  }
+
+  /// Resolves an algorithm from its identifier stored in the index.
+  pub fn from_id(id: u8) -> crate::Result<Self> {
+    match id {
+      0x01 => Ok(Self::Sha3_256),
+      0x02 => Ok(Self::Sha3_384),
+      0x03 => Ok(Self::Sha3_512),
+      _ => Err(crate::Error::InvalidDigestAlgorithm(id)),
+    }
+  }
+
+  /// Computes the digest of the given data.
+  #[cfg(feature = "digest")]
+  pub fn digest(&self, data: &[u8]) -> Vec<u8> {
+    let mut hasher = self.new_hasher();
+    hasher.update(data);
+    hasher.finalize()
+  }
+
+  /// Creates a hasher which computes the digest chunk by chunk, failing if the `digest` feature
+  /// is not enabled.
+  #[cfg(feature = "digest")]
+  pub(crate) fn hasher(&self) -> crate::Result<DigestHasher> {
+    Ok(self.new_hasher())
+  }
+
+  /// Creates a hasher which computes the digest chunk by chunk, failing if the `digest` feature
+  /// is not enabled.
+  #[cfg(not(feature = "digest"))]
+  pub(crate) fn hasher(&self) -> crate::Result<DigestHasher> {
+    Err(crate::Error::FeatureNotEnabled("digest"))
+  }
+
+  #[cfg(feature = "digest")]
+  fn new_hasher(&self) -> DigestHasher {
+    match self {
+      Self::Sha3_256 => DigestHasher::Sha3_256(Sha3_256::new()),
+      Self::Sha3_384 => DigestHasher::Sha3_384(Sha3_384::new()),
+      Self::Sha3_512 => DigestHasher::Sha3_512(Sha3_512::new()),
+    }
+  }
+}
+
+impl Display for DigestAlgorithm {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let s = match self {
+      Self::Sha3_256 => "sha3-256",
+      Self::Sha3_384 => "sha3-384",
+      Self::Sha3_512 => "sha3-512",
+    };
+    f.write_str(s)
+  }
+}
+
+/// Incremental hasher of a [`DigestAlgorithm`], which can't be created without the `digest`
+/// feature.
+pub(crate) enum DigestHasher {
+  #[cfg(feature = "digest")]
+  Sha3_256(Sha3_256),
+  #[cfg(feature = "digest")]
+  Sha3_384(Sha3_384),
+  #[cfg(feature = "digest")]
+  Sha3_512(Sha3_512),
+}
+
+impl DigestHasher {
+  #[cfg_attr(not(feature = "digest"), allow(unused_variables))]
+  pub(crate) fn update(&mut self, data: &[u8]) {
+    match *self {
+      #[cfg(feature = "digest")]
+      Self::Sha3_256(ref mut h) => h.update(data),
+      #[cfg(feature = "digest")]
+      Self::Sha3_384(ref mut h) => h.update(data),
+      #[cfg(feature = "digest")]
+      Self::Sha3_512(ref mut h) => h.update(data),
+    }
+  }
+
+  pub(crate) fn finalize(self) -> Vec<u8> {
+    match self {
+      #[cfg(feature = "digest")]
+      Self::Sha3_256(h) => h.finalize().to_vec(),
+      #[cfg(feature = "digest")]
+      Self::Sha3_384(h) => h.finalize().to_vec(),
+      #[cfg(feature = "digest")]
+      Self::Sha3_512(h) => h.finalize().to_vec(),
+    }
+  }
+}
+
+/// Cryptographic digest of a single file in the bundle.
+///
+/// # Example
+///
+/// ```
+/// use wvb::{DigestAlgorithm, EntryDigest};
+///
+/// let digest = EntryDigest::compute(DigestAlgorithm::Sha3_256, b"hello");
+/// assert!(digest.verify(b"hello"));
+/// assert!(!digest.verify(b"world"));
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct EntryDigest {
+  algorithm: DigestAlgorithm,
+  value: Vec<u8>,
+}
+
+impl EntryDigest {
+  /// Creates a digest from an already computed value.
+  pub fn new(algorithm: DigestAlgorithm, value: Vec<u8>) -> Self {
+    Self { algorithm, value }
+  }
+
+  /// Computes the digest of the given data.
+  #[cfg(feature = "digest")]
+  pub fn compute(algorithm: DigestAlgorithm, data: &[u8]) -> Self {
+    Self::new(algorithm, algorithm.digest(data))
+  }
+
+  /// Computes the digest of the given data, failing if the `digest` feature is not enabled.
+  pub(crate) fn try_compute(algorithm: DigestAlgorithm, data: &[u8]) -> crate::Result<Self> {
+    let mut hasher = algorithm.hasher()?;
+    hasher.update(data);
+    Ok(Self::new(algorithm, hasher.finalize()))
+  }
+
+  /// Returns the hash algorithm.
+  pub fn algorithm(&self) -> DigestAlgorithm {
+    self.algorithm
+  }
+
+  /// Returns the digest value.
+  pub fn value(&self) -> &[u8] {
+    &self.value
+  }
+
+  /// Returns `true` if the digest of the given data matches.
+  #[cfg(feature = "digest")]
+  pub fn verify(&self, data: &[u8]) -> bool {
+    self.value == self.algorithm.digest(data)
+  }
+}
+
+/// Verifies data against a digest, if there is one.
+pub(crate) fn verify_digest(digest: Option<&EntryDigest>, data: &[u8]) -> crate::Result<()> {
+  let Some(digest) = digest else {
+    return Ok(());
+  };
+  if EntryDigest::try_compute(digest.algorithm, data)?.value != digest.value {
+    return Err(crate::Error::DigestMismatch);
+  }
+  Ok(())
+}
+
+/// Verifies the decompressed data of an entry against its digest, if the entry has one.
+pub(crate) fn verify_entry_digest(entry: &IndexEntry, data: &[u8]) -> crate::Result<()> {
+  verify_digest(entry.digest(), data)
+}
+
+/// Verifies the encoded data of a precompressed variant against its digest, if it has one.
+pub(crate) fn verify_variant_digest(variant: &EntryVariant, data: &[u8]) -> crate::Result<()> {
+  verify_digest(variant.digest(), data)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const ALGORITHMS: [DigestAlgorithm; 3] = [
+    DigestAlgorithm::Sha3_256,
+    DigestAlgorithm::Sha3_384,
+    DigestAlgorithm::Sha3_512,
+  ];
+
+  #[test]
+  fn id() {
+    for algorithm in ALGORITHMS {
+      assert_eq!(DigestAlgorithm::from_id(algorithm.id()).unwrap(), algorithm);
+    }
+    assert!(matches!(
+      DigestAlgorithm::from_id(0x00),
+      Err(crate::Error::InvalidDigestAlgorithm(0x00))
+    ));
+  }
+
+  #[cfg(feature = "digest")]
+  #[test]
+  fn hasher() {
+    for algorithm in ALGORITHMS {
+      let mut hasher = algorithm.hasher().unwrap();
+      hasher.update(b"hello ");
+      hasher.update(b"world");
+      assert_eq!(hasher.finalize(), algorithm.digest(b"hello world"));
+    }
+  }
+
+  #[cfg(feature = "digest")]
+  #[test]
+  fn verify() {
+    let digest = EntryDigest::compute(DigestAlgorithm::Sha3_256, b"test");
+    assert_eq!(digest.value().len(), 32);
+    assert!(digest.verify(b"test"));
+    assert!(!digest.verify(b"test2"));
+    assert!(verify_digest(Some(&digest), b"test").is_ok());
+    assert!(matches!(
+      verify_digest(Some(&digest), b"test2"),
+      Err(crate::Error::DigestMismatch)
+    ));
+  }
+
+  #[cfg(not(feature = "digest"))]
+  #[test]
+  fn not_enabled() {
+    let digest = EntryDigest::new(DigestAlgorithm::Sha3_256, vec![0; 32]);
+    assert!(matches!(
+      verify_digest(Some(&digest), b"test"),
+      Err(crate::Error::FeatureNotEnabled("digest"))
+    ));
+    assert!(verify_digest(None, b"test").is_ok());
+  }
+}
@@ -0,0 +1,198 @@
+use crate::builder::{BundleBuilder, BundleBuilderOptions, BundleEntry};
+use crate::path_pattern::PathPattern;
+use crate::version::Version;
+use std::path::Path;
+
+/// Options for building a bundle from a directory.
+///
+/// # Example
+///
+/// ```no_run
+/// use wvb::{BundleBuilder, DirOptions, Version};
+///
+/// let mut options = DirOptions::new();
+/// options
+///   .version(Version::V2)
+///   .ignore("**/*.map")
+///   .ignore(".DS_Store");
+/// let bundle = BundleBuilder::from_dir("./dist", &options)
+///   .unwrap()
+///   .build()
+///   .unwrap();
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct DirOptions {
+  pub(crate) builder: BundleBuilderOptions,
+  pub(crate) version: Version,
+  pub(crate) ignore: Vec<String>,
+}
+
+impl DirOptions {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Sets the options of the returned builder.
+  pub fn builder(&mut self, options: BundleBuilderOptions) -> &mut Self {
+    self.builder = options;
+    self
+  }
+
+  /// Sets the format version of the returned builder.
+  pub fn version(&mut self, version: Version) -> &mut Self {
+    self.version = version;
+    self
+  }
+
+  /// Skips files and directories matching the given glob pattern.
+  ///
+  /// Patterns are matched against paths relative to the directory, using `/` as the separator.
+  /// `*` doesn't match across directories while `**` does, and a pattern without `/` also
+  /// matches the name of a file or directory at any depth (e.g. `node_modules` or `*.map`).
+  pub fn ignore(&mut self, pattern: impl Into<String>) -> &mut Self {
+    self.ignore.push(pattern.into());
+    self
+  }
+}
+
+/// Compiled ignore patterns.
+struct IgnoreRules {
+  patterns: Vec<PathPattern>,
+}
+
+impl IgnoreRules {
+  fn new(patterns: &[String]) -> crate::Result<Self> {
+    let patterns = patterns
+      .iter()
+      .map(|x| PathPattern::new(x))
+      .collect::<crate::Result<Vec<_>>>()?;
+    Ok(Self { patterns })
+  }
+
+  fn is_ignored(&self, relative_path: &str) -> bool {
+    self.patterns.iter().any(|x| x.matches(relative_path))
+  }
+}
+
+impl BundleBuilder {
+  /// Creates a builder with every file of a directory, walked recursively.
+  ///
+  /// Paths are relative to the directory and normalised to `/`-prefixed paths with `/` as the
+  /// separator (e.g. `/assets/app.js`). Content types are resolved with
+  /// [`BundleBuilderOptions::resolve_content_type`], from the file extension first and the file
+  /// content for unknown extensions. Symbolic links to directories are not followed.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if an ignore pattern is invalid, a path is not valid UTF-8, or the
+  /// directory can't be read.
+  pub fn from_dir(dir: impl AsRef<Path>, options: &DirOptions) -> crate::Result<Self> {
+    let rules = IgnoreRules::new(&options.ignore)?;
+    let mut builder = Self::new_with_options(options.builder.clone());
+    builder.set_version(options.version);
+    builder.add_dir(dir.as_ref(), "", &rules)?;
+    Ok(builder)
+  }
+
+  fn add_dir(&mut self, dir: &Path, prefix: &str, rules: &IgnoreRules) -> crate::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+      let entry = entry?;
+      let name = entry.file_name();
+      let name = name.to_str().ok_or_else(|| {
+        std::io::Error::new(
+          std::io::ErrorKind::InvalidData,
+          format!("path is not valid UTF-8: {}", entry.path().display()),
+        )
+      })?;
+      let relative_path = format!("{prefix}{name}");
+      if rules.is_ignored(&relative_path) {
+        continue;
+      }
+      let file_type = entry.file_type()?;
+      if file_type.is_dir() {
+        self.add_dir(&entry.path(), &format!("{relative_path}/"), rules)?;
+      } else if file_type.is_file() || entry.path().is_file() {
+        let data = std::fs::read(entry.path())?;
+        let path = format!("/{relative_path}");
+        let content_type = self.options().resolve_content_type(&path, &data);
+        self.insert_entry(path, BundleEntry::new(&data, content_type, None));
+      }
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::TempDir;
+
+  fn write(dir: &Path, path: &str, data: &[u8]) {
+    let path = dir.join(path);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, data).unwrap();
+  }
+
+  #[test]
+  fn from_dir() {
+    let dir = TempDir::new("from_dir");
+    write(dir.path(), "index.html", b"<html></html>");
+    write(dir.path(), "assets/app.js", b"console.log(1);");
+    write(dir.path(), "assets/app.js.map", b"{}");
+    write(dir.path(), "assets/logo", b"\x89PNG\r\n\x1a\n");
+    write(dir.path(), "node_modules/x/index.js", b"");
+    write(dir.path(), "docs/a/b.md", b"# b");
+
+    let mut builder_options = BundleBuilderOptions::new();
+    builder_options.mime_type("html", "text/html; charset=utf-8");
+    let mut options = DirOptions::new();
+    options
+      .builder(builder_options)
+      .version(Version::V2)
+      .ignore("*.map")
+      .ignore("node_modules")
+      .ignore("docs/**/*.md");
+    let builder = BundleBuilder::from_dir(dir.path(), &options).unwrap();
+    assert_eq!(builder.version(), Version::V2);
+    let mut paths = builder.entries().keys().cloned().collect::<Vec<_>>();
+    paths.sort();
+    assert_eq!(paths, ["/assets/app.js", "/assets/logo", "/index.html"]);
+    let content_type = |path: &str| builder.get_entry(path).unwrap().content_type().to_string();
+    assert_eq!(content_type("/index.html"), "text/html; charset=utf-8");
+    assert_eq!(content_type("/assets/app.js"), "text/javascript");
+    assert_eq!(content_type("/assets/logo"), "image/png");
+
+    let bundle = builder.build().unwrap();
+    assert_eq!(
+      bundle.get_data("/assets/app.js").unwrap().unwrap(),
+      b"console.log(1);"
+    );
+  }
+
+  #[test]
+  fn ignore_rules() {
+    let rules = IgnoreRules::new(&[
+      "*.map".to_string(),
+      "/dist/*.txt".to_string(),
+      "**/.DS_Store".to_string(),
+    ])
+    .unwrap();
+    assert!(rules.is_ignored("app.js.map"));
+    assert!(rules.is_ignored("assets/js/app.js.map"));
+    assert!(rules.is_ignored("dist/a.txt"));
+    assert!(!rules.is_ignored("dist/sub/a.txt"));
+    assert!(rules.is_ignored(".DS_Store"));
+    assert!(rules.is_ignored("assets/.DS_Store"));
+    assert!(!rules.is_ignored("app.js"));
+  }
+
+  #[test]
+  fn invalid_pattern() {
+    let mut options = DirOptions::new();
+    options.ignore("a/**b");
+    assert!(matches!(
+      BundleBuilder::from_dir(".", &options),
+      Err(crate::Error::Glob(_))
+    ));
+  }
+}
@@ -0,0 +1,205 @@
+#[cfg(feature = "precompress")]
+use crate::codec::CodecEncoder;
+#[cfg(feature = "precompress")]
+use flate2::write::GzEncoder;
+#[cfg(feature = "precompress")]
+use flate2::Compression;
+use std::fmt::{Display, Formatter};
+#[cfg(feature = "precompress")]
+use std::io::Write;
+
+/// HTTP content encoding of a precompressed entry variant.
+///
+/// `V2` bundles can store variants of an entry which are already encoded for HTTP, so a
+/// protocol handler can send them as-is with a `Content-Encoding` header instead of
+/// decompressing the entry. Encoding variants needs the `precompress` feature, while serving
+/// them doesn't.
+///
+/// # Example
+///
+/// ```
+/// use wvb::ContentEncoding;
+///
+/// assert_eq!(ContentEncoding::Brotli.as_str(), "br");
+/// assert_eq!(ContentEncoding::parse("GZIP"), Some(ContentEncoding::Gzip));
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, PartialOrd, Ord)]
+pub enum ContentEncoding {
+  /// Brotli (`br`)
+  Brotli,
+  /// Gzip (`gzip`)
+  Gzip,
+}
+
+impl ContentEncoding {
+  /// Returns the identifier of this encoding stored in the index.
+  pub const fn id(&self) -> u8 {
+    match self {
+      Self::Brotli => 0x01,
+      Self::Gzip => 0x02,
+    }
+  }
+
+  /// Resolves an encoding from its identifier stored in the index.
+  pub fn from_id(id: u8) -> crate::Result<Self> {
+    match id {
+      0x01 => Ok(Self::Brotli),
+      0x02 => Ok(Self::Gzip),
+      _ => Err(crate::Error::InvalidContentEncoding(id)),
+    }
+  }
+
+  /// Returns the token of this encoding used in `Content-Encoding` and `Accept-Encoding`.
+  pub const fn as_str(&self) -> &'static str {
+    match self {
+      Self::Brotli => "br",
+      Self::Gzip => "gzip",
+    }
+  }
+
+  /// Parses a content coding token case-insensitively.
+  ///
+  /// `x-gzip` is accepted as an alias of `gzip`.
+  pub fn parse(token: &str) -> Option<Self> {
+    let token = token.trim();
+    if token.eq_ignore_ascii_case("br") {
+      Some(Self::Brotli)
+    } else if token.eq_ignore_ascii_case("gzip") || token.eq_ignore_ascii_case("x-gzip") {
+      Some(Self::Gzip)
+    } else {
+      None
+    }
+  }
+
+  /// Encodes the given data.
+  ///
+  /// The output is identical to feeding the data chunk by chunk to the encoder used by
+  /// [`BundleStreamWriter`](crate::BundleStreamWriter).
+  pub fn encode(&self, data: &[u8]) -> crate::Result<Vec<u8>> {
+    let mut encoder = self.encoder()?;
+    let mut buf = encoder.update(data)?;
+    buf.extend(encoder.finish()?);
+    Ok(buf)
+  }
+
+  /// Creates an encoder which encodes data chunk by chunk.
+  #[cfg(feature = "precompress")]
+  pub(crate) fn encoder(&self) -> crate::Result<ContentEncoder> {
+    let encoder = match self {
+      Self::Brotli => ContentEncoder::Brotli(crate::Codec::Brotli.encoder()?),
+      Self::Gzip => ContentEncoder::Gzip(GzEncoder::new(Vec::new(), Compression::best())),
+    };
+    Ok(encoder)
+  }
+
+  /// Creates an encoder which encodes data chunk by chunk.
+  #[cfg(not(feature = "precompress"))]
+  pub(crate) fn encoder(&self) -> crate::Result<ContentEncoder> {
+    Err(crate::Error::FeatureNotEnabled("precompress"))
+  }
+}
+
+impl Display for ContentEncoding {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+/// Incremental encoder of a [`ContentEncoding`].
+pub(crate) enum ContentEncoder {
+  #[cfg(feature = "precompress")]
+  Brotli(CodecEncoder),
+  #[cfg(feature = "precompress")]
+  Gzip(GzEncoder<Vec<u8>>),
+}
+
+impl ContentEncoder {
+  /// Feeds a chunk of data and returns the encoded bytes produced so far.
+  #[cfg_attr(not(feature = "precompress"), allow(unused_variables))]
+  pub(crate) fn update(&mut self, chunk: &[u8]) -> crate::Result<Vec<u8>> {
+    match *self {
+      #[cfg(feature = "precompress")]
+      Self::Brotli(ref mut encoder) => encoder.update(chunk),
+      #[cfg(feature = "precompress")]
+      Self::Gzip(ref mut w) => {
+        w.write_all(chunk)?;
+        Ok(std::mem::take(w.get_mut()))
+      }
+    }
+  }
+
+  /// Finishes the stream and returns the remaining encoded bytes.
+  pub(crate) fn finish(self) -> crate::Result<Vec<u8>> {
+    match self {
+      #[cfg(feature = "precompress")]
+      Self::Brotli(encoder) => encoder.finish(),
+      #[cfg(feature = "precompress")]
+      Self::Gzip(w) => Ok(w.finish()?),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[cfg(feature = "precompress")]
+  const DATA: &[u8] = br#"function hello() { console.log('Hello World'); } hello(); hello();"#;
+
+  #[cfg(feature = "precompress")]
+  fn decode(encoding: ContentEncoding, data: &[u8]) -> Vec<u8> {
+    match encoding {
+      ContentEncoding::Brotli => crate::Codec::Brotli
+        .decompress(data, DATA.len() as u64)
+        .unwrap(),
+      ContentEncoding::Gzip => {
+        use std::io::Read;
+        let mut buf = vec![];
+        flate2::read::GzDecoder::new(data)
+          .read_to_end(&mut buf)
+          .unwrap();
+        buf
+      }
+    }
+  }
+
+  #[test]
+  fn id() {
+    for encoding in [ContentEncoding::Brotli, ContentEncoding::Gzip] {
+      assert_eq!(ContentEncoding::from_id(encoding.id()).unwrap(), encoding);
+      assert_eq!(ContentEncoding::parse(encoding.as_str()), Some(encoding));
+    }
+    assert!(matches!(
+      ContentEncoding::from_id(0x00),
+      Err(crate::Error::InvalidContentEncoding(0x00))
+    ));
+    assert_eq!(ContentEncoding::parse("identity"), None);
+  }
+
+  #[cfg(feature = "precompress")]
+  #[test]
+  fn encode() {
+    for encoding in [ContentEncoding::Brotli, ContentEncoding::Gzip] {
+      let encoded = encoding.encode(DATA).unwrap();
+      assert_eq!(decode(encoding, &encoded), DATA, "encoding: {encoding}");
+      assert_eq!(encoded, encoding.encode(DATA).unwrap());
+
+      let mut encoder = encoding.encoder().unwrap();
+      let mut chunked = vec![];
+      for chunk in DATA.chunks(7) {
+        chunked.extend(encoder.update(chunk).unwrap());
+      }
+      chunked.extend(encoder.finish().unwrap());
+      assert_eq!(chunked, encoded, "encoding: {encoding}");
+    }
+  }
+
+  #[cfg(not(feature = "precompress"))]
+  #[test]
+  fn not_enabled() {
+    assert!(matches!(
+      ContentEncoding::Gzip.encode(b"hello"),
+      Err(crate::Error::FeatureNotEnabled("precompress"))
+    ));
+  }
+}
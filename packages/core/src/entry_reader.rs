@@ -0,0 +1,569 @@
+use crate::block::check_blocks;
+use crate::checksum::{parse_checksum, CHECKSUM_LEN};
+use crate::codec::{Codec, CodecDecoder};
+use crate::digest::DigestHasher;
+use crate::index::IndexEntry;
+use std::hash::Hasher;
+use std::io::Read;
+use twox_hash::XxHash32;
+
+#[cfg(feature = "async")]
+use std::pin::Pin;
+#[cfg(feature = "async")]
+use std::task::{ready, Context, Poll};
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// Size of the chunks of stored data read at once.
+const INPUT_CHUNK_SIZE: usize = 16 * 1024;
+/// Maximum size of the data decompressed at once by an incremental decoder.
+const OUTPUT_CHUNK_SIZE: usize = 16 * 1024;
+
+/// How the stored data of an entry is decoded.
+enum Decode {
+  /// Stored without compression, passed through as read.
+  Stored,
+  /// Decompressed as the stored data arrives.
+  Incremental(CodecDecoder),
+  /// Split into units which are buffered and decompressed once complete. These are the blocks
+  /// of an entry compressed in blocks, or a whole LZ4 entry.
+  Units {
+    codec: Codec,
+    /// Stored and original length of every unit.
+    units: Vec<(u64, u64)>,
+    current: usize,
+    buf: Vec<u8>,
+  },
+}
+
+enum State {
+  Data,
+  Checksum,
+  Done,
+}
+
+/// Decodes the stored data of an entry fed in chunks, checking the trailing checksum, size and
+/// digest once all of it has been decoded.
+struct EntryDecoder {
+  decode: Decode,
+  state: State,
+  content_length: u64,
+  /// Stored data left to be read.
+  remaining: u64,
+  input: Vec<u8>,
+  input_start: usize,
+  input_end: usize,
+  output: Vec<u8>,
+  output_start: usize,
+  produced: u64,
+  checksum: Vec<u8>,
+  checksum_hasher: XxHash32,
+  digest_hasher: Option<(DigestHasher, Vec<u8>)>,
+}
+
+impl EntryDecoder {
+  fn new(entry: &IndexEntry, verify_digest: bool) -> crate::Result<Self> {
+    let content_length = entry.content_length();
+    let decode = match (entry.codec(), entry.blocks()) {
+      (Codec::None, _) => Decode::Stored,
+      (codec, Some(blocks)) => {
+        check_blocks(entry, blocks)?;
+        Decode::Units {
+          codec,
+          units: (0..blocks.len())
+            .map(|i| {
+              (
+                blocks.lens()[i],
+                blocks.content_length_of(i, content_length),
+              )
+            })
+            .collect(),
+          current: 0,
+          buf: Vec::new(),
+        }
+      }
+      (codec, None) => match CodecDecoder::new(codec)? {
+        Some(decoder) => Decode::Incremental(decoder),
+        None => Decode::Units {
+          codec,
+          units: vec![(entry.len(), content_length)],
+          current: 0,
+          buf: Vec::new(),
+        },
+      },
+    };
+    let digest_hasher = entry
+      .digest()
+      .filter(|_| verify_digest)
+      .map(|x| Ok::<_, crate::Error>((x.algorithm().hasher()?, x.value().to_vec())))
+      .transpose()?;
+    Ok(Self {
+      decode,
+      state: State::Data,
+      content_length,
+      remaining: entry.len(),
+      input: Vec::new(),
+      input_start: 0,
+      input_end: 0,
+      output: Vec::new(),
+      output_start: 0,
+      produced: 0,
+      checksum: Vec::with_capacity(CHECKSUM_LEN),
+      checksum_hasher: XxHash32::with_seed(0),
+      digest_hasher,
+    })
+  }
+
+  /// Returns a buffer to read the next chunk of input into. Only called when [`Self::read`]
+  /// needs more input.
+  fn input_buf(&mut self) -> &mut [u8] {
+    let len = match self.state {
+      State::Data => self.remaining.min(INPUT_CHUNK_SIZE as u64) as usize,
+      _ => CHECKSUM_LEN - self.checksum.len(),
+    };
+    if self.input.len() < len {
+      self.input.resize(len, 0);
+    }
+    &mut self.input[..len]
+  }
+
+  /// Accepts `n` bytes read into the buffer returned by [`Self::input_buf`].
+  fn fill(&mut self, n: usize) {
+    let input = &self.input[..n];
+    match self.state {
+      State::Data => {
+        self.checksum_hasher.write(input);
+        self.remaining -= n as u64;
+        self.input_start = 0;
+        self.input_end = n;
+      }
+      _ => self.checksum.extend_from_slice(input),
+    }
+  }
+
+  /// Reads decoded data into `buf`, or returns `None` if more input is needed first.
+  fn read(&mut self, buf: &mut [u8]) -> crate::Result<Option<usize>> {
+    if buf.is_empty() {
+      return Ok(Some(0));
+    }
+    loop {
+      if self.output_start < self.output.len() {
+        let output = &self.output[self.output_start..];
+        let n = output.len().min(buf.len());
+        buf[..n].copy_from_slice(&output[..n]);
+        self.output_start += n;
+        return Ok(Some(n));
+      }
+      match self.state {
+        State::Data if self.input_start < self.input_end || self.remaining == 0 => {
+          self.decode()?;
+        }
+        State::Checksum if self.checksum.len() == CHECKSUM_LEN => {
+          self.finish()?;
+          self.state = State::Done;
+        }
+        State::Done => return Ok(Some(0)),
+        _ => return Ok(None),
+      }
+    }
+  }
+
+  /// Decodes buffered input into the output, moving on to the checksum once all stored data
+  /// has been decoded.
+  fn decode(&mut self) -> crate::Result<()> {
+    let input = &self.input[self.input_start..self.input_end];
+    let end = self.remaining == 0 && input.is_empty();
+    self.output.clear();
+    self.output_start = 0;
+    let consumed = match &mut self.decode {
+      Decode::Stored => {
+        self.output.extend_from_slice(input);
+        input.len()
+      }
+      Decode::Incremental(decoder) => {
+        self.output.resize(OUTPUT_CHUNK_SIZE, 0);
+        let (consumed, produced) = decoder.decode(input, &mut self.output)?;
+        self.output.truncate(produced);
+        if produced == 0 && (consumed == 0 || end) && !decoder.is_finished() {
+          return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        consumed
+      }
+      Decode::Units {
+        codec,
+        units,
+        current,
+        buf,
+      } => {
+        let mut consumed = 0;
+        while let Some(&(len, content_length)) = units.get(*current) {
+          let take = ((len - buf.len() as u64) as usize).min(input.len() - consumed);
+          buf.extend_from_slice(&input[consumed..consumed + take]);
+          consumed += take;
+          if (buf.len() as u64) < len {
+            break;
+          }
+          self.output = codec.decompress(buf, content_length)?;
+          buf.clear();
+          *current += 1;
+          if !self.output.is_empty() {
+            break;
+          }
+        }
+        if end && *current < units.len() {
+          return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        // data after the last unit is covered by the checksum only
+        if *current == units.len() {
+          consumed = input.len();
+        }
+        consumed
+      }
+    };
+    self.input_start += consumed;
+    self.produced += self.output.len() as u64;
+    if self.produced > self.content_length {
+      return Err(self.size_mismatch());
+    }
+    if let Some((hasher, _)) = &mut self.digest_hasher {
+      hasher.update(&self.output);
+    }
+    if end && self.output.is_empty() {
+      self.state = State::Checksum;
+    }
+    Ok(())
+  }
+
+  fn finish(&mut self) -> crate::Result<()> {
+    if self.checksum_hasher.finish_32() != parse_checksum(&self.checksum) {
+      return Err(crate::Error::ChecksumMismatch);
+    }
+    if self.produced != self.content_length {
+      return Err(self.size_mismatch());
+    }
+    if let Some((hasher, expected)) = self.digest_hasher.take() {
+      if hasher.finalize() != expected {
+        return Err(crate::Error::DigestMismatch);
+      }
+    }
+    Ok(())
+  }
+
+  fn size_mismatch(&self) -> crate::Error {
+    crate::Error::EntrySizeMismatch {
+      expected: self.content_length,
+      actual: self.produced,
+    }
+  }
+}
+
+fn into_io_error(e: crate::Error) -> std::io::Error {
+  match e {
+    crate::Error::Io(e) => e,
+    e => std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+  }
+}
+
+/// Reader of the original data of a single entry, decompressing it as it is read.
+///
+/// Created by [`BundleDescriptor::open_entry`](crate::BundleDescriptor::open_entry). The trailing
+/// checksum of the stored data, the size of the original data and, if digest verification is
+/// enabled, its digest are checked once the end of the entry is reached, so reading fails before
+/// reporting the end of a corrupt entry. Errors other than I/O errors are returned as
+/// [`std::io::ErrorKind::InvalidData`] errors wrapping [`Error`](crate::Error).
+///
+/// Entries compressed with Zstandard or Brotli are decompressed in bounded chunks, and entries
+/// compressed in blocks one block at a time. LZ4 entries not split into blocks, as in `V1`
+/// bundles or when blocks are disabled, are buffered in full before being decompressed, up to
+/// [`ReaderLimits::max_entry_size`](crate::ReaderLimits::max_entry_size) checked when the reader
+/// is opened. `V2` bundles split LZ4 entries into blocks by default (see
+/// [`BundleBuilderOptions::block_size`](crate::BundleBuilderOptions::block_size)).
+///
+/// # Example
+///
+/// ```no_run
+/// # use wvb::{BundleDescriptor, BundleReader, Reader};
+/// # use std::fs::File;
+/// let mut file = File::open("app.wvb").unwrap();
+/// let descriptor: BundleDescriptor = BundleReader::new(&mut file).read().unwrap();
+///
+/// let mut entry = descriptor.open_entry(&mut file, "/video.mp4").unwrap().unwrap();
+/// std::io::copy(&mut entry, &mut std::io::sink()).unwrap();
+/// ```
+pub struct EntryReader<R: Read> {
+  r: R,
+  decoder: EntryDecoder,
+}
+
+impl<R: Read> EntryReader<R> {
+  /// Creates a reader of an entry. `r` must be positioned at the start of its stored data.
+  pub(crate) fn new(r: R, entry: &IndexEntry, verify_digest: bool) -> crate::Result<Self> {
+    Ok(Self {
+      r,
+      decoder: EntryDecoder::new(entry, verify_digest)?,
+    })
+  }
+
+  /// Returns the underlying reader.
+  pub fn into_inner(self) -> R {
+    self.r
+  }
+}
+
+impl<R: Read> Read for EntryReader<R> {
+  fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+    loop {
+      if let Some(n) = self.decoder.read(buf).map_err(into_io_error)? {
+        return Ok(n);
+      }
+      let n = self.r.read(self.decoder.input_buf())?;
+      if n == 0 {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+      }
+      self.decoder.fill(n);
+    }
+  }
+}
+
+/// Asynchronous reader of the original data of a single entry, decompressing it as it is read.
+///
+/// Created by [`BundleDescriptor::async_open_entry`](crate::BundleDescriptor::async_open_entry).
+/// See [`EntryReader`].
+#[cfg(feature = "async")]
+pub struct AsyncEntryReader<R: AsyncRead + Unpin> {
+  r: R,
+  decoder: EntryDecoder,
+}
+
+#[cfg(feature = "async")]
+impl<R: AsyncRead + Unpin> AsyncEntryReader<R> {
+  /// Creates a reader of an entry. `r` must be positioned at the start of its stored data.
+  pub(crate) fn new(r: R, entry: &IndexEntry, verify_digest: bool) -> crate::Result<Self> {
+    Ok(Self {
+      r,
+      decoder: EntryDecoder::new(entry, verify_digest)?,
+    })
+  }
+
+  /// Returns the underlying reader.
+  pub fn into_inner(self) -> R {
+    self.r
+  }
+}
+
+#[cfg(feature = "async")]
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncEntryReader<R> {
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut ReadBuf<'_>,
+  ) -> Poll<std::io::Result<()>> {
+    let this = self.get_mut();
+    loop {
+      if let Some(n) = this
+        .decoder
+        .read(buf.initialize_unfilled())
+        .map_err(into_io_error)?
+      {
+        buf.advance(n);
+        return Poll::Ready(Ok(()));
+      }
+      let mut input = ReadBuf::new(this.decoder.input_buf());
+      ready!(Pin::new(&mut this.r).poll_read(cx, &mut input))?;
+      let n = input.filled().len();
+      if n == 0 {
+        return Poll::Ready(Err(std::io::ErrorKind::UnexpectedEof.into()));
+      }
+      this.decoder.fill(n);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::testing::CODECS;
+  use crate::{
+    BundleBuilder, BundleBuilderOptions, BundleDescriptor, BundleEntry, BundleReader, BundleWriter,
+    Codec, Reader, Version, Writer,
+  };
+  use std::io::{Cursor, Read};
+
+  fn data() -> Vec<u8> {
+    (0..50_000u32)
+      .flat_map(|x| format!("line {}\n", x % 997).into_bytes())
+      .take(50_000)
+      .collect()
+  }
+
+  fn write_bundle(codec: Codec, block_size: Option<u64>) -> Vec<u8> {
+    let mut options = BundleBuilderOptions::new();
+    options.codec(codec);
+    #[cfg(feature = "digest")]
+    options.digest(crate::DigestAlgorithm::Sha3_256);
+    if let Some(block_size) = block_size {
+      options.block_size(block_size);
+    }
+    let mut builder = BundleBuilder::new_with_options(options);
+    builder.set_version(Version::V2);
+    builder.insert_entry("/data.txt", BundleEntry::new(&data(), "text/plain", None));
+    builder.insert_entry("/empty.txt", BundleEntry::new(&[], "text/plain", None));
+    let bundle = builder.build().unwrap();
+    let mut buf = vec![];
+    BundleWriter::new(&mut buf).write(&bundle).unwrap();
+    buf
+  }
+
+  fn read_descriptor(buf: &[u8]) -> BundleDescriptor {
+    let mut descriptor: BundleDescriptor = BundleReader::new(Cursor::new(buf)).read().unwrap();
+    descriptor.set_verify_digest(true);
+    descriptor
+  }
+
+  fn read_in_chunks<R: Read>(mut r: R) -> std::io::Result<Vec<u8>> {
+    let mut data = vec![];
+    let mut chunk = [0u8; 777];
+    loop {
+      let n = r.read(&mut chunk)?;
+      if n == 0 {
+        return Ok(data);
+      }
+      data.extend_from_slice(&chunk[..n]);
+    }
+  }
+
+  fn error_of(e: std::io::Error) -> crate::Error {
+    *e.into_inner().unwrap().downcast::<crate::Error>().unwrap()
+  }
+
+  #[test]
+  fn open_entry() {
+    for &codec in CODECS {
+      for block_size in [None, Some(4096)] {
+        let buf = write_bundle(codec, block_size);
+        let descriptor = read_descriptor(&buf);
+        let reader = descriptor
+          .open_entry(Cursor::new(&buf), "/data.txt")
+          .unwrap()
+          .unwrap();
+        let read = read_in_chunks(reader).unwrap();
+        assert!(read == data(), "codec: {codec}, block size: {block_size:?}");
+
+        let mut reader = descriptor
+          .open_entry(Cursor::new(&buf), "/empty.txt")
+          .unwrap()
+          .unwrap();
+        let mut read = vec![];
+        reader.read_to_end(&mut read).unwrap();
+        assert!(read.is_empty());
+        // the end is reported again
+        assert_eq!(reader.read(&mut [0u8; 8]).unwrap(), 0);
+
+        assert!(descriptor
+          .open_entry(Cursor::new(&buf), "/not-found.txt")
+          .unwrap()
+          .is_none());
+      }
+    }
+  }
+
+  #[test]
+  fn checksum_mismatch() {
+    for &codec in CODECS {
+      let mut buf = write_bundle(codec, None);
+      let descriptor = read_descriptor(&buf);
+      let entry = descriptor.index().get_entry("/data.txt").unwrap();
+      let checksum_offset = descriptor.header().data_offset() + entry.offset() + entry.len();
+      buf[checksum_offset as usize] ^= 0xff;
+
+      let reader = descriptor
+        .open_entry(Cursor::new(&buf), "/data.txt")
+        .unwrap()
+        .unwrap();
+      let e = read_in_chunks(reader).unwrap_err();
+      assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+      assert!(
+        matches!(error_of(e), crate::Error::ChecksumMismatch),
+        "codec: {codec}"
+      );
+    }
+  }
+
+  #[test]
+  fn corrupt_data() {
+    let mut buf = write_bundle(Codec::None, None);
+    let descriptor = read_descriptor(&buf);
+    let entry = descriptor.index().get_entry("/data.txt").unwrap();
+    let offset = descriptor.header().data_offset() + entry.offset() + 10;
+    buf[offset as usize] ^= 0xff;
+    let reader = descriptor
+      .open_entry(Cursor::new(&buf), "/data.txt")
+      .unwrap()
+      .unwrap();
+    let e = read_in_chunks(reader).unwrap_err();
+    assert!(matches!(error_of(e), crate::Error::ChecksumMismatch));
+  }
+
+  #[test]
+  fn truncated() {
+    for &codec in CODECS {
+      let buf = write_bundle(codec, None);
+      let descriptor = read_descriptor(&buf);
+      let entry = descriptor.index().get_entry("/data.txt").unwrap();
+      let end = descriptor.header().data_offset() + entry.offset() + entry.len() / 2;
+      // the bundle ends in the middle of the entry
+      let reader = descriptor
+        .open_entry(Cursor::new(&buf), "/data.txt")
+        .unwrap()
+        .unwrap()
+        .into_inner()
+        .take(end);
+      let mut reader = crate::EntryReader::new(reader, entry, false).unwrap();
+      let e = reader.read_to_end(&mut vec![]).unwrap_err();
+      assert_eq!(
+        e.kind(),
+        std::io::ErrorKind::UnexpectedEof,
+        "codec: {codec}"
+      );
+    }
+  }
+
+  #[cfg(feature = "async")]
+  #[tokio::test]
+  async fn async_open_entry() {
+    use tokio::io::AsyncReadExt;
+
+    for &codec in CODECS {
+      for block_size in [None, Some(4096)] {
+        let buf = write_bundle(codec, block_size);
+        let descriptor = read_descriptor(&buf);
+        let mut reader = descriptor
+          .async_open_entry(Cursor::new(&buf), "/data.txt")
+          .await
+          .unwrap()
+          .unwrap();
+        let mut read = vec![];
+        reader.read_to_end(&mut read).await.unwrap();
+        assert!(read == data(), "codec: {codec}, block size: {block_size:?}");
+      }
+    }
+  }
+
+  #[cfg(all(feature = "async", feature = "codec-zstd"))]
+  #[tokio::test]
+  async fn async_checksum_mismatch() {
+    use tokio::io::AsyncReadExt;
+
+    let mut buf = write_bundle(Codec::Zstd, None);
+    let descriptor = read_descriptor(&buf);
+    let entry = descriptor.index().get_entry("/data.txt").unwrap();
+    let checksum_offset = descriptor.header().data_offset() + entry.offset() + entry.len();
+    buf[checksum_offset as usize + 1] ^= 0xff;
+    let mut reader = descriptor
+      .async_open_entry(Cursor::new(&buf), "/data.txt")
+      .await
+      .unwrap()
+      .unwrap();
+    let e = reader.read_to_end(&mut vec![]).await.unwrap_err();
+    assert!(matches!(error_of(e), crate::Error::ChecksumMismatch));
+  }
+}
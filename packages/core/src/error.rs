@@ -28,8 +28,73 @@ pub enum Error {
   InvalidHeaderChecksum,
   #[error("invalid index checksum")]
   InvalidIndexChecksum,
+  #[error("invalid index table")]
+  InvalidIndexTable,
+  #[error("index is too large")]
+  IndexTooLarge,
+  #[error("index size {size} exceeds the limit of {limit} bytes")]
+  IndexSizeLimitExceeded { size: u64, limit: u64 },
+  #[error("entry count {count} exceeds the limit of {limit}")]
+  EntryCountLimitExceeded { count: u64, limit: u64 },
+  #[error("entry size {size} exceeds the limit of {limit} bytes")]
+  EntrySizeLimitExceeded { size: u64, limit: u64 },
+  #[error("bundle size {size} exceeds the limit of {limit} bytes")]
+  TotalSizeLimitExceeded { size: u64, limit: u64 },
+  #[error("decompressed entry size mismatch (expected: {expected}, actual: {actual})")]
+  EntrySizeMismatch { expected: u64, actual: u64 },
+  #[error("bundle metadata is not supported in {0} bundle")]
+  UnsupportedMetadata(crate::Version),
+  #[error("invalid signatures checksum")]
+  InvalidSignaturesChecksum,
+  #[error("embedded signatures are not supported in {0} bundle")]
+  UnsupportedSignature(crate::Version),
+  #[error("invalid codec: {0}")]
+  InvalidCodec(u8),
+  #[error("`{0}` feature is not enabled")]
+  FeatureNotEnabled(&'static str),
+  #[error("codec {codec} is not supported in {version} bundle")]
+  UnsupportedCodec {
+    codec: crate::Codec,
+    version: crate::Version,
+  },
+  #[error("invalid digest algorithm: {0}")]
+  InvalidDigestAlgorithm(u8),
+  #[error("entry digest is not supported in {0} bundle")]
+  UnsupportedDigest(crate::Version),
+  #[error("digest mismatch")]
+  DigestMismatch,
+  #[error("invalid content encoding: {0}")]
+  InvalidContentEncoding(u8),
+  #[error("precompressed variants are not supported in {0} bundle")]
+  UnsupportedPrecompression(crate::Version),
+  #[error("block-compressed entries are not supported in {0} bundle")]
+  UnsupportedBlocks(crate::Version),
+  #[error("block lengths do not add up to the entry length")]
+  InvalidEntryBlocks,
+  #[error("entry already exists: {0}")]
+  EntryAlreadyExists(String),
+  #[error("stream writer is poisoned by a failed entry write")]
+  StreamWriterPoisoned,
+  #[error("unsafe entry path: {0}")]
+  UnsafeEntryPath(String),
+  #[error("entry path is extracted to the same file as another entry: {0}")]
+  DuplicateEntryPath(String),
   #[error("checksum mismatch")]
   ChecksumMismatch,
+  #[cfg(any(feature = "dir", feature = "header-rules"))]
+  #[error("invalid glob pattern: {0}")]
+  Glob(#[from] glob::PatternError),
+  #[cfg(feature = "header-rules")]
+  #[error("invalid header rules: {0}")]
+  InvalidHeaderRules(String),
+  #[error("invalid patch: {0}")]
+  InvalidPatch(String),
+  #[error("invalid patch checksum")]
+  InvalidPatchChecksum,
+  #[error("patch does not apply to this bundle")]
+  PatchSourceMismatch,
+  #[error("patched bundle does not match the patch target")]
+  PatchTargetMismatch,
   #[error("bundle not found")]
   BundleNotFound,
   #[cfg(feature = "source")]
@@ -39,15 +104,24 @@ pub enum Error {
     version: String,
   },
   #[cfg(feature = "source")]
+  #[error("bundle metadata mismatch (expected: {expected}, actual: {actual})")]
+  BundleMetadataMismatch { expected: String, actual: String },
+  #[cfg(feature = "source")]
   #[error("bundle cannot be removed (bundle_name: {bundle_name}, version: {version}): {reason}")]
   BundleCannotBeRemoved {
     bundle_name: String,
     version: String,
     reason: String,
   },
+  #[cfg(feature = "source")]
+  #[error("bundle has no remote version to roll back (bundle_name: {0})")]
+  BundleCannotBeRolledBack(String),
   #[cfg(feature = "_serde")]
   #[error("serde json error: {0}")]
   SerdeJson(#[from] serde_json::Error),
+  #[cfg(feature = "header-rules")]
+  #[error("toml error: {0}")]
+  Toml(#[from] toml::de::Error),
   #[cfg(feature = "protocol-local")]
   #[error("cannot resolve local host")]
   CannotResolveLocalHost,
@@ -102,6 +176,9 @@ pub enum Error {
   #[cfg(feature = "signature")]
   #[error("signature verify failed")]
   SignatureVerifyFailed,
+  #[cfg(feature = "signature")]
+  #[error("entry has no digest covered by the signature: {0}")]
+  EntryDigestNotExists(String),
   #[error("generic error: {0}")]
   Generic(#[source] Box<dyn std::error::Error + Send + Sync + 'static>),
 }
@@ -161,6 +238,16 @@ impl Error {
     Self::InvalidVerifyingKey(error.into())
   }
 
+  #[cfg(feature = "patch")]
+  pub(crate) fn invalid_patch(message: impl Into<String>) -> Self {
+    Self::InvalidPatch(message.into())
+  }
+
+  #[cfg(feature = "header-rules")]
+  pub(crate) fn invalid_header_rules(message: impl Into<String>) -> Self {
+    Self::InvalidHeaderRules(message.into())
+  }
+
   #[allow(dead_code)]
   pub(crate) fn generic(
     error: impl Into<Box<dyn std::error::Error + Send + Sync + 'static>>,
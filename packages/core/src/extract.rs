@@ -0,0 +1,387 @@
+use crate::bundle::Bundle;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+type OnExtract = dyn Fn(usize, usize, &str) + Send + Sync + 'static;
+
+/// How entry paths which are unsafe to join to the output directory are handled.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum UnsafePathPolicy {
+  /// Fails the extraction with [`Error::UnsafeEntryPath`](crate::Error::UnsafeEntryPath).
+  #[default]
+  Reject,
+  /// Drops `..` and absolute prefixes from the path and removes NUL bytes and `:`. Entries with
+  /// nothing left of their path, or sanitised to the path of another entry, are still rejected.
+  Sanitize,
+}
+
+/// Options for extracting a bundle to a directory.
+///
+/// # Example
+///
+/// ```no_run
+/// use wvb::{Bundle, BundleReader, ExtractOptions, Reader, UnsafePathPolicy};
+///
+/// let file = std::fs::File::open("app.wvb").unwrap();
+/// let bundle: Bundle = BundleReader::new(file).read().unwrap();
+/// let mut options = ExtractOptions::new();
+/// options
+///   .unsafe_paths(UnsafePathPolicy::Sanitize)
+///   .on_progress(|extracted, total, path| println!("[{extracted}/{total}] {path}"));
+/// bundle.extract_to("./app", &options).unwrap();
+/// ```
+#[derive(Default, Clone)]
+pub struct ExtractOptions {
+  pub(crate) unsafe_paths: UnsafePathPolicy,
+  #[cfg(feature = "extract-sidecar")]
+  pub(crate) sidecar: Option<PathBuf>,
+  pub(crate) on_progress: Option<Arc<OnExtract>>,
+}
+
+impl ExtractOptions {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Sets how unsafe entry paths are handled. Defaults to [`UnsafePathPolicy::Reject`].
+  pub fn unsafe_paths(&mut self, policy: UnsafePathPolicy) -> &mut Self {
+    self.unsafe_paths = policy;
+    self
+  }
+
+  /// Writes a JSON file at the given path with the content type and headers of every entry.
+  ///
+  /// The file maps entry paths to their metadata:
+  ///
+  /// ```json
+  /// {
+  ///   "/index.html": {
+  ///     "content_type": "text/html",
+  ///     "headers": { "cache-control": ["no-cache"] }
+  ///   }
+  /// }
+  /// ```
+  #[cfg(feature = "extract-sidecar")]
+  pub fn sidecar(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+    self.sidecar = Some(path.into());
+    self
+  }
+
+  /// Sets a callback called after each entry is written, with the number of entries written so
+  /// far, the total number of entries and the entry path.
+  pub fn on_progress<F>(&mut self, on_progress: F) -> &mut Self
+  where
+    F: Fn(usize, usize, &str) + Send + Sync + 'static,
+  {
+    self.on_progress = Some(Arc::new(on_progress));
+    self
+  }
+}
+
+/// Resolves the file path of an entry relative to the output directory.
+///
+/// Entry paths are `/`-prefixed, so one leading `/` is expected. Paths with `..`, another
+/// absolute prefix (`//`, `\`, or a drive like `C:`), NUL bytes or `:` are unsafe to join to
+/// the directory, and are rejected or sanitised following the policy. A `:` anywhere in a
+/// segment is unsafe, since Windows reads it as a drive prefix or an alternate data stream.
+pub(crate) fn entry_file_path(path: &str, policy: UnsafePathPolicy) -> crate::Result<PathBuf> {
+  let unsafe_path = || crate::Error::UnsafeEntryPath(path.to_string());
+  let relative = path.strip_prefix('/').unwrap_or(path);
+  let mut file_path = PathBuf::new();
+  for (i, segment) in relative.split(['/', '\\']).enumerate() {
+    let is_drive = i == 0 && segment.len() == 2 && segment.ends_with(':');
+    let is_unsafe = segment == ".." || segment.contains(['\0', ':']);
+    // an empty first segment means another absolute prefix
+    let is_absolute = i == 0 && segment.is_empty() && relative.len() > segment.len();
+    if (is_unsafe || is_absolute) && policy == UnsafePathPolicy::Reject {
+      return Err(unsafe_path());
+    }
+    let segment = segment.replace(['\0', ':'], "");
+    if segment.is_empty() || segment == "." || segment == ".." || is_drive {
+      continue;
+    }
+    file_path.push(segment);
+  }
+  if file_path.as_os_str().is_empty() {
+    return Err(unsafe_path());
+  }
+  Ok(file_path)
+}
+
+/// Files to write for each entry, resolved before anything is written.
+fn extract_plan(
+  bundle: &Bundle,
+  options: &ExtractOptions,
+) -> crate::Result<Vec<(String, PathBuf)>> {
+  let mut paths = bundle
+    .descriptor()
+    .index()
+    .try_entries()?
+    .keys()
+    .cloned()
+    .collect::<Vec<_>>();
+  paths.sort_unstable();
+  let mut file_paths = HashSet::with_capacity(paths.len());
+  paths
+    .into_iter()
+    .map(|path| {
+      let file_path = entry_file_path(&path, options.unsafe_paths)?;
+      // sanitised paths may collide, and one entry would silently overwrite the other
+      if !file_paths.insert(file_path.clone()) {
+        return Err(crate::Error::DuplicateEntryPath(path));
+      }
+      Ok((path, file_path))
+    })
+    .collect()
+}
+
+#[cfg(feature = "extract-sidecar")]
+fn sidecar(bundle: &Bundle) -> crate::Result<Vec<u8>> {
+  use serde::Serialize;
+  use std::collections::BTreeMap;
+
+  #[derive(Serialize)]
+  struct SidecarEntry<'a> {
+    content_type: &'a str,
+    headers: BTreeMap<&'a str, Vec<String>>,
+  }
+
+  let entries = bundle
+    .descriptor()
+    .index()
+    .try_entries()?
+    .iter()
+    .map(|(path, entry)| {
+      let mut headers = BTreeMap::<_, Vec<_>>::new();
+      for (name, value) in entry.headers() {
+        let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+        headers.entry(name.as_str()).or_default().push(value);
+      }
+      let entry = SidecarEntry {
+        content_type: entry.content_type(),
+        headers,
+      };
+      (path.as_str(), entry)
+    })
+    .collect::<BTreeMap<_, _>>();
+  Ok(serde_json::to_vec_pretty(&entries)?)
+}
+
+impl Bundle {
+  /// Writes every entry of the bundle as a file under the given directory.
+  ///
+  /// Entry paths are joined to the directory, so `/assets/app.js` is written to
+  /// `<dir>/assets/app.js`. Every path is checked before anything is written, and paths which
+  /// could escape the directory are handled following [`ExtractOptions::unsafe_paths`].
+  /// Existing files are overwritten.
+  ///
+  /// Fails with [`Error::DuplicateEntryPath`](crate::Error::DuplicateEntryPath) if two entries
+  /// resolve to the same file, e.g. `/x` and `/../x` sanitised.
+  pub fn extract_to(&self, dir: impl AsRef<Path>, options: &ExtractOptions) -> crate::Result<()> {
+    let dir = dir.as_ref();
+    let plan = extract_plan(self, options)?;
+    for (i, (path, file_path)) in plan.iter().enumerate() {
+      let file_path = dir.join(file_path);
+      if let Some(parent) = file_path.parent() {
+        std::fs::create_dir_all(parent)?;
+      }
+      let data = self.get_data(path)?.unwrap_or_default();
+      std::fs::write(&file_path, data)?;
+      if let Some(on_progress) = &options.on_progress {
+        on_progress(i + 1, plan.len(), path);
+      }
+    }
+    #[cfg(feature = "extract-sidecar")]
+    if let Some(sidecar_path) = &options.sidecar {
+      std::fs::write(sidecar_path, sidecar(self)?)?;
+    }
+    Ok(())
+  }
+
+  /// Asynchronously writes every entry of the bundle as a file under the given directory.
+  ///
+  /// See [`Bundle::extract_to`].
+  #[cfg(feature = "async")]
+  pub async fn async_extract_to(
+    &self,
+    dir: impl AsRef<Path>,
+    options: &ExtractOptions,
+  ) -> crate::Result<()> {
+    let dir = dir.as_ref();
+    let plan = extract_plan(self, options)?;
+    for (i, (path, file_path)) in plan.iter().enumerate() {
+      let file_path = dir.join(file_path);
+      if let Some(parent) = file_path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+      }
+      let data = self.get_data(path)?.unwrap_or_default();
+      tokio::fs::write(&file_path, data).await?;
+      if let Some(on_progress) = &options.on_progress {
+        on_progress(i + 1, plan.len(), path);
+      }
+    }
+    #[cfg(feature = "extract-sidecar")]
+    if let Some(sidecar_path) = &options.sidecar {
+      tokio::fs::write(sidecar_path, sidecar(self)?).await?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::builder::{BundleBuilder, BundleEntry};
+  use crate::testing::TempDir;
+  use std::sync::Mutex;
+
+  fn bundle(paths: &[&str]) -> Bundle {
+    let mut builder = BundleBuilder::new();
+    for path in paths {
+      builder.insert_entry(*path, BundleEntry::new(path.as_bytes(), "text/plain", None));
+    }
+    builder.build().unwrap()
+  }
+
+  #[test]
+  fn entry_paths() {
+    let reject = |path: &str| entry_file_path(path, UnsafePathPolicy::Reject).ok();
+    let sanitize = |path: &str| entry_file_path(path, UnsafePathPolicy::Sanitize).ok();
+    let path = |x: &str| Some(x.split('/').collect::<PathBuf>());
+
+    assert_eq!(reject("/index.html"), path("index.html"));
+    assert_eq!(reject("/assets/./app.js"), path("assets/app.js"));
+    assert_eq!(reject("relative.js"), path("relative.js"));
+    for unsafe_path in [
+      "/../etc/passwd",
+      "/assets/../../x",
+      "//etc/passwd",
+      "/\\server\\share",
+      "/C:/Windows",
+      "/assets/C:x",
+      "/a.txt:stream",
+      "/a\0.js",
+      "/",
+    ] {
+      assert_eq!(reject(unsafe_path), None, "{unsafe_path:?}");
+    }
+
+    assert_eq!(sanitize("/../etc/passwd"), path("etc/passwd"));
+    assert_eq!(sanitize("/assets/../../x"), path("assets/x"));
+    assert_eq!(sanitize("//etc/passwd"), path("etc/passwd"));
+    assert_eq!(sanitize("/C:\\Windows\\x"), path("Windows/x"));
+    assert_eq!(sanitize("/a\0.js"), path("a.js"));
+    assert_eq!(sanitize("/assets/C:x"), path("assets/Cx"));
+    assert_eq!(sanitize("/a.txt:stream"), path("a.txtstream"));
+    assert_eq!(sanitize("/.."), None);
+  }
+
+  #[test]
+  fn extract_to() {
+    let dir = TempDir::new("extract_to");
+    let bundle = bundle(&["/index.html", "/assets/app.js", "/assets/img/logo.png"]);
+    let progress = Arc::new(Mutex::new(vec![]));
+    let mut options = ExtractOptions::new();
+    let recorded = progress.clone();
+    options.on_progress(move |extracted, total, path| {
+      recorded
+        .lock()
+        .unwrap()
+        .push((extracted, total, path.to_string()));
+    });
+    bundle.extract_to(dir.path(), &options).unwrap();
+
+    for path in ["/index.html", "/assets/app.js", "/assets/img/logo.png"] {
+      let data = std::fs::read(dir.path().join(&path[1..])).unwrap();
+      assert_eq!(data, path.as_bytes());
+    }
+    assert_eq!(
+      *progress.lock().unwrap(),
+      [
+        (1, 3, "/assets/app.js".to_string()),
+        (2, 3, "/assets/img/logo.png".to_string()),
+        (3, 3, "/index.html".to_string()),
+      ]
+    );
+  }
+
+  #[test]
+  fn unsafe_paths() {
+    let dir = TempDir::new("extract_unsafe_paths");
+    let out = dir.path().join("out");
+    let bundle = bundle(&["/index.html", "/../escape.txt"]);
+    assert!(matches!(
+      bundle.extract_to(&out, &ExtractOptions::new()),
+      Err(crate::Error::UnsafeEntryPath(path)) if path == "/../escape.txt"
+    ));
+    // nothing is written when a path is rejected
+    assert!(!out.exists());
+
+    let mut options = ExtractOptions::new();
+    options.unsafe_paths(UnsafePathPolicy::Sanitize);
+    bundle.extract_to(&out, &options).unwrap();
+    assert!(out.join("escape.txt").is_file());
+    assert!(!dir.path().join("escape.txt").exists());
+  }
+
+  #[test]
+  fn duplicate_paths() {
+    let dir = TempDir::new("extract_duplicate_paths");
+    let out = dir.path().join("out");
+    let bundle = bundle(&["/x", "/../x"]);
+    let mut options = ExtractOptions::new();
+    options.unsafe_paths(UnsafePathPolicy::Sanitize);
+    assert!(matches!(
+      bundle.extract_to(&out, &options),
+      Err(crate::Error::DuplicateEntryPath(path)) if path == "/x"
+    ));
+    assert!(!out.exists());
+  }
+
+  #[cfg(feature = "extract-sidecar")]
+  #[test]
+  fn sidecar() {
+    let dir = TempDir::new("extract_sidecar");
+    let mut builder = BundleBuilder::new();
+    let mut headers = http::HeaderMap::new();
+    headers.append("link", "</a.css>".parse().unwrap());
+    headers.append("link", "</b.js>".parse().unwrap());
+    builder.insert_entry(
+      "/index.html",
+      BundleEntry::new(b"<html></html>", "text/html", Some(headers)),
+    );
+    let bundle = builder.build().unwrap();
+    let sidecar_path = dir.path().join("entries.json");
+    let mut options = ExtractOptions::new();
+    options.sidecar(&sidecar_path);
+    bundle.extract_to(dir.path().join("out"), &options).unwrap();
+
+    let json: serde_json::Value =
+      serde_json::from_slice(&std::fs::read(sidecar_path).unwrap()).unwrap();
+    assert_eq!(
+      json,
+      serde_json::json!({
+        "/index.html": {
+          "content_type": "text/html",
+          "headers": { "link": ["</a.css>", "</b.js>"] }
+        }
+      })
+    );
+  }
+
+  #[cfg(feature = "async")]
+  #[tokio::test]
+  async fn async_extract_to() {
+    let dir = TempDir::new("async_extract_to");
+    let bundle = bundle(&["/index.html", "/assets/app.js"]);
+    bundle
+      .async_extract_to(dir.path(), &ExtractOptions::new())
+      .await
+      .unwrap();
+    assert_eq!(
+      std::fs::read(dir.path().join("assets").join("app.js")).unwrap(),
+      b"/assets/app.js"
+    );
+  }
+}
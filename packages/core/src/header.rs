@@ -13,14 +13,21 @@ use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, As
 
 /// Bundle header containing format metadata.
 ///
-/// The header is the first 17 bytes of a `.wvb` file:
+/// The header is the first 17 bytes of a `V1` `.wvb` file:
 ///
 /// | Magic (8) | Version (1) | Index Size (4) | Checksum (4) |
 /// |-----------|-------------|----------------|--------------|
 ///
+/// `V2` headers are 25 bytes and also record the size of the data section, because the index
+/// is located after the data section:
+///
+/// | Magic (8) | Version (1) | Index Size (4) | Data Size (8) | Checksum (4) |
+/// |-----------|-------------|----------------|---------------|--------------|
+///
 /// - **Magic Number**: `0xf09f8c90f09f8e81` (🌐🎁 in UTF-8)
-/// - **Version**: Bundle format version (currently 0x01)
+/// - **Version**: Bundle format version (0x01 or 0x02)
 /// - **Index Size**: Size of the index section in bytes (u32, big-endian)
+/// - **Data Size**: Size of the data section in bytes (u64, big-endian, `V2` only)
 /// - **Checksum**: xxHash-32 checksum of the header data
 ///
 /// # Example
@@ -36,6 +43,7 @@ use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, As
 pub struct Header {
   version: Version,
   index_size: u32,
+  data_size: u64,
 }
 
 impl Header {
@@ -63,11 +71,60 @@ impl Header {
   /// Total size of the header in bytes (17 bytes)
   pub const END_OFFSET: u64 = Self::CHECKSUM_OFFSET + CHECKSUM_LEN as u64;
 
-  /// Calculates the byte offset where the index section ends.
+  /// Offset of the data size field (`V2` only)
+  pub const DATA_SIZE_OFFSET: u64 = Self::INDEX_SIZE_OFFSET + Self::INDEX_SIZE_BYTES_LEN as u64;
+
+  /// Length of the data size field in bytes (`V2` only)
+  pub const DATA_SIZE_BYTES_LEN: usize = 8;
+
+  /// Offset of the header checksum in `V2` header
+  pub const V2_CHECKSUM_OFFSET: u64 = Self::DATA_SIZE_OFFSET + Self::DATA_SIZE_BYTES_LEN as u64;
+
+  /// Total size of `V2` header in bytes (25 bytes)
+  pub const V2_END_OFFSET: u64 = Self::V2_CHECKSUM_OFFSET + CHECKSUM_LEN as u64;
+
+  /// Returns the offset of the header checksum for the given format version.
+  pub const fn checksum_offset_of(version: Version) -> u64 {
+    match version {
+      Version::V1 => Self::CHECKSUM_OFFSET,
+      Version::V2 => Self::V2_CHECKSUM_OFFSET,
+    }
+  }
+
+  /// Returns the total size of the header for the given format version.
+  pub const fn end_offset_of(version: Version) -> u64 {
+    Self::checksum_offset_of(version) + CHECKSUM_LEN as u64
+  }
+
+  /// Returns the total size of this header in bytes.
+  pub fn end_offset(&self) -> u64 {
+    Self::end_offset_of(self.version)
+  }
+
+  /// Calculates the byte offset where the index section starts.
   ///
-  /// This is the starting point of the data section.
+  /// The index follows the header in `V1`, and follows the data section in `V2`.
+  pub fn index_offset(&self) -> u64 {
+    match self.version {
+      Version::V1 => Self::END_OFFSET,
+      // saturates on data sizes read from a corrupt header, so reads past the end fail instead
+      Version::V2 => Self::V2_END_OFFSET.saturating_add(self.data_size),
+    }
+  }
+
+  /// Calculates the byte offset where the index section ends.
   pub fn index_end_offset(&self) -> u64 {
-    Self::END_OFFSET + self.index_size as u64 + CHECKSUM_LEN as u64
+    self
+      .index_offset()
+      .saturating_add(self.index_size as u64 + CHECKSUM_LEN as u64)
+  }
+
+  /// Calculates the byte offset where the data section starts.
+  pub fn data_offset(&self) -> u64 {
+    match self.version {
+      Version::V1 => self.index_end_offset(),
+      Version::V2 => Self::V2_END_OFFSET,
+    }
   }
 
   /// Creates a new header.
@@ -80,6 +137,7 @@ impl Header {
     Self {
       version,
       index_size,
+      data_size: 0,
     }
   }
 
@@ -92,6 +150,22 @@ impl Header {
   pub fn index_size(&self) -> u32 {
     self.index_size
   }
+
+  /// Returns the size of the data section in bytes.
+  ///
+  /// Returns `None` for `V1` whose header does not record it.
+  pub fn data_size(&self) -> Option<u64> {
+    match self.version {
+      Version::V1 => None,
+      Version::V2 => Some(self.data_size),
+    }
+  }
+
+  /// Sets the size of the data section in bytes, which is only written in `V2` header.
+  pub fn set_data_size(&mut self, data_size: u64) -> &mut Self {
+    self.data_size = data_size;
+    self
+  }
 }
 
 fn write_magic() -> Vec<u8> {
@@ -106,6 +180,10 @@ fn write_index_size(index_size: u32) -> Vec<u8> {
   index_size.to_be_bytes().to_vec()
 }
 
+fn write_data_size(data_size: u64) -> Vec<u8> {
+  data_size.to_be_bytes().to_vec()
+}
+
 #[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub struct HeaderWriterOptions {
   pub(crate) checksum_seed: u32,
@@ -162,6 +240,12 @@ impl<W: Write> HeaderWriter<W> {
     Ok(bytes)
   }
 
+  pub fn write_data_size(&mut self, data_size: u64) -> crate::Result<Vec<u8>> {
+    let bytes = write_data_size(data_size);
+    self.w.write_all(&bytes)?;
+    Ok(bytes)
+  }
+
   pub fn write_checksum(&mut self, checksum: u32) -> crate::Result<Vec<u8>> {
     let bytes = write_checksum(checksum);
     self.w.write_all(&bytes)?;
@@ -175,6 +259,9 @@ impl<W: Write> Writer<Header> for HeaderWriter<W> {
     bytes.extend(self.write_magic()?);
     bytes.extend(self.write_version(header.version)?);
     bytes.extend(self.write_index_size(header.index_size)?);
+    if let Some(data_size) = header.data_size() {
+      bytes.extend(self.write_data_size(data_size)?);
+    }
 
     let checksum = make_checksum(self.options.checksum_seed, &bytes);
     bytes.extend(self.write_checksum(checksum)?);
@@ -224,6 +311,12 @@ impl<W: AsyncWrite + Unpin> AsyncHeaderWriter<W> {
     Ok(bytes)
   }
 
+  pub async fn write_data_size(&mut self, data_size: u64) -> crate::Result<Vec<u8>> {
+    let bytes = write_data_size(data_size);
+    self.w.write_all(&bytes).await?;
+    Ok(bytes)
+  }
+
   pub async fn write_checksum(&mut self, checksum: u32) -> crate::Result<Vec<u8>> {
     let bytes = write_checksum(checksum);
     self.w.write_all(&bytes).await?;
@@ -238,6 +331,9 @@ impl<W: AsyncWrite + Unpin> AsyncWriter<Header> for AsyncHeaderWriter<W> {
     bytes.extend(self.write_magic().await?);
     bytes.extend(self.write_version(header.version).await?);
     bytes.extend(self.write_index_size(header.index_size).await?);
+    if let Some(data_size) = header.data_size() {
+      bytes.extend(self.write_data_size(data_size).await?);
+    }
 
     let checksum = make_checksum(self.options.checksum_seed, &bytes);
     bytes.extend(self.write_checksum(checksum).await?);
@@ -264,6 +360,9 @@ fn parse_version(buf: &[u8; VERSION_LEN]) -> crate::Result<Version> {
   if buf == Version::V1.bytes().as_ref() {
     return Ok(Version::V1);
   }
+  if buf == Version::V2.bytes().as_ref() {
+    return Ok(Version::V2);
+  }
   Err(crate::Error::InvalidVersion)
 }
 
@@ -278,14 +377,22 @@ fn parse_index_size(buf: &[u8; Header::INDEX_SIZE_BYTES_LEN]) -> u32 {
   u32::from_be_bytes(AsRef::<[u8]>::as_ref(&buf).try_into().unwrap())
 }
 
-fn read_checksum() -> (u64, [u8; CHECKSUM_LEN]) {
-  (Header::CHECKSUM_OFFSET, [0u8; CHECKSUM_LEN])
+fn read_data_size() -> (u64, [u8; Header::DATA_SIZE_BYTES_LEN]) {
+  (Header::DATA_SIZE_OFFSET, [0u8; Header::DATA_SIZE_BYTES_LEN])
+}
+
+fn parse_data_size(buf: &[u8; Header::DATA_SIZE_BYTES_LEN]) -> u64 {
+  u64::from_be_bytes(*buf)
 }
 
-fn read_total() -> (u64, [u8; Header::CHECKSUM_OFFSET as usize]) {
+fn read_checksum(version: Version) -> (u64, [u8; CHECKSUM_LEN]) {
+  (Header::checksum_offset_of(version), [0u8; CHECKSUM_LEN])
+}
+
+fn read_total(version: Version) -> (u64, Vec<u8>) {
   (
     Header::MAGIC_OFFSET,
-    [0u8; Header::CHECKSUM_OFFSET as usize],
+    vec![0u8; Header::checksum_offset_of(version) as usize],
   )
 }
 
@@ -352,16 +459,30 @@ impl<R: Read + Seek> HeaderReader<R> {
     Ok(parse_index_size(&buf))
   }
 
+  pub fn read_data_size(&mut self) -> crate::Result<u64> {
+    let (offset, mut buf) = read_data_size();
+    self.r.seek(SeekFrom::Start(offset))?;
+    self.r.read_exact(&mut buf)?;
+    Ok(parse_data_size(&buf))
+  }
+
+  /// Reads the header checksum, located by the version read from the header.
   pub fn read_checksum(&mut self) -> crate::Result<u32> {
-    let (offset, mut buf) = read_checksum();
+    let version = self.read_version()?;
+    self.read_checksum_of(version)
+  }
+
+  /// Reads the header checksum of a header in the given format version.
+  pub fn read_checksum_of(&mut self, version: Version) -> crate::Result<u32> {
+    let (offset, mut buf) = read_checksum(version);
     self.r.seek(SeekFrom::Start(offset))?;
     self.r.read_exact(&mut buf)?;
     let checksum = parse_checksum(&buf);
     Ok(checksum)
   }
 
-  fn verify_checksum(&mut self, checksum: u32) -> crate::Result<()> {
-    let (offset, mut total) = read_total();
+  fn verify_checksum(&mut self, version: Version, checksum: u32) -> crate::Result<()> {
+    let (offset, mut total) = read_total(version);
     self.r.seek(SeekFrom::Start(offset))?;
     self.r.read_exact(&mut total)?;
 
@@ -378,11 +499,15 @@ impl<R: Read + Seek> Reader<Header> for HeaderReader<R> {
     self.read_magic()?;
     let version = self.read_version()?;
     let index_size = self.read_index_size()?;
-    let checksum = self.read_checksum()?;
+    let mut header = Header::new(version, index_size);
+    if version != Version::V1 {
+      header.set_data_size(self.read_data_size()?);
+    }
+    let checksum = self.read_checksum_of(version)?;
     if self.options.verify_checksum {
-      self.verify_checksum(checksum)?;
+      self.verify_checksum(version, checksum)?;
     }
-    Ok(Header::new(version, index_size))
+    Ok(header)
   }
 }
 
@@ -429,16 +554,30 @@ impl<R: AsyncRead + AsyncSeek + Unpin> AsyncHeaderReader<R> {
     Ok(parse_index_size(&buf))
   }
 
+  pub async fn read_data_size(&mut self) -> crate::Result<u64> {
+    let (offset, mut buf) = read_data_size();
+    self.r.seek(SeekFrom::Start(offset)).await?;
+    self.r.read_exact(&mut buf).await?;
+    Ok(parse_data_size(&buf))
+  }
+
+  /// Reads the header checksum, located by the version read from the header.
   pub async fn read_checksum(&mut self) -> crate::Result<u32> {
-    let (offset, mut buf) = read_checksum();
+    let version = self.read_version().await?;
+    self.read_checksum_of(version).await
+  }
+
+  /// Reads the header checksum of a header in the given format version.
+  pub async fn read_checksum_of(&mut self, version: Version) -> crate::Result<u32> {
+    let (offset, mut buf) = read_checksum(version);
     self.r.seek(SeekFrom::Start(offset)).await?;
     self.r.read_exact(&mut buf).await?;
     let checksum = parse_checksum(&buf);
     Ok(checksum)
   }
 
-  async fn verify_checksum(&mut self, checksum: u32) -> crate::Result<()> {
-    let (offset, mut total) = read_total();
+  async fn verify_checksum(&mut self, version: Version, checksum: u32) -> crate::Result<()> {
+    let (offset, mut total) = read_total(version);
     self.r.seek(SeekFrom::Start(offset)).await?;
     self.r.read_exact(&mut total).await?;
 
@@ -456,11 +595,15 @@ impl<R: AsyncRead + AsyncSeek + Unpin> AsyncReader<Header> for AsyncHeaderReader
     self.read_magic().await?;
     let version = self.read_version().await?;
     let index_size = self.read_index_size().await?;
-    let checksum = self.read_checksum().await?;
+    let mut header = Header::new(version, index_size);
+    if version != Version::V1 {
+      header.set_data_size(self.read_data_size().await?);
+    }
+    let checksum = self.read_checksum_of(version).await?;
     if self.options.verify_checksum {
-      self.verify_checksum(checksum).await?;
+      self.verify_checksum(version, checksum).await?;
     }
-    Ok(Header::new(version, index_size))
+    Ok(header)
   }
 }
 
@@ -484,6 +627,43 @@ mod tests {
     assert_eq!(header, read_header);
     assert_eq!(read_header.version(), Version::V1);
     assert_eq!(read_header.index_size(), 1234);
+    assert_eq!(read_header.data_size(), None);
+  }
+
+  #[test]
+  fn read_and_write_v2() {
+    let mut header = Header::new(Version::V2, 1234);
+    header.set_data_size(5678);
+    let mut buf = vec![];
+    let mut writer = HeaderWriter::new(Cursor::new(&mut buf));
+    let size = writer.write(&header).unwrap();
+    assert_eq!(size as u64, Header::V2_END_OFFSET);
+    assert_eq!(
+      &buf[..Header::V2_CHECKSUM_OFFSET as usize],
+      [240, 159, 140, 144, 240, 159, 142, 129, 2, 0, 0, 4, 210, 0, 0, 0, 0, 0, 0, 22, 46]
+    );
+    let mut reader = HeaderReader::new_with_options(
+      Cursor::new(&buf),
+      HeaderReaderOptions::new().verify_checksum(true),
+    );
+    let read_header = reader.read().unwrap();
+    assert_eq!(header, read_header);
+    assert_eq!(read_header.data_size(), Some(5678));
+    assert_eq!(read_header.data_offset(), Header::V2_END_OFFSET);
+    assert_eq!(read_header.index_offset(), Header::V2_END_OFFSET + 5678);
+    assert_eq!(
+      read_header.index_end_offset(),
+      Header::V2_END_OFFSET + 5678 + 1234 + 4
+    );
+  }
+
+  #[test]
+  fn offsets_v1() {
+    let header = Header::new(Version::V1, 1234);
+    assert_eq!(header.end_offset(), Header::END_OFFSET);
+    assert_eq!(header.index_offset(), Header::END_OFFSET);
+    assert_eq!(header.index_end_offset(), Header::END_OFFSET + 1234 + 4);
+    assert_eq!(header.data_offset(), header.index_end_offset());
   }
 
   #[cfg(feature = "async")]
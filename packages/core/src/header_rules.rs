@@ -0,0 +1,282 @@
+use crate::builder::merge_headers;
+use crate::path_pattern::PathPattern;
+use http::HeaderMap;
+
+/// A set of headers applied to bundle entries whose path matches a glob pattern.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderRule {
+  pattern: PathPattern,
+  headers: HeaderMap,
+}
+
+impl HeaderRule {
+  /// Creates a rule applying `headers` to entries whose path matches `pattern`.
+  ///
+  /// Patterns are matched against entry paths like `/assets/app.js`. `*` doesn't match across
+  /// directories while `**` does, and a pattern without `/` matches the file name at any depth
+  /// (e.g. `*.html`).
+  pub fn new(pattern: &str, headers: HeaderMap) -> crate::Result<Self> {
+    Ok(Self {
+      pattern: PathPattern::new(pattern)?,
+      headers,
+    })
+  }
+
+  pub fn pattern(&self) -> &str {
+    self.pattern.as_str()
+  }
+
+  pub fn headers(&self) -> &HeaderMap {
+    &self.headers
+  }
+
+  pub fn matches(&self, path: &str) -> bool {
+    self.pattern.matches(path)
+  }
+}
+
+/// Glob-based HTTP header rules evaluated for each entry when building a bundle.
+///
+/// Every matching rule applies, in order. A rule replaces headers of the same name set by an
+/// earlier rule, and headers set on a [`BundleEntry`](crate::BundleEntry) take precedence over
+/// every rule.
+///
+/// Rules can also be loaded from a JSON or TOML file in the format of Netlify's `netlify.toml`
+/// headers:
+///
+/// ```toml
+/// [[headers]]
+/// for = "/assets/**"
+/// [headers.values]
+/// Cache-Control = "public, max-age=31536000, immutable"
+///
+/// [[headers]]
+/// for = "*.html"
+/// [headers.values]
+/// Content-Security-Policy = "default-src 'self'"
+/// ```
+///
+/// # Example
+///
+/// ```
+/// use wvb::http::HeaderMap;
+/// use wvb::{BundleBuilderOptions, HeaderRule, HeaderRules};
+///
+/// let mut headers = HeaderMap::new();
+/// headers.insert("cache-control", "max-age=31536000".parse().unwrap());
+/// let mut rules = HeaderRules::new();
+/// rules.add(HeaderRule::new("/assets/**", headers).unwrap());
+///
+/// let mut options = BundleBuilderOptions::new();
+/// options.header_rules(rules);
+/// ```
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct HeaderRules {
+  rules: Vec<HeaderRule>,
+}
+
+impl HeaderRules {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn add(&mut self, rule: HeaderRule) -> &mut Self {
+    self.rules.push(rule);
+    self
+  }
+
+  pub fn rules(&self) -> &[HeaderRule] {
+    &self.rules
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.rules.is_empty()
+  }
+
+  /// Resolves the headers of every rule matching the given path.
+  pub fn resolve(&self, path: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for rule in self.rules.iter().filter(|x| x.matches(path)) {
+      merge_headers(&mut headers, &rule.headers);
+    }
+    headers
+  }
+
+  /// Parses rules from JSON.
+  pub fn from_json(json: &str) -> crate::Result<Self> {
+    let file: file::HeaderRulesFile = serde_json::from_str(json)?;
+    file.try_into()
+  }
+
+  /// Parses rules from TOML.
+  pub fn from_toml(toml: &str) -> crate::Result<Self> {
+    let file: file::HeaderRulesFile = toml::from_str(toml)?;
+    file.try_into()
+  }
+
+  /// Loads rules from a `.json` or `.toml` file.
+  pub fn from_file(path: impl AsRef<std::path::Path>) -> crate::Result<Self> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path)?;
+    match path.extension().and_then(|x| x.to_str()) {
+      Some("json") => Self::from_json(&content),
+      Some("toml") => Self::from_toml(&content),
+      _ => Err(crate::Error::invalid_header_rules(format!(
+        "unsupported file format: {}",
+        path.display()
+      ))),
+    }
+  }
+}
+
+mod file {
+  use super::*;
+  use http::header::{HeaderName, HeaderValue};
+  use serde::Deserialize;
+  use std::collections::BTreeMap;
+
+  #[derive(Deserialize)]
+  pub(super) struct HeaderRulesFile {
+    #[serde(default)]
+    headers: Vec<HeaderRuleFile>,
+  }
+
+  #[derive(Deserialize)]
+  struct HeaderRuleFile {
+    #[serde(rename = "for")]
+    pattern: String,
+    #[serde(default)]
+    values: BTreeMap<String, HeaderValues>,
+  }
+
+  #[derive(Deserialize)]
+  #[serde(untagged)]
+  enum HeaderValues {
+    One(String),
+    Many(Vec<String>),
+  }
+
+  impl TryFrom<HeaderRulesFile> for HeaderRules {
+    type Error = crate::Error;
+
+    fn try_from(file: HeaderRulesFile) -> crate::Result<Self> {
+      let mut rules = HeaderRules::new();
+      for rule in file.headers {
+        let mut headers = HeaderMap::new();
+        for (name, values) in rule.values {
+          let name = HeaderName::try_from(name).map_err(http::Error::from)?;
+          let values = match values {
+            HeaderValues::One(x) => vec![x],
+            HeaderValues::Many(x) => x,
+          };
+          for value in values {
+            let value = HeaderValue::try_from(value).map_err(http::Error::from)?;
+            headers.append(name.clone(), value);
+          }
+        }
+        rules.add(HeaderRule::new(&rule.pattern, headers)?);
+      }
+      Ok(rules)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use http::header::{HeaderName, HeaderValue};
+
+  fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+      headers.append(
+        HeaderName::from_static(name),
+        HeaderValue::from_static(value),
+      );
+    }
+    headers
+  }
+
+  #[test]
+  fn resolve() {
+    let mut rules = HeaderRules::new();
+    rules
+      .add(HeaderRule::new("/**", headers(&[("cache-control", "no-cache")])).unwrap())
+      .add(
+        HeaderRule::new(
+          "/assets/**",
+          headers(&[("cache-control", "max-age=31536000")]),
+        )
+        .unwrap(),
+      )
+      .add(
+        HeaderRule::new(
+          "*.html",
+          headers(&[("content-security-policy", "default-src 'self'")]),
+        )
+        .unwrap(),
+      );
+    let resolved = rules.resolve("/assets/app.js");
+    assert_eq!(resolved.len(), 1);
+    assert_eq!(resolved["cache-control"], "max-age=31536000");
+    let resolved = rules.resolve("/docs/index.html");
+    assert_eq!(resolved.len(), 2);
+    assert_eq!(resolved["cache-control"], "no-cache");
+    assert_eq!(resolved["content-security-policy"], "default-src 'self'");
+    assert!(HeaderRules::new().resolve("/index.html").is_empty());
+  }
+
+  #[test]
+  fn from_json_and_toml() {
+    let json = HeaderRules::from_json(
+      r#"{
+        "headers": [
+          { "for": "/assets/**", "values": { "Cache-Control": "max-age=31536000" } },
+          { "for": "*.html", "values": { "Link": ["</a.css>; rel=preload", "</b.js>; rel=preload"] } }
+        ]
+      }"#,
+    )
+    .unwrap();
+    let toml = HeaderRules::from_toml(
+      r#"
+        [[headers]]
+        for = "/assets/**"
+        [headers.values]
+        Cache-Control = "max-age=31536000"
+
+        [[headers]]
+        for = "*.html"
+        [headers.values]
+        Link = ["</a.css>; rel=preload", "</b.js>; rel=preload"]
+      "#,
+    )
+    .unwrap();
+    assert_eq!(json, toml);
+    assert_eq!(json.rules().len(), 2);
+    assert_eq!(json.rules()[0].pattern(), "assets/**");
+    assert_eq!(
+      json.resolve("/assets/a.js")["cache-control"],
+      "max-age=31536000"
+    );
+    assert_eq!(
+      json.resolve("/index.html").get_all("link").iter().count(),
+      2
+    );
+  }
+
+  #[test]
+  fn invalid_rules() {
+    assert!(matches!(
+      HeaderRules::from_json(r#"{ "headers": [{ "for": "/a/**b" }] }"#),
+      Err(crate::Error::Glob(_))
+    ));
+    assert!(matches!(
+      HeaderRules::from_json(r#"{ "headers": [{ "for": "*", "values": { "bad name": "x" } }] }"#),
+      Err(crate::Error::Http(_))
+    ));
+    assert!(matches!(
+      HeaderRules::from_toml("headers = 1"),
+      Err(crate::Error::Toml(_))
+    ));
+  }
+}
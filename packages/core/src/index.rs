@@ -1,16 +1,28 @@
+use crate::block::EntryBlocks;
 use crate::checksum::{make_checksum, parse_checksum, write_checksum, CHECKSUM_LEN};
+use crate::codec::Codec;
+use crate::digest::{DigestAlgorithm, EntryDigest};
+use crate::encoding::ContentEncoding;
 use crate::header::Header;
+use crate::limits::{read_exact_vec, ReaderLimits, DECODE_LIMIT};
+use crate::metadata::BundleMetadata;
 use crate::reader::Reader;
+use crate::version::Version;
 use crate::writer::Writer;
 use bincode::de::Decoder;
 use bincode::enc::Encoder;
 use bincode::error::{DecodeError, EncodeError};
-use bincode::{config, decode_from_slice, encode_to_vec, Decode, Encode};
+use bincode::{
+  config, decode_from_slice, decode_from_slice_with_context, encode_to_vec, Decode, Encode,
+};
 use http::{HeaderMap, HeaderName, HeaderValue};
 use std::collections::HashMap;
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, OnceLock};
 
+#[cfg(feature = "async")]
+use crate::limits::async_read_exact_vec;
 #[cfg(feature = "async")]
 use crate::reader::AsyncReader;
 #[cfg(feature = "async")]
@@ -22,6 +34,10 @@ use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, As
 ///
 /// An index entry contains:
 /// - File location (offset and length in the data section)
+/// - Compression codec of the data
+/// - Optional cryptographic digest of the original data (`V2` only)
+/// - Optional precompressed variants for HTTP content encoding (`V2` only)
+/// - Optional block table of large files compressed in blocks (`V2` only)
 /// - HTTP metadata (content-type, content-length)
 /// - Optional HTTP headers for protocol serving
 ///
@@ -39,13 +55,17 @@ use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, As
 pub struct IndexEntry {
   offset: u64,
   len: u64,
+  pub(crate) codec: Codec,
   content_type: String,
   content_length: u64,
   pub(crate) headers: HeaderMap,
+  pub(crate) digest: Option<EntryDigest>,
+  pub(crate) variants: Vec<EntryVariant>,
+  pub(crate) blocks: Option<EntryBlocks>,
 }
 
 impl IndexEntry {
-  /// Creates a new index entry.
+  /// Creates a new index entry compressed with [`Codec::Lz4`].
   ///
   /// # Arguments
   ///
@@ -57,12 +77,21 @@ impl IndexEntry {
     Self {
       offset,
       len,
+      codec: Codec::Lz4,
       content_type: content_type.into(),
       content_length,
       headers: HeaderMap::default(),
+      digest: None,
+      variants: Vec::new(),
+      blocks: None,
     }
   }
 
+  /// Returns the compression codec of the data.
+  pub fn codec(&self) -> Codec {
+    self.codec
+  }
+
   /// Returns the MIME type of the file.
   pub fn content_type(&self) -> &str {
     &self.content_type
@@ -78,6 +107,26 @@ impl IndexEntry {
     &self.headers
   }
 
+  /// Returns the digest of the original file data, if the bundle was built with digests.
+  pub fn digest(&self) -> Option<&EntryDigest> {
+    self.digest.as_ref()
+  }
+
+  /// Returns precompressed variants of the file, if the bundle was built with them.
+  pub fn variants(&self) -> &[EntryVariant] {
+    &self.variants
+  }
+
+  /// Returns the precompressed variant of the given content encoding.
+  pub fn variant(&self, encoding: ContentEncoding) -> Option<&EntryVariant> {
+    self.variants.iter().find(|v| v.encoding == encoding)
+  }
+
+  /// Returns the block table, if the file is compressed in blocks.
+  pub fn blocks(&self) -> Option<&EntryBlocks> {
+    self.blocks.as_ref()
+  }
+
   /// Returns the byte offset in the data section.
   pub fn offset(&self) -> u64 {
     self.offset
@@ -94,49 +143,181 @@ impl IndexEntry {
   }
 }
 
-impl Encode for IndexEntry {
+/// Location of a precompressed variant of a file in the data section.
+///
+/// Variant data is already encoded for HTTP and is served as-is with a `Content-Encoding`
+/// header. Like entry data, it is followed by an xxHash-32 checksum. Bundles built with digests
+/// also store a digest of the encoded data of every variant.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct EntryVariant {
+  encoding: ContentEncoding,
+  offset: u64,
+  len: u64,
+  pub(crate) digest: Option<EntryDigest>,
+}
+
+impl EntryVariant {
+  /// Creates a new variant.
+  pub fn new(encoding: ContentEncoding, offset: u64, len: u64) -> Self {
+    Self {
+      encoding,
+      offset,
+      len,
+      digest: None,
+    }
+  }
+
+  /// Returns the digest of the encoded data, if the bundle was built with digests.
+  pub fn digest(&self) -> Option<&EntryDigest> {
+    self.digest.as_ref()
+  }
+
+  /// Returns the content encoding of the variant.
+  pub fn encoding(&self) -> ContentEncoding {
+    self.encoding
+  }
+
+  /// Returns the byte offset in the data section.
+  pub fn offset(&self) -> u64 {
+    self.offset
+  }
+
+  /// Returns `true` if the encoded data length is zero.
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  /// Returns the length of the encoded data in bytes.
+  pub fn len(&self) -> u64 {
+    self.len
+  }
+}
+
+fn encode_digest(digest: Option<&EntryDigest>) -> Option<(u8, &[u8])> {
+  digest.map(|d| (d.algorithm().id(), d.value()))
+}
+
+fn decode_digest(digest: Option<(u8, Vec<u8>)>) -> Result<Option<EntryDigest>, DecodeError> {
+  digest
+    .map(|(id, value)| {
+      DigestAlgorithm::from_id(id)
+        .map(|algorithm| EntryDigest::new(algorithm, value))
+        .map_err(|_| DecodeError::OtherString("invalid digest algorithm".into()))
+    })
+    .transpose()
+}
+
+fn encode_headers<E: Encoder>(headers: &HeaderMap, encoder: &mut E) -> Result<(), EncodeError> {
+  let mut pairs: Vec<(&str, &[u8])> = Vec::with_capacity(headers.len());
+  for (name, value) in headers.iter() {
+    pairs.push((name.as_str(), value.as_bytes()));
+  }
+  // sort by name for the stable encoding, keeping the order of values with the same name
+  pairs.sort_by_key(|(name, _)| *name);
+  pairs.encode(encoder)
+}
+
+fn decode_headers<D: Decoder<Context = Version>>(
+  decoder: &mut D,
+) -> Result<HeaderMap, DecodeError> {
+  let pairs: Vec<(String, Vec<u8>)> = Decode::decode(decoder)?;
+  let mut headers = HeaderMap::new();
+  for (name, value_bytes) in pairs {
+    let header_name = HeaderName::try_from(name.as_str())
+      .map_err(|_| DecodeError::OtherString("invalid header name".into()))?;
+    let header_value = HeaderValue::from_bytes(&value_bytes)
+      .map_err(|_| DecodeError::OtherString("invalid header value".into()))?;
+    headers.append(header_name, header_value);
+  }
+  Ok(headers)
+}
+
+/// Encodes an index entry in the layout of the given format version.
+struct VersionedIndexEntry<'a> {
+  version: Version,
+  entry: &'a IndexEntry,
+}
+
+impl Encode for VersionedIndexEntry<'_> {
   fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
-    let mut headers: Vec<(String, Vec<u8>)> = Vec::with_capacity(self.headers.len());
-    for (name, value) in self.headers.iter() {
-      headers.push((name.as_str().to_string(), value.as_bytes().to_vec()));
+    let entry = self.entry;
+    entry.offset.encode(encoder)?;
+    entry.len.encode(encoder)?;
+    if self.version != Version::V1 {
+      entry.codec.id().encode(encoder)?;
+    }
+    entry.content_type.as_bytes().encode(encoder)?;
+    entry.content_length.encode(encoder)?;
+    encode_headers(&entry.headers, encoder)?;
+    if self.version != Version::V1 {
+      encode_digest(entry.digest.as_ref()).encode(encoder)?;
+      let variants = entry
+        .variants
+        .iter()
+        .map(|v| {
+          (
+            v.encoding.id(),
+            v.offset,
+            v.len,
+            encode_digest(v.digest.as_ref()),
+          )
+        })
+        .collect::<Vec<_>>();
+      variants.encode(encoder)?;
+      let blocks = entry.blocks.as_ref().map(|b| (b.block_size(), b.lens()));
+      blocks.encode(encoder)?;
     }
-    let tuple = (
-      self.offset,
-      self.len,
-      self.content_type.as_bytes(),
-      self.content_length,
-      headers,
-    );
-    tuple.encode(encoder)?;
     Ok(())
   }
 }
 
-impl<T> Decode<T> for IndexEntry {
-  fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
-    let (offset, len, content_type_raw, content_length, pairs): (
-      u64,
-      u64,
-      Vec<u8>,
-      u64,
-      Vec<(String, Vec<u8>)>,
-    ) = Decode::decode(decoder)?;
+impl Decode<Version> for IndexEntry {
+  fn decode<D: Decoder<Context = Version>>(decoder: &mut D) -> Result<Self, DecodeError> {
+    let version = *decoder.context();
+    let offset = u64::decode(decoder)?;
+    let len = u64::decode(decoder)?;
+    let codec = match version {
+      Version::V1 => Codec::Lz4,
+      _ => Codec::from_id(u8::decode(decoder)?)
+        .map_err(|_| DecodeError::OtherString("invalid codec".into()))?,
+    };
+    let content_type_raw = Vec::<u8>::decode(decoder)?;
     let content_type = String::from_utf8(content_type_raw)
       .map_err(|_| DecodeError::OtherString("invalid content type".into()))?;
-    let mut headers = HeaderMap::new();
-    for (name, value_bytes) in pairs {
-      let header_name = HeaderName::try_from(name.as_str())
-        .map_err(|_| DecodeError::OtherString("invalid header name".into()))?;
-      let header_value = HeaderValue::from_bytes(&value_bytes)
-        .map_err(|_| DecodeError::OtherString("invalid header value".into()))?;
-      headers.append(header_name, header_value);
-    }
+    let content_length = u64::decode(decoder)?;
+    let headers = decode_headers(decoder)?;
+    let digest = match version {
+      Version::V1 => None,
+      _ => decode_digest(Decode::decode(decoder)?)?,
+    };
+    let variants = match version {
+      Version::V1 => Vec::new(),
+      _ => Vec::<(u8, u64, u64, Option<(u8, Vec<u8>)>)>::decode(decoder)?
+        .into_iter()
+        .map(|(id, offset, len, digest)| {
+          let encoding = ContentEncoding::from_id(id)
+            .map_err(|_| DecodeError::OtherString("invalid content encoding".into()))?;
+          let mut variant = EntryVariant::new(encoding, offset, len);
+          variant.digest = decode_digest(digest)?;
+          Ok(variant)
+        })
+        .collect::<Result<Vec<_>, DecodeError>>()?,
+    };
+    let blocks = match version {
+      Version::V1 => None,
+      _ => Option::<(u64, Vec<u64>)>::decode(decoder)?
+        .map(|(block_size, lens)| EntryBlocks::new(block_size, lens)),
+    };
     Ok(IndexEntry {
       offset,
       len,
+      codec,
       content_type,
       content_length,
       headers,
+      digest,
+      variants,
+      blocks,
     })
   }
 }
@@ -146,9 +327,13 @@ pub(crate) struct IndexEntryMap(pub(crate) HashMap<String, IndexEntry>);
 
 /// Bundle index mapping file paths to their metadata.
 ///
-/// The index is a HashMap stored as binary-encoded data in the bundle file,
-/// immediately after the header. It maps file paths (keys) to `IndexEntry`
-/// metadata (values).
+/// The index is stored as binary-encoded data in the bundle file. It maps file paths (keys) to
+/// `IndexEntry` metadata (values).
+///
+/// `V1` bundles store the index as a single encoded map, which is decoded as a whole. `V2`
+/// bundles store a path table sorted by path with the offset of every entry, so an index read
+/// from a `V2` bundle looks up paths with a binary search and only decodes the entries it
+/// touches. Iterating [`entries`](Index::entries) or modifying the index decodes every entry.
 ///
 /// The index section includes an xxHash-32 checksum for verification.
 ///
@@ -167,9 +352,13 @@ pub(crate) struct IndexEntryMap(pub(crate) HashMap<String, IndexEntry>);
 /// assert!(index.contains_path("/app.js"));
 /// assert_eq!(index.len(), 2);
 /// ```
-#[derive(Debug, Default, PartialEq, Clone)]
+#[derive(Default, Clone)]
 pub struct Index {
-  entries: IndexEntryMap,
+  /// Entries decoded as a whole or built in memory.
+  entries: OnceLock<IndexEntryMap>,
+  /// Path table of an index read from a `V2` bundle, until every entry is decoded.
+  table: Option<Arc<IndexTable>>,
+  pub(crate) metadata: Option<BundleMetadata>,
 }
 
 impl Index {
@@ -183,19 +372,250 @@ impl Index {
   /// Use this when you know how many files will be in the bundle.
   pub fn new_with_capacity(capacity: usize) -> Self {
     Self {
-      entries: IndexEntryMap(HashMap::with_capacity(capacity)),
+      entries: OnceLock::from(IndexEntryMap(HashMap::with_capacity(capacity))),
+      table: None,
+      metadata: None,
+    }
+  }
+}
+
+impl std::fmt::Debug for Index {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Index")
+      .field("entries", self.entries())
+      .field("metadata", &self.metadata)
+      .finish()
+  }
+}
+
+impl PartialEq for Index {
+  fn eq(&self, other: &Self) -> bool {
+    self.metadata == other.metadata && self.entries() == other.entries()
+  }
+}
+
+/// Sorted path table of a `V2` index, decoding entries on demand.
+///
+/// The layout is big-endian:
+///
+/// | Count (u32) | Path ends (u32 × count) | Entry ends (u32 × count) | Paths | Entries |
+///
+/// Path and entry ends are offsets into the paths and entries blobs, where each entry is an
+/// encoded [`IndexEntry`].
+struct IndexTable {
+  buf: Vec<u8>,
+  version: Version,
+  count: usize,
+  paths_offset: usize,
+  entries_offset: usize,
+  cache: Vec<OnceLock<Option<Box<IndexEntry>>>>,
+}
+
+impl IndexTable {
+  const COUNT_LEN: usize = 4;
+  const END_LEN: usize = 4;
+
+  /// Parses the table at the start of the buffer, returning it with the number of bytes it
+  /// spans.
+  fn parse(buf: &[u8], version: Version) -> crate::Result<(Self, usize)> {
+    let invalid = || crate::Error::InvalidIndexTable;
+    let count = read_u32(buf, 0).ok_or_else(invalid)? as usize;
+    let paths_offset = count
+      .checked_mul(Self::END_LEN * 2)
+      .and_then(|x| x.checked_add(Self::COUNT_LEN))
+      .ok_or_else(invalid)?;
+    if buf.len() < paths_offset {
+      return Err(invalid());
+    }
+    let last_end = |table: usize| match count {
+      0 => Some(0),
+      _ => read_u32(
+        buf,
+        Self::COUNT_LEN + (table * count + count - 1) * Self::END_LEN,
+      ),
+    };
+    let paths_len = last_end(0).ok_or_else(invalid)? as usize;
+    let entries_offset = paths_offset + paths_len;
+    let entries_len = last_end(1).ok_or_else(invalid)? as usize;
+    let len = entries_offset
+      .checked_add(entries_len)
+      .ok_or_else(invalid)?;
+    if buf.len() < len {
+      return Err(invalid());
+    }
+    let mut cache = Vec::with_capacity(count);
+    cache.resize_with(count, OnceLock::new);
+    let table = Self {
+      buf: buf[..len].to_vec(),
+      version,
+      count,
+      paths_offset,
+      entries_offset,
+      cache,
+    };
+    table.validate()?;
+    Ok((table, len))
+  }
+
+  /// Checks that the ends of both blobs are non-decreasing, and that paths are valid UTF-8,
+  /// sorted and unique, so a binary search finds every path.
+  fn validate(&self) -> crate::Result<()> {
+    let mut prev: Option<&[u8]> = None;
+    for i in 0..self.count {
+      let path = self.path(i).ok_or(crate::Error::InvalidIndexTable)?;
+      self.item(1, i).ok_or(crate::Error::InvalidIndexTable)?;
+      std::str::from_utf8(path).map_err(|_| crate::Error::InvalidIndexTable)?;
+      if prev.is_some_and(|prev| prev >= path) {
+        return Err(crate::Error::InvalidIndexTable);
+      }
+      prev = Some(path);
+    }
+    Ok(())
+  }
+
+  /// Returns the bytes of the `i`-th item of the paths (`table == 0`) or entries blob.
+  fn item(&self, table: usize, i: usize) -> Option<&[u8]> {
+    let ends = Self::COUNT_LEN + table * self.count * Self::END_LEN;
+    let start = match i {
+      0 => 0,
+      _ => read_u32(&self.buf, ends + (i - 1) * Self::END_LEN)? as usize,
+    };
+    let end = read_u32(&self.buf, ends + i * Self::END_LEN)? as usize;
+    let offset = [self.paths_offset, self.entries_offset][table];
+    self.buf.get(offset + start..offset.checked_add(end)?)
+  }
+
+  fn path(&self, i: usize) -> Option<&[u8]> {
+    self.item(0, i)
+  }
+
+  /// Returns the position of the path with a binary search.
+  fn position(&self, path: &str) -> Option<usize> {
+    let (mut lo, mut hi) = (0, self.count);
+    while lo < hi {
+      let mid = lo + (hi - lo) / 2;
+      match self.path(mid)?.cmp(path.as_bytes()) {
+        std::cmp::Ordering::Less => lo = mid + 1,
+        std::cmp::Ordering::Greater => hi = mid,
+        std::cmp::Ordering::Equal => return Some(mid),
+      }
+    }
+    None
+  }
+
+  /// Returns the `i`-th entry, decoding it on first access.
+  fn entry(&self, i: usize) -> crate::Result<&IndexEntry> {
+    self.cache[i]
+      .get_or_init(|| {
+        let bytes = self.item(1, i)?;
+        let config = config::standard()
+          .with_big_endian()
+          .with_limit::<DECODE_LIMIT>();
+        decode_from_slice_with_context(bytes, config, self.version)
+          .ok()
+          .map(|(entry, _)| Box::new(entry))
+      })
+      .as_deref()
+      .ok_or(crate::Error::InvalidIndexTable)
+  }
+
+  fn get(&self, path: &str) -> crate::Result<Option<&IndexEntry>> {
+    self.position(path).map(|i| self.entry(i)).transpose()
+  }
+
+  /// Returns the path of the `i`-th entry, validated by [`parse`](Self::parse).
+  fn path_str(&self, i: usize) -> &str {
+    self
+      .path(i)
+      .and_then(|x| std::str::from_utf8(x).ok())
+      .unwrap_or_default()
+  }
+
+  /// Decodes every entry, leaving out the ones which fail to decode.
+  fn decode_all(&self) -> IndexEntryMap {
+    let mut entries = HashMap::with_capacity(self.count);
+    for i in 0..self.count {
+      if let Ok(entry) = self.entry(i) {
+        entries.insert(self.path_str(i).to_string(), entry.clone());
+      }
     }
+    IndexEntryMap(entries)
   }
+
+  /// Returns the paths of the entries which fail to decode.
+  fn invalid_paths(&self) -> Vec<&str> {
+    (0..self.count)
+      .filter(|&i| self.entry(i).is_err())
+      .map(|i| self.path_str(i))
+      .collect()
+  }
+
+  /// Encodes the table of the given entries.
+  fn write(entries: &IndexEntryMap, version: Version) -> crate::Result<Vec<u8>> {
+    let config = config::standard().with_big_endian();
+    let mut sorted = entries.iter().collect::<Vec<_>>();
+    sorted.sort_unstable_by_key(|(path, _)| path.as_str());
+    let mut path_ends = Vec::with_capacity(sorted.len());
+    let mut entry_ends = Vec::with_capacity(sorted.len());
+    let mut paths = vec![];
+    let mut encoded = vec![];
+    for (path, entry) in sorted {
+      paths.extend_from_slice(path.as_bytes());
+      path_ends.push(paths.len());
+      let versioned = VersionedIndexEntry { version, entry };
+      let bytes = encode_to_vec(versioned, config).map_err(|e| crate::Error::Encode {
+        error: e,
+        message: "fail to encode index".to_string(),
+      })?;
+      encoded.extend(bytes);
+      entry_ends.push(encoded.len());
+    }
+    let to_u32 = |x: usize| u32::try_from(x).map_err(|_| crate::Error::IndexTooLarge);
+    let mut bytes = Vec::with_capacity(
+      Self::COUNT_LEN + entries.len() * Self::END_LEN * 2 + paths.len() + encoded.len(),
+    );
+    bytes.extend(to_u32(entries.len())?.to_be_bytes());
+    for end in path_ends.into_iter().chain(entry_ends) {
+      bytes.extend(to_u32(end)?.to_be_bytes());
+    }
+    bytes.extend(paths);
+    bytes.extend(encoded);
+    Ok(bytes)
+  }
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> Option<u32> {
+  let bytes = buf.get(offset..offset.checked_add(4)?)?;
+  Some(u32::from_be_bytes(bytes.try_into().ok()?))
+}
+
+/// Encodes an index entry map in the layout of the given format version.
+struct VersionedIndexEntryMap<'a> {
+  version: Version,
+  entries: &'a IndexEntryMap,
 }
 
-impl Encode for IndexEntryMap {
+impl Encode for VersionedIndexEntryMap<'_> {
   fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
-    self.0.encode(encoder)
+    // entries are encoded in the order of paths, so the same index always has the same bytes
+    let mut entries = self
+      .entries
+      .iter()
+      .map(|(path, entry)| {
+        let versioned = VersionedIndexEntry {
+          version: self.version,
+          entry,
+        };
+        (path.as_str(), versioned)
+      })
+      .collect::<Vec<_>>();
+    entries.sort_unstable_by_key(|(path, _)| *path);
+    entries.encode(encoder)
   }
 }
 
-impl<T> Decode<T> for IndexEntryMap {
-  fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
+impl Decode<Version> for IndexEntryMap {
+  fn decode<D: Decoder<Context = Version>>(decoder: &mut D) -> Result<Self, DecodeError> {
     let map = HashMap::<String, IndexEntry>::decode(decoder)?;
     Ok(IndexEntryMap(map))
   }
@@ -215,8 +635,63 @@ impl DerefMut for IndexEntryMap {
 }
 
 impl Index {
+  /// Returns every entry, decoding the whole index if it was read lazily.
+  ///
+  /// Entries of a bundle which fail to decode are left out, see
+  /// [`try_entries`](Self::try_entries) to detect them.
   pub fn entries(&self) -> &HashMap<String, IndexEntry> {
-    &self.entries
+    self.entry_map()
+  }
+
+  /// Returns every entry, decoding the whole index if it was read lazily.
+  ///
+  /// Returns [`Error::InvalidIndexTable`](crate::Error::InvalidIndexTable) if an entry of the
+  /// bundle fails to decode.
+  pub fn try_entries(&self) -> crate::Result<&HashMap<String, IndexEntry>> {
+    match self.invalid_paths().is_empty() {
+      true => Ok(self.entry_map()),
+      false => Err(crate::Error::InvalidIndexTable),
+    }
+  }
+
+  fn entry_map(&self) -> &IndexEntryMap {
+    self.entries.get_or_init(|| match &self.table {
+      Some(table) => table.decode_all(),
+      None => IndexEntryMap::default(),
+    })
+  }
+
+  /// Returns the paths of the entries of the bundle which fail to decode.
+  pub(crate) fn invalid_paths(&self) -> Vec<&str> {
+    match &self.table {
+      Some(table) => table.invalid_paths(),
+      None => vec![],
+    }
+  }
+
+  /// Decodes every entry and drops the path table, so the entries can be modified.
+  fn entries_mut(&mut self) -> &mut IndexEntryMap {
+    self.entry_map();
+    self.table = None;
+    self.entries.get_mut().unwrap()
+  }
+
+  /// Returns the number of entries.
+  ///
+  /// For an index read from a bundle, this counts every path of the index, including entries
+  /// which fail to decode; those are reported by [`try_entries`](Self::try_entries) and
+  /// [`try_get_entry`](Self::try_get_entry).
+  pub fn len(&self) -> usize {
+    match (&self.table, self.entries.get()) {
+      (Some(table), _) => table.count,
+      (None, Some(entries)) => entries.len(),
+      (None, None) => 0,
+    }
+  }
+
+  /// Returns `true` if the index has no entries.
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
   }
 
   pub fn insert_entry<S: Into<String>>(
@@ -224,38 +699,95 @@ impl Index {
     path: S,
     entry: IndexEntry,
   ) -> Option<IndexEntry> {
-    self.entries.insert(path.into(), entry)
+    self.entries_mut().insert(path.into(), entry)
   }
 
+  /// Returns the entry of the path.
+  ///
+  /// An entry of a bundle which fails to decode is treated as missing, see
+  /// [`try_get_entry`](Self::try_get_entry) to detect it.
   pub fn get_entry(&self, path: &str) -> Option<&IndexEntry> {
-    self.entries.get(path)
+    self.try_get_entry(path).ok().flatten()
+  }
+
+  /// Returns the entry of the path.
+  ///
+  /// Returns [`Error::InvalidIndexTable`](crate::Error::InvalidIndexTable) if the entry of the
+  /// bundle fails to decode.
+  pub fn try_get_entry(&self, path: &str) -> crate::Result<Option<&IndexEntry>> {
+    match (self.entries.get(), &self.table) {
+      (_, Some(table)) => table.get(path),
+      (Some(entries), None) => Ok(entries.get(path)),
+      (None, None) => Ok(None),
+    }
   }
 
   pub fn get_entry_mut(&mut self, path: &str) -> Option<&mut IndexEntry> {
-    self.entries.get_mut(path)
+    self.entries_mut().get_mut(path)
   }
 
   pub fn remove_entry(&mut self, path: &str) -> Option<IndexEntry> {
-    self.entries.remove(path)
+    self.entries_mut().remove(path)
   }
 
   pub fn contains_path(&self, path: &str) -> bool {
-    self.entries.contains_key(path)
+    match (self.entries.get(), &self.table) {
+      (_, Some(table)) => table.position(path).is_some(),
+      (Some(entries), None) => entries.contains_key(path),
+      (None, None) => false,
+    }
+  }
+
+  /// Returns the size in bytes of stored data which is shared by more than one entry, counted
+  /// once for every entry after the first.
+  ///
+  /// This is how much a bundle built with [`dedup`](crate::BundleBuilderOptions::dedup) saved
+  /// compared to storing the data of every entry separately.
+  pub fn deduplicated_size(&self) -> u64 {
+    let mut ranges = HashMap::new();
+    for entry in self.entries().values() {
+      let variants = entry.variants.iter().map(|x| (x.offset(), x.len()));
+      for range in std::iter::once((entry.offset, entry.len)).chain(variants) {
+        *ranges.entry(range).or_insert(0u64) += 1;
+      }
+    }
+    ranges
+      .into_iter()
+      .map(|((_, len), count)| (len + CHECKSUM_LEN as u64) * (count - 1))
+      .sum()
   }
 }
 
-fn write_index(index: &Index) -> crate::Result<Vec<u8>> {
+pub(crate) fn write_index(index: &Index, version: Version) -> crate::Result<Vec<u8>> {
   let config = config::standard().with_big_endian();
-  let bytes = encode_to_vec(&index.entries, config).map_err(|e| crate::Error::Encode {
+  if version == Version::V1 {
+    let entries = VersionedIndexEntryMap {
+      version,
+      entries: index.entry_map(),
+    };
+    return encode_to_vec(entries, config).map_err(|e| crate::Error::Encode {
+      error: e,
+      message: "fail to encode index".to_string(),
+    });
+  }
+  let mut bytes = match (index.entries.get(), &index.table) {
+    // an index read lazily is written as it was read
+    (None, Some(table)) if table.version == version => table.buf.clone(),
+    _ => IndexTable::write(index.entry_map(), version)?,
+  };
+  // bundle metadata follows the entries in `V2`
+  let metadata = encode_to_vec(&index.metadata, config).map_err(|e| crate::Error::Encode {
     error: e,
-    message: "fail to encode index".to_string(),
+    message: "fail to encode bundle metadata".to_string(),
   })?;
+  bytes.extend(metadata);
   Ok(bytes)
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub struct IndexWriterOptions {
   pub(crate) checksum_seed: u32,
+  pub(crate) version: Version,
 }
 
 impl IndexWriterOptions {
@@ -263,6 +795,12 @@ impl IndexWriterOptions {
     Self::default()
   }
 
+  /// Sets the bundle format version which decides the layout of index entries.
+  pub fn version(&mut self, version: Version) -> &mut Self {
+    self.version = version;
+    self
+  }
+
   pub fn checksum_seed(&mut self, seed: u32) -> &mut Self {
     self.checksum_seed = seed;
     self
@@ -287,7 +825,7 @@ impl<W: Write> IndexWriter<W> {
   }
 
   pub fn write_index(&mut self, index: &Index) -> crate::Result<Vec<u8>> {
-    let bytes = write_index(index)?;
+    let bytes = write_index(index, self.options.version)?;
     self.w.write_all(&bytes)?;
     Ok(bytes)
   }
@@ -329,7 +867,7 @@ impl<W: AsyncWrite + Unpin> AsyncIndexWriter<W> {
   }
 
   pub async fn write_index(&mut self, index: &Index) -> crate::Result<Vec<u8>> {
-    let bytes = write_index(index)?;
+    let bytes = write_index(index, self.options.version)?;
     self.w.write_all(&bytes).await?;
     Ok(bytes)
   }
@@ -352,31 +890,52 @@ impl<W: AsyncWrite + Unpin> AsyncWriter<Index> for AsyncIndexWriter<W> {
   }
 }
 
-fn read_index(header: &Header) -> (u64, Vec<u8>) {
-  (Header::END_OFFSET, vec![0u8; header.index_size() as usize])
+fn read_index(header: &Header, limits: &ReaderLimits) -> crate::Result<(u64, u64)> {
+  let size = header.index_size() as u64;
+  limits.check_index_size(size)?;
+  Ok((header.index_offset(), size))
 }
 
-fn parse_index(buf: &[u8]) -> crate::Result<Index> {
-  let config = config::standard().with_big_endian();
-  let (entries, _): (IndexEntryMap, _) =
-    decode_from_slice(buf, config).map_err(|e| crate::Error::Decode {
-      error: e,
-      message: "fail to decode index".to_string(),
-    })?;
-  Ok(Index { entries })
+fn parse_index(buf: &[u8], version: Version, limits: &ReaderLimits) -> crate::Result<Index> {
+  let config = config::standard()
+    .with_big_endian()
+    .with_limit::<DECODE_LIMIT>();
+  if version == Version::V1 {
+    // Checks the length prefix of the map before decoding allocates for it.
+    if let Ok((count, _)) = decode_from_slice::<u64, _>(buf, config) {
+      limits.check_entries(count)?;
+    }
+    let (entries, _): (IndexEntryMap, _) = decode_from_slice_with_context(buf, config, version)
+      .map_err(|e| crate::Error::Decode {
+        error: e,
+        message: "fail to decode index".to_string(),
+      })?;
+    return Ok(Index {
+      entries: OnceLock::from(entries),
+      table: None,
+      metadata: None,
+    });
+  }
+  let (table, len) = IndexTable::parse(buf, version)?;
+  limits.check_entries(table.count as u64)?;
+  let (metadata, _) = decode_from_slice(&buf[len..], config).map_err(|e| crate::Error::Decode {
+    error: e,
+    message: "fail to decode bundle metadata".to_string(),
+  })?;
+  Ok(Index {
+    entries: OnceLock::new(),
+    table: Some(Arc::new(table)),
+    metadata,
+  })
 }
 
 fn read_checksum(header: &Header) -> (u64, [u8; CHECKSUM_LEN]) {
   (
-    Header::END_OFFSET + header.index_size() as u64,
+    header.index_offset() + header.index_size() as u64,
     [0u8; CHECKSUM_LEN],
   )
 }
 
-fn read_total(header: &Header) -> (u64, Vec<u8>) {
-  (Header::END_OFFSET, vec![0u8; header.index_size() as usize])
-}
-
 pub struct IndexReader<R: Read + Seek> {
   r: R,
   header: Header,
@@ -387,6 +946,7 @@ pub struct IndexReader<R: Read + Seek> {
 pub struct IndexReaderOptions {
   pub checksum_seed: u32,
   pub verify_checksum: bool,
+  pub limits: ReaderLimits,
 }
 
 impl IndexReaderOptions {
@@ -403,6 +963,11 @@ impl IndexReaderOptions {
     self.verify_checksum = verify;
     self
   }
+
+  pub fn limits(mut self, limits: ReaderLimits) -> Self {
+    self.limits = limits;
+    self
+  }
 }
 
 impl<R: Read + Seek> IndexReader<R> {
@@ -415,10 +980,10 @@ impl<R: Read + Seek> IndexReader<R> {
   }
 
   pub fn read_index(&mut self) -> crate::Result<Index> {
-    let (offset, mut buf) = read_index(&self.header);
+    let (offset, size) = read_index(&self.header, &self.options.limits)?;
     self.r.seek(SeekFrom::Start(offset))?;
-    self.r.read_exact(&mut buf)?;
-    parse_index(&buf)
+    let buf = read_exact_vec(&mut self.r, size)?;
+    parse_index(&buf, self.header.version(), &self.options.limits)
   }
 
   pub fn read_checksum(&mut self) -> crate::Result<u32> {
@@ -430,9 +995,9 @@ impl<R: Read + Seek> IndexReader<R> {
   }
 
   fn verify_checksum(&mut self, checksum: u32) -> crate::Result<()> {
-    let (offset, mut buf) = read_total(&self.header);
+    let (offset, size) = read_index(&self.header, &self.options.limits)?;
     self.r.seek(SeekFrom::Start(offset))?;
-    self.r.read_exact(&mut buf)?;
+    let buf = read_exact_vec(&mut self.r, size)?;
 
     let expected_checksum = make_checksum(self.options.checksum_seed, &buf);
     if checksum != expected_checksum {
@@ -471,10 +1036,10 @@ impl<R: AsyncRead + AsyncSeek + Unpin> AsyncIndexReader<R> {
   }
 
   pub async fn read_index(&mut self) -> crate::Result<Index> {
-    let (offset, mut buf) = read_index(&self.header);
+    let (offset, size) = read_index(&self.header, &self.options.limits)?;
     self.r.seek(SeekFrom::Start(offset)).await?;
-    self.r.read_exact(&mut buf).await?;
-    parse_index(&buf)
+    let buf = async_read_exact_vec(&mut self.r, size).await?;
+    parse_index(&buf, self.header.version(), &self.options.limits)
   }
 
   pub async fn read_checksum(&mut self) -> crate::Result<u32> {
@@ -486,9 +1051,9 @@ impl<R: AsyncRead + AsyncSeek + Unpin> AsyncIndexReader<R> {
   }
 
   async fn verify_checksum(&mut self, checksum: u32) -> crate::Result<()> {
-    let (offset, mut buf) = read_total(&self.header);
+    let (offset, size) = read_index(&self.header, &self.options.limits)?;
     self.r.seek(SeekFrom::Start(offset)).await?;
-    self.r.read_exact(&mut buf).await?;
+    let buf = async_read_exact_vec(&mut self.r, size).await?;
 
     let expected_checksum = make_checksum(self.options.checksum_seed, &buf);
     if checksum != expected_checksum {
@@ -541,7 +1106,6 @@ mod tests {
   #[tokio::test]
   async fn async_read_and_write() {
     use crate::HeaderWriter;
-    use crate::Version;
     use std::io::Cursor;
 
     let mut index = Index::default();
@@ -569,4 +1133,236 @@ mod tests {
     let read_index = reader.read().await.unwrap();
     assert_eq!(read_index, index);
   }
+
+  #[test]
+  fn stable_encoding() {
+    let paths = [
+      "/a.js",
+      "/b.css",
+      "/c/d.html",
+      "/e.png",
+      "/f.json",
+      "/g.svg",
+      "/h.txt",
+    ];
+    let make_index = |paths: &[&str]| {
+      let mut index = Index::default();
+      for (i, path) in paths.iter().enumerate() {
+        let mut entry = IndexEntry::new(i as u64, 1, "text/plain", 1);
+        entry.headers.append("x-b", "1".parse().unwrap());
+        entry.headers.append("x-a", "1".parse().unwrap());
+        entry.headers.append("x-a", "2".parse().unwrap());
+        index.insert_entry(*path, entry);
+      }
+      index
+    };
+    let index = make_index(&paths);
+    let mut reversed = Index::default();
+    for path in paths.iter().rev() {
+      let mut entry = index.get_entry(path).unwrap().clone();
+      let mut headers = HeaderMap::new();
+      headers.append("x-a", "1".parse().unwrap());
+      headers.append("x-a", "2".parse().unwrap());
+      headers.append("x-b", "1".parse().unwrap());
+      entry.headers = headers;
+      reversed.insert_entry(*path, entry);
+    }
+    let bytes = write_index(&index, Version::V2).unwrap();
+    assert_eq!(bytes, write_index(&reversed, Version::V2).unwrap());
+
+    let parsed = parse_index(&bytes, Version::V2, &ReaderLimits::default()).unwrap();
+    let values = parsed
+      .get_entry("/a.js")
+      .unwrap()
+      .headers()
+      .get_all("x-a")
+      .iter()
+      .collect::<Vec<_>>();
+    assert_eq!(values, ["1", "2"]);
+  }
+
+  #[test]
+  fn read_and_write_v2() {
+    use crate::HeaderWriter;
+    use std::io::Cursor;
+
+    let mut index = Index::default();
+    let mut entry = IndexEntry::new(0, 0, "application/javascript", 55);
+    entry.codec = Codec::Zstd;
+    index.insert_entry("/index.jsx", entry);
+    let mut entry = IndexEntry::new(0, 0, "text/html", 13);
+    entry.digest = Some(EntryDigest::new(DigestAlgorithm::Sha3_384, vec![1; 48]));
+    index.insert_entry("/index.html", entry);
+    let mut metadata = BundleMetadata::new("app", "1.0.0");
+    metadata
+      .set_git_commit("4f2a9c1")
+      .insert_custom("channel", "beta");
+    index.metadata = Some(metadata.clone());
+    let mut entry = IndexEntry::new(20, 13, "text/css", 40);
+    let mut brotli = EntryVariant::new(ContentEncoding::Brotli, 37, 11);
+    brotli.digest = Some(EntryDigest::new(DigestAlgorithm::Sha3_256, vec![2; 32]));
+    entry.variants = vec![brotli, EntryVariant::new(ContentEncoding::Gzip, 52, 12)];
+    index.insert_entry("/index.css", entry);
+    let mut entry = IndexEntry::new(80, 40, "video/mp4", 3000);
+    entry.blocks = Some(EntryBlocks::new(1024, vec![10, 20, 10]));
+    index.insert_entry("/video.mp4", entry);
+
+    let mut buf = vec![];
+    let mut options = IndexWriterOptions::new();
+    options.version(Version::V2);
+    IndexWriter::new_with_options(Cursor::new(&mut buf), options)
+      .write(&index)
+      .unwrap();
+    let header = Header::new(Version::V2, (buf.len() - CHECKSUM_LEN) as u32);
+    let mut total = vec![];
+    HeaderWriter::new(Cursor::new(&mut total))
+      .write(&header)
+      .unwrap();
+    total.extend(&buf);
+    let read_index = IndexReader::new(Cursor::new(&total), header)
+      .read()
+      .unwrap();
+    assert_eq!(read_index, index);
+    assert_eq!(
+      read_index.get_entry("/index.jsx").unwrap().codec(),
+      Codec::Zstd
+    );
+    assert!(read_index
+      .get_entry("/index.jsx")
+      .unwrap()
+      .digest()
+      .is_none());
+    let digest = read_index
+      .get_entry("/index.html")
+      .unwrap()
+      .digest()
+      .unwrap();
+    assert_eq!(digest.algorithm(), DigestAlgorithm::Sha3_384);
+    assert_eq!(digest.value(), [1; 48]);
+    assert_eq!(read_index.metadata, Some(metadata));
+    let css = read_index.get_entry("/index.css").unwrap();
+    assert_eq!(
+      css.variant(ContentEncoding::Gzip),
+      Some(&EntryVariant::new(ContentEncoding::Gzip, 52, 12))
+    );
+    let brotli = css.variant(ContentEncoding::Brotli).unwrap();
+    assert_eq!(brotli.digest().unwrap().value(), [2; 32]);
+    let video = read_index.get_entry("/video.mp4").unwrap();
+    assert_eq!(video.blocks().unwrap().lens(), [10, 20, 10]);
+  }
+
+  #[test]
+  fn lazy_lookup() {
+    let mut index = Index::default();
+    for i in 0..1000u64 {
+      let entry = IndexEntry::new(i * 10, 10, "text/plain", i);
+      index.insert_entry(format!("/files/{i:04}.txt"), entry);
+    }
+    let bytes = write_index(&index, Version::V2).unwrap();
+    let parsed = parse_index(&bytes, Version::V2, &ReaderLimits::default()).unwrap();
+    assert_eq!(parsed.len(), 1000);
+    assert!(parsed.contains_path("/files/0000.txt"));
+    assert!(parsed.contains_path("/files/0999.txt"));
+    assert!(!parsed.contains_path("/files/1000.txt"));
+    assert!(!parsed.contains_path(""));
+    assert_eq!(
+      parsed
+        .get_entry("/files/0421.txt")
+        .unwrap()
+        .content_length(),
+      421
+    );
+    // only the touched entry is decoded
+    assert!(parsed.entries.get().is_none());
+    let table = parsed.table.as_ref().unwrap();
+    assert_eq!(table.cache.iter().filter(|x| x.get().is_some()).count(), 1);
+    // a lazily read index is written as it was read
+    assert_eq!(write_index(&parsed, Version::V2).unwrap(), bytes);
+    assert_eq!(parsed, index);
+  }
+
+  #[test]
+  fn lazy_modify() {
+    let mut index = Index::default();
+    index.insert_entry("/a.js", IndexEntry::new(0, 1, "text/javascript", 1));
+    index.insert_entry("/b.css", IndexEntry::new(1, 1, "text/css", 1));
+    let bytes = write_index(&index, Version::V2).unwrap();
+    let mut parsed = parse_index(&bytes, Version::V2, &ReaderLimits::default()).unwrap();
+    parsed.remove_entry("/a.js");
+    parsed.insert_entry("/c.html", IndexEntry::new(2, 1, "text/html", 1));
+    assert!(parsed.table.is_none());
+    assert!(!parsed.contains_path("/a.js"));
+    assert!(parsed.contains_path("/c.html"));
+    let reparsed = parse_index(
+      &write_index(&parsed, Version::V2).unwrap(),
+      Version::V2,
+      &ReaderLimits::default(),
+    )
+    .unwrap();
+    assert_eq!(reparsed, parsed);
+  }
+
+  #[test]
+  fn invalid_table() {
+    let mut index = Index::default();
+    index.insert_entry("/a.js", IndexEntry::new(0, 1, "text/javascript", 1));
+    let bytes = write_index(&index, Version::V2).unwrap();
+    assert!(matches!(
+      parse_index(&bytes[..10], Version::V2, &ReaderLimits::default()),
+      Err(crate::Error::InvalidIndexTable)
+    ));
+    let mut bytes = bytes;
+    bytes[..4].copy_from_slice(&u32::MAX.to_be_bytes());
+    assert!(matches!(
+      parse_index(&bytes, Version::V2, &ReaderLimits::default()),
+      Err(crate::Error::InvalidIndexTable)
+    ));
+  }
+
+  fn two_entry_table() -> Vec<u8> {
+    let mut index = Index::default();
+    index.insert_entry("/a.js", IndexEntry::new(0, 1, "text/javascript", 1));
+    index.insert_entry("/b.js", IndexEntry::new(1, 1, "text/javascript", 1));
+    write_index(&index, Version::V2).unwrap()
+  }
+
+  #[test]
+  fn unsorted_table() {
+    // count, then two path ends and two entry ends
+    let paths = 4 + 4 * 4;
+    let mut bytes = two_entry_table();
+    bytes[paths..paths + 10].copy_from_slice(b"/b.js/a.js");
+    assert!(matches!(
+      parse_index(&bytes, Version::V2, &ReaderLimits::default()),
+      Err(crate::Error::InvalidIndexTable)
+    ));
+    let mut bytes = two_entry_table();
+    bytes[paths..paths + 10].copy_from_slice(b"/a.js/a.js");
+    assert!(matches!(
+      parse_index(&bytes, Version::V2, &ReaderLimits::default()),
+      Err(crate::Error::InvalidIndexTable)
+    ));
+  }
+
+  #[test]
+  fn undecodable_entry() {
+    let mut bytes = two_entry_table();
+    // the first entry is empty
+    bytes[12..16].copy_from_slice(&0u32.to_be_bytes());
+    let parsed = parse_index(&bytes, Version::V2, &ReaderLimits::default()).unwrap();
+    assert_eq!(parsed.len(), 2);
+    assert!(parsed.contains_path("/a.js"));
+    assert!(parsed.get_entry("/a.js").is_none());
+    assert!(matches!(
+      parsed.try_get_entry("/a.js"),
+      Err(crate::Error::InvalidIndexTable)
+    ));
+    assert!(parsed.try_get_entry("/b.js").unwrap().is_some());
+    assert!(matches!(
+      parsed.try_entries(),
+      Err(crate::Error::InvalidIndexTable)
+    ));
+    assert_eq!(parsed.entries().len(), 1);
+    assert_eq!(parsed.invalid_paths(), ["/a.js"]);
+  }
 }
@@ -1,5 +1,5 @@
+use crate::DigestAlgorithm;
 use base64ct::{Base64, Encoding};
-use sha3::{Digest, Sha3_256, Sha3_384, Sha3_512};
 use std::str::FromStr;
 
 #[derive(Default, Debug, Eq, PartialEq, Clone, Copy)]
@@ -12,10 +12,16 @@ pub enum IntegrityAlgorithm {
 
 impl IntegrityAlgorithm {
   pub fn digest(&self, data: &[u8]) -> Vec<u8> {
-    match self {
-      Self::Sha256 => Sha3_256::digest(data).to_vec(),
-      Self::Sha384 => Sha3_384::digest(data).to_vec(),
-      Self::Sha512 => Sha3_512::digest(data).to_vec(),
+    DigestAlgorithm::from(*self).digest(data)
+  }
+}
+
+impl From<IntegrityAlgorithm> for DigestAlgorithm {
+  fn from(value: IntegrityAlgorithm) -> Self {
+    match value {
+      IntegrityAlgorithm::Sha256 => Self::Sha3_256,
+      IntegrityAlgorithm::Sha384 => Self::Sha3_384,
+      IntegrityAlgorithm::Sha512 => Self::Sha3_512,
     }
   }
 }
@@ -23,8 +23,28 @@
 //! | Magic number, version, index size, checksum | File paths and metadata | Compressed file contents |
 //!
 //! - **Header**: Magic number (🌐🎁), format version, index size, and checksum
-//! - **Index**: HashMap of file paths to offset/length/headers, with checksum
-//! - **Data**: LZ4-compressed file contents with xxHash-32 checksums
+//! - **Index**: Map of file paths to offset/length/headers sorted by path, with checksum. `V2`
+//!   entries may also carry a SHA3 digest of the original file data, and the index may carry
+//!   [`BundleMetadata`] (name, version, build info and custom fields). The `V2` index is a
+//!   sorted path table with entry offsets, so paths are looked up with a binary search and only
+//!   the entries a reader touches are decoded
+//! - **Data**: Compressed file contents with xxHash-32 checksums. `V1` bundles use LZ4 for every
+//!   file, while `V2` bundles record a codec (none, LZ4, zstd or brotli) per file in the index.
+//!   `V2` text files may also have precompressed brotli/gzip variants for HTTP content encoding,
+//!   and large files may be split into independently compressed blocks so byte ranges can be
+//!   read without decompressing the whole file
+//!
+//! `V2` bundles place the index after the data section, so [`BundleStreamWriter`] can stream
+//! entries to disk one at a time and write the index and header when finished:
+//!
+//! | Header (25 bytes) | Data (variable) | Index (variable) | Signatures (optional) |
+//! |-------------------|-----------------|------------------|-----------------------|
+//!
+//! The optional signatures section holds [`BundleSignature`]s over the header and index, so a
+//! bundle copied off disk still carries its provenance.
+//!
+//! Readers check the sizes read from a bundle against [`ReaderLimits`] before allocating for
+//! them, so a crafted or corrupt file fails to read instead of exhausting memory.
 //!
 //! ## Quick Start
 //!
@@ -57,8 +77,18 @@
 //! ## Features
 //!
 //! - `async`: Async I/O support with tokio
+//! - `mmap`: Memory-mapped bundle reading
+//! - `codec-zstd`: Zstandard compressed entries
+//! - `codec-brotli`: Brotli compressed entries
+//! - `digest`: SHA3 digests of entry data
+//! - `precompress`: Precompressed brotli/gzip variants when building bundles
+//! - `dir`: Building bundles from a directory
+//! - `mime-sniff`: Inferring content types from file data
+//! - `header-rules`: Glob-based header rules, loadable from JSON/TOML files
+//! - `extract-sidecar`: Writing entry metadata to a JSON file when extracting bundles
 //! - `source`: Bundle source management (builtin/remote)
 //! - `remote`: HTTP client for downloading bundles
+//! - `patch`: Binary patches between bundle versions
 //! - `updater`: Automatic bundle updates
 //! - `protocol`: Custom protocol handlers for serving bundles
 //! - `protocol-local`: Local file protocol support
@@ -106,31 +136,75 @@
 //! # };
 //! ```
 
+mod block;
 mod builder;
 mod bundle;
 mod checksum;
+mod codec;
+mod digest;
+#[cfg(feature = "dir")]
+mod dir;
+mod encoding;
+mod entry_reader;
 mod error;
+mod extract;
 mod header;
+#[cfg(feature = "header-rules")]
+mod header_rules;
 mod index;
+mod limits;
+#[cfg(feature = "mmap")]
+mod mapped;
+mod metadata;
+mod mime;
+#[cfg(feature = "patch")]
+mod patch;
+#[cfg(any(feature = "dir", feature = "header-rules"))]
+mod path_pattern;
 mod reader;
+mod signatures;
+mod stream;
+mod verify;
 mod version;
 mod writer;
 
 pub(crate) type Result<T> = std::result::Result<T, Error>;
 
+pub use block::*;
 pub use builder::*;
 pub use bundle::*;
+pub use codec::*;
 pub use consts::*;
+pub use digest::*;
+#[cfg(feature = "dir")]
+pub use dir::*;
+pub use encoding::*;
+pub use entry_reader::*;
 pub use error::Error;
+pub use extract::*;
 pub use header::*;
+#[cfg(feature = "header-rules")]
+pub use header_rules::*;
 pub use index::*;
+pub use limits::*;
+#[cfg(feature = "mmap")]
+pub use mapped::*;
+pub use metadata::*;
+pub use mime::*;
+#[cfg(feature = "patch")]
+pub use patch::*;
 pub use reader::*;
+pub use signatures::*;
+pub use stream::*;
+pub use verify::*;
 pub use version::*;
 pub use writer::*;
 
+pub use bytes;
 pub use http;
 
 mod consts;
+pub mod diff;
 #[cfg(feature = "integrity")]
 pub mod integrity;
 #[cfg(feature = "protocol")]
@@ -0,0 +1,406 @@
+use crate::index::IndexEntry;
+use std::io::{ErrorKind, Read};
+
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Maximum bytes bincode may allocate while decoding a section, so crafted lengths fail to
+/// decode instead of aborting on allocation.
+pub(crate) const DECODE_LIMIT: usize = 1024 * 1024 * 1024;
+
+/// Bytes allocated up front when reading a section, before its bytes are known to exist.
+pub(crate) const PREALLOC_LEN: u64 = 64 * 1024;
+
+/// Limits on the resources spent reading a bundle.
+///
+/// Sizes in a bundle file are not trusted before they are checked against these limits, so a
+/// crafted or corrupt file can't make readers allocate more memory than the limits allow.
+/// Defaults are generous for apps, and every limit can be lowered for untrusted sources.
+///
+/// # Example
+///
+/// ```no_run
+/// use wvb::{Bundle, BundleReader, Reader, ReaderLimits};
+///
+/// let file = std::fs::File::open("app.wvb").unwrap();
+/// let limits = ReaderLimits::new()
+///   .max_entries(10_000)
+///   .max_entry_size(64 * 1024 * 1024);
+/// let bundle: Bundle = BundleReader::new(file).limits(limits).read().unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReaderLimits {
+  pub(crate) max_index_size: u64,
+  pub(crate) max_entries: u64,
+  pub(crate) max_entry_size: u64,
+  pub(crate) max_total_size: u64,
+}
+
+impl Default for ReaderLimits {
+  fn default() -> Self {
+    Self {
+      max_index_size: 64 * 1024 * 1024,
+      max_entries: 1024 * 1024,
+      max_entry_size: 1024 * 1024 * 1024,
+      max_total_size: 4 * 1024 * 1024 * 1024,
+    }
+  }
+}
+
+impl ReaderLimits {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Returns limits which never fail a read.
+  pub fn unlimited() -> Self {
+    Self {
+      max_index_size: u64::MAX,
+      max_entries: u64::MAX,
+      max_entry_size: u64::MAX,
+      max_total_size: u64::MAX,
+    }
+  }
+
+  /// Sets the maximum size of the index section in bytes. Defaults to 64 MiB.
+  pub fn max_index_size(mut self, size: u64) -> Self {
+    self.max_index_size = size;
+    self
+  }
+
+  /// Sets the maximum number of entries in the index. Defaults to 1,048,576.
+  pub fn max_entries(mut self, count: u64) -> Self {
+    self.max_entries = count;
+    self
+  }
+
+  /// Sets the maximum decompressed size of an entry in bytes. Defaults to 1 GiB.
+  pub fn max_entry_size(mut self, size: u64) -> Self {
+    self.max_entry_size = size;
+    self
+  }
+
+  /// Sets the maximum size of the data and signatures sections in bytes. Defaults to 4 GiB.
+  pub fn max_total_size(mut self, size: u64) -> Self {
+    self.max_total_size = size;
+    self
+  }
+
+  pub(crate) fn check_index_size(&self, size: u64) -> crate::Result<()> {
+    if size > self.max_index_size {
+      return Err(crate::Error::IndexSizeLimitExceeded {
+        size,
+        limit: self.max_index_size,
+      });
+    }
+    Ok(())
+  }
+
+  pub(crate) fn check_entries(&self, count: u64) -> crate::Result<()> {
+    if count > self.max_entries {
+      return Err(crate::Error::EntryCountLimitExceeded {
+        count,
+        limit: self.max_entries,
+      });
+    }
+    Ok(())
+  }
+
+  /// Checks the sizes of an entry before its data is read.
+  pub(crate) fn check_entry(&self, entry: &IndexEntry) -> crate::Result<()> {
+    self.check_entry_size(entry.content_length().max(entry.len()))
+  }
+
+  pub(crate) fn check_entry_size(&self, size: u64) -> crate::Result<()> {
+    if size > self.max_entry_size {
+      return Err(crate::Error::EntrySizeLimitExceeded {
+        size,
+        limit: self.max_entry_size,
+      });
+    }
+    Ok(())
+  }
+
+  pub(crate) fn check_total_size(&self, size: u64) -> crate::Result<()> {
+    if size > self.max_total_size {
+      return Err(crate::Error::TotalSizeLimitExceeded {
+        size,
+        limit: self.max_total_size,
+      });
+    }
+    Ok(())
+  }
+}
+
+/// Reads exactly `len` bytes, growing the buffer as bytes arrive instead of allocating `len`
+/// bytes up front.
+pub(crate) fn read_exact_vec<R: Read>(r: R, len: u64) -> crate::Result<Vec<u8>> {
+  let mut buf = Vec::with_capacity(len.min(PREALLOC_LEN) as usize);
+  r.take(len).read_to_end(&mut buf)?;
+  if (buf.len() as u64) < len {
+    return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
+  }
+  Ok(buf)
+}
+
+/// Reads to the end, failing once more than `limit` bytes are read.
+pub(crate) fn read_to_end_limited<R: Read>(r: R, limit: u64) -> crate::Result<Vec<u8>> {
+  let mut buf = vec![];
+  r.take(limit.saturating_add(1)).read_to_end(&mut buf)?;
+  let size = buf.len() as u64;
+  if size > limit {
+    return Err(crate::Error::TotalSizeLimitExceeded { size, limit });
+  }
+  Ok(buf)
+}
+
+#[cfg(feature = "async")]
+pub(crate) async fn async_read_exact_vec<R: AsyncRead + Unpin>(
+  r: R,
+  len: u64,
+) -> crate::Result<Vec<u8>> {
+  let mut buf = Vec::with_capacity(len.min(PREALLOC_LEN) as usize);
+  r.take(len).read_to_end(&mut buf).await?;
+  if (buf.len() as u64) < len {
+    return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
+  }
+  Ok(buf)
+}
+
+#[cfg(feature = "async")]
+pub(crate) async fn async_read_to_end_limited<R: AsyncRead + Unpin>(
+  r: R,
+  limit: u64,
+) -> crate::Result<Vec<u8>> {
+  let mut buf = vec![];
+  r.take(limit.saturating_add(1))
+    .read_to_end(&mut buf)
+    .await?;
+  let size = buf.len() as u64;
+  if size > limit {
+    return Err(crate::Error::TotalSizeLimitExceeded { size, limit });
+  }
+  Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::builder::{BundleBuilder, BundleBuilderOptions, BundleEntry};
+  use crate::bundle::{Bundle, BundleDescriptor, BundleReader, BundleWriter};
+  use crate::codec::Codec;
+  use crate::encoding::ContentEncoding;
+  use crate::header::Header;
+  use crate::index::{IndexReader, IndexReaderOptions};
+  use crate::reader::Reader;
+  use crate::testing::{Fixtures, CODECS};
+  use crate::version::Version;
+  use crate::writer::Writer;
+  use std::io::Cursor;
+
+  fn build_v2(codec: Codec) -> Vec<u8> {
+    let mut options = BundleBuilderOptions::new();
+    options.codec(codec).block_size(64).dedup(true);
+    #[cfg(feature = "precompress")]
+    options.precompress(ContentEncoding::Gzip);
+    let mut builder = BundleBuilder::new_with_options(options);
+    builder.set_version(Version::V2);
+    let js = "console.log('hello world');\n".repeat(20);
+    builder.insert_entry(
+      "/index.html",
+      BundleEntry::new(b"<html><body>hello</body></html>", "text/html", None),
+    );
+    builder.insert_entry(
+      "/index.js",
+      BundleEntry::new(js.as_bytes(), "text/javascript", None),
+    );
+    builder.insert_entry(
+      "/copy.js",
+      BundleEntry::new(js.as_bytes(), "text/javascript", None),
+    );
+    builder.insert_entry("/empty.txt", BundleEntry::new(b"", "text/plain", None));
+    let bundle = builder.build().unwrap();
+    let mut bytes = vec![];
+    BundleWriter::new(&mut bytes).write(&bundle).unwrap();
+    bytes
+  }
+
+  fn read_descriptor(bytes: &[u8], limits: ReaderLimits) -> crate::Result<BundleDescriptor> {
+    BundleReader::new(Cursor::new(bytes)).limits(limits).read()
+  }
+
+  fn read_bundle(bytes: &[u8], limits: ReaderLimits) -> crate::Result<Bundle> {
+    BundleReader::new(Cursor::new(bytes)).limits(limits).read()
+  }
+
+  #[test]
+  fn index_limits() {
+    let bytes = build_v2(Codec::Lz4);
+    assert!(read_descriptor(&bytes, ReaderLimits::new()).is_ok());
+    assert!(matches!(
+      read_descriptor(&bytes, ReaderLimits::new().max_index_size(16)),
+      Err(crate::Error::IndexSizeLimitExceeded { limit: 16, .. })
+    ));
+    assert!(matches!(
+      read_descriptor(&bytes, ReaderLimits::new().max_entries(3)),
+      Err(crate::Error::EntryCountLimitExceeded { count: 4, limit: 3 })
+    ));
+    let v1 = std::fs::read(Fixtures::bundles().get_path("builtin/app/app_1.0.0.wvb")).unwrap();
+    assert!(matches!(
+      read_descriptor(&v1, ReaderLimits::new().max_entries(1)),
+      Err(crate::Error::EntryCountLimitExceeded { limit: 1, .. })
+    ));
+  }
+
+  #[test]
+  fn crafted_index_size() {
+    let bytes = build_v2(Codec::Lz4);
+    let header = Header::new(Version::V2, u32::MAX);
+    let mut reader = IndexReader::new(Cursor::new(&bytes), header);
+    assert!(matches!(
+      reader.read(),
+      Err(crate::Error::IndexSizeLimitExceeded { .. })
+    ));
+    // within the limit, the missing bytes fail the read without being allocated up front
+    let options = IndexReaderOptions::new().limits(ReaderLimits::unlimited());
+    let mut reader = IndexReader::new_with_options(Cursor::new(&bytes), header, options);
+    assert!(matches!(reader.read(), Err(crate::Error::Io(_))));
+  }
+
+  #[test]
+  fn data_limits() {
+    let bytes = build_v2(Codec::Lz4);
+    assert!(matches!(
+      read_bundle(&bytes, ReaderLimits::new().max_total_size(16)),
+      Err(crate::Error::TotalSizeLimitExceeded { limit: 16, .. })
+    ));
+
+    let limits = ReaderLimits::new().max_entry_size(64);
+    let descriptor = read_descriptor(&bytes, limits).unwrap();
+    assert_eq!(descriptor.limits(), &limits);
+    assert!(descriptor
+      .get_data(Cursor::new(&bytes), "/index.html")
+      .unwrap()
+      .is_some());
+    assert!(matches!(
+      descriptor.get_data(Cursor::new(&bytes), "/index.js"),
+      Err(crate::Error::EntrySizeLimitExceeded { limit: 64, .. })
+    ));
+    assert!(matches!(
+      descriptor.get_data_range(Cursor::new(&bytes), "/index.js", 0..10),
+      Err(crate::Error::EntrySizeLimitExceeded { .. })
+    ));
+
+    let mut bundle = read_bundle(&bytes, ReaderLimits::new()).unwrap();
+    assert!(bundle.get_data("/index.js").unwrap().is_some());
+    bundle.set_limits(limits);
+    assert!(matches!(
+      bundle.get_data("/index.js"),
+      Err(crate::Error::EntrySizeLimitExceeded { .. })
+    ));
+  }
+
+  /// Deterministic xorshift generator, so failures of the corpus are reproducible.
+  struct Rng(u64);
+
+  impl Rng {
+    fn next(&mut self) -> u64 {
+      self.0 ^= self.0 << 13;
+      self.0 ^= self.0 >> 7;
+      self.0 ^= self.0 << 17;
+      self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+      (self.next() % n as u64) as usize
+    }
+  }
+
+  /// Returns mutations of a valid bundle: truncations, bit flips, and extreme values written
+  /// over sizes and offsets, mostly in the header and the index.
+  fn mutations(bytes: &[u8], seed: u64, count: usize) -> Vec<Vec<u8>> {
+    let header_len = Header::end_offset_of(Version::V2) as usize;
+    let descriptor = read_descriptor(bytes, ReaderLimits::new()).unwrap();
+    let index_end = (descriptor.header().data_offset() as usize).min(bytes.len());
+    let mut rng = Rng(seed);
+    let mut corpus = vec![];
+    for i in 0..count {
+      let mut mutated = bytes.to_vec();
+      // target the header and the index three times out of four
+      let pos = match i % 4 {
+        0 => rng.below(header_len),
+        1 | 2 => rng.below(index_end),
+        _ => rng.below(bytes.len()),
+      };
+      match rng.below(5) {
+        0 => mutated.truncate(pos),
+        1 => mutated[pos] ^= 1 << rng.below(8),
+        2 => mutated[pos] = rng.next() as u8,
+        3 => {
+          let value = [u32::MAX, u32::MAX / 2, 1 << 31, 0][rng.below(4)];
+          let end = (pos + 4).min(mutated.len());
+          mutated[pos..end].copy_from_slice(&value.to_be_bytes()[..end - pos]);
+        }
+        _ => {
+          let end = (pos + 8).min(mutated.len());
+          mutated[pos..end].fill(0xff);
+        }
+      }
+      corpus.push(mutated);
+    }
+    corpus
+  }
+
+  /// Reads a bundle and all of its data, which may fail but must not panic or allocate past the
+  /// limits.
+  fn read_all(bytes: &[u8], limits: ReaderLimits) {
+    if let Ok(descriptor) = read_descriptor(bytes, limits) {
+      let paths = descriptor
+        .index()
+        .entries()
+        .keys()
+        .cloned()
+        .collect::<Vec<_>>();
+      for path in &paths {
+        let _ = descriptor.get_data(Cursor::new(bytes), path);
+        let _ = descriptor.get_data_range(Cursor::new(bytes), path, 10..100);
+        let _ = descriptor.get_data_checksum(Cursor::new(bytes), path);
+        let _ = descriptor.get_variant(Cursor::new(bytes), path, ContentEncoding::Gzip);
+        if let Ok(Some(mut reader)) = descriptor.open_entry(Cursor::new(bytes), path) {
+          let _ = std::io::copy(&mut reader, &mut std::io::sink());
+        }
+      }
+    }
+    if let Ok(bundle) = read_bundle(bytes, limits) {
+      let paths = bundle
+        .descriptor()
+        .index()
+        .entries()
+        .keys()
+        .cloned()
+        .collect::<Vec<_>>();
+      for path in &paths {
+        let _ = bundle.get_data(path);
+        let _ = bundle.get_data_range(path, 0..1);
+      }
+    }
+  }
+
+  #[test]
+  fn mutated_bundles() {
+    let limits = ReaderLimits::new()
+      .max_index_size(1024 * 1024)
+      .max_entry_size(16 * 1024 * 1024)
+      .max_total_size(64 * 1024 * 1024);
+    let codecs = CODECS.iter().filter(|&&x| x != Codec::None);
+    for (seed, &codec) in (1..).zip(codecs) {
+      for mutated in mutations(&build_v2(codec), seed, 500) {
+        read_all(&mutated, limits);
+      }
+    }
+    let v1 = std::fs::read(Fixtures::bundles().get_path("remote/app/app_1.0.0.wvb")).unwrap();
+    for mutated in mutations(&v1, 4, 40) {
+      read_all(&mutated, limits);
+    }
+  }
+}
@@ -0,0 +1,490 @@
+use crate::block::{decompress_entry, EntrySpan};
+use crate::bundle::{data_position, BundleReader};
+use crate::checksum::{parse_checksum, CHECKSUM_LEN};
+use crate::codec::{check_stored_len, Codec};
+use crate::digest::{verify_entry_digest, verify_variant_digest};
+use crate::encoding::ContentEncoding;
+use crate::index::IndexEntry;
+use crate::limits::ReaderLimits;
+use crate::reader::Reader;
+use crate::BundleDescriptor;
+use bytes::Bytes;
+use memmap2::Mmap;
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{Cursor, ErrorKind};
+use std::ops::Range;
+use std::path::Path;
+
+/// A bundle file mapped into memory.
+///
+/// The header and index are parsed once when the bundle is mapped, and reading an entry is a
+/// slice access into the mapping followed by decompression. Entries stored without compression
+/// ([`Codec::None`]) are returned without copying, either borrowed or as [`Bytes`] sharing the
+/// mapping (see [`MappedBundle::get_bytes`]).
+///
+/// The bundle file must not be modified or truncated while it is mapped, which would be
+/// undefined behavior, so replace bundle files by renaming a new file over them instead of
+/// writing to them in place.
+///
+/// # Example
+///
+/// ```no_run
+/// use wvb::MappedBundle;
+///
+/// let bundle = MappedBundle::open("app.wvb").unwrap();
+/// let html = bundle.get_data("/index.html").unwrap().unwrap();
+/// ```
+pub struct MappedBundle {
+  /// The whole mapping, which stays mapped while any slice of it is alive.
+  mmap: Bytes,
+  descriptor: BundleDescriptor,
+}
+
+impl std::fmt::Debug for MappedBundle {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("MappedBundle")
+      .field("len", &self.mmap.len())
+      .field("descriptor", &self.descriptor)
+      .finish()
+  }
+}
+
+impl MappedBundle {
+  /// Maps the bundle file at the given path.
+  pub fn open(path: impl AsRef<Path>) -> crate::Result<Self> {
+    let file = File::open(path)?;
+    Self::from_file(&file)
+  }
+
+  /// Maps the given bundle file.
+  pub fn from_file(file: &File) -> crate::Result<Self> {
+    // SAFETY: the file must not be modified or truncated while it is mapped, as documented on
+    // `MappedBundle`. `BundleSource` writes remote bundles to a temporary file and renames it
+    // into place, so a mapped file is replaced rather than modified.
+    let mmap = unsafe { Mmap::map(file)? };
+    Self::from_mmap(mmap)
+  }
+
+  /// Creates a mapped bundle from an existing memory map.
+  pub fn from_mmap(mmap: Mmap) -> crate::Result<Self> {
+    let descriptor: BundleDescriptor = BundleReader::new(Cursor::new(&mmap[..])).read()?;
+    Ok(Self {
+      mmap: Bytes::from_owner(mmap),
+      descriptor,
+    })
+  }
+
+  /// Returns a reference to the bundle descriptor (header and index).
+  pub fn descriptor(&self) -> &BundleDescriptor {
+    &self.descriptor
+  }
+
+  /// Sets whether file data is verified against the digest in the index when read.
+  ///
+  /// See [`BundleDescriptor::set_verify_digest`].
+  pub fn set_verify_digest(&mut self, verify: bool) -> &mut Self {
+    self.descriptor.set_verify_digest(verify);
+    self
+  }
+
+  /// Sets the limits applied when decompressing file data.
+  ///
+  /// See [`BundleDescriptor::set_limits`].
+  pub fn set_limits(&mut self, limits: ReaderLimits) -> &mut Self {
+    self.descriptor.set_limits(limits);
+    self
+  }
+
+  /// Retrieves file data by path.
+  ///
+  /// Returns `None` if the path doesn't exist in the bundle. Stored entries are borrowed from
+  /// the mapping, while compressed entries are decompressed into an owned buffer.
+  pub fn get_data(&self, path: &str) -> crate::Result<Option<Cow<'_, [u8]>>> {
+    let Some(entry) = self.descriptor.index.try_get_entry(path)? else {
+      return Ok(None);
+    };
+    self.descriptor.limits.check_entry(entry)?;
+    let buf = self.slice(entry.offset(), entry.len())?;
+    let data = match entry.codec() {
+      Codec::None => {
+        check_stored_len(buf, entry.content_length())?;
+        Cow::Borrowed(buf)
+      }
+      _ => Cow::Owned(decompress_entry(entry, buf)?),
+    };
+    if self.descriptor.verify_digest {
+      verify_entry_digest(entry, &data)?;
+    }
+    Ok(Some(data))
+  }
+
+  /// Retrieves a byte range of file data by path.
+  ///
+  /// Ranges of stored entries are borrowed from the mapping. See
+  /// [`BundleDescriptor::get_data_range`].
+  pub fn get_data_range(
+    &self,
+    path: &str,
+    range: Range<u64>,
+  ) -> crate::Result<Option<Cow<'_, [u8]>>> {
+    let Some(entry) = self.descriptor.index.try_get_entry(path)? else {
+      return Ok(None);
+    };
+    self.descriptor.limits.check_entry(entry)?;
+    let span = EntrySpan::new(entry, range);
+    let buf = self.slice(entry.offset().saturating_add(span.offset), span.len)?;
+    let data = match entry.codec() {
+      Codec::None => Cow::Borrowed(buf),
+      _ => Cow::Owned(span.decode(entry, buf)?),
+    };
+    Ok(Some(data))
+  }
+
+  /// Retrieves the precompressed variant of file data in the given content encoding.
+  ///
+  /// Returns `None` if the path doesn't exist in the bundle or has no such variant. The encoded
+  /// data is borrowed from the mapping.
+  pub fn get_variant(&self, path: &str, encoding: ContentEncoding) -> crate::Result<Option<&[u8]>> {
+    let Some(variant) = self
+      .descriptor
+      .index
+      .try_get_entry(path)?
+      .and_then(|x| x.variant(encoding))
+    else {
+      return Ok(None);
+    };
+    let data = self.slice(variant.offset(), variant.len())?;
+    if self.descriptor.verify_digest {
+      verify_variant_digest(variant, data)?;
+    }
+    Ok(Some(data))
+  }
+
+  /// Retrieves the checksum of file data by path.
+  ///
+  /// Returns `None` if the path doesn't exist in the bundle.
+  pub fn get_data_checksum(&self, path: &str) -> crate::Result<Option<u32>> {
+    let Some(entry) = self.descriptor.index.try_get_entry(path)? else {
+      return Ok(None);
+    };
+    let buf = self.slice(checksum_offset(entry), CHECKSUM_LEN as u64)?;
+    Ok(Some(parse_checksum(buf)))
+  }
+
+  /// Retrieves file data by path as [`Bytes`].
+  ///
+  /// Stored entries share the mapping without copying, so they can be kept or sent elsewhere
+  /// after this bundle is dropped. Compressed entries are decompressed into a new buffer.
+  pub fn get_bytes(&self, path: &str) -> crate::Result<Option<Bytes>> {
+    let Some(entry) = self.descriptor.index.try_get_entry(path)? else {
+      return Ok(None);
+    };
+    if entry.codec() != Codec::None {
+      return Ok(self.get_data(path)?.map(|x| Bytes::from(x.into_owned())));
+    }
+    let data = self.mmap.slice(self.range(entry.offset(), entry.len())?);
+    check_stored_len(&data, entry.content_length())?;
+    if self.descriptor.verify_digest {
+      verify_entry_digest(entry, &data)?;
+    }
+    Ok(Some(data))
+  }
+
+  /// Retrieves a byte range of file data by path as [`Bytes`].
+  ///
+  /// Ranges of stored entries share the mapping. See [`MappedBundle::get_bytes`].
+  pub fn get_bytes_range(&self, path: &str, range: Range<u64>) -> crate::Result<Option<Bytes>> {
+    let Some(entry) = self.descriptor.index.try_get_entry(path)? else {
+      return Ok(None);
+    };
+    if entry.codec() != Codec::None {
+      return Ok(
+        self
+          .get_data_range(path, range)?
+          .map(|x| Bytes::from(x.into_owned())),
+      );
+    }
+    let span = EntrySpan::new(entry, range);
+    let range = self.range(entry.offset().saturating_add(span.offset), span.len)?;
+    Ok(Some(self.mmap.slice(range)))
+  }
+
+  /// Retrieves the precompressed variant of file data in the given content encoding as
+  /// [`Bytes`] sharing the mapping.
+  ///
+  /// See [`MappedBundle::get_variant`].
+  pub fn get_variant_bytes(
+    &self,
+    path: &str,
+    encoding: ContentEncoding,
+  ) -> crate::Result<Option<Bytes>> {
+    let Some(variant) = self
+      .descriptor
+      .index
+      .try_get_entry(path)?
+      .and_then(|x| x.variant(encoding))
+    else {
+      return Ok(None);
+    };
+    let range = self.range(variant.offset(), variant.len())?;
+    let data = self.mmap.slice(range);
+    if self.descriptor.verify_digest {
+      verify_variant_digest(variant, &data)?;
+    }
+    Ok(Some(data))
+  }
+
+  /// Returns the bytes at `offset` of the data section, failing with an unexpected EOF error
+  /// like the other bundle readers when the range exceeds the mapping.
+  fn slice(&self, offset: u64, len: u64) -> crate::Result<&[u8]> {
+    Ok(&self.mmap[self.range(offset, len)?])
+  }
+
+  /// Resolves the range of the mapping holding `len` bytes at `offset` of the data section.
+  fn range(&self, offset: u64, len: u64) -> crate::Result<Range<usize>> {
+    let header = &self.descriptor.header;
+    let start = data_position(header.data_offset(), header.data_size(), offset, len)?;
+    match start.checked_add(len) {
+      Some(end) if end <= self.mmap.len() as u64 => Ok(start as usize..end as usize),
+      _ => Err(std::io::Error::from(ErrorKind::UnexpectedEof).into()),
+    }
+  }
+}
+
+fn checksum_offset(entry: &IndexEntry) -> u64 {
+  // an overflowing offset is out of the mapping either way
+  entry.offset().saturating_add(entry.len())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::Fixtures;
+  use crate::{BundleBuilder, BundleBuilderOptions, BundleEntry, BundleWriter, Version, Writer};
+  use memmap2::MmapMut;
+
+  fn map_bytes(bytes: &[u8]) -> Mmap {
+    let mut mmap = MmapMut::map_anon(bytes.len()).unwrap();
+    mmap.copy_from_slice(bytes);
+    mmap.make_read_only().unwrap()
+  }
+
+  #[test]
+  fn open() {
+    let fixture = Fixtures::bundles();
+    let bundle = MappedBundle::open(fixture.get_path("builtin/app/app_1.0.0.wvb")).unwrap();
+    assert!(bundle.descriptor().index().contains_path("/index.html"));
+    let data = bundle.get_data("/index.html").unwrap().unwrap();
+    assert!(matches!(data, Cow::Owned(_)));
+    assert!(bundle.get_data("/not-found.html").unwrap().is_none());
+  }
+
+  #[test]
+  fn stored_entries_are_borrowed() {
+    let mut options = BundleBuilderOptions::new();
+    options.content_type_codec("image/png", Codec::None);
+    let mut builder = BundleBuilder::new_with_options(options);
+    builder.set_version(Version::V2);
+    builder.insert_entry("/logo.png", BundleEntry::new(b"png", "image/png", None));
+    builder.insert_entry(
+      "/index.html",
+      BundleEntry::new(b"<html></html>", "text/html", None),
+    );
+    let built = builder.build().unwrap();
+    let mut bytes = vec![];
+    BundleWriter::new(&mut bytes).write(&built).unwrap();
+
+    let bundle = MappedBundle::from_mmap(map_bytes(&bytes)).unwrap();
+    assert_eq!(bundle.descriptor(), built.descriptor());
+    let png = bundle.get_data("/logo.png").unwrap().unwrap();
+    assert!(matches!(png, Cow::Borrowed(b"png")));
+    let png = bundle.get_data_range("/logo.png", 1..3).unwrap().unwrap();
+    assert!(matches!(png, Cow::Borrowed(b"ng")));
+    let html = bundle.get_data_range("/index.html", 1..5).unwrap().unwrap();
+    assert_eq!(html.as_ref(), b"html");
+    let html = bundle.get_data("/index.html").unwrap().unwrap();
+    assert_eq!(html.as_ref(), b"<html></html>");
+    assert!(bundle
+      .get_variant("/index.html", ContentEncoding::Gzip)
+      .unwrap()
+      .is_none());
+    assert_eq!(
+      bundle.get_data_checksum("/index.html").unwrap(),
+      built.get_data_checksum("/index.html").unwrap()
+    );
+  }
+
+  #[cfg(feature = "precompress")]
+  #[test]
+  fn bytes_share_the_mapping() {
+    let mut options = BundleBuilderOptions::new();
+    options
+      .content_type_codec("image/png", Codec::None)
+      .precompress(ContentEncoding::Gzip);
+    let mut builder = BundleBuilder::new_with_options(options);
+    builder.set_version(Version::V2);
+    builder.insert_entry("/logo.png", BundleEntry::new(b"png", "image/png", None));
+    let html = "<html><body>hello</body></html>".repeat(10);
+    builder.insert_entry(
+      "/index.html",
+      BundleEntry::new(html.as_bytes(), "text/html", None),
+    );
+    let built = builder.build().unwrap();
+    let mut bytes = vec![];
+    BundleWriter::new(&mut bytes).write(&built).unwrap();
+
+    let bundle = MappedBundle::from_mmap(map_bytes(&bytes)).unwrap();
+    let mapping = bundle.mmap.as_ptr_range();
+    let png = bundle.get_bytes("/logo.png").unwrap().unwrap();
+    assert_eq!(png, b"png".as_slice());
+    assert!(mapping.contains(&png.as_ptr()));
+    let png = bundle.get_bytes_range("/logo.png", 1..3).unwrap().unwrap();
+    assert_eq!(png, b"ng".as_slice());
+    assert!(mapping.contains(&png.as_ptr()));
+    let variant = bundle
+      .get_variant_bytes("/index.html", ContentEncoding::Gzip)
+      .unwrap()
+      .unwrap();
+    assert!(mapping.contains(&variant.as_ptr()));
+    assert_eq!(
+      bundle.get_bytes("/index.html").unwrap().unwrap(),
+      html.as_bytes()
+    );
+    assert_eq!(
+      bundle
+        .get_bytes_range("/index.html", 0..6)
+        .unwrap()
+        .unwrap(),
+      b"<html>".as_slice()
+    );
+    assert!(bundle.get_bytes("/not-found").unwrap().is_none());
+
+    // slices outlive the bundle
+    drop(bundle);
+    assert_eq!(png, b"ng".as_slice());
+  }
+
+  #[cfg(all(feature = "digest", feature = "precompress"))]
+  #[test]
+  fn verify_variant_digest() {
+    let mut options = BundleBuilderOptions::new();
+    options
+      .digest(crate::DigestAlgorithm::Sha3_256)
+      .precompress(ContentEncoding::Gzip);
+    let mut builder = BundleBuilder::new_with_options(options);
+    builder.set_version(Version::V2);
+    let html = "<html><body>hello</body></html>".repeat(10);
+    builder.insert_entry(
+      "/index.html",
+      BundleEntry::new(html.as_bytes(), "text/html", None),
+    );
+    let built = builder.build().unwrap();
+    let mut bytes = vec![];
+    BundleWriter::new(&mut bytes).write(&built).unwrap();
+    let variant = built
+      .descriptor()
+      .index()
+      .get_entry("/index.html")
+      .unwrap()
+      .variant(ContentEncoding::Gzip)
+      .cloned()
+      .unwrap();
+    assert!(variant.digest().is_some());
+    let offset = built.descriptor().header().data_offset() + variant.offset();
+    bytes[offset as usize + 4] ^= 0xff;
+
+    let mut bundle = MappedBundle::from_mmap(map_bytes(&bytes)).unwrap();
+    assert!(bundle
+      .get_variant("/index.html", ContentEncoding::Gzip)
+      .unwrap()
+      .is_some());
+    bundle.set_verify_digest(true);
+    assert!(matches!(
+      bundle.get_variant("/index.html", ContentEncoding::Gzip),
+      Err(crate::Error::DigestMismatch)
+    ));
+    assert!(matches!(
+      bundle.get_variant_bytes("/index.html", ContentEncoding::Gzip),
+      Err(crate::Error::DigestMismatch)
+    ));
+  }
+
+  #[test]
+  fn out_of_range() {
+    let mut builder = BundleBuilder::new();
+    builder.insert_entry(
+      "/index.html",
+      BundleEntry::new(b"<html></html>", "text/html", None),
+    );
+    let mut built = builder.build().unwrap();
+    built
+      .descriptor
+      .index
+      .insert_entry("/index.html", IndexEntry::new(0, 250, "text/html", 13));
+    let mut bytes = vec![];
+    BundleWriter::new(&mut bytes).write(&built).unwrap();
+
+    let bundle = MappedBundle::from_mmap(map_bytes(&bytes)).unwrap();
+    assert!(matches!(
+      bundle.get_data("/index.html"),
+      Err(crate::Error::Io(e)) if e.kind() == ErrorKind::UnexpectedEof
+    ));
+  }
+
+  #[test]
+  fn out_of_data_section() {
+    let mut builder = BundleBuilder::new();
+    builder.set_version(Version::V2);
+    builder.insert_entry(
+      "/index.html",
+      BundleEntry::new(b"<html></html>", "text/html", None),
+    );
+    let mut built = builder.build().unwrap();
+    built
+      .descriptor
+      .index
+      .insert_entry("/index.html", IndexEntry::new(0, 30, "text/html", 13));
+    let mut bytes = vec![];
+    BundleWriter::new(&mut bytes).write(&built).unwrap();
+    // the entry reaches into the index after the data section, which is in the mapping
+    assert!(bytes.len() as u64 >= built.descriptor().header().data_offset() + 34);
+
+    let bundle = MappedBundle::from_mmap(map_bytes(&bytes)).unwrap();
+    assert!(matches!(
+      bundle.get_data("/index.html"),
+      Err(crate::Error::Io(e)) if e.kind() == ErrorKind::UnexpectedEof
+    ));
+  }
+
+  #[test]
+  fn stored_size_mismatch() {
+    let mut options = BundleBuilderOptions::new();
+    options.codec(Codec::None);
+    let mut builder = BundleBuilder::new_with_options(options);
+    builder.insert_entry(
+      "/index.html",
+      BundleEntry::new(b"<html></html>", "text/html", None),
+    );
+    builder.set_version(Version::V2);
+    let mut built = builder.build().unwrap();
+    let mut entry = IndexEntry::new(0, 13, "text/html", 20);
+    entry.codec = Codec::None;
+    built.descriptor.index.insert_entry("/index.html", entry);
+    let mut bytes = vec![];
+    BundleWriter::new(&mut bytes).write(&built).unwrap();
+
+    let bundle = MappedBundle::from_mmap(map_bytes(&bytes)).unwrap();
+    assert!(matches!(
+      bundle.get_data("/index.html"),
+      Err(crate::Error::EntrySizeMismatch {
+        expected: 20,
+        actual: 13
+      })
+    ));
+    assert!(matches!(
+      bundle.get_bytes("/index.html"),
+      Err(crate::Error::EntrySizeMismatch { .. })
+    ));
+  }
+}
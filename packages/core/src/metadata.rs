@@ -0,0 +1,103 @@
+use bincode::{Decode, Encode};
+use std::collections::BTreeMap;
+
+/// Bundle-level metadata stored in the index of `V2` bundles.
+///
+/// Metadata lets a `.wvb` file describe itself without relying on its file name or a manifest,
+/// and is covered by embedded signatures together with the index.
+///
+/// The build timestamp is never filled in automatically, so bundles stay reproducible unless
+/// the caller opts in.
+///
+/// # Example
+///
+/// ```
+/// use wvb::BundleMetadata;
+///
+/// let mut metadata = BundleMetadata::new("app", "1.2.0");
+/// metadata
+///   .set_git_commit("4f2a9c1")
+///   .set_min_host_version("3.0.0")
+///   .insert_custom("channel", "beta");
+/// assert_eq!(metadata.name(), "app");
+/// assert_eq!(metadata.custom().get("channel").unwrap(), "beta");
+/// ```
+#[derive(Debug, Default, PartialEq, Eq, Clone, Encode, Decode)]
+pub struct BundleMetadata {
+  name: String,
+  version: String,
+  built_at: Option<u64>,
+  git_commit: Option<String>,
+  min_host_version: Option<String>,
+  custom: BTreeMap<String, String>,
+}
+
+impl BundleMetadata {
+  /// Creates metadata with the bundle name and its semver version.
+  pub fn new(name: impl Into<String>, version: impl Into<String>) -> Self {
+    Self {
+      name: name.into(),
+      version: version.into(),
+      ..Default::default()
+    }
+  }
+
+  /// Returns the bundle name.
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  /// Returns the semver version of the bundle.
+  pub fn version(&self) -> &str {
+    &self.version
+  }
+
+  /// Returns the build timestamp in seconds since the Unix epoch.
+  pub fn built_at(&self) -> Option<u64> {
+    self.built_at
+  }
+
+  /// Sets the build timestamp in seconds since the Unix epoch.
+  pub fn set_built_at(&mut self, timestamp: u64) -> &mut Self {
+    self.built_at = Some(timestamp);
+    self
+  }
+
+  /// Returns the git commit the bundle was built from.
+  pub fn git_commit(&self) -> Option<&str> {
+    self.git_commit.as_deref()
+  }
+
+  /// Sets the git commit the bundle was built from.
+  pub fn set_git_commit(&mut self, commit: impl Into<String>) -> &mut Self {
+    self.git_commit = Some(commit.into());
+    self
+  }
+
+  /// Returns the minimum version of the host application which can load the bundle.
+  pub fn min_host_version(&self) -> Option<&str> {
+    self.min_host_version.as_deref()
+  }
+
+  /// Sets the minimum version of the host application which can load the bundle.
+  pub fn set_min_host_version(&mut self, version: impl Into<String>) -> &mut Self {
+    self.min_host_version = Some(version.into());
+    self
+  }
+
+  /// Returns custom key/value fields.
+  pub fn custom(&self) -> &BTreeMap<String, String> {
+    &self.custom
+  }
+
+  /// Inserts a custom key/value field.
+  pub fn insert_custom(&mut self, key: impl Into<String>, value: impl Into<String>) -> &mut Self {
+    self.custom.insert(key.into(), value.into());
+    self
+  }
+
+  /// Removes a custom key/value field.
+  pub fn remove_custom(&mut self, key: &str) -> Option<String> {
+    self.custom.remove(key)
+  }
+}
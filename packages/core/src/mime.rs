@@ -0,0 +1,317 @@
+// This code is originally from https://github.com/tauri-apps/tauri/blob/dev/crates/tauri-utils/src/mime_type.rs
+
+// Copyright 2019-2024 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+use std::fmt;
+
+const MIMETYPE_PLAIN: &str = "text/plain";
+
+/// File extensions and their mimetypes, sorted by extension.
+///
+/// Mostly follows the [common types](https://developer.mozilla.org/en-US/docs/Web/HTTP/Guides/MIME_types/Common_types)
+/// list, plus formats served by web apps such as web app manifests and source maps.
+const EXTENSIONS: &[(&str, &str)] = &[
+  ("7z", "application/x-7z-compressed"),
+  ("aac", "audio/aac"),
+  ("abw", "application/x-abiword"),
+  ("apng", "image/apng"),
+  ("arc", "application/x-freearc"),
+  ("atom", "application/atom+xml"),
+  ("avi", "video/x-msvideo"),
+  ("avif", "image/avif"),
+  ("azw", "application/vnd.amazon.ebook"),
+  ("bin", "application/octet-stream"),
+  ("bmp", "image/bmp"),
+  ("br", "application/x-brotli"),
+  ("bz", "application/x-bzip"),
+  ("bz2", "application/x-bzip2"),
+  ("cjs", "text/javascript"),
+  ("css", "text/css"),
+  ("csv", "text/csv"),
+  ("doc", "application/msword"),
+  (
+    "docx",
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+  ),
+  ("eot", "application/vnd.ms-fontobject"),
+  ("epub", "application/epub+zip"),
+  ("flac", "audio/flac"),
+  ("gif", "image/gif"),
+  ("glb", "model/gltf-binary"),
+  ("gltf", "model/gltf+json"),
+  ("gz", "application/gzip"),
+  ("heic", "image/heic"),
+  ("heif", "image/heif"),
+  ("htm", "text/html"),
+  ("html", "text/html"),
+  ("ico", "image/vnd.microsoft.icon"),
+  ("ics", "text/calendar"),
+  ("jar", "application/java-archive"),
+  ("jpeg", "image/jpeg"),
+  ("jpg", "image/jpeg"),
+  ("js", "text/javascript"),
+  ("json", "application/json"),
+  ("jsonld", "application/ld+json"),
+  ("jxl", "image/jxl"),
+  ("less", "text/css"),
+  ("m4a", "audio/mp4"),
+  ("m4v", "video/mp4"),
+  ("map", "application/json"),
+  ("md", "text/markdown"),
+  ("mid", "audio/midi"),
+  ("midi", "audio/midi"),
+  ("mjs", "text/javascript"),
+  ("mov", "video/quicktime"),
+  ("mp3", "audio/mpeg"),
+  ("mp4", "video/mp4"),
+  ("mpeg", "video/mpeg"),
+  ("oga", "audio/ogg"),
+  ("ogg", "audio/ogg"),
+  ("ogv", "video/ogg"),
+  ("ogx", "application/ogg"),
+  ("opus", "audio/opus"),
+  ("otf", "font/otf"),
+  ("pdf", "application/pdf"),
+  ("png", "image/png"),
+  ("ppt", "application/vnd.ms-powerpoint"),
+  (
+    "pptx",
+    "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+  ),
+  ("rar", "application/vnd.rar"),
+  ("rss", "application/rss+xml"),
+  ("rtf", "application/rtf"),
+  ("sass", "text/css"),
+  ("scss", "text/css"),
+  ("sh", "application/x-sh"),
+  ("styl", "text/css"),
+  ("svg", "image/svg+xml"),
+  ("svgz", "image/svg+xml"),
+  ("tar", "application/x-tar"),
+  ("tif", "image/tiff"),
+  ("tiff", "image/tiff"),
+  ("ts", "video/mp2t"),
+  ("ttc", "font/collection"),
+  ("ttf", "font/ttf"),
+  ("txt", "text/plain"),
+  ("vtt", "text/vtt"),
+  ("wasm", "application/wasm"),
+  ("wav", "audio/wav"),
+  ("weba", "audio/webm"),
+  ("webm", "video/webm"),
+  ("webmanifest", "application/manifest+json"),
+  ("webp", "image/webp"),
+  ("woff", "font/woff"),
+  ("woff2", "font/woff2"),
+  ("xhtml", "application/xhtml+xml"),
+  ("xls", "application/vnd.ms-excel"),
+  (
+    "xlsx",
+    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+  ),
+  ("xml", "application/xml"),
+  ("yaml", "application/yaml"),
+  ("yml", "application/yaml"),
+  ("zip", "application/zip"),
+  ("zst", "application/zstd"),
+];
+
+/// [Web Compatible MimeTypes](https://developer.mozilla.org/en-US/docs/Web/HTTP/Basics_of_HTTP/MIME_types#important_mime_types_for_web_developers)
+///
+/// # Example
+///
+/// ```
+/// use wvb::MimeType;
+///
+/// assert_eq!(MimeType::detect(b"body {}", "/style.css"), "text/css");
+/// assert_eq!(MimeType::detect(b"\x89PNG\r\n\x1a\n", "/logo"), "image/png");
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MimeType {
+  Css,
+  Csv,
+  Html,
+  Ico,
+  Js,
+  Json,
+  Jsonld,
+  Mp4,
+  OctetStream,
+  Rtf,
+  Svg,
+  Txt,
+}
+
+impl fmt::Display for MimeType {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mime = match self {
+      MimeType::Css => "text/css",
+      MimeType::Csv => "text/csv",
+      MimeType::Html => "text/html",
+      MimeType::Ico => "image/vnd.microsoft.icon",
+      MimeType::Js => "text/javascript",
+      MimeType::Json => "application/json",
+      MimeType::Jsonld => "application/ld+json",
+      MimeType::Mp4 => "video/mp4",
+      MimeType::OctetStream => "application/octet-stream",
+      MimeType::Rtf => "application/rtf",
+      MimeType::Svg => "image/svg+xml",
+      MimeType::Txt => MIMETYPE_PLAIN,
+    };
+    write!(f, "{mime}")
+  }
+}
+
+impl MimeType {
+  /// Resolves the mimetype of a file extension, without the leading `.`.
+  ///
+  /// Extensions are matched case-insensitively. Returns `None` for unknown extensions.
+  pub fn from_extension(extension: &str) -> Option<&'static str> {
+    let extension = extension.to_ascii_lowercase();
+    EXTENSIONS
+      .binary_search_by(|(x, _)| (*x).cmp(extension.as_str()))
+      .ok()
+      .map(|i| EXTENSIONS[i].1)
+  }
+
+  /// Resolves the mimetype of a path from the extension of its last segment.
+  ///
+  /// Returns `None` for unknown or missing extensions.
+  pub fn from_path(path: &str) -> Option<&'static str> {
+    Self::from_extension(path_extension(path)?)
+  }
+
+  /// parse a URI suffix to convert text/plain mimeType to their actual web compatible mimeType with specified fallback for unknown file extensions.
+  pub fn parse_from_uri_with_fallback(uri: &str, fallback: MimeType) -> MimeType {
+    let suffix = uri.split('.').next_back();
+    match suffix {
+      Some("bin") => Self::OctetStream,
+      Some("css" | "less" | "sass" | "styl") => Self::Css,
+      Some("csv") => Self::Csv,
+      Some("html") => Self::Html,
+      Some("ico") => Self::Ico,
+      Some("js") | Some("mjs") => Self::Js,
+      Some("json") => Self::Json,
+      Some("jsonld") => Self::Jsonld,
+      Some("mp4") => Self::Mp4,
+      Some("rtf") => Self::Rtf,
+      Some("svg") => Self::Svg,
+      Some("txt") => Self::Txt,
+      // Assume HTML when a TLD is found for eg. `wry:://tauri.app` | `wry://hello.com`
+      Some(_) => fallback,
+      // using octet stream according to this:
+      // <https://developer.mozilla.org/en-US/docs/Web/HTTP/Basics_of_HTTP/MIME_types/Common_types>
+      None => Self::OctetStream,
+    }
+  }
+
+  /// infer mimetype from content (or) URI if needed with specified fallback for unknown file extensions.
+  pub fn parse_with_fallback(content: &[u8], uri: &str, fallback: MimeType) -> String {
+    let mime = if uri.ends_with(".svg") {
+      // when reading svg, we can't use `infer`
+      None
+    } else {
+      sniff(content)
+    };
+
+    match mime {
+      Some(mime) if mime == MIMETYPE_PLAIN => {
+        Self::parse_from_uri_with_fallback(uri, fallback).to_string()
+      }
+      None => Self::parse_from_uri_with_fallback(uri, fallback).to_string(),
+      Some(mime) => mime.to_string(),
+    }
+  }
+
+  /// Detects the mimetype of a file from the extension of its path, inferring it from the
+  /// content for unknown extensions.
+  ///
+  /// Falls back to `application/octet-stream` if neither is known.
+  pub fn detect(content: &[u8], path: &str) -> String {
+    if let Some(mime) = Self::from_path(path) {
+      return mime.to_string();
+    }
+    match sniff(content) {
+      Some(mime) => mime.to_string(),
+      None => Self::OctetStream.to_string(),
+    }
+  }
+}
+
+/// Infers the mimetype from the magic bytes of the content.
+///
+/// Without the `mime-sniff` feature, nothing is inferred.
+fn sniff(content: &[u8]) -> Option<&'static str> {
+  #[cfg(feature = "mime-sniff")]
+  {
+    infer::get(content).map(|info| info.mime_type())
+  }
+  #[cfg(not(feature = "mime-sniff"))]
+  {
+    let _ = content;
+    None
+  }
+}
+
+/// Returns the extension of the last segment of a path, if any.
+pub(crate) fn path_extension(path: &str) -> Option<&str> {
+  let name = path.rsplit('/').next().unwrap_or(path);
+  name.rsplit_once('.').map(|(_, extension)| extension)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn detect() {
+    assert_eq!(MimeType::detect(b"", "/index.html"), "text/html");
+    assert_eq!(
+      MimeType::detect(b"", "/assets/app.min.js"),
+      "text/javascript"
+    );
+    assert_eq!(
+      MimeType::detect(b"<svg></svg>", "/logo.svg"),
+      "image/svg+xml"
+    );
+    #[cfg(feature = "mime-sniff")]
+    assert_eq!(MimeType::detect(b"\x89PNG\r\n\x1a\n", "/logo"), "image/png");
+    assert_eq!(
+      MimeType::detect(b"hello", "/v1.2/LICENSE"),
+      "application/octet-stream"
+    );
+  }
+
+  #[test]
+  fn extensions_sorted() {
+    assert!(EXTENSIONS.windows(2).all(|x| x[0].0 < x[1].0));
+  }
+
+  #[test]
+  fn from_path() {
+    let cases = [
+      ("/app.wasm", "application/wasm"),
+      ("/fonts/inter.woff2", "font/woff2"),
+      ("/fonts/inter.woff", "font/woff"),
+      ("/fonts/inter.ttf", "font/ttf"),
+      ("/fonts/inter.otf", "font/otf"),
+      ("/images/a.webp", "image/webp"),
+      ("/images/a.avif", "image/avif"),
+      ("/images/A.JPG", "image/jpeg"),
+      ("/manifest.webmanifest", "application/manifest+json"),
+      ("/assets/app.js.map", "application/json"),
+      ("/assets/app.mjs", "text/javascript"),
+      ("/feed.xml", "application/xml"),
+      ("/media/a.mp3", "audio/mpeg"),
+      ("/archive.7z", "application/x-7z-compressed"),
+    ];
+    for (path, mime) in cases {
+      assert_eq!(MimeType::from_path(path), Some(mime), "{path}");
+    }
+    assert_eq!(MimeType::from_path("/LICENSE"), None);
+    assert_eq!(MimeType::from_path("/v1.2/LICENSE"), None);
+    assert_eq!(MimeType::from_path("/a.unknown"), None);
+  }
+}
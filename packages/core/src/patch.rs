@@ -0,0 +1,496 @@
+use crate::checksum::{make_checksum, parse_checksum, write_checksum, CHECKSUM_LEN};
+use crate::digest::{DigestAlgorithm, EntryDigest};
+use crate::limits::{read_to_end_limited, ReaderLimits, DECODE_LIMIT, PREALLOC_LEN};
+use crate::reader::Reader;
+use crate::writer::Writer;
+use crate::{BundleDescriptor, BundleReader};
+use bincode::{config, decode_from_slice, encode_to_vec, Decode, Encode};
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Write};
+use std::ops::Range;
+
+/// Delta patch which rebuilds a bundle from a previous version of it.
+///
+/// A patch (`.wvbp`) describes the bytes of the new bundle file as a sequence of operations
+/// which either copy a range of the old bundle file, or insert new bytes. Entries whose stored
+/// data and checksum are unchanged are copied from the old bundle, so a patch only ships new
+/// or changed compressed entries plus the new header and index.
+///
+/// Applying a patch always verifies the old bundle against the source digest recorded in the
+/// patch, and the rebuilt bundle against the target digest, so the result is byte-identical to
+/// the bundle the patch was created from.
+///
+/// The patch file layout is:
+///
+/// | Magic (8) | Version (1) | Body (variable) | Checksum (4) |
+/// |-----------|-------------|-----------------|--------------|
+///
+/// - **Magic Number**: `0xf09f8c90f09fa9b9` (🌐🩹 in UTF-8)
+/// - **Version**: Patch format version (0x01)
+/// - **Body**: Binary-encoded digests and operations
+/// - **Checksum**: xxHash-32 checksum of the version and body
+///
+/// # Example
+///
+/// ```no_run
+/// use wvb::BundlePatch;
+///
+/// let old = std::fs::read("app_1.0.0.wvb").unwrap();
+/// let new = std::fs::read("app_1.1.0.wvb").unwrap();
+///
+/// let patch = BundlePatch::create(&old, &new).unwrap();
+/// println!("patch ships {} of {} bytes", patch.inserted_len(), patch.target_len());
+///
+/// let rebuilt = patch.apply(&old).unwrap();
+/// assert_eq!(rebuilt, new);
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BundlePatch {
+  source: EntryDigest,
+  target: EntryDigest,
+  target_len: u64,
+  ops: Vec<PatchOp>,
+}
+
+/// Operation of a [`BundlePatch`], producing the next bytes of the new bundle.
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+pub enum PatchOp {
+  /// Copies a range of the old bundle.
+  Copy {
+    /// Offset in the old bundle file
+    offset: u64,
+    /// Number of bytes to copy
+    len: u64,
+  },
+  /// Inserts new bytes.
+  Insert(Vec<u8>),
+}
+
+impl BundlePatch {
+  /// Length of the magic number in bytes (8 bytes for "🌐🩹")
+  pub const MAGIC_LEN: usize = 8;
+
+  /// Magic number bytes: 0xf09f8c90f09fa9b9 ("🌐🩹")
+  pub const MAGIC: [u8; BundlePatch::MAGIC_LEN] = [0xf0, 0x9f, 0x8c, 0x90, 0xf0, 0x9f, 0xa9, 0xb9];
+
+  /// Current patch format version.
+  pub const VERSION: u8 = 0x01;
+
+  const DIGEST_ALGORITHM: DigestAlgorithm = DigestAlgorithm::Sha3_256;
+
+  /// Creates a patch from the old bundle file to the new one.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if either file is not a valid bundle.
+  pub fn create(old: &[u8], new: &[u8]) -> crate::Result<Self> {
+    let old_regions = stored_regions(old)?;
+    let mut reusable = HashMap::<(u32, usize), Vec<usize>>::new();
+    for region in &old_regions {
+      let checksum = parse_checksum(&old[region.end - CHECKSUM_LEN..region.end]);
+      reusable
+        .entry((checksum, region.end - region.start))
+        .or_default()
+        .push(region.start);
+    }
+    let mut ops = PatchOps::default();
+    // bytes between stored data (header, index, signatures) are copied if they didn't move
+    let gap = |ops: &mut PatchOps, range: Range<usize>| match old.get(range.clone()) {
+      Some(bytes) if !range.is_empty() && bytes == &new[range.clone()] => {
+        ops.copy(range.start as u64, range.len() as u64)
+      }
+      _ => ops.insert(&new[range]),
+    };
+    let mut offset = 0;
+    for region in stored_regions(new)? {
+      if region.start < offset {
+        // overlapping regions are left to the inserted bytes
+        continue;
+      }
+      gap(&mut ops, offset..region.start);
+      let bytes = &new[region.start..region.end];
+      let checksum = parse_checksum(&bytes[bytes.len() - CHECKSUM_LEN..]);
+      let found = reusable
+        .get(&(checksum, bytes.len()))
+        .into_iter()
+        .flatten()
+        .find(|start| &old[**start..**start + bytes.len()] == bytes);
+      match found {
+        Some(start) => ops.copy(*start as u64, bytes.len() as u64),
+        None => ops.insert(bytes),
+      }
+      offset = region.end;
+    }
+    gap(&mut ops, offset..new.len());
+    Ok(Self {
+      source: EntryDigest::compute(Self::DIGEST_ALGORITHM, old),
+      target: EntryDigest::compute(Self::DIGEST_ALGORITHM, new),
+      target_len: new.len() as u64,
+      ops: ops.0,
+    })
+  }
+
+  /// Rebuilds the new bundle file from the old one.
+  ///
+  /// # Errors
+  ///
+  /// - [`Error::PatchSourceMismatch`](crate::Error::PatchSourceMismatch) if the old bundle is not
+  ///   the one the patch was created from
+  /// - [`Error::InvalidPatch`](crate::Error::InvalidPatch) if an operation is out of range
+  /// - [`Error::PatchTargetMismatch`](crate::Error::PatchTargetMismatch) if the rebuilt bundle
+  ///   doesn't match the target digest
+  pub fn apply(&self, old: &[u8]) -> crate::Result<Vec<u8>> {
+    if !self.source.verify(old) {
+      return Err(crate::Error::PatchSourceMismatch);
+    }
+    // the target length is not trusted, so the buffer grows as the operations are applied
+    let mut data = Vec::with_capacity(self.target_len.min(PREALLOC_LEN) as usize);
+    for op in &self.ops {
+      match op {
+        PatchOp::Copy { offset, len } => {
+          let bytes = offset
+            .checked_add(*len)
+            .and_then(|end| old.get(*offset as usize..end as usize))
+            .ok_or_else(|| crate::Error::invalid_patch("copy out of range"))?;
+          data.extend_from_slice(bytes);
+        }
+        PatchOp::Insert(bytes) => data.extend_from_slice(bytes),
+      }
+      if data.len() as u64 > self.target_len {
+        return Err(crate::Error::invalid_patch("output exceeds target length"));
+      }
+    }
+    if data.len() as u64 != self.target_len || !self.target.verify(&data) {
+      return Err(crate::Error::PatchTargetMismatch);
+    }
+    Ok(data)
+  }
+
+  /// Returns the digest of the old bundle file.
+  pub fn source_digest(&self) -> &EntryDigest {
+    &self.source
+  }
+
+  /// Returns the digest of the new bundle file.
+  pub fn target_digest(&self) -> &EntryDigest {
+    &self.target
+  }
+
+  /// Returns the length of the new bundle file.
+  pub fn target_len(&self) -> u64 {
+    self.target_len
+  }
+
+  /// Returns the operations producing the new bundle file.
+  pub fn ops(&self) -> &[PatchOp] {
+    &self.ops
+  }
+
+  /// Returns the number of bytes copied from the old bundle.
+  pub fn copied_len(&self) -> u64 {
+    self
+      .ops
+      .iter()
+      .map(|op| match op {
+        PatchOp::Copy { len, .. } => *len,
+        PatchOp::Insert(_) => 0,
+      })
+      .sum()
+  }
+
+  /// Returns the number of new bytes shipped in the patch.
+  pub fn inserted_len(&self) -> u64 {
+    self.target_len - self.copied_len().min(self.target_len)
+  }
+}
+
+/// Byte ranges of the old or new bundle file holding stored data followed by its checksum,
+/// ordered by offset.
+struct Region {
+  start: usize,
+  end: usize,
+}
+
+fn stored_regions(file: &[u8]) -> crate::Result<Vec<Region>> {
+  let descriptor: BundleDescriptor = BundleReader::new(Cursor::new(file)).read()?;
+  let data_offset = descriptor.header().data_offset();
+  let mut regions = vec![];
+  for entry in descriptor.index().try_entries()?.values() {
+    let spans = std::iter::once((entry.offset(), entry.len()))
+      .chain(entry.variants().iter().map(|x| (x.offset(), x.len())));
+    for (offset, len) in spans {
+      let start = data_offset.saturating_add(offset);
+      let end = start.saturating_add(len + CHECKSUM_LEN as u64);
+      if end <= file.len() as u64 {
+        regions.push(Region {
+          start: start as usize,
+          end: end as usize,
+        });
+      }
+    }
+  }
+  regions.sort_unstable_by_key(|x| (x.start, x.end));
+  // data shared by deduplicated entries is one region
+  regions.dedup_by_key(|x| (x.start, x.end));
+  Ok(regions)
+}
+
+/// Operations being built, merging adjacent operations of the same kind.
+#[derive(Default)]
+struct PatchOps(Vec<PatchOp>);
+
+impl PatchOps {
+  fn copy(&mut self, offset: u64, len: u64) {
+    if let Some(PatchOp::Copy {
+      offset: last_offset,
+      len: last_len,
+    }) = self.0.last_mut()
+    {
+      if *last_offset + *last_len == offset {
+        *last_len += len;
+        return;
+      }
+    }
+    self.0.push(PatchOp::Copy { offset, len });
+  }
+
+  fn insert(&mut self, bytes: &[u8]) {
+    if bytes.is_empty() {
+      return;
+    }
+    match self.0.last_mut() {
+      Some(PatchOp::Insert(last)) => last.extend_from_slice(bytes),
+      _ => self.0.push(PatchOp::Insert(bytes.to_vec())),
+    }
+  }
+}
+
+/// Body of a patch as encoded in the patch file.
+#[derive(Encode, Decode)]
+struct PatchBody {
+  source: (u8, Vec<u8>),
+  target: (u8, Vec<u8>),
+  target_len: u64,
+  ops: Vec<PatchOp>,
+}
+
+pub struct BundlePatchWriter<W: Write> {
+  w: W,
+}
+
+impl<W: Write> BundlePatchWriter<W> {
+  pub fn new(w: W) -> Self {
+    Self { w }
+  }
+}
+
+impl<W: Write> Writer<BundlePatch> for BundlePatchWriter<W> {
+  fn write(&mut self, data: &BundlePatch) -> crate::Result<usize> {
+    let body = PatchBody {
+      source: (data.source.algorithm().id(), data.source.value().to_vec()),
+      target: (data.target.algorithm().id(), data.target.value().to_vec()),
+      target_len: data.target_len,
+      ops: data.ops.clone(),
+    };
+    let config = config::standard().with_big_endian();
+    let mut bytes = vec![BundlePatch::VERSION];
+    bytes.extend(
+      encode_to_vec(body, config).map_err(|e| crate::Error::Encode {
+        error: e,
+        message: "fail to encode patch".to_string(),
+      })?,
+    );
+    let checksum = make_checksum(0, &bytes);
+    self.w.write_all(&BundlePatch::MAGIC)?;
+    self.w.write_all(&bytes)?;
+    self.w.write_all(&write_checksum(checksum))?;
+    Ok(BundlePatch::MAGIC_LEN + bytes.len() + CHECKSUM_LEN)
+  }
+}
+
+pub struct BundlePatchReader<R: Read> {
+  r: R,
+  limits: ReaderLimits,
+}
+
+impl<R: Read> BundlePatchReader<R> {
+  pub fn new(r: R) -> Self {
+    Self {
+      r,
+      limits: ReaderLimits::default(),
+    }
+  }
+
+  /// Sets the limits applied when reading the patch.
+  ///
+  /// The patch file may not exceed [`ReaderLimits::max_total_size`].
+  pub fn limits(mut self, limits: ReaderLimits) -> Self {
+    self.limits = limits;
+    self
+  }
+}
+
+impl<R: Read> Reader<BundlePatch> for BundlePatchReader<R> {
+  fn read(&mut self) -> crate::Result<BundlePatch> {
+    let buf = read_to_end_limited(&mut self.r, self.limits.max_total_size)?;
+    if buf.len() < BundlePatch::MAGIC_LEN + 1 + CHECKSUM_LEN
+      || buf[..BundlePatch::MAGIC_LEN] != BundlePatch::MAGIC
+    {
+      return Err(crate::Error::InvalidMagicNum);
+    }
+    let (bytes, checksum) =
+      buf[BundlePatch::MAGIC_LEN..].split_at(buf.len() - BundlePatch::MAGIC_LEN - CHECKSUM_LEN);
+    if make_checksum(0, bytes) != parse_checksum(checksum) {
+      return Err(crate::Error::InvalidPatchChecksum);
+    }
+    if bytes[0] != BundlePatch::VERSION {
+      return Err(crate::Error::InvalidVersion);
+    }
+    let config = config::standard()
+      .with_big_endian()
+      .with_limit::<DECODE_LIMIT>();
+    let (body, _): (PatchBody, _) =
+      decode_from_slice(&bytes[1..], config).map_err(|e| crate::Error::Decode {
+        error: e,
+        message: "fail to decode patch".to_string(),
+      })?;
+    let digest = |(id, value): (u8, Vec<u8>)| -> crate::Result<EntryDigest> {
+      Ok(EntryDigest::new(DigestAlgorithm::from_id(id)?, value))
+    };
+    Ok(BundlePatch {
+      source: digest(body.source)?,
+      target: digest(body.target)?,
+      target_len: body.target_len,
+      ops: body.ops,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{Bundle, BundleBuilder, BundleBuilderOptions, BundleEntry, BundleWriter, Version};
+
+  fn build(version: Version, entries: &[(&str, String)]) -> Vec<u8> {
+    #[cfg_attr(not(feature = "precompress"), allow(unused_mut))]
+    let mut options = BundleBuilderOptions::new();
+    if version == Version::V2 {
+      #[cfg(feature = "precompress")]
+      options.precompress(crate::ContentEncoding::Gzip);
+    }
+    let mut builder = BundleBuilder::new_with_options(options);
+    builder.set_version(version);
+    for (path, data) in entries {
+      builder.insert_entry(*path, BundleEntry::new(data.as_bytes(), "text/plain", None));
+    }
+    let bundle = builder.build().unwrap();
+    let mut buf = vec![];
+    BundleWriter::new(&mut buf).write(&bundle).unwrap();
+    buf
+  }
+
+  fn entries(changed: &str) -> Vec<(&'static str, String)> {
+    vec![
+      ("/a.txt", "a".repeat(4000)),
+      ("/b.txt", changed.repeat(1000)),
+      (
+        "/c.txt",
+        "the quick brown fox jumps over the lazy dog ".repeat(200),
+      ),
+    ]
+  }
+
+  #[cfg(feature = "precompress")]
+  #[test]
+  fn create_and_apply() {
+    for version in [Version::V1, Version::V2] {
+      let old = build(version, &entries("old"));
+      let new = build(version, &entries("new!"));
+      let patch = BundlePatch::create(&old, &new).unwrap();
+      assert_eq!(patch.apply(&old).unwrap(), new, "{version}");
+      assert!(patch.copied_len() > 0, "{version}");
+      assert!(patch.inserted_len() < new.len() as u64 / 2, "{version}");
+
+      let same = BundlePatch::create(&old, &old).unwrap();
+      assert_eq!(same.ops().len(), 1);
+      assert_eq!(same.apply(&old).unwrap(), old);
+    }
+  }
+
+  #[test]
+  fn apply_to_other_bundle() {
+    let old = build(Version::V2, &entries("old"));
+    let new = build(Version::V2, &entries("new!"));
+    let patch = BundlePatch::create(&old, &new).unwrap();
+    assert!(matches!(
+      patch.apply(&new),
+      Err(crate::Error::PatchSourceMismatch)
+    ));
+    let mut tampered = patch.clone();
+    if let Some(PatchOp::Insert(bytes)) = tampered
+      .ops
+      .iter_mut()
+      .find(|x| matches!(x, PatchOp::Insert(_)))
+    {
+      bytes[0] ^= 0xff;
+    }
+    assert!(matches!(
+      tampered.apply(&old),
+      Err(crate::Error::PatchTargetMismatch)
+    ));
+    // a crafted target length is not allocated up front
+    let mut crafted = patch.clone();
+    crafted.target_len = u64::MAX;
+    assert!(matches!(
+      crafted.apply(&old),
+      Err(crate::Error::PatchTargetMismatch)
+    ));
+  }
+
+  #[test]
+  fn read_and_write() {
+    let old = build(Version::V2, &entries("old"));
+    let new = build(Version::V2, &entries("new!"));
+    let patch = BundlePatch::create(&old, &new).unwrap();
+    let mut buf = vec![];
+    let size = BundlePatchWriter::new(&mut buf).write(&patch).unwrap();
+    assert_eq!(size, buf.len());
+    assert_eq!(&buf[..BundlePatch::MAGIC_LEN], BundlePatch::MAGIC);
+    let read = BundlePatchReader::new(Cursor::new(&buf)).read().unwrap();
+    assert_eq!(read, patch);
+    let bundle: Bundle = BundleReader::new(Cursor::new(read.apply(&old).unwrap()))
+      .read()
+      .unwrap();
+    assert_eq!(
+      bundle.get_data("/b.txt").unwrap().unwrap(),
+      "new!".repeat(1000).as_bytes()
+    );
+
+    buf[BundlePatch::MAGIC_LEN + 2] ^= 0xff;
+    assert!(matches!(
+      BundlePatchReader::new(Cursor::new(&buf)).read(),
+      Err(crate::Error::InvalidPatchChecksum)
+    ));
+    assert!(matches!(
+      BundlePatchReader::new(Cursor::new(&old)).read(),
+      Err(crate::Error::InvalidMagicNum)
+    ));
+    let limits = ReaderLimits::new().max_total_size(16);
+    assert!(matches!(
+      BundlePatchReader::new(Cursor::new(&buf))
+        .limits(limits)
+        .read(),
+      Err(crate::Error::TotalSizeLimitExceeded { .. })
+    ));
+  }
+
+  #[test]
+  fn fixtures() {
+    let read =
+      |name: &str| std::fs::read(crate::testing::Fixtures::bundles().get_path(name)).unwrap();
+    let old = read("remote/app/app_1.0.0.wvb");
+    let new = read("remote/app/app_1.1.0.wvb");
+    let patch = BundlePatch::create(&old, &new).unwrap();
+    assert_eq!(patch.apply(&old).unwrap(), new);
+  }
+}
@@ -0,0 +1,64 @@
+use glob::{MatchOptions, Pattern};
+
+/// A glob pattern matched against `/`-separated paths.
+///
+/// `*` doesn't match across directories while `**` does. A pattern without `/` also matches the
+/// last segment of a path, so `*.map` matches `assets/app.js.map`. Leading `/` of patterns and
+/// paths are ignored.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PathPattern {
+  pattern: Pattern,
+  match_name: bool,
+}
+
+impl PathPattern {
+  const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+  };
+
+  pub(crate) fn new(pattern: &str) -> crate::Result<Self> {
+    let pattern = pattern.trim_start_matches('/');
+    Ok(Self {
+      pattern: Pattern::new(pattern)?,
+      match_name: !pattern.contains('/'),
+    })
+  }
+
+  #[cfg(feature = "header-rules")]
+  pub(crate) fn as_str(&self) -> &str {
+    self.pattern.as_str()
+  }
+
+  pub(crate) fn matches(&self, path: &str) -> bool {
+    let path = path.trim_start_matches('/');
+    if self.pattern.matches_with(path, Self::MATCH_OPTIONS) {
+      return true;
+    }
+    let name = path.rsplit('/').next().unwrap_or(path);
+    self.match_name && self.pattern.matches_with(name, Self::MATCH_OPTIONS)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn matches() {
+    let pattern = PathPattern::new("/assets/**").unwrap();
+    assert!(pattern.matches("/assets/app.js"));
+    assert!(pattern.matches("assets/js/app.js"));
+    assert!(!pattern.matches("/index.html"));
+
+    let pattern = PathPattern::new("*.html").unwrap();
+    assert!(pattern.matches("/index.html"));
+    assert!(pattern.matches("/docs/a/index.html"));
+    assert!(!pattern.matches("/index.htm"));
+
+    let pattern = PathPattern::new("/dist/*.txt").unwrap();
+    assert!(pattern.matches("dist/a.txt"));
+    assert!(!pattern.matches("dist/sub/a.txt"));
+  }
+}
@@ -1,6 +1,8 @@
 use crate::protocol::uri::{DefaultUriResolver, UriResolver};
-use crate::source::BundleSource;
+use crate::source::{read_mapped, BundleSource};
+use crate::{Codec, ContentEncoding, EntryVariant};
 use async_trait::async_trait;
+use bytes::Bytes;
 use http::{header, HeaderValue, Method, Request, Response, StatusCode};
 use http_range::HttpRange;
 use std::sync::Arc;
@@ -12,7 +14,9 @@ use tokio::io::AsyncWriteExt;
 /// `.wvb` bundle files stored in a `BundleSource`. It supports:
 ///
 /// - GET and HEAD HTTP methods
-/// - HTTP Range requests for streaming large files (video, audio)
+/// - HTTP Range requests for streaming large files (video, audio), which only decompress the
+///   blocks a range touches for entries compressed in blocks
+/// - Precompressed brotli/gzip variants negotiated with `Accept-Encoding`
 /// - Content-Type and custom HTTP headers from bundle index
 /// - Custom URI resolution for flexible URL-to-bundle mapping
 ///
@@ -77,6 +81,14 @@ use tokio::io::AsyncWriteExt;
 /// assert_eq!(response.status(), 206); // Partial Content
 /// # };
 /// ```
+///
+/// # Content Encoding
+///
+/// If an entry has precompressed variants (see
+/// [`BundleBuilderOptions::precompress`](crate::BundleBuilderOptions::precompress)), the variant
+/// preferred by the request's `Accept-Encoding` header is sent as-is with `Content-Encoding`,
+/// and responses for the entry carry `Vary: Accept-Encoding`. Range requests are always served
+/// from the identity (decompressed) data.
 pub struct BundleProtocol {
   source: Arc<BundleSource>,
   uri_resolver: Box<dyn UriResolver + 'static>,
@@ -125,14 +137,17 @@ impl super::Protocol for BundleProtocol {
     if !(request.method() == Method::GET || request.method() == Method::HEAD) {
       let response = Response::builder()
         .status(StatusCode::METHOD_NOT_ALLOWED)
-        .body(Vec::new().into())?;
+        .body(Bytes::new())?;
       return Ok(response);
     }
 
     let mut resp = Response::builder();
-    let descriptor = self.source.load_descriptor(&name).await?;
+    let bundle = self.source.load_mapped(&name).await?;
+    let descriptor = bundle.descriptor();
 
-    if let Some(entry) = descriptor.index().get_entry(&path) {
+    if let Some(entry) = descriptor.index().try_get_entry(&path)? {
+      // compressed entries are decompressed on a blocking thread
+      let compressed = entry.codec() != Codec::None;
       let resp_headers = resp.headers_mut().unwrap();
       resp_headers.clone_from(entry.headers());
       resp_headers.insert(
@@ -143,6 +158,9 @@ impl super::Protocol for BundleProtocol {
         header::CONTENT_LENGTH,
         HeaderValue::from(entry.content_length()),
       );
+      if !entry.variants().is_empty() {
+        resp_headers.append(header::VARY, HeaderValue::from_static("accept-encoding"));
+      }
 
       if let Some(range_header) = request
         .headers()
@@ -160,7 +178,7 @@ impl super::Protocol for BundleProtocol {
           Response::builder()
             .status(StatusCode::RANGE_NOT_SATISFIABLE)
             .header(header::CONTENT_RANGE, format!("bytes */{len}"))
-            .body(Vec::new().into())
+            .body(Bytes::new())
             .map_err(Into::into)
         };
 
@@ -199,15 +217,19 @@ impl super::Protocol for BundleProtocol {
           resp = resp.status(StatusCode::PARTIAL_CONTENT);
 
           if request.method() == Method::HEAD {
-            resp.body(Vec::new().into())
+            resp.body(Bytes::new())
           } else {
-            let reader = self.source.reader(&name).await?;
-            let buf = if let Some(data) = descriptor.async_get_data(reader, &path).await? {
-              extract_buf(&data, start, end)
+            let entry_path = path.clone();
+            let data = read_mapped(&bundle, compressed, move |b| {
+              b.get_bytes_range(&entry_path, start..end + 1)
+            })
+            .await?;
+            let buf = if let Some(data) = data {
+              data
             } else {
               return not_found();
             };
-            resp.body(buf.into())
+            resp.body(buf)
           }
         } else {
           let ranges = ranges
@@ -237,12 +259,20 @@ impl super::Protocol for BundleProtocol {
           resp = resp.status(StatusCode::PARTIAL_CONTENT);
 
           if request.method() == Method::HEAD {
-            resp.body(Vec::new().into())
+            resp.body(Bytes::new())
           } else {
-            let reader = self.source.reader(&name).await?;
-            let buf = if let Some(data) = descriptor.async_get_data(reader, &path).await? {
+            let entry_path = path.clone();
+            let parts = ranges.clone();
+            let range_bufs = read_mapped(&bundle, compressed, move |b| {
+              parts
+                .into_iter()
+                .map(|(start, end)| b.get_bytes_range(&entry_path, start..end + 1))
+                .collect::<crate::Result<Vec<_>>>()
+            })
+            .await?;
+            let buf = if bundle.descriptor().index().contains_path(&path) {
               let mut buf = Vec::new();
-              for (start, end) in ranges {
+              for ((start, end), range_buf) in ranges.into_iter().zip(range_bufs) {
                 buf.write_all(boundary_sep.as_bytes()).await?;
                 buf
                   .write_all(
@@ -256,8 +286,9 @@ impl super::Protocol for BundleProtocol {
                   .await?;
                 buf.write_all("\r\n".as_bytes()).await?;
 
-                let range_buf = extract_buf(&data, start, end);
-                buf.extend_from_slice(&range_buf);
+                if let Some(range_buf) = range_buf {
+                  buf.extend_from_slice(&range_buf);
+                }
               }
               buf.write_all(boundary_sep.as_bytes()).await?;
               buf
@@ -270,19 +301,43 @@ impl super::Protocol for BundleProtocol {
         return Ok(response);
       }
 
+      let encoding = request
+        .headers()
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| negotiate_encoding(x, entry.variants()));
+      if let Some(variant) = encoding.and_then(|x| entry.variant(x)) {
+        let resp_headers = resp.headers_mut().unwrap();
+        resp_headers.insert(
+          header::CONTENT_ENCODING,
+          HeaderValue::from_static(variant.encoding().as_str()),
+        );
+        resp_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(variant.len()));
+        if request.method() == Method::HEAD {
+          let response = resp.body(Bytes::new())?;
+          return Ok(response);
+        }
+        let data = if let Some(data) = bundle.get_variant_bytes(&path, variant.encoding())? {
+          data
+        } else {
+          return not_found();
+        };
+        let response = resp.body(data)?;
+        return Ok(response);
+      }
+
       if request.method() == Method::HEAD {
-        let response = resp.body(Vec::new().into())?;
+        let response = resp.body(Bytes::new())?;
         return Ok(response);
       }
 
-      let reader = self.source.reader(&name).await?;
-      let data = if let Some(data) = descriptor.async_get_data(reader, &path).await? {
+      let data = if let Some(data) = self.source.load_data(&name, &path).await? {
         data
       } else {
         return not_found();
       };
 
-      let response = resp.body(data.into())?;
+      let response = resp.body(data)?;
       Ok(response)
     } else {
       not_found()
@@ -293,10 +348,46 @@ impl super::Protocol for BundleProtocol {
 fn not_found() -> crate::Result<super::ProtocolResponse> {
   let resp = Response::builder()
     .status(StatusCode::NOT_FOUND)
-    .body(Vec::new().into())?;
+    .body(Bytes::new())?;
   Ok(resp)
 }
 
+/// Picks the variant encoding preferred by an `Accept-Encoding` header value.
+///
+/// Encodings with a higher quality value win, and ties are broken by the order of variants in
+/// the index (brotli before gzip). Returns `None` if the identity data should be sent.
+fn negotiate_encoding(accept_encoding: &str, variants: &[EntryVariant]) -> Option<ContentEncoding> {
+  let mut qualities = Vec::new();
+  let mut wildcard = None;
+  for item in accept_encoding.split(',') {
+    let mut parts = item.split(';');
+    let token = parts.next().unwrap_or_default().trim();
+    let quality = parts
+      .filter_map(|x| x.trim().strip_prefix("q="))
+      .find_map(|x| x.trim().parse::<f32>().ok())
+      .unwrap_or(1.0);
+    if token == "*" {
+      wildcard = Some(quality);
+    } else if let Some(encoding) = ContentEncoding::parse(token) {
+      qualities.push((encoding, quality));
+    }
+  }
+  let mut preferred: Option<(ContentEncoding, f32)> = None;
+  for variant in variants {
+    let encoding = variant.encoding();
+    let quality = qualities
+      .iter()
+      .find(|(x, _)| *x == encoding)
+      .map(|(_, q)| *q)
+      .or(wildcard)
+      .unwrap_or(0.0);
+    if quality > 0.0 && preferred.is_none_or(|(_, q)| quality > q) {
+      preferred = Some((encoding, quality));
+    }
+  }
+  preferred.map(|(encoding, _)| encoding)
+}
+
 fn random_boundary() -> String {
   let mut values = [0_u8; 30];
   getrandom::fill(&mut values).expect("failed to get random bytes");
@@ -309,26 +400,14 @@ fn random_boundary() -> String {
     })
 }
 
-fn extract_buf(data: &[u8], start: u64, end: u64) -> Vec<u8> {
-  let data_len = data.len() as u64;
-  let start_i = start.min(data_len);
-  let end_i = end.min(data_len.saturating_sub(1));
-
-  let capacity = end + 1 - start;
-  let mut buf = Vec::with_capacity(capacity as usize);
-  if start_i <= end_i {
-    let s = start as usize;
-    let e = (end + 1) as usize;
-    buf.extend_from_slice(&data[s..e]);
-  }
-  buf
-}
-
 #[cfg(test)]
 mod tests {
   use super::*;
   use crate::protocol::Protocol;
-  use crate::testing::Fixtures;
+  use crate::testing::{Fixtures, TempDir};
+  use crate::{
+    Bundle, BundleBuilder, BundleBuilderOptions, BundleEntry, BundleWriter, Codec, Version, Writer,
+  };
 
   #[tokio::test]
   async fn smoke() {
@@ -670,4 +749,143 @@ mod tests {
       .unwrap();
     assert_eq!(resp.status(), 405);
   }
+
+  /// Writes the bundle as version `1.0.0` of `app` in a builtin source.
+  fn write_source(dir: &TempDir, bundle: &Bundle) -> Arc<BundleSource> {
+    std::fs::create_dir_all(dir.path().join("app")).unwrap();
+    let mut file = std::fs::File::create(dir.path().join("app/app_1.0.0.wvb")).unwrap();
+    BundleWriter::new(&mut file).write(bundle).unwrap();
+    std::fs::write(
+      dir.path().join("manifest.json"),
+      r#"{"manifestVersion":1,"entries":{"app":{"versions":{"1.0.0":{}},"currentVersion":"1.0.0"}}}"#,
+    )
+    .unwrap();
+    Arc::new(BundleSource::builder().builtin_dir(dir.path()).build())
+  }
+
+  #[tokio::test]
+  async fn partial_request_with_blocks() {
+    let video = (0..10_000u32).map(|x| (x % 251) as u8).collect::<Vec<_>>();
+    let mut options = BundleBuilderOptions::new();
+    options.codec(Codec::Lz4).block_size(1024);
+    let mut builder = BundleBuilder::new_with_options(options);
+    builder.set_version(Version::V2);
+    builder.insert_entry("/video.mp4", BundleEntry::new(&video, "video/mp4", None));
+    let dir = TempDir::new("protocol");
+    let protocol = BundleProtocol::new(write_source(&dir, &builder.build().unwrap()));
+
+    let resp = protocol
+      .handle(
+        Request::builder()
+          .uri("https://app.wvb/video.mp4")
+          .method("GET")
+          .header(header::RANGE, "bytes=2000-4999")
+          .body(vec![])
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), 206);
+    assert_eq!(
+      resp.headers().get(header::CONTENT_RANGE).unwrap(),
+      "bytes 2000-4999/10000"
+    );
+    assert_eq!(resp.body().as_ref(), &video[2000..5000]);
+
+    let resp = protocol
+      .handle(
+        Request::builder()
+          .uri("https://app.wvb/video.mp4")
+          .method("GET")
+          .header(header::RANGE, "bytes=0-9,9990-")
+          .body(vec![])
+          .unwrap(),
+      )
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), 206);
+    let body = resp.body().as_ref();
+    assert!(body.windows(10).any(|x| x == &video[..10]));
+    assert!(body.windows(10).any(|x| x == &video[9990..]));
+  }
+
+  #[test]
+  fn negotiate() {
+    let variants = [
+      EntryVariant::new(ContentEncoding::Brotli, 0, 1),
+      EntryVariant::new(ContentEncoding::Gzip, 5, 1),
+    ];
+    let negotiate = |x: &str| negotiate_encoding(x, &variants);
+    assert_eq!(
+      negotiate("gzip, deflate, br"),
+      Some(ContentEncoding::Brotli)
+    );
+    assert_eq!(negotiate("gzip, br;q=0.5"), Some(ContentEncoding::Gzip));
+    assert_eq!(negotiate("br;q=0, gzip"), Some(ContentEncoding::Gzip));
+    assert_eq!(negotiate("*"), Some(ContentEncoding::Brotli));
+    assert_eq!(negotiate("br;q=0, *;q=0.1"), Some(ContentEncoding::Gzip));
+    assert_eq!(negotiate("identity"), None);
+    assert_eq!(negotiate(""), None);
+    assert_eq!(negotiate_encoding("br", &variants[1..]), None);
+  }
+
+  #[cfg(feature = "precompress")]
+  #[tokio::test]
+  async fn content_encoding() {
+    let js = "console.log('Hello World');\n".repeat(32);
+    let mut options = BundleBuilderOptions::new();
+    options
+      .precompress(ContentEncoding::Brotli)
+      .precompress(ContentEncoding::Gzip);
+    let mut builder = BundleBuilder::new_with_options(options);
+    builder.set_version(Version::V2);
+    builder.insert_entry(
+      "/index.js",
+      BundleEntry::new(js.as_bytes(), "text/javascript", None),
+    );
+    let bundle = builder.build().unwrap();
+    let dir = TempDir::new("protocol");
+    let protocol = BundleProtocol::new(write_source(&dir, &bundle));
+    let request = |accept_encoding: Option<&str>, method: &str| {
+      let mut builder = Request::builder()
+        .uri("https://app.wvb/index.js")
+        .method(method);
+      if let Some(x) = accept_encoding {
+        builder = builder.header(header::ACCEPT_ENCODING, x);
+      }
+      builder.body(vec![]).unwrap()
+    };
+
+    let resp = protocol
+      .handle(request(Some("gzip, br"), "GET"))
+      .await
+      .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get(header::CONTENT_ENCODING).unwrap(), "br");
+    assert_eq!(resp.headers().get(header::VARY).unwrap(), "accept-encoding");
+    let brotli = bundle
+      .get_variant("/index.js", ContentEncoding::Brotli)
+      .unwrap()
+      .unwrap();
+    assert_eq!(resp.body().as_ref(), brotli);
+    assert_eq!(
+      resp.headers().get(header::CONTENT_LENGTH).unwrap(),
+      &brotli.len().to_string()
+    );
+
+    let resp = protocol
+      .handle(request(Some("gzip"), "HEAD"))
+      .await
+      .unwrap();
+    assert_eq!(
+      resp.headers().get(header::CONTENT_ENCODING).unwrap(),
+      "gzip"
+    );
+    assert!(resp.body().is_empty());
+
+    let resp = protocol.handle(request(None, "GET")).await.unwrap();
+    assert!(resp.headers().get(header::CONTENT_ENCODING).is_none());
+    assert_eq!(resp.headers().get(header::VARY).unwrap(), "accept-encoding");
+    assert_eq!(resp.body().as_ref(), js.as_bytes());
+  }
 }
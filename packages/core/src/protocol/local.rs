@@ -2,7 +2,6 @@ use async_trait::async_trait;
 use dashmap::DashMap;
 use http;
 use http::Uri;
-use std::borrow::Cow;
 use std::collections::HashMap;
 
 /// Trait for resolving custom URIs to localhost URLs.
@@ -199,7 +198,7 @@ impl super::Protocol for LocalProtocol {
   async fn handle(
     &self,
     request: http::Request<Vec<u8>>,
-  ) -> crate::Result<http::Response<Cow<'static, [u8]>>> {
+  ) -> crate::Result<super::ProtocolResponse> {
     let url = self
       .uri_resolver
       .get_localhost_uri(request.uri())
@@ -234,7 +233,7 @@ impl super::Protocol for LocalProtocol {
     }
     let resp = builder
       .status(response.status)
-      .body(response.body.to_vec().into())?;
+      .body(response.body.clone())?;
     Ok(resp)
   }
 }
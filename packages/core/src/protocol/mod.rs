@@ -4,9 +4,7 @@ mod local;
 mod uri;
 
 use async_trait::async_trait;
-use std::borrow::Cow;
-
-pub type ProtocolResponse = http::Response<Cow<'static, [u8]>>;
+pub type ProtocolResponse = http::Response<bytes::Bytes>;
 
 #[async_trait]
 pub trait Protocol: Send + Sync {
@@ -14,6 +14,12 @@
 //! - `HEAD /bundles/{name}` - Get bundle metadata without downloading
 //! - `GET /bundles/{name}` - Download the current version of a bundle
 //! - `GET /bundles/{name}/{version}` - Download a specific version
+//! - `GET /bundles/{name}/patch?from={version}` - Download a patch (`.wvbp`) from an installed
+//!   version to the current version
+//! - `GET /bundles/{name}/{version}/patch?from={version}` - Download a patch from an installed
+//!   version to a specific version
+//!
+//! Patch responses carry the same headers as bundle downloads, describing the patched bundle.
 //!
 //! ## Example
 //!
@@ -1,5 +1,7 @@
 use crate::remote::HttpConfig;
 use crate::{Bundle, BundleReader, Reader};
+#[cfg(feature = "patch")]
+use crate::{BundlePatch, BundlePatchReader};
 use futures_util::StreamExt;
 use http::{header, uri::Uri, StatusCode};
 use serde::{Deserialize, Serialize};
@@ -166,6 +168,49 @@ impl Remote {
       .await
   }
 
+  /// GET /bundles/:name/patch?from=:version
+  ///
+  /// Downloads a patch from the given installed version to the current version of the bundle.
+  /// Returns [`Error::RemoteBundleNotFound`](crate::Error::RemoteBundleNotFound) if the server
+  /// has no such patch.
+  #[cfg(feature = "patch")]
+  pub async fn download_patch(
+    &self,
+    bundle_name: &str,
+    from_version: &str,
+    channel: Option<&String>,
+  ) -> crate::Result<(RemoteBundleInfo, BundlePatch)> {
+    let mut query = vec![("from", from_version)];
+    if let Some(channel) = channel {
+      query.push(("channel", channel.as_str()));
+    }
+    let (info, data) = self
+      .download_bytes(format!("/bundles/{bundle_name}/patch"), Some(query))
+      .await?;
+    let patch = BundlePatchReader::new(Cursor::new(&data)).read()?;
+    Ok((info, patch))
+  }
+
+  /// GET /bundles/:name/:version/patch?from=:version
+  ///
+  /// Downloads a patch from the given installed version to a specific version of the bundle.
+  #[cfg(feature = "patch")]
+  pub async fn download_version_patch(
+    &self,
+    bundle_name: &str,
+    version: &str,
+    from_version: &str,
+  ) -> crate::Result<(RemoteBundleInfo, BundlePatch)> {
+    let (info, data) = self
+      .download_bytes(
+        format!("/bundles/{bundle_name}/{version}/patch"),
+        Some(vec![("from", from_version)]),
+      )
+      .await?;
+    let patch = BundlePatchReader::new(Cursor::new(&data)).read()?;
+    Ok((info, patch))
+  }
+
   fn endpoint(
     &self,
     path: impl Into<String>,
@@ -239,7 +284,20 @@ impl Remote {
     path: String,
     channel: Option<&String>,
   ) -> crate::Result<(RemoteBundleInfo, Bundle, Vec<u8>)> {
-    let endpoint = self.endpoint(path, channel.map(|x| vec![("channel", x)]))?;
+    let (info, data) = self
+      .download_bytes(path, channel.map(|x| vec![("channel", x.as_str())]))
+      .await?;
+    let mut reader = Cursor::new(&data);
+    let bundle = Reader::<Bundle>::read(&mut BundleReader::new(&mut reader))?;
+    Ok((info, bundle, data))
+  }
+
+  async fn download_bytes(
+    &self,
+    path: String,
+    query: Option<Vec<(&str, &str)>>,
+  ) -> crate::Result<(RemoteBundleInfo, Vec<u8>)> {
+    let endpoint = self.endpoint(path, query)?;
     let resp = self.client.get(&endpoint).send().await?;
     if !resp.status().is_success() {
       return Err(self.parse_err(resp).await);
@@ -257,9 +315,7 @@ impl Remote {
         on_download(downloaded_bytes, total_size, endpoint.to_owned());
       }
     }
-    let mut reader = Cursor::new(&data);
-    let bundle = Reader::<Bundle>::read(&mut BundleReader::new(&mut reader))?;
-    Ok((info, bundle, data))
+    Ok((info, data))
   }
 }
 
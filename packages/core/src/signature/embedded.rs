@@ -0,0 +1,268 @@
+use crate::signature::SignatureVerifier;
+use crate::{Bundle, BundleDescriptor, IndexEntry};
+use bytes::Bytes;
+
+impl SignatureVerifier {
+  /// Returns the algorithm recorded in embedded signatures made for this verifier.
+  ///
+  /// Returns `None` for custom verifiers, which are tried against every embedded signature.
+  pub fn algorithm(&self) -> Option<&'static str> {
+    match self {
+      #[cfg(feature = "signature-ecdsa_secp256r1")]
+      Self::EcdsaSecp256r1(_) => Some("ecdsa-secp256r1"),
+      #[cfg(feature = "signature-ecdsa_secp384r1")]
+      Self::EcdsaSecp384r1(_) => Some("ecdsa-secp384r1"),
+      #[cfg(feature = "signature-edd25519")]
+      Self::Ed25519(_) => Some("ed25519"),
+      #[cfg(feature = "signature-rsa_pkcs1_v1_5")]
+      Self::RsaPkcs1V15(_) => Some("rsa-pkcs1-v1_5"),
+      #[cfg(feature = "signature-rsa_pss")]
+      Self::RsaPss(_) => Some("rsa-pss"),
+      Self::Custom(_) => None,
+    }
+  }
+}
+
+impl Bundle {
+  /// Verifies the signatures embedded in the bundle, and the data of every file.
+  ///
+  /// Succeeds if any embedded signature of the verifier's algorithm (and of the given key id,
+  /// if any) is valid for [`BundleDescriptor::signing_message`], and the data of every file and
+  /// of its precompressed variants matches its digest in the signed index.
+  ///
+  /// # Errors
+  ///
+  /// - [`Error::SignatureNotExists`](crate::Error::SignatureNotExists) if no embedded signature
+  ///   matches the algorithm and key id
+  /// - [`Error::SignatureVerifyFailed`](crate::Error::SignatureVerifyFailed) if none of the
+  ///   matching signatures is valid
+  /// - [`Error::EntryDigestNotExists`](crate::Error::EntryDigestNotExists) if a file has no
+  ///   digest, so its data is not covered by the signature
+  /// - [`Error::DigestMismatch`](crate::Error::DigestMismatch) if the data of a file does not
+  ///   match its digest
+  pub async fn verify_signature(
+    &self,
+    verifier: &SignatureVerifier,
+    key_id: Option<&str>,
+  ) -> crate::Result<()> {
+    verify_signatures(self, verifier, key_id).await?;
+    let index = self.descriptor().index();
+    for (path, entry) in index.try_entries()? {
+      require_digest(path, entry)?;
+      let mut reader = self.data_reader().verify_digest(true);
+      reader.read_entry_data(entry)?;
+      for variant in entry.variants() {
+        reader.read_variant_data(variant)?;
+      }
+    }
+    Ok(())
+  }
+}
+
+impl BundleDescriptor {
+  /// Verifies the signatures embedded in the bundle without reading file data.
+  ///
+  /// See [`Bundle::verify_signature`]. The signature covers file data through the digests in
+  /// the index, so every file must have a digest, and file data must be verified against it
+  /// when read (see [`BundleDescriptor::set_verify_digest`]). Custom verifiers receive a bundle
+  /// with this descriptor and an empty data section.
+  pub async fn verify_signature(
+    &self,
+    verifier: &SignatureVerifier,
+    key_id: Option<&str>,
+  ) -> crate::Result<()> {
+    let bundle = Bundle {
+      descriptor: self.clone(),
+      data: Bytes::new(),
+    };
+    verify_signatures(&bundle, verifier, key_id).await?;
+    for (path, entry) in self.index().try_entries()? {
+      require_digest(path, entry)?;
+    }
+    Ok(())
+  }
+}
+
+/// Checks that an entry and its precompressed variants have digests.
+fn require_digest(path: &str, entry: &IndexEntry) -> crate::Result<()> {
+  if entry.digest().is_none() || entry.variants().iter().any(|x| x.digest().is_none()) {
+    return Err(crate::Error::EntryDigestNotExists(path.to_string()));
+  }
+  Ok(())
+}
+
+/// Verifies the embedded signatures over the header and index of a bundle.
+async fn verify_signatures(
+  bundle: &Bundle,
+  verifier: &SignatureVerifier,
+  key_id: Option<&str>,
+) -> crate::Result<()> {
+  let descriptor = bundle.descriptor();
+  let mut signatures = descriptor
+    .signatures()
+    .iter()
+    .filter(|s| verifier.algorithm().is_none_or(|alg| s.algorithm() == alg))
+    .filter(|s| key_id.is_none_or(|id| s.key_id() == Some(id)))
+    .peekable();
+  if signatures.peek().is_none() {
+    return Err(crate::Error::SignatureNotExists);
+  }
+  let message = descriptor.signing_message()?;
+  for signature in signatures {
+    if verifier.verify(bundle, &message, signature.value()).await? {
+      return Ok(());
+    }
+  }
+  Err(crate::Error::SignatureVerifyFailed)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    BundleBuilder, BundleBuilderOptions, BundleEntry, BundleReader, BundleSignature, BundleWriter,
+    Codec, DigestAlgorithm, Reader, Version, Writer,
+  };
+  use std::io::Cursor;
+  use std::sync::Arc;
+
+  fn sign(message: &[u8]) -> String {
+    DigestAlgorithm::Sha3_256
+      .digest(message)
+      .iter()
+      .map(|x| format!("{x:02x}"))
+      .collect()
+  }
+
+  fn verifier() -> SignatureVerifier {
+    SignatureVerifier::Custom(Arc::new(|_bundle, message, signature| {
+      let verified = sign(message) == signature;
+      Box::pin(async move { Ok(verified) })
+    }))
+  }
+
+  fn signed_bundle() -> Vec<u8> {
+    let mut options = BundleBuilderOptions::new();
+    options.digest(DigestAlgorithm::Sha3_256);
+    let mut builder = BundleBuilder::new_with_options(options);
+    builder.set_version(Version::V2);
+    builder.insert_entry(
+      "/index.html",
+      BundleEntry::new(b"<html></html>", "text/html", None),
+    );
+    let mut bundle = builder.build().unwrap();
+    let signature = sign(&bundle.descriptor().signing_message().unwrap());
+    bundle.add_signature(BundleSignature::new("custom", Some("key-1"), signature));
+    let mut buf = vec![];
+    BundleWriter::new(&mut buf).write(&bundle).unwrap();
+    buf
+  }
+
+  #[tokio::test]
+  async fn verify() {
+    let buf = signed_bundle();
+    let bundle: Bundle = BundleReader::new(Cursor::new(&buf)).read().unwrap();
+    assert_eq!(bundle.descriptor().signatures().len(), 1);
+    bundle.verify_signature(&verifier(), None).await.unwrap();
+    bundle
+      .verify_signature(&verifier(), Some("key-1"))
+      .await
+      .unwrap();
+    assert!(matches!(
+      bundle.verify_signature(&verifier(), Some("key-2")).await,
+      Err(crate::Error::SignatureNotExists)
+    ));
+
+    let descriptor: BundleDescriptor = BundleReader::new(Cursor::new(&buf)).read().unwrap();
+    descriptor
+      .verify_signature(&verifier(), None)
+      .await
+      .unwrap();
+  }
+
+  #[tokio::test]
+  async fn verify_tampered() {
+    let buf = signed_bundle();
+    let mut bundle: Bundle = BundleReader::new(Cursor::new(&buf)).read().unwrap();
+    let entry = bundle
+      .descriptor()
+      .index()
+      .get_entry("/index.html")
+      .unwrap();
+    let tampered = IndexEntry::new(
+      entry.offset(),
+      entry.len(),
+      "text/plain",
+      entry.content_length(),
+    );
+    bundle
+      .descriptor
+      .index
+      .insert_entry("/index.html", tampered);
+    assert!(matches!(
+      bundle.verify_signature(&verifier(), None).await,
+      Err(crate::Error::SignatureVerifyFailed)
+    ));
+  }
+
+  #[tokio::test]
+  async fn verify_tampered_data() {
+    let build = |html: &[u8]| {
+      let mut options = BundleBuilderOptions::new();
+      options.digest(DigestAlgorithm::Sha3_256).codec(Codec::None);
+      let mut builder = BundleBuilder::new_with_options(options);
+      builder.set_version(Version::V2);
+      builder.insert_entry("/index.html", BundleEntry::new(html, "text/html", None));
+      builder.build().unwrap()
+    };
+    let mut bundle = build(b"<html></html>");
+    let signature = sign(&bundle.descriptor().signing_message().unwrap());
+    bundle.add_signature(BundleSignature::new("custom", Some("key-1"), signature));
+    bundle.verify_signature(&verifier(), None).await.unwrap();
+
+    // data of the same length is swapped in, which the signing message does not include
+    let tampered = Bundle {
+      descriptor: bundle.descriptor().clone(),
+      data: build(b"<html>!</html").data,
+    };
+    tampered
+      .descriptor()
+      .verify_signature(&verifier(), None)
+      .await
+      .unwrap();
+    assert!(matches!(
+      tampered.verify_signature(&verifier(), None).await,
+      Err(crate::Error::DigestMismatch)
+    ));
+  }
+
+  #[tokio::test]
+  async fn verify_without_digest() {
+    let mut builder = BundleBuilder::new();
+    builder.set_version(Version::V2);
+    builder.insert_entry(
+      "/index.html",
+      BundleEntry::new(b"<html></html>", "text/html", None),
+    );
+    let mut bundle = builder.build().unwrap();
+    let signature = sign(&bundle.descriptor().signing_message().unwrap());
+    bundle.add_signature(BundleSignature::new("custom", Some("key-1"), signature));
+    assert!(matches!(
+      bundle.verify_signature(&verifier(), None).await,
+      Err(crate::Error::EntryDigestNotExists(path)) if path == "/index.html"
+    ));
+    assert!(matches!(
+      bundle.descriptor().verify_signature(&verifier(), None).await,
+      Err(crate::Error::EntryDigestNotExists(path)) if path == "/index.html"
+    ));
+  }
+
+  #[tokio::test]
+  async fn verify_unsigned() {
+    let bundle = BundleBuilder::new().build().unwrap();
+    assert!(matches!(
+      bundle.verify_signature(&verifier(), None).await,
+      Err(crate::Error::SignatureNotExists)
+    ));
+  }
+}
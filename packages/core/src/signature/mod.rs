@@ -43,6 +43,30 @@
 //! # };
 //! ```
 //!
+//! ## Embedded Signatures
+//!
+//! `V2` bundles can embed signatures over the header and index, which also cover per-entry
+//! digests, so a bundle keeps its provenance when copied off disk. Verifying requires every
+//! entry to have a digest (see `BundleBuilderOptions::digest`), and checks the data of every
+//! entry against it:
+//!
+//! ```no_run
+//! # #[cfg(feature = "signature-edd25519")]
+//! # async {
+//! # use wvb::signature::SignatureVerifier;
+//! # use wvb::{Bundle, BundleSignature};
+//! # let verifier: SignatureVerifier = todo!();
+//! # let mut bundle = Bundle::new();
+//! let message = bundle.descriptor().signing_message().unwrap();
+//! let signature = /* sign the message with the private key */
+//! # String::new();
+//! bundle.add_signature(BundleSignature::new("ed25519", Some("release"), signature));
+//!
+//! // After reading the bundle back
+//! bundle.verify_signature(&verifier, Some("release")).await.unwrap();
+//! # };
+//! ```
+//!
 //! ## Custom Verifiers
 //!
 //! Implement custom verification logic:
@@ -71,6 +95,7 @@ mod ecdsa_secp256r1;
 mod ecdsa_secp384r1;
 #[cfg(feature = "signature-edd25519")]
 mod ed25519;
+mod embedded;
 #[cfg(feature = "signature-rsa_pkcs1_v1_5")]
 mod rsa_pkcs1_v1_5;
 #[cfg(feature = "signature-rsa_pss")]
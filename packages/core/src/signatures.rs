@@ -0,0 +1,123 @@
+use crate::checksum::{make_checksum, parse_checksum, write_checksum, CHECKSUM_LEN};
+use crate::limits::DECODE_LIMIT;
+use bincode::{config, decode_from_slice, encode_to_vec, Decode, Encode};
+
+/// A signature embedded in a bundle.
+///
+/// `V2` bundles can carry signatures in a section after the index, so a bundle keeps its
+/// provenance when copied off disk. Each signature is made over the signing message of the
+/// bundle (see [`BundleDescriptor::signing_message`](crate::BundleDescriptor::signing_message)),
+/// which covers the header and the index.
+///
+/// # Example
+///
+/// ```
+/// use wvb::BundleSignature;
+///
+/// let signature = BundleSignature::new("ed25519", Some("release-2025"), "signature");
+/// assert_eq!(signature.algorithm(), "ed25519");
+/// assert_eq!(signature.key_id(), Some("release-2025"));
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+pub struct BundleSignature {
+  algorithm: String,
+  key_id: Option<String>,
+  value: String,
+}
+
+impl BundleSignature {
+  /// Creates a new signature.
+  ///
+  /// # Arguments
+  ///
+  /// * `algorithm` - Signature algorithm (e.g. `ed25519`, `ecdsa-secp256r1`, `rsa-pss`)
+  /// * `key_id` - Optional identifier of the signing key
+  /// * `value` - Signature in the same form as the `webview-bundle-signature` header
+  pub fn new(
+    algorithm: impl Into<String>,
+    key_id: Option<impl Into<String>>,
+    value: impl Into<String>,
+  ) -> Self {
+    Self {
+      algorithm: algorithm.into(),
+      key_id: key_id.map(Into::into),
+      value: value.into(),
+    }
+  }
+
+  /// Returns the signature algorithm.
+  pub fn algorithm(&self) -> &str {
+    &self.algorithm
+  }
+
+  /// Returns the identifier of the signing key.
+  pub fn key_id(&self) -> Option<&str> {
+    self.key_id.as_deref()
+  }
+
+  /// Returns the signature value.
+  pub fn value(&self) -> &str {
+    &self.value
+  }
+}
+
+/// Encodes the signatures section, followed by its checksum.
+pub(crate) fn write_signatures(signatures: &[BundleSignature]) -> crate::Result<Vec<u8>> {
+  let config = config::standard().with_big_endian();
+  let mut bytes = encode_to_vec(signatures, config).map_err(|e| crate::Error::Encode {
+    error: e,
+    message: "fail to encode signatures".to_string(),
+  })?;
+  let checksum = make_checksum(0, &bytes);
+  bytes.extend(write_checksum(checksum));
+  Ok(bytes)
+}
+
+/// Decodes the signatures section, verifying its checksum.
+pub(crate) fn parse_signatures(buf: &[u8]) -> crate::Result<Vec<BundleSignature>> {
+  if buf.is_empty() {
+    return Ok(vec![]);
+  }
+  if buf.len() < CHECKSUM_LEN {
+    return Err(crate::Error::InvalidSignaturesChecksum);
+  }
+  let (bytes, checksum) = buf.split_at(buf.len() - CHECKSUM_LEN);
+  if make_checksum(0, bytes) != parse_checksum(checksum) {
+    return Err(crate::Error::InvalidSignaturesChecksum);
+  }
+  let config = config::standard()
+    .with_big_endian()
+    .with_limit::<DECODE_LIMIT>();
+  let (signatures, _) = decode_from_slice(bytes, config).map_err(|e| crate::Error::Decode {
+    error: e,
+    message: "fail to decode signatures".to_string(),
+  })?;
+  Ok(signatures)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn write_and_parse() {
+    let signatures = vec![
+      BundleSignature::new("ed25519", Some("key-1"), "abc"),
+      BundleSignature::new("rsa-pss", None::<String>, "def"),
+    ];
+    let bytes = write_signatures(&signatures).unwrap();
+    assert_eq!(parse_signatures(&bytes).unwrap(), signatures);
+    assert!(parse_signatures(&[]).unwrap().is_empty());
+  }
+
+  #[test]
+  fn invalid_checksum() {
+    let signatures = vec![BundleSignature::new("ed25519", Some("key-1"), "abc")];
+    let mut bytes = write_signatures(&signatures).unwrap();
+    bytes[0] ^= 0xff;
+    assert!(matches!(
+      parse_signatures(&bytes),
+      Err(crate::Error::InvalidSignaturesChecksum)
+    ));
+  }
+}
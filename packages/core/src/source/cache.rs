@@ -0,0 +1,143 @@
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// A cache of decompressed entry data bounded by the total size of the data, evicting the least
+/// recently used entries first.
+pub(crate) struct EntryCache {
+  capacity: usize,
+  state: Mutex<CacheState>,
+}
+
+#[derive(Default)]
+struct CacheState {
+  /// Entries by bundle name and entry path.
+  bundles: HashMap<String, HashMap<String, CachedEntry>>,
+  size: usize,
+  tick: u64,
+}
+
+struct CachedEntry {
+  data: Bytes,
+  last_used: u64,
+}
+
+impl CacheState {
+  fn evict_one(&mut self) {
+    let oldest = self
+      .bundles
+      .iter()
+      .flat_map(|(name, entries)| {
+        entries
+          .iter()
+          .map(move |(path, x)| (x.last_used, name, path))
+      })
+      .min()
+      .map(|(_, name, path)| (name.clone(), path.clone()));
+    if let Some((name, path)) = oldest {
+      let entries = self.bundles.get_mut(&name).unwrap();
+      let entry = entries.remove(&path).unwrap();
+      self.size -= entry.data.len();
+      if entries.is_empty() {
+        self.bundles.remove(&name);
+      }
+    }
+  }
+}
+
+impl EntryCache {
+  /// Creates a cache holding at most `capacity` bytes of data. A zero capacity disables it.
+  pub(crate) fn new(capacity: usize) -> Self {
+    Self {
+      capacity,
+      state: Mutex::default(),
+    }
+  }
+
+  pub(crate) fn get(&self, bundle_name: &str, path: &str) -> Option<Bytes> {
+    if self.capacity == 0 {
+      return None;
+    }
+    let mut state = self.state.lock().unwrap();
+    state.tick += 1;
+    let tick = state.tick;
+    let entry = state.bundles.get_mut(bundle_name)?.get_mut(path)?;
+    entry.last_used = tick;
+    Some(entry.data.clone())
+  }
+
+  /// Caches the data of an entry, evicting the least recently used entries to make room for it.
+  ///
+  /// Data larger than the capacity is not cached.
+  pub(crate) fn insert(&self, bundle_name: &str, path: &str, data: Bytes) {
+    if self.capacity == 0 || data.len() > self.capacity {
+      return;
+    }
+    let mut state = self.state.lock().unwrap();
+    state.tick += 1;
+    let entry = CachedEntry {
+      data,
+      last_used: state.tick,
+    };
+    let len = entry.data.len();
+    let replaced = state
+      .bundles
+      .entry(bundle_name.to_string())
+      .or_default()
+      .insert(path.to_string(), entry);
+    state.size += len;
+    if let Some(replaced) = replaced {
+      state.size -= replaced.data.len();
+    }
+    while state.size > self.capacity {
+      state.evict_one();
+    }
+  }
+
+  /// Removes every cached entry of a bundle.
+  pub(crate) fn remove_bundle(&self, bundle_name: &str) {
+    let mut state = self.state.lock().unwrap();
+    if let Some(entries) = state.bundles.remove(bundle_name) {
+      state.size -= entries.values().map(|x| x.data.len()).sum::<usize>();
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn data(len: usize) -> Bytes {
+    Bytes::from(vec![0u8; len])
+  }
+
+  #[test]
+  fn evicts_least_recently_used() {
+    let cache = EntryCache::new(10);
+    cache.insert("app", "/a", data(4));
+    cache.insert("app", "/b", data(4));
+    assert!(cache.get("app", "/a").is_some());
+    // evicts "/b", which was used less recently than "/a"
+    cache.insert("other", "/c", data(4));
+    assert!(cache.get("app", "/a").is_some());
+    assert!(cache.get("app", "/b").is_none());
+    assert!(cache.get("other", "/c").is_some());
+    assert_eq!(cache.state.lock().unwrap().size, 8);
+
+    // too large to cache
+    cache.insert("app", "/large", data(11));
+    assert!(cache.get("app", "/large").is_none());
+
+    cache.remove_bundle("app");
+    assert!(cache.get("app", "/a").is_none());
+    assert!(cache.get("other", "/c").is_some());
+    assert_eq!(cache.state.lock().unwrap().size, 4);
+  }
+
+  #[test]
+  fn disabled() {
+    let cache = EntryCache::new(0);
+    cache.insert("app", "/a", data(1));
+    assert!(cache.get("app", "/a").is_none());
+  }
+}
@@ -25,7 +25,26 @@ pub struct BundleManifestMetadata {
 #[serde(rename_all = "camelCase")]
 pub struct BundleManifestEntry {
   pub versions: HashMap<String, BundleManifestMetadata>,
+  /// Currently active version, or empty if the bundle falls back to its builtin version, e.g.
+  /// after a rollback with no previous version to restore.
   pub current_version: String,
+  /// Version which was current before the current version, restored by a rollback.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub previous_version: Option<String>,
+  /// Set while the current version is pending confirmation that it is healthy.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub pending: Option<BundleManifestPending>,
+  /// Versions rolled back from, which are not activated again unless set as current explicitly.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub failed_versions: Vec<String>,
+}
+
+/// Confirmation state of a newly activated version, cleared once it is marked healthy.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleManifestPending {
+  /// Number of launches since the version was activated.
+  pub launches: u32,
 }
 
 #[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq, Eq)]
@@ -42,6 +61,8 @@ pub struct ListBundleManifestItem {
   pub name: String,
   pub version: String,
   pub current: bool,
+  /// Whether this is the version which was current before the current version.
+  pub previous: bool,
   pub metadata: BundleManifestMetadata,
 }
 
@@ -79,6 +100,7 @@ where
           name: bundle_name.to_string(),
           version: version.to_string(),
           current: version == &current_version,
+          previous: entry.previous_version.as_ref() == Some(version),
           metadata: metadata.clone(),
         };
         items.push(item);
@@ -100,6 +122,7 @@ where
     let version = data
       .entries
       .get(bundle_name)
+      .filter(|x| !x.current_version.is_empty())
       .map(|x| x.current_version.to_string());
     Ok(version)
   }
@@ -116,6 +139,30 @@ where
     Ok(None)
   }
 
+  /// Returns the pending confirmation state of the current version, if any.
+  pub async fn load_pending(
+    &self,
+    bundle_name: &str,
+  ) -> crate::Result<Option<BundleManifestPending>> {
+    let data = self.load().await?.read().await;
+    let pending = data
+      .entries
+      .get(bundle_name)
+      .and_then(|entry| entry.pending.clone());
+    Ok(pending)
+  }
+
+  /// Returns `true` if the version of a bundle was rolled back from, and not set as current
+  /// since.
+  pub async fn is_failed_version(&self, bundle_name: &str, version: &str) -> crate::Result<bool> {
+    let data = self.load().await?.read().await;
+    let failed = data
+      .entries
+      .get(bundle_name)
+      .is_some_and(|entry| entry.failed_versions.iter().any(|x| x == version));
+    Ok(failed)
+  }
+
   pub async fn load_metadata(
     &self,
     bundle_name: &str,
@@ -160,11 +207,83 @@ impl BundleManifest<ReadWrite> {
       .entries
       .entry(bundle_name.to_string())
       .and_modify(|entry| {
-        entry.current_version = version.to_string();
+        if entry.current_version != version {
+          let previous = std::mem::replace(&mut entry.current_version, version.to_string());
+          entry.previous_version = Some(previous).filter(|x| !x.is_empty());
+          entry.pending = None;
+        }
+        entry.failed_versions.retain(|x| x != version);
       });
     Ok(())
   }
 
+  /// Marks the current version of a bundle as pending confirmation that it is healthy.
+  pub async fn mark_pending(&self, bundle_name: &str) -> crate::Result<()> {
+    let mut data = self.load().await?.write().await;
+    if let Some(entry) = data.entries.get_mut(bundle_name) {
+      entry.pending = Some(BundleManifestPending::default());
+    }
+    Ok(())
+  }
+
+  /// Confirms that the current version of a bundle is healthy.
+  ///
+  /// Returns `true` if the version was pending confirmation.
+  pub async fn mark_healthy(&self, bundle_name: &str) -> crate::Result<bool> {
+    let mut data = self.load().await?.write().await;
+    let pending = data
+      .entries
+      .get_mut(bundle_name)
+      .and_then(|entry| entry.pending.take());
+    Ok(pending.is_some())
+  }
+
+  /// Counts a launch of the current version of a bundle if it is pending confirmation.
+  ///
+  /// Returns the number of launches since the version was activated, or `None` if it isn't
+  /// pending confirmation.
+  pub async fn record_launch(&self, bundle_name: &str) -> crate::Result<Option<u32>> {
+    let mut data = self.load().await?.write().await;
+    let launches = data
+      .entries
+      .get_mut(bundle_name)
+      .and_then(|entry| entry.pending.as_mut())
+      .map(|pending| {
+        pending.launches = pending.launches.saturating_add(1);
+        pending.launches
+      });
+    Ok(launches)
+  }
+
+  /// Switches the current version of a bundle back to its previous version. If there is no
+  /// previous version, the current version is cleared so the bundle falls back to its builtin
+  /// version. Every version stays in the manifest, and the version rolled back from is recorded
+  /// as failed.
+  ///
+  /// Returns the restored version, or `None` if the bundle falls back to its builtin version.
+  pub async fn rollback(&self, bundle_name: &str) -> crate::Result<Option<String>> {
+    let mut data = self.load().await?.write().await;
+    let Some(entry) = data.entries.get_mut(bundle_name) else {
+      return Ok(None);
+    };
+    if !entry.current_version.is_empty() && !entry.failed_versions.contains(&entry.current_version)
+    {
+      entry.failed_versions.push(entry.current_version.clone());
+    }
+    let previous = entry
+      .previous_version
+      .take()
+      .filter(|x| x != &entry.current_version && entry.versions.contains_key(x));
+    if let Some(previous) = previous {
+      entry.current_version = previous.clone();
+      entry.pending = None;
+      return Ok(Some(previous));
+    }
+    entry.current_version.clear();
+    entry.pending = None;
+    Ok(None)
+  }
+
   pub async fn insert_entry(
     &self,
     bundle_name: &str,
@@ -181,11 +300,20 @@ impl BundleManifest<ReadWrite> {
           inserted = false;
         } else {
           entry.versions.insert(version.to_string(), metadata.clone());
+          // a bundle falling back to its builtin version switches to a new version written,
+          // unless it was rolled back from before
+          if entry.current_version.is_empty() && !entry.failed_versions.iter().any(|x| x == version)
+          {
+            entry.current_version = version.to_string();
+          }
         }
       })
       .or_insert_with(|| BundleManifestEntry {
         versions: HashMap::from([(version.to_string(), metadata.clone())]),
         current_version: version.to_string(),
+        previous_version: None,
+        pending: None,
+        failed_versions: vec![],
       });
     Ok(inserted)
   }
@@ -309,6 +437,105 @@ mod tests {
     );
   }
 
+  #[tokio::test]
+  async fn rollback() {
+    let fixture = Fixtures::bundles();
+    let manifest = BundleManifest::new(&fixture.get_path("remote/manifest.json"), ReadWrite);
+    manifest
+      .update_current_version("app", "1.1.0")
+      .await
+      .unwrap();
+    manifest.mark_pending("app").await.unwrap();
+    assert_eq!(manifest.record_launch("app").await.unwrap(), Some(1));
+    assert_eq!(manifest.record_launch("app").await.unwrap(), Some(2));
+    assert_eq!(
+      manifest.load_pending("app").await.unwrap(),
+      Some(BundleManifestPending { launches: 2 })
+    );
+
+    assert_eq!(
+      manifest.rollback("app").await.unwrap().as_deref(),
+      Some("1.0.0")
+    );
+    assert_eq!(
+      manifest.load_current_version("app").await.unwrap().unwrap(),
+      "1.0.0"
+    );
+    assert!(manifest.load_pending("app").await.unwrap().is_none());
+    assert_eq!(manifest.record_launch("app").await.unwrap(), None);
+
+    // without a previous version, the bundle falls back to its builtin version and keeps every
+    // remote version
+    assert!(manifest.rollback("app").await.unwrap().is_none());
+    assert!(manifest
+      .load_current_version("app")
+      .await
+      .unwrap()
+      .is_none());
+    assert!(manifest.rollback("app").await.unwrap().is_none());
+    let items = manifest.list_entries().await.unwrap();
+    let mut versions = items
+      .iter()
+      .filter(|x| x.name == "app")
+      .map(|x| (x.version.as_str(), x.current))
+      .collect::<Vec<_>>();
+    versions.sort();
+    assert_eq!(versions, [("1.0.0", false), ("1.1.0", false)]);
+
+    // a version rolled back from is not activated when written again
+    assert!(manifest.is_failed_version("app", "1.1.0").await.unwrap());
+    assert!(manifest.is_failed_version("app", "1.0.0").await.unwrap());
+    let inserted = manifest
+      .insert_entry("app", "1.1.0", Default::default())
+      .await
+      .unwrap();
+    assert!(!inserted);
+    assert!(manifest
+      .load_current_version("app")
+      .await
+      .unwrap()
+      .is_none());
+
+    // a new version written becomes current
+    let inserted = manifest
+      .insert_entry("app", "1.2.0", Default::default())
+      .await
+      .unwrap();
+    assert!(inserted);
+    assert_eq!(
+      manifest.load_current_version("app").await.unwrap().unwrap(),
+      "1.2.0"
+    );
+
+    // setting a failed version as current explicitly clears its failure
+    manifest
+      .update_current_version("app", "1.1.0")
+      .await
+      .unwrap();
+    assert!(!manifest.is_failed_version("app", "1.1.0").await.unwrap());
+  }
+
+  #[tokio::test]
+  async fn mark_healthy() {
+    let fixture = Fixtures::bundles();
+    let manifest = BundleManifest::new(&fixture.get_path("remote/manifest.json"), ReadWrite);
+    assert!(!manifest.mark_healthy("app").await.unwrap());
+    manifest.mark_pending("app").await.unwrap();
+    assert!(manifest.mark_healthy("app").await.unwrap());
+    assert!(manifest.load_pending("app").await.unwrap().is_none());
+  }
+
+  #[test]
+  fn entry_without_rollback_state() {
+    let entry: BundleManifestEntry =
+      serde_json::from_str(r#"{"versions":{"1.0.0":{}},"currentVersion":"1.0.0"}"#).unwrap();
+    assert!(entry.previous_version.is_none());
+    assert!(entry.pending.is_none());
+    let json = serde_json::to_string(&entry).unwrap();
+    assert!(!json.contains("previousVersion"));
+    assert!(!json.contains("pending"));
+  }
+
   #[tokio::test]
   async fn update_current_version_entry_not_exists() {
     let fixture = Fixtures::bundles();
@@ -44,6 +44,28 @@
 //! # };
 //! ```
 //!
+//! ## Retention
+//!
+//! Every bundle written to the remote source adds a version. [`BundleSource::prune`] removes
+//! versions not kept by the [`RetentionPolicy`] set on the source, and bundle files in the remote
+//! directory which aren't in the manifest. The current version of a bundle, and the previous
+//! version a rollback restores, are never removed.
+//!
+//! ## Rollback
+//!
+//! [`BundleSource::rollback`] switches a bundle back to the remote version which was current
+//! before, or to the builtin version. Remote versions stay in the manifest either way. With
+//! [`BundleSourceBuilder::confirmation_launches`] set, a newly activated version stays pending
+//! until [`BundleSource::mark_healthy`] is called, and is rolled back automatically if it is
+//! still pending after that many launches. A launch is counted when the app calls
+//! [`BundleSource::launch`] as it starts.
+//!
+//! ## Bundle Metadata
+//!
+//! If a `V2` bundle embeds [`BundleMetadata`](crate::BundleMetadata), its name and version must
+//! match the manifest entry which points at the file, otherwise loading it fails with
+//! [`Error::BundleMetadataMismatch`](crate::Error::BundleMetadataMismatch).
+//!
 //! ## Manifest Format
 //!
 //! The `manifest.json` file tracks bundle versions and metadata:
@@ -66,9 +88,12 @@
 //! }
 //! ```
 
+mod cache;
 mod manifest;
+mod retention;
 mod source;
 mod utils;
 
 pub use manifest::*;
+pub use retention::*;
 pub use source::*;
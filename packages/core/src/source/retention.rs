@@ -0,0 +1,186 @@
+use std::cmp::Ordering;
+use std::path::PathBuf;
+
+/// Retention policy of remote bundle versions, applied by
+/// [`BundleSource::prune`](crate::source::BundleSource::prune).
+///
+/// The current version of a bundle is always kept, and so is the version which was current
+/// before it, since [`BundleSource::rollback`](crate::source::BundleSource::rollback) restores
+/// it, e.g. when the current version is still pending confirmation. The previous version counts
+/// toward the limits first. Other versions are ranked from the newest, comparing them as semver
+/// versions, and kept as long as they fit the policy, so a newer version never evicts the
+/// version a rollback returns to. The default policy keeps every version.
+///
+/// # Example
+///
+/// ```
+/// use wvb::source::RetentionPolicy;
+///
+/// // keep the current version, and up to 2 previous versions within 50 MiB per bundle
+/// let policy = RetentionPolicy::new()
+///     .previous_versions(2)
+///     .max_bytes(50 * 1024 * 1024);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+  previous_versions: Option<usize>,
+  max_bytes: Option<u64>,
+}
+
+impl RetentionPolicy {
+  /// Creates a policy which keeps every version.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Keeps at most `count` versions of a bundle besides the current version, including the
+  /// previous version which is always kept.
+  #[must_use]
+  pub fn previous_versions(mut self, count: usize) -> Self {
+    self.previous_versions = Some(count);
+    self
+  }
+
+  /// Keeps versions of a bundle while the total size of their files, including the file of the
+  /// current version, is at most `bytes`.
+  #[must_use]
+  pub fn max_bytes(mut self, bytes: u64) -> Self {
+    self.max_bytes = Some(bytes);
+    self
+  }
+
+  /// Returns the versions of a bundle to remove, given every version with the size of its file,
+  /// the current version and the version which was current before it.
+  pub(crate) fn select(
+    &self,
+    current: &str,
+    previous: Option<&str>,
+    versions: &[(String, u64)],
+  ) -> Vec<String> {
+    let previous = previous.filter(|x| *x != current);
+    let protected = |version: &str| version == current || Some(version) == previous;
+    let mut size = versions
+      .iter()
+      .filter(|(version, _)| protected(version))
+      .map(|(_, len)| *len)
+      .sum::<u64>();
+    let mut kept = versions
+      .iter()
+      .filter(|(version, _)| Some(version.as_str()) == previous)
+      .count();
+    let mut others = versions
+      .iter()
+      .filter(|(version, _)| !protected(version))
+      .collect::<Vec<_>>();
+    others.sort_by(|(a, _), (b, _)| compare_versions(b, a));
+    let mut removed = vec![];
+    for (version, len) in others {
+      // once a version doesn't fit, older versions are removed too
+      let fits = removed.is_empty()
+        && self.previous_versions.is_none_or(|count| kept < count)
+        && self
+          .max_bytes
+          .is_none_or(|bytes| size.saturating_add(*len) <= bytes);
+      if fits {
+        kept += 1;
+        size = size.saturating_add(*len);
+      } else {
+        removed.push(version.to_string());
+      }
+    }
+    removed
+  }
+}
+
+/// Compares versions as semver versions, ranking versions which aren't valid semver lower and
+/// comparing them as strings.
+fn compare_versions(a: &str, b: &str) -> Ordering {
+  match (semver::Version::parse(a), semver::Version::parse(b)) {
+    (Ok(a), Ok(b)) => a.cmp(&b),
+    (Ok(_), Err(_)) => Ordering::Greater,
+    (Err(_), Ok(_)) => Ordering::Less,
+    (Err(_), Err(_)) => a.cmp(b),
+  }
+}
+
+/// Result of [`BundleSource::prune`](crate::source::BundleSource::prune).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PruneReport {
+  /// Versions removed from the manifest along with their files, as `(bundle name, version)`.
+  pub versions: Vec<(String, String)>,
+  /// Bundle files removed because they were not in the manifest.
+  pub orphans: Vec<PathBuf>,
+  /// Total size of the removed files in bytes.
+  pub freed_bytes: u64,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn versions(items: &[(&str, u64)]) -> Vec<(String, u64)> {
+    items.iter().map(|(v, len)| (v.to_string(), *len)).collect()
+  }
+
+  #[test]
+  fn keeps_everything_by_default() {
+    let versions = versions(&[("1.0.0", 10), ("1.1.0", 10), ("2.0.0", 10)]);
+    assert!(RetentionPolicy::new()
+      .select("2.0.0", None, &versions)
+      .is_empty());
+  }
+
+  #[test]
+  fn previous_versions() {
+    let versions = versions(&[
+      ("1.0.0", 10),
+      ("1.10.0", 10),
+      ("1.2.0", 10),
+      ("2.0.0", 10),
+      ("nightly", 10),
+    ]);
+    let policy = RetentionPolicy::new().previous_versions(2);
+    let mut removed = policy.select("1.2.0", None, &versions);
+    removed.sort();
+    assert_eq!(removed, ["1.0.0", "nightly"]);
+    let mut removed = RetentionPolicy::new()
+      .previous_versions(0)
+      .select("1.2.0", None, &versions);
+    removed.sort();
+    assert_eq!(removed, ["1.0.0", "1.10.0", "2.0.0", "nightly"]);
+  }
+
+  #[test]
+  fn max_bytes() {
+    let versions = versions(&[("1.0.0", 1), ("1.1.0", 30), ("1.2.0", 5), ("1.3.0", 50)]);
+    let policy = RetentionPolicy::new().max_bytes(60);
+    // 1.0.0 would fit, but is older than 1.1.0 which doesn't
+    assert_eq!(policy.select("1.3.0", None, &versions), ["1.1.0", "1.0.0"]);
+    // the current version is kept even if it exceeds the limit
+    let policy = RetentionPolicy::new().max_bytes(10);
+    assert_eq!(
+      policy.select("1.3.0", None, &versions),
+      ["1.2.0", "1.1.0", "1.0.0"]
+    );
+  }
+
+  #[test]
+  fn keeps_previous_version() {
+    let versions = versions(&[("1.0.0", 10), ("1.1.0", 10), ("1.2.0", 10), ("2.0.0", 10)]);
+    // 2.0.0 is pending confirmation, and a rollback returns to 1.0.0
+    let policy = RetentionPolicy::new().previous_versions(1);
+    let mut removed = policy.select("2.0.0", Some("1.0.0"), &versions);
+    removed.sort();
+    assert_eq!(removed, ["1.1.0", "1.2.0"]);
+    let policy = RetentionPolicy::new().previous_versions(0).max_bytes(10);
+    let mut removed = policy.select("2.0.0", Some("1.0.0"), &versions);
+    removed.sort();
+    assert_eq!(removed, ["1.1.0", "1.2.0"]);
+    // the previous version counts toward the number of versions kept
+    let policy = RetentionPolicy::new().previous_versions(2);
+    let mut removed = policy.select("1.1.0", Some("1.0.0"), &versions);
+    removed.sort();
+    assert_eq!(removed, ["1.2.0"]);
+  }
+}
@@ -1,16 +1,20 @@
+use crate::source::cache::EntryCache;
 use crate::source::{
-  utils, BundleManifest, BundleManifestMetadata, ListBundleManifestItem, ReadOnly, ReadWrite,
+  utils, BundleManifest, BundleManifestMetadata, ListBundleManifestItem, PruneReport, ReadOnly,
+  ReadWrite, RetentionPolicy,
 };
 use crate::{
-  AsyncBundleReader, AsyncBundleWriter, AsyncReader, AsyncWriter, Bundle, BundleDescriptor,
-  EXTENSION, MANIFEST_FILENAME,
+  AsyncBundleReader, AsyncBundleWriter, AsyncReader, AsyncWriter, Bundle, BundleDescriptor, Codec,
+  MappedBundle, EXTENSION, MANIFEST_FILENAME,
 };
+use bytes::Bytes;
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::fs::File;
-use tokio::sync::OnceCell;
+use tokio::sync::{Mutex, OnceCell};
 
 /// The type of bundle source: builtin or remote.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -65,16 +69,38 @@ impl BundleSourceVersion {
 ///     .build();
 /// # }
 /// ```
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Clone)]
 #[non_exhaustive]
 pub struct BundleSourceBuilder {
   builtin_dir: PathBuf,
   builtin_manifest_filepath: Option<PathBuf>,
   remote_dir: PathBuf,
   remote_manifest_filepath: Option<PathBuf>,
+  verify_digest: bool,
+  entry_cache_capacity: usize,
+  retention: RetentionPolicy,
+  confirmation_launches: Option<u32>,
+}
+
+impl Default for BundleSourceBuilder {
+  fn default() -> Self {
+    Self {
+      builtin_dir: PathBuf::default(),
+      builtin_manifest_filepath: None,
+      remote_dir: PathBuf::default(),
+      remote_manifest_filepath: None,
+      verify_digest: false,
+      entry_cache_capacity: Self::DEFAULT_ENTRY_CACHE_CAPACITY,
+      retention: RetentionPolicy::default(),
+      confirmation_launches: None,
+    }
+  }
 }
 
 impl BundleSourceBuilder {
+  /// Default total size of decompressed entry data cached by [`BundleSource::load_data`].
+  pub const DEFAULT_ENTRY_CACHE_CAPACITY: usize = 32 * 1024 * 1024;
+
   pub fn new() -> Self {
     Self::default()
   }
@@ -101,6 +127,42 @@ impl BundleSourceBuilder {
     self
   }
 
+  /// Verifies file data and precompressed variants against the digests in the index when read
+  /// from mapped bundles.
+  #[must_use]
+  pub fn verify_digest(mut self, verify: bool) -> Self {
+    self.verify_digest = verify;
+    self
+  }
+
+  /// Sets the total size in bytes of decompressed entry data cached by
+  /// [`BundleSource::load_data`]. Defaults to 32 MiB, and `0` disables the cache.
+  #[must_use]
+  pub fn entry_cache_capacity(mut self, capacity: usize) -> Self {
+    self.entry_cache_capacity = capacity;
+    self
+  }
+
+  /// Sets the retention policy of remote bundle versions applied by [`BundleSource::prune`].
+  /// Every version is kept by default.
+  #[must_use]
+  pub fn retention(mut self, policy: RetentionPolicy) -> Self {
+    self.retention = policy;
+    self
+  }
+
+  /// Requires newly activated remote versions to be confirmed with
+  /// [`BundleSource::mark_healthy`] within the given number of launches. A version still
+  /// pending confirmation is rolled back (see [`BundleSource::rollback`]) on the next launch.
+  ///
+  /// Launches are counted by [`BundleSource::launch`], which the app calls once per bundle when
+  /// it starts. Disabled by default.
+  #[must_use]
+  pub fn confirmation_launches(mut self, launches: u32) -> Self {
+    self.confirmation_launches = Some(launches);
+    self
+  }
+
   pub fn build(self) -> BundleSource {
     let builtin_dir = self.builtin_dir;
     let builtin_manifest_filepath = self
@@ -118,6 +180,13 @@ impl BundleSourceBuilder {
       remote_dir,
       remote_manifest: BundleManifest::new(&remote_manifest_filepath, ReadWrite),
       descriptors: DashMap::default(),
+      mapped: DashMap::default(),
+      entry_cache: EntryCache::new(self.entry_cache_capacity),
+      verify_digest: self.verify_digest,
+      retention: self.retention,
+      confirmation_launches: self.confirmation_launches,
+      launched: DashMap::default(),
+      remote_lock: Mutex::default(),
     }
   }
 }
@@ -135,6 +204,14 @@ pub struct BundleSource {
   remote_dir: PathBuf,
   remote_manifest: BundleManifest<ReadWrite>,
   descriptors: DashMap<String, Arc<OnceCell<Arc<BundleDescriptor>>>>,
+  mapped: DashMap<String, Arc<OnceCell<Arc<MappedBundle>>>>,
+  entry_cache: EntryCache,
+  verify_digest: bool,
+  retention: RetentionPolicy,
+  confirmation_launches: Option<u32>,
+  launched: DashMap<String, Arc<OnceCell<()>>>,
+  /// Held while writing to the remote directory, so pruning doesn't remove a file being written.
+  remote_lock: Mutex<()>,
 }
 
 impl BundleSource {
@@ -187,45 +264,128 @@ impl BundleSource {
   }
 
   pub async fn update_version(&self, bundle_name: &str, version: &str) -> crate::Result<()> {
+    let current = self
+      .remote_manifest
+      .load_current_version(bundle_name)
+      .await?;
     self
       .remote_manifest
       .update_current_version(bundle_name, version)
+      .await?;
+    if current.as_deref() != Some(version) {
+      self.mark_pending(bundle_name).await?;
+    }
+    Ok(())
+  }
+
+  /// Returns `true` if a remote version of a bundle was rolled back from, and not set as current
+  /// since. Such a version isn't activated again when it is written.
+  pub async fn is_failed_version(&self, bundle_name: &str, version: &str) -> crate::Result<bool> {
+    self
+      .remote_manifest
+      .is_failed_version(bundle_name, version)
       .await
   }
 
+  /// Rolls a bundle back from its current remote version to the version which was current
+  /// before it, or to the builtin version if there is no such remote version.
+  ///
+  /// Returns the version in use after the rollback.
+  pub async fn rollback(&self, bundle_name: &str) -> crate::Result<Option<BundleSourceVersion>> {
+    if self
+      .remote_manifest
+      .load_current_version(bundle_name)
+      .await?
+      .is_none()
+    {
+      return Err(crate::Error::BundleCannotBeRolledBack(
+        bundle_name.to_string(),
+      ));
+    }
+    self.remote_manifest.rollback(bundle_name).await?;
+    self.remote_manifest.save().await?;
+    self.unload_descriptor(bundle_name);
+    self.load_version(bundle_name).await
+  }
+
+  /// Confirms that the current version of a bundle is healthy, so it is no longer rolled back
+  /// automatically (see [`BundleSourceBuilder::confirmation_launches`]).
+  ///
+  /// Returns `true` if the version was pending confirmation.
+  pub async fn mark_healthy(&self, bundle_name: &str) -> crate::Result<bool> {
+    let marked = self.remote_manifest.mark_healthy(bundle_name).await?;
+    if marked {
+      self.remote_manifest.save().await?;
+    }
+    Ok(marked)
+  }
+
+  /// Marks the newly activated current version of a bundle as pending confirmation, if
+  /// confirmation is required.
+  async fn mark_pending(&self, bundle_name: &str) -> crate::Result<()> {
+    if self.confirmation_launches.is_some() {
+      self.remote_manifest.mark_pending(bundle_name).await?;
+      self.remote_manifest.save().await?;
+    }
+    Ok(())
+  }
+
+  /// Launches a bundle, to be called once when the app starts using it.
+  ///
+  /// With [`BundleSourceBuilder::confirmation_launches`] set, this counts a launch of the
+  /// current version if it is pending confirmation, and rolls it back if it is still pending
+  /// after the allowed number of launches. Only the first call for a bundle counts a launch, so
+  /// loading the bundle elsewhere (e.g. by the updater) never does.
+  ///
+  /// Returns the version in use after the launch.
+  pub async fn launch(&self, bundle_name: &str) -> crate::Result<Option<BundleSourceVersion>> {
+    let Some(max_launches) = self.confirmation_launches else {
+      return self.load_version(bundle_name).await;
+    };
+    let launched = {
+      let entry = self.launched.entry(bundle_name.to_string()).or_default();
+      entry.clone()
+    };
+    launched
+      .get_or_try_init(|| async {
+        if let Some(launches) = self.remote_manifest.record_launch(bundle_name).await? {
+          if launches > max_launches {
+            self.remote_manifest.rollback(bundle_name).await?;
+            self.unload_descriptor(bundle_name);
+          }
+          self.remote_manifest.save().await?;
+        }
+        Ok::<(), crate::Error>(())
+      })
+      .await?;
+    self.load_version(bundle_name).await
+  }
+
   pub async fn filepath(&self, bundle_name: &str) -> crate::Result<PathBuf> {
     let ver = self
       .load_version(bundle_name)
       .await?
       .ok_or(crate::Error::BundleNotFound)?;
-    let filepath = match &ver.kind {
-      BundleSourceKind::Builtin => self.get_builtin_filepath(bundle_name, &ver.version),
-      BundleSourceKind::Remote => self.get_remote_filepath(bundle_name, &ver.version),
-    };
-    Ok(filepath)
+    Ok(self.get_version_filepath(bundle_name, &ver))
   }
 
   pub async fn reader(&self, bundle_name: &str) -> crate::Result<File> {
-    let filepath = self.filepath(bundle_name).await?;
-    let file = File::open(filepath).await.map_err(|e| {
-      if e.kind() == std::io::ErrorKind::NotFound {
-        return crate::Error::BundleNotFound;
-      }
-      crate::Error::from(e)
-    })?;
+    let (file, _) = self.open(bundle_name).await?;
     Ok(file)
   }
 
   pub async fn fetch(&self, bundle_name: &str) -> crate::Result<Bundle> {
-    let mut file = self.reader(bundle_name).await?;
+    let (mut file, ver) = self.open(bundle_name).await?;
     let bundle = AsyncReader::<Bundle>::read(&mut AsyncBundleReader::new(&mut file)).await?;
+    check_metadata(bundle.descriptor(), bundle_name, &ver.version)?;
     Ok(bundle)
   }
 
   pub async fn fetch_descriptor(&self, bundle_name: &str) -> crate::Result<BundleDescriptor> {
-    let mut file = self.reader(bundle_name).await?;
+    let (mut file, ver) = self.open(bundle_name).await?;
     let manifest =
       AsyncReader::<BundleDescriptor>::read(&mut AsyncBundleReader::new(&mut file)).await?;
+    check_metadata(&manifest, bundle_name, &ver.version)?;
     Ok(manifest)
   }
 
@@ -249,10 +409,79 @@ impl BundleSource {
     Ok(descriptor)
   }
 
+  /// Unloads the cached descriptor, memory-mapped bundle and entry data.
   pub fn unload_descriptor(&self, bundle_name: &str) -> bool {
-    self.descriptors.remove(bundle_name).is_some()
+    let descriptor = self.descriptors.remove(bundle_name).is_some();
+    let mapped = self.mapped.remove(bundle_name).is_some();
+    self.entry_cache.remove_bundle(bundle_name);
+    descriptor || mapped
+  }
+
+  pub async fn fetch_mapped(&self, bundle_name: &str) -> crate::Result<MappedBundle> {
+    let (file, ver) = self.open(bundle_name).await?;
+    let file = file.into_std().await;
+    let mut mapped = tokio::task::spawn_blocking(move || MappedBundle::from_file(&file))
+      .await
+      .map_err(crate::Error::generic)??;
+    check_metadata(mapped.descriptor(), bundle_name, &ver.version)?;
+    mapped.set_verify_digest(self.verify_digest);
+    Ok(mapped)
   }
 
+  /// Loads the memory-mapped bundle, mapping the file on the first call and returning the
+  /// cached mapping afterward.
+  pub async fn load_mapped(&self, bundle_name: &str) -> crate::Result<Arc<MappedBundle>> {
+    if let Some(entry) = self.mapped.get(bundle_name) {
+      if let Some(m) = entry.get() {
+        return Ok(m.clone());
+      }
+    }
+    let mapped_cell = {
+      let entry = self.mapped.entry(bundle_name.to_string()).or_default();
+      entry.clone()
+    };
+    let mapped = mapped_cell
+      .get_or_try_init(|| async {
+        let m = self.fetch_mapped(bundle_name).await?;
+        Ok::<Arc<MappedBundle>, crate::Error>(Arc::new(m))
+      })
+      .await?
+      .clone();
+    Ok(mapped)
+  }
+
+  /// Loads the data of an entry from the memory-mapped bundle.
+  ///
+  /// Entries stored without compression share the mapping. Decompressed data of other entries
+  /// is kept in a bounded cache (see [`BundleSourceBuilder::entry_cache_capacity`]), so repeated
+  /// loads of the same entry are neither decompressed nor copied again.
+  ///
+  /// Returns `None` if the path doesn't exist in the bundle.
+  pub async fn load_data(&self, bundle_name: &str, path: &str) -> crate::Result<Option<Bytes>> {
+    if let Some(data) = self.entry_cache.get(bundle_name, path) {
+      return Ok(Some(data));
+    }
+    let bundle = self.load_mapped(bundle_name).await?;
+    let Some(entry) = bundle.descriptor().index().try_get_entry(path)? else {
+      return Ok(None);
+    };
+    let compressed = entry.codec() != Codec::None;
+    let entry_path = path.to_string();
+    let data = read_mapped(&bundle, compressed, move |b| b.get_bytes(&entry_path)).await?;
+    let Some(data) = data else {
+      return Ok(None);
+    };
+    if compressed {
+      self.entry_cache.insert(bundle_name, path, data.clone());
+    }
+    Ok(Some(data))
+  }
+
+  /// Writes a remote bundle version and adds it to the manifest.
+  ///
+  /// The bundle is written to a temporary file which is then renamed over the bundle file, so a
+  /// file which may be memory-mapped is never modified in place. The cached descriptor, mapping
+  /// and entry data of the bundle are unloaded afterward.
   pub async fn write_remote_bundle(
     &self,
     bundle_name: &str,
@@ -260,16 +489,136 @@ impl BundleSource {
     bundle: &Bundle,
     metadata: BundleManifestMetadata,
   ) -> crate::Result<()> {
+    let _lock = self.remote_lock.lock().await;
     let filepath = self.get_remote_filepath(bundle_name, version);
-    let mut file = File::create(&filepath).await?;
-    AsyncBundleWriter::new(&mut file).write(bundle).await?;
+    let tmp_filepath = filepath.with_extension(format!("{EXTENSION}.tmp"));
+    let written = async {
+      let mut file = File::create(&tmp_filepath).await?;
+      AsyncBundleWriter::new(&mut file).write(bundle).await?;
+      file.sync_all().await?;
+      Ok::<(), crate::Error>(())
+    }
+    .await;
+    if let Err(e) = written {
+      remove_file(&tmp_filepath).await.ok();
+      return Err(e);
+    }
+    // a file which is still mapped can't be replaced on Windows, so the cached mapping is
+    // dropped first, and again afterward in case it was mapped in the meantime
+    self.unload_descriptor(bundle_name);
+    if let Err(e) = tokio::fs::rename(&tmp_filepath, &filepath).await {
+      remove_file(&tmp_filepath).await.ok();
+      return Err(e.into());
+    }
+    self.unload_descriptor(bundle_name);
+    let current = self
+      .remote_manifest
+      .load_current_version(bundle_name)
+      .await?;
     self
       .remote_manifest
       .insert_entry(bundle_name, version, metadata)
       .await?;
+    // a new remote version replaces the builtin version
+    if current.is_none()
+      && self
+        .remote_manifest
+        .load_current_version(bundle_name)
+        .await?
+        .is_some()
+    {
+      self.mark_pending(bundle_name).await?;
+    }
     Ok(())
   }
 
+  /// Removes remote bundle versions not kept by the retention policy (see
+  /// [`BundleSourceBuilder::retention`]) along with their files, and `.wvb` files in the remote
+  /// directory which aren't in the manifest.
+  ///
+  /// The current version of a bundle, and the previous version a rollback restores, are never
+  /// removed. The manifest is saved before any file is removed. On Windows, a file which is
+  /// still memory-mapped can't be removed, so it is left in place and removed as an orphan by a
+  /// later prune.
+  pub async fn prune(&self) -> crate::Result<PruneReport> {
+    let _lock = self.remote_lock.lock().await;
+    let mut report = PruneReport::default();
+
+    let mut bundles = HashMap::<String, (String, Option<String>, Vec<(String, u64)>)>::new();
+    for item in self.remote_manifest.list_entries().await? {
+      let filepath = self.get_remote_filepath(&item.name, &item.version);
+      let len = file_len(&filepath).await?.unwrap_or(0);
+      let (current, previous, versions) = bundles.entry(item.name).or_default();
+      if item.current {
+        *current = item.version.clone();
+      }
+      if item.previous {
+        *previous = Some(item.version.clone());
+      }
+      versions.push((item.version, len));
+    }
+    for (bundle_name, (current, previous, versions)) in &bundles {
+      for version in self
+        .retention
+        .select(current, previous.as_deref(), versions)
+      {
+        self
+          .remote_manifest
+          .remove_entry(bundle_name, &version)
+          .await?;
+        report.versions.push((bundle_name.to_string(), version));
+      }
+    }
+    if !report.versions.is_empty() {
+      self.remote_manifest.save().await?;
+    }
+    for (bundle_name, version) in &report.versions {
+      let filepath = self.get_remote_filepath(bundle_name, version);
+      report.freed_bytes += remove_file(&filepath).await?.unwrap_or(0);
+    }
+
+    let known = self
+      .remote_manifest
+      .list_entries()
+      .await?
+      .into_iter()
+      .map(|x| self.get_remote_filepath(&x.name, &x.version))
+      .collect::<HashSet<_>>();
+    for filepath in list_bundle_files(&self.remote_dir).await? {
+      if known.contains(&filepath) {
+        continue;
+      }
+      if let Some(len) = remove_file(&filepath).await? {
+        report.freed_bytes += len;
+        report.orphans.push(filepath);
+      }
+    }
+    Ok(report)
+  }
+
+  /// Opens the file of the current version of the bundle.
+  async fn open(&self, bundle_name: &str) -> crate::Result<(File, BundleSourceVersion)> {
+    let ver = self
+      .load_version(bundle_name)
+      .await?
+      .ok_or(crate::Error::BundleNotFound)?;
+    let filepath = self.get_version_filepath(bundle_name, &ver);
+    let file = File::open(filepath).await.map_err(|e| {
+      if e.kind() == std::io::ErrorKind::NotFound {
+        return crate::Error::BundleNotFound;
+      }
+      crate::Error::from(e)
+    })?;
+    Ok((file, ver))
+  }
+
+  fn get_version_filepath(&self, bundle_name: &str, ver: &BundleSourceVersion) -> PathBuf {
+    match &ver.kind {
+      BundleSourceKind::Builtin => self.get_builtin_filepath(bundle_name, &ver.version),
+      BundleSourceKind::Remote => self.get_remote_filepath(bundle_name, &ver.version),
+    }
+  }
+
   fn get_builtin_filepath(&self, bundle_name: &str, version: &str) -> PathBuf {
     self.get_filepath(&self.builtin_dir, bundle_name, version)
   }
@@ -285,10 +634,97 @@ impl BundleSource {
   }
 }
 
+/// Returns the size of a file, or `None` if it doesn't exist.
+async fn file_len(filepath: &Path) -> crate::Result<Option<u64>> {
+  match tokio::fs::metadata(filepath).await {
+    Ok(metadata) => Ok(Some(metadata.len())),
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+    Err(e) => Err(e.into()),
+  }
+}
+
+/// Removes a file, returning its size, or `None` if it doesn't exist or is still mapped on
+/// Windows.
+async fn remove_file(filepath: &Path) -> crate::Result<Option<u64>> {
+  let Some(len) = file_len(filepath).await? else {
+    return Ok(None);
+  };
+  match tokio::fs::remove_file(filepath).await {
+    Ok(()) => Ok(Some(len)),
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+    // the file is still mapped by a loaded bundle
+    Err(e) if cfg!(windows) && e.kind() == std::io::ErrorKind::PermissionDenied => Ok(None),
+    Err(e) => Err(e.into()),
+  }
+}
+
+/// Lists bundle files in the bundle directories of a source directory.
+async fn list_bundle_files(dir: &Path) -> crate::Result<Vec<PathBuf>> {
+  let mut files = vec![];
+  let mut bundle_dirs = match tokio::fs::read_dir(dir).await {
+    Ok(x) => x,
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(files),
+    Err(e) => return Err(e.into()),
+  };
+  while let Some(bundle_dir) = bundle_dirs.next_entry().await? {
+    if !bundle_dir.file_type().await?.is_dir() {
+      continue;
+    }
+    let mut entries = tokio::fs::read_dir(bundle_dir.path()).await?;
+    while let Some(entry) = entries.next_entry().await? {
+      let path = entry.path();
+      if entry.file_type().await?.is_file() && path.extension().is_some_and(|x| x == EXTENSION) {
+        files.push(path);
+      }
+    }
+  }
+  Ok(files)
+}
+
+/// Reads from a memory-mapped bundle, on a blocking thread if the entry read is compressed so
+/// that decompressing it doesn't stall the async runtime.
+pub(crate) async fn read_mapped<T, F>(
+  bundle: &Arc<MappedBundle>,
+  compressed: bool,
+  f: F,
+) -> crate::Result<T>
+where
+  T: Send + 'static,
+  F: FnOnce(&MappedBundle) -> crate::Result<T> + Send + 'static,
+{
+  if !compressed {
+    return f(bundle);
+  }
+  let bundle = bundle.clone();
+  tokio::task::spawn_blocking(move || f(&bundle))
+    .await
+    .map_err(crate::Error::generic)?
+}
+
+/// Checks that the metadata embedded in the bundle matches the manifest entry pointing at it.
+///
+/// Bundles without metadata are accepted as-is.
+pub(crate) fn check_metadata(
+  descriptor: &BundleDescriptor,
+  bundle_name: &str,
+  version: &str,
+) -> crate::Result<()> {
+  if let Some(metadata) = descriptor.metadata() {
+    if metadata.name() != bundle_name || metadata.version() != version {
+      return Err(crate::Error::BundleMetadataMismatch {
+        expected: format!("{bundle_name}@{version}"),
+        actual: format!("{}@{}", metadata.name(), metadata.version()),
+      });
+    }
+  }
+  Ok(())
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
-  use crate::testing::Fixtures;
+  use crate::testing::{Fixtures, TempDir};
+  use crate::{BundleBuilder, BundleEntry, BundleMetadata, Version, Writer};
 
   #[tokio::test]
   async fn fetch() {
@@ -341,6 +777,93 @@ mod tests {
     }
   }
 
+  #[tokio::test]
+  async fn load_mapped() {
+    let fixture = Fixtures::bundles();
+    let source = BundleSource::builder()
+      .builtin_dir(fixture.get_path("builtin"))
+      .remote_dir(fixture.get_path("remote"))
+      .build();
+    let m1 = source.load_mapped("app").await.unwrap();
+    let m2 = source.load_mapped("app").await.unwrap();
+    assert!(Arc::ptr_eq(&m1, &m2));
+    let descriptor = source.fetch_descriptor("app").await.unwrap();
+    assert_eq!(m1.descriptor(), &descriptor);
+    let data = m1.get_data("/index.html").unwrap().unwrap();
+    let expected = source
+      .fetch("app")
+      .await
+      .unwrap()
+      .get_data("/index.html")
+      .unwrap()
+      .unwrap();
+    assert_eq!(data.as_ref(), expected);
+
+    assert!(source.unload_descriptor("app"));
+    let m3 = source.load_mapped("app").await.unwrap();
+    assert!(!Arc::ptr_eq(&m1, &m3));
+  }
+
+  #[tokio::test]
+  async fn load_data() {
+    let fixture = Fixtures::bundles();
+    let source = BundleSource::builder()
+      .builtin_dir(fixture.get_path("builtin"))
+      .remote_dir(fixture.get_path("remote"))
+      .build();
+    let first = source
+      .load_data("app", "/index.html")
+      .await
+      .unwrap()
+      .unwrap();
+    let expected = source
+      .fetch("app")
+      .await
+      .unwrap()
+      .get_data("/index.html")
+      .unwrap()
+      .unwrap();
+    assert_eq!(first, expected);
+    // served from the cache without copying
+    let second = source
+      .load_data("app", "/index.html")
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(first.as_ptr(), second.as_ptr());
+    assert!(source
+      .load_data("app", "/not-found.html")
+      .await
+      .unwrap()
+      .is_none());
+
+    source.unload_descriptor("app");
+    let third = source
+      .load_data("app", "/index.html")
+      .await
+      .unwrap()
+      .unwrap();
+    assert_ne!(first.as_ptr(), third.as_ptr());
+
+    let source = BundleSource::builder()
+      .builtin_dir(fixture.get_path("builtin"))
+      .remote_dir(fixture.get_path("remote"))
+      .entry_cache_capacity(0)
+      .build();
+    let first = source
+      .load_data("app", "/index.html")
+      .await
+      .unwrap()
+      .unwrap();
+    let second = source
+      .load_data("app", "/index.html")
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(first, second);
+    assert_ne!(first.as_ptr(), second.as_ptr());
+  }
+
   #[tokio::test]
   async fn source_version_not_found() {
     let fixture = Fixtures::bundles();
@@ -485,4 +1008,282 @@ mod tests {
       assert!(Arc::ptr_eq(&after_jobs[0], m));
     }
   }
+
+  #[tokio::test]
+  async fn write_remote_bundle_while_mapped() {
+    let dir = TempDir::new("source_write_remote");
+    let remote_dir = dir.path().join("remote");
+    std::fs::create_dir_all(remote_dir.join("app")).unwrap();
+    let source = BundleSource::builder()
+      .builtin_dir(dir.path().join("builtin"))
+      .remote_dir(&remote_dir)
+      .build();
+    let bundle = |data: &[u8]| {
+      let mut builder = BundleBuilder::new();
+      builder.insert_entry("/index.html", BundleEntry::new(data, "text/html", None));
+      builder.build().unwrap()
+    };
+    let load = || async {
+      source
+        .load_data("app", "/index.html")
+        .await
+        .unwrap()
+        .unwrap()
+    };
+    source
+      .write_remote_bundle("app", "1.0.0", &bundle(b"first"), Default::default())
+      .await
+      .unwrap();
+    assert_eq!(load().await, &b"first"[..]);
+
+    // rewriting the version mapped by the source drops the cached mapping before replacing
+    // the file
+    source
+      .write_remote_bundle("app", "1.0.0", &bundle(b"second one"), Default::default())
+      .await
+      .unwrap();
+    assert_eq!(load().await, &b"second one"[..]);
+    assert!(!remote_dir.join("app/app_1.0.0.wvb.tmp").exists());
+
+    // a mapping held elsewhere keeps the file it was mapped from, which can't be replaced on
+    // Windows
+    let mapped = source.load_mapped("app").await.unwrap();
+    let written = source
+      .write_remote_bundle("app", "1.0.0", &bundle(b"third"), Default::default())
+      .await;
+    assert_eq!(
+      mapped.get_data("/index.html").unwrap().unwrap(),
+      &b"second one"[..]
+    );
+    if cfg!(windows) {
+      assert!(written.is_err());
+      assert_eq!(load().await, &b"second one"[..]);
+    } else {
+      written.unwrap();
+      assert_eq!(load().await, &b"third"[..]);
+    }
+    assert!(!remote_dir.join("app/app_1.0.0.wvb.tmp").exists());
+  }
+
+  #[tokio::test]
+  async fn prune() {
+    let dir = TempDir::new("source_prune");
+    let remote_dir = dir.path().join("remote");
+    let build = |retention| {
+      BundleSource::builder()
+        .builtin_dir(dir.path().join("builtin"))
+        .remote_dir(&remote_dir)
+        .retention(retention)
+        .build()
+    };
+    let source = build(RetentionPolicy::new().previous_versions(1));
+    std::fs::create_dir_all(remote_dir.join("app")).unwrap();
+    for version in ["1.0.0", "1.1.0", "1.2.0", "1.3.0"] {
+      let mut builder = BundleBuilder::new();
+      builder.insert_entry(
+        "/index.html",
+        BundleEntry::new(version.as_bytes(), "text/html", None),
+      );
+      let bundle = builder.build().unwrap();
+      source
+        .write_remote_bundle("app", version, &bundle, Default::default())
+        .await
+        .unwrap();
+    }
+    source.update_version("app", "1.1.0").await.unwrap();
+    std::fs::write(remote_dir.join("app/app_0.9.0.wvb"), b"orphan").unwrap();
+    std::fs::write(remote_dir.join("app/notes.txt"), b"not a bundle").unwrap();
+
+    let report = source.prune().await.unwrap();
+    let mut versions = report.versions.clone();
+    versions.sort();
+    // keeps the current version and the previous version, which a rollback restores, over
+    // newer versions
+    assert_eq!(
+      versions,
+      [
+        ("app".to_string(), "1.2.0".to_string()),
+        ("app".to_string(), "1.3.0".to_string())
+      ]
+    );
+    assert_eq!(report.orphans, [remote_dir.join("app/app_0.9.0.wvb")]);
+    assert!(report.freed_bytes > 6);
+    assert!(remote_dir.join("app/app_1.0.0.wvb").exists());
+    assert!(!remote_dir.join("app/app_1.2.0.wvb").exists());
+    assert!(!remote_dir.join("app/app_1.3.0.wvb").exists());
+    assert!(remote_dir.join("app/notes.txt").exists());
+    let bundle = source.fetch("app").await.unwrap();
+    assert_eq!(bundle.get_data("/index.html").unwrap().unwrap(), b"1.1.0");
+
+    // the manifest is saved
+    let source = build(RetentionPolicy::new().previous_versions(0));
+    let mut versions = source
+      .list_bundles()
+      .await
+      .unwrap()
+      .into_iter()
+      .map(|x| x.item.version)
+      .collect::<Vec<_>>();
+    versions.sort();
+    assert_eq!(versions, ["1.0.0", "1.1.0"]);
+    // the previous version is kept even when no previous version fits the policy
+    assert_eq!(source.prune().await.unwrap(), PruneReport::default());
+    assert!(remote_dir.join("app/app_1.0.0.wvb").exists());
+    assert!(remote_dir.join("app/app_1.1.0.wvb").exists());
+  }
+
+  #[tokio::test]
+  async fn rollback_and_confirmation() {
+    let dir = TempDir::new("source_rollback");
+    let bundle = |version: &str| {
+      let mut builder = BundleBuilder::new();
+      builder.insert_entry(
+        "/index.html",
+        BundleEntry::new(version.as_bytes(), "text/html", None),
+      );
+      builder.build().unwrap()
+    };
+    std::fs::create_dir_all(dir.path().join("builtin/app")).unwrap();
+    std::fs::create_dir_all(dir.path().join("remote/app")).unwrap();
+    let mut file = std::fs::File::create(dir.path().join("builtin/app/app_1.0.0.wvb")).unwrap();
+    crate::BundleWriter::new(&mut file)
+      .write(&bundle("1.0.0"))
+      .unwrap();
+    std::fs::write(
+      dir.path().join("builtin/manifest.json"),
+      r#"{"manifestVersion":1,"entries":{"app":{"versions":{"1.0.0":{}},"currentVersion":"1.0.0"}}}"#,
+    )
+    .unwrap();
+    // every source created is a run of the app, which launches the bundle
+    let launch = || {
+      BundleSource::builder()
+        .builtin_dir(dir.path().join("builtin"))
+        .remote_dir(dir.path().join("remote"))
+        .confirmation_launches(1)
+        .build()
+    };
+    let current = |source: BundleSource| async move {
+      let version = source.launch("app").await.unwrap().unwrap();
+      assert_eq!(source.launch("app").await.unwrap().unwrap(), version);
+      assert_eq!(source.load_version("app").await.unwrap().unwrap(), version);
+      let data = source
+        .fetch("app")
+        .await
+        .unwrap()
+        .get_data("/index.html")
+        .unwrap()
+        .unwrap();
+      assert_eq!(data, version.version.as_bytes());
+      version
+    };
+
+    // reverted to builtin if never marked healthy
+    let source = launch();
+    source
+      .write_remote_bundle("app", "2.0.0", &bundle("2.0.0"), Default::default())
+      .await
+      .unwrap();
+    assert_eq!(
+      current(launch()).await,
+      BundleSourceVersion::remote("2.0.0".to_string())
+    );
+    assert_eq!(
+      current(launch()).await,
+      BundleSourceVersion::builtin("1.0.0".to_string())
+    );
+    // the remote version stays in the manifest, so it isn't pruned as an orphan
+    let source = launch();
+    let remote = source
+      .list_bundles()
+      .await
+      .unwrap()
+      .into_iter()
+      .filter(|x| x.kind == BundleSourceKind::Remote)
+      .map(|x| (x.item.version, x.item.current))
+      .collect::<Vec<_>>();
+    assert_eq!(remote, [("2.0.0".to_string(), false)]);
+    assert!(source.prune().await.unwrap().orphans.is_empty());
+    assert!(dir.path().join("remote/app/app_2.0.0.wvb").exists());
+
+    // writing the version rolled back from again doesn't activate it
+    source
+      .write_remote_bundle("app", "2.0.0", &bundle("2.0.0"), Default::default())
+      .await
+      .unwrap();
+    assert_eq!(
+      current(launch()).await,
+      BundleSourceVersion::builtin("1.0.0".to_string())
+    );
+
+    // kept once marked healthy
+    source.update_version("app", "2.0.0").await.unwrap();
+    // loading the version without launching the bundle doesn't count a launch
+    for _ in 0..3 {
+      assert_eq!(
+        launch().load_version("app").await.unwrap(),
+        Some(BundleSourceVersion::remote("2.0.0".to_string()))
+      );
+    }
+    let source = launch();
+    assert_eq!(
+      current(launch()).await,
+      BundleSourceVersion::remote("2.0.0".to_string())
+    );
+    assert!(source.mark_healthy("app").await.unwrap());
+    assert!(!source.mark_healthy("app").await.unwrap());
+    for _ in 0..3 {
+      assert_eq!(
+        current(launch()).await,
+        BundleSourceVersion::remote("2.0.0".to_string())
+      );
+    }
+
+    // rolled back manually to the previous remote version, then to builtin
+    let source = launch();
+    source
+      .write_remote_bundle("app", "3.0.0", &bundle("3.0.0"), Default::default())
+      .await
+      .unwrap();
+    source.update_version("app", "3.0.0").await.unwrap();
+    assert_eq!(
+      current(launch()).await,
+      BundleSourceVersion::remote("3.0.0".to_string())
+    );
+    let source = launch();
+    assert_eq!(
+      source.rollback("app").await.unwrap(),
+      Some(BundleSourceVersion::remote("2.0.0".to_string()))
+    );
+    assert_eq!(
+      source.rollback("app").await.unwrap(),
+      Some(BundleSourceVersion::builtin("1.0.0".to_string()))
+    );
+    assert_eq!(
+      current(launch()).await,
+      BundleSourceVersion::builtin("1.0.0".to_string())
+    );
+    assert!(matches!(
+      source.rollback("app").await,
+      Err(crate::Error::BundleCannotBeRolledBack(_))
+    ));
+  }
+
+  #[test]
+  fn metadata_mismatch() {
+    let mut builder = BundleBuilder::new();
+    builder.set_version(Version::V2);
+    let descriptor = builder.build().unwrap().descriptor().clone();
+    check_metadata(&descriptor, "app", "1.0.0").unwrap();
+
+    builder.set_metadata(BundleMetadata::new("app", "1.0.0"));
+    let descriptor = builder.build().unwrap().descriptor().clone();
+    check_metadata(&descriptor, "app", "1.0.0").unwrap();
+    let err = check_metadata(&descriptor, "app", "1.1.0").unwrap_err();
+    assert!(matches!(
+      err,
+      crate::Error::BundleMetadataMismatch { expected, actual }
+        if expected == "app@1.1.0" && actual == "app@1.0.0"
+    ));
+    assert!(check_metadata(&descriptor, "other", "1.0.0").is_err());
+  }
 }
@@ -0,0 +1,774 @@
+use crate::block::EntryBlocks;
+use crate::builder::{merge_headers, BundleBuilderOptions};
+use crate::checksum::{make_checksum, write_checksum, CHECKSUM_LEN};
+use crate::codec::{Codec, CodecEncoder};
+use crate::digest::{DigestHasher, EntryDigest};
+use crate::encoding::{ContentEncoder, ContentEncoding};
+use crate::header::{Header, HeaderWriter};
+use crate::index::{EntryVariant, Index, IndexEntry, IndexWriter, IndexWriterOptions};
+use crate::metadata::BundleMetadata;
+use crate::version::Version;
+use crate::writer::Writer;
+use crate::BundleDescriptor;
+use http::HeaderMap;
+use std::hash::Hasher;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use twox_hash::XxHash32;
+
+#[cfg(feature = "async")]
+use crate::header::AsyncHeaderWriter;
+#[cfg(feature = "async")]
+use crate::index::AsyncIndexWriter;
+#[cfg(feature = "async")]
+use crate::writer::AsyncWriter;
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Streaming bundles are always written in `V2`, which locates the index after the data section.
+const STREAM_VERSION: Version = Version::V2;
+
+/// Bookkeeping shared by the sync and async stream writers.
+struct StreamState {
+  options: BundleBuilderOptions,
+  index: Index,
+  data_size: u64,
+  /// Set while an entry is being written, and left set if writing it fails, since its data may
+  /// be partially written and the offsets of later entries would be wrong.
+  poisoned: bool,
+}
+
+impl StreamState {
+  fn new(options: BundleBuilderOptions) -> Self {
+    Self {
+      options,
+      index: Index::new(),
+      data_size: 0,
+      poisoned: false,
+    }
+  }
+
+  fn check_poisoned(&self) -> crate::Result<()> {
+    if self.poisoned {
+      return Err(crate::Error::StreamWriterPoisoned);
+    }
+    Ok(())
+  }
+
+  fn begin_entry(&mut self, path: &str, content_type: &str) -> crate::Result<EntryStream> {
+    self.check_poisoned()?;
+    if self.index.contains_path(path) {
+      return Err(crate::Error::EntryAlreadyExists(path.to_string()));
+    }
+    let codec = self.options.resolve_codec(path, content_type);
+    // LZ4 entries are compressed as a whole, so they are split into blocks to bound the data
+    // buffered for a single entry
+    let block_size = self.options.resolve_block_size(codec);
+    let variants = self
+      .options
+      .resolve_precompress(content_type)
+      .iter()
+      .map(|encoding| Ok((*encoding, encoding.encoder()?, Vec::new())))
+      .collect::<crate::Result<Vec<_>>>()?;
+    let encoder = codec.encoder()?;
+    self.poisoned = true;
+    Ok(EntryStream {
+      codec,
+      encoder,
+      hasher: XxHash32::with_seed(self.options.data_checksum_seed),
+      digest: self
+        .options
+        .digest
+        .map(|algorithm| Ok::<_, crate::Error>((algorithm, algorithm.hasher()?)))
+        .transpose()?,
+      variants,
+      block_size,
+      blocks: Vec::new(),
+      block_len: 0,
+      block_content_length: 0,
+      len: 0,
+      content_length: 0,
+    })
+  }
+
+  /// Returns the bytes of precompressed variants, each followed by its checksum.
+  fn variants_bytes(&self, variants: &[(ContentEncoding, Vec<u8>)]) -> Vec<u8> {
+    let mut bytes = vec![];
+    for (_, encoded) in variants {
+      bytes.extend_from_slice(encoded);
+      bytes.extend(write_checksum(make_checksum(
+        self.options.data_checksum_seed,
+        encoded,
+      )));
+    }
+    bytes
+  }
+
+  fn end_entry(
+    &mut self,
+    path: String,
+    stream: EntryStream,
+    variants: &[(ContentEncoding, Vec<u8>)],
+    content_type: String,
+    headers: Option<HeaderMap>,
+  ) -> crate::Result<()> {
+    let mut entry = IndexEntry::new(
+      self.data_size,
+      stream.len,
+      content_type,
+      stream.content_length,
+    );
+    entry.codec = stream.codec;
+    entry.blocks = stream.blocks();
+    entry.digest = stream
+      .digest
+      .map(|(algorithm, hasher)| EntryDigest::new(algorithm, hasher.finalize()));
+    #[cfg(feature = "header-rules")]
+    {
+      entry.headers = self.options.header_rules.resolve(&path);
+    }
+    if let Some(headers) = headers {
+      merge_headers(&mut entry.headers, &headers);
+    }
+    self.data_size += stream.len + CHECKSUM_LEN as u64;
+    for (encoding, encoded) in variants {
+      let len = encoded.len() as u64;
+      let mut variant = EntryVariant::new(*encoding, self.data_size, len);
+      variant.digest = self
+        .options
+        .digest
+        .map(|algorithm| EntryDigest::try_compute(algorithm, encoded))
+        .transpose()?;
+      entry.variants.push(variant);
+      self.data_size += len + CHECKSUM_LEN as u64;
+    }
+    self.index.insert_entry(path, entry);
+    self.poisoned = false;
+    Ok(())
+  }
+
+  fn index_writer_options(&self) -> IndexWriterOptions {
+    let mut options = self.options.index;
+    options.version(STREAM_VERSION);
+    options
+  }
+
+  fn header(&self, index_len: usize) -> Header {
+    let mut header = Header::new(STREAM_VERSION, (index_len - CHECKSUM_LEN) as u32);
+    header.set_data_size(self.data_size);
+    header
+  }
+}
+
+/// Compresses and checksums a single entry chunk by chunk.
+struct EntryStream {
+  codec: Codec,
+  encoder: CodecEncoder,
+  hasher: XxHash32,
+  digest: Option<(crate::DigestAlgorithm, DigestHasher)>,
+  /// Precompressed variants are buffered and written after the entry data.
+  variants: Vec<(ContentEncoding, ContentEncoder, Vec<u8>)>,
+  block_size: Option<u64>,
+  /// Compressed lengths of the finished blocks.
+  blocks: Vec<u64>,
+  /// Compressed and original lengths of the current block.
+  block_len: u64,
+  block_content_length: u64,
+  len: u64,
+  content_length: u64,
+}
+
+impl EntryStream {
+  fn update(&mut self, chunk: &[u8]) -> crate::Result<Vec<u8>> {
+    self.content_length += chunk.len() as u64;
+    if let Some((_, hasher)) = self.digest.as_mut() {
+      hasher.update(chunk);
+    }
+    for (_, encoder, buf) in self.variants.iter_mut() {
+      buf.extend(encoder.update(chunk)?);
+    }
+    let Some(block_size) = self.block_size else {
+      let compressed = self.encoder.update(chunk)?;
+      self.consume(&compressed);
+      return Ok(compressed);
+    };
+    let mut compressed = vec![];
+    let mut rest = chunk;
+    while !rest.is_empty() {
+      // a full block is only finished when more data follows, so an entry which fits in a
+      // single block is compressed as a whole like in `BundleBuilder`
+      if self.block_content_length == block_size {
+        compressed.extend(self.finish_block()?);
+        self.encoder = self.codec.encoder()?;
+      }
+      let n = ((block_size - self.block_content_length) as usize).min(rest.len());
+      let produced = self.encoder.update(&rest[..n])?;
+      self.consume(&produced);
+      compressed.extend(produced);
+      self.block_content_length += n as u64;
+      rest = &rest[n..];
+    }
+    Ok(compressed)
+  }
+
+  /// Finishes the entry and returns the remaining compressed bytes followed by the checksum.
+  fn finish(&mut self) -> crate::Result<Vec<u8>> {
+    let mut bytes = self.finish_block()?;
+    bytes.extend(write_checksum(self.hasher.finish_32()));
+    Ok(bytes)
+  }
+
+  fn finish_block(&mut self) -> crate::Result<Vec<u8>> {
+    let encoder = std::mem::replace(&mut self.encoder, CodecEncoder::None);
+    let bytes = encoder.finish()?;
+    self.consume(&bytes);
+    self.blocks.push(self.block_len);
+    self.block_len = 0;
+    self.block_content_length = 0;
+    Ok(bytes)
+  }
+
+  /// Returns the block table, if the entry was split into more than one block.
+  fn blocks(&self) -> Option<EntryBlocks> {
+    match self.block_size {
+      Some(block_size) if self.blocks.len() > 1 => {
+        Some(EntryBlocks::new(block_size, self.blocks.clone()))
+      }
+      _ => None,
+    }
+  }
+
+  /// Finishes the precompressed variants, dropping those which are not smaller than the
+  /// original data.
+  fn finish_variants(&mut self) -> crate::Result<Vec<(ContentEncoding, Vec<u8>)>> {
+    let mut variants = vec![];
+    for (encoding, encoder, mut buf) in std::mem::take(&mut self.variants) {
+      buf.extend(encoder.finish()?);
+      if (buf.len() as u64) < self.content_length {
+        variants.push((encoding, buf));
+      }
+    }
+    Ok(variants)
+  }
+
+  fn consume(&mut self, compressed: &[u8]) {
+    self.hasher.write(compressed);
+    self.len += compressed.len() as u64;
+    self.block_len += compressed.len() as u64;
+  }
+}
+
+/// Writes a bundle entry by entry without holding the whole data section in memory.
+///
+/// Each entry is compressed and checksummed while it is streamed into the writer. The index is
+/// written after the data section when the writer is finished, and the header is written over
+/// the placeholder at the start position. Bundles are always written in `V2` format.
+///
+/// Unlike [`BundleBuilder`](crate::BundleBuilder), entries are never stored uncompressed as a
+/// fallback when compression does not reduce their size, because the compressed size is not
+/// known until the entry is fully written. LZ4 compresses an entry as a whole, so LZ4 entries
+/// are split into blocks of 1 MiB unless a block size is set in the options.
+///
+/// If writing an entry fails, its data may already be partially written, so the writer is
+/// poisoned and every further call fails with [`Error::StreamWriterPoisoned`].
+///
+/// [`Error::StreamWriterPoisoned`]: crate::Error::StreamWriterPoisoned
+///
+/// # Example
+///
+/// ```no_run
+/// use wvb::BundleStreamWriter;
+/// use std::fs::File;
+///
+/// let mut file = File::create("app.wvb").unwrap();
+/// let mut writer = BundleStreamWriter::new(&mut file).unwrap();
+/// writer.write_file("/index.html", "./dist/index.html", "text/html", None).unwrap();
+/// writer.write_entry("/app.js", &b"console.log('hello');"[..], "text/javascript", None).unwrap();
+/// let descriptor = writer.finish().unwrap();
+/// ```
+pub struct BundleStreamWriter<W: Write + Seek> {
+  w: W,
+  start: u64,
+  state: StreamState,
+}
+
+impl<W: Write + Seek> BundleStreamWriter<W> {
+  pub fn new(w: W) -> crate::Result<Self> {
+    Self::new_with_options(w, Default::default())
+  }
+
+  pub fn new_with_options(mut w: W, options: BundleBuilderOptions) -> crate::Result<Self> {
+    let start = w.stream_position()?;
+    w.write_all(&[0; Header::V2_END_OFFSET as usize])?;
+    Ok(Self {
+      w,
+      start,
+      state: StreamState::new(options),
+    })
+  }
+
+  /// Sets the bundle-level metadata stored in the index.
+  pub fn set_metadata(&mut self, metadata: BundleMetadata) -> &mut Self {
+    self.state.index.metadata = Some(metadata);
+    self
+  }
+
+  /// Streams an entry from the reader into the bundle.
+  pub fn write_entry<R: Read>(
+    &mut self,
+    path: impl Into<String>,
+    mut r: R,
+    content_type: impl Into<String>,
+    headers: Option<HeaderMap>,
+  ) -> crate::Result<()> {
+    let path = path.into();
+    let content_type = content_type.into();
+    let mut stream = self.state.begin_entry(&path, &content_type)?;
+    let mut buf = vec![0; CHUNK_SIZE];
+    loop {
+      let n = r.read(&mut buf)?;
+      if n == 0 {
+        break;
+      }
+      self.w.write_all(&stream.update(&buf[..n])?)?;
+    }
+    self.w.write_all(&stream.finish()?)?;
+    let variants = stream.finish_variants()?;
+    self.w.write_all(&self.state.variants_bytes(&variants))?;
+    self
+      .state
+      .end_entry(path, stream, &variants, content_type, headers)
+  }
+
+  /// Streams the file at `filepath` into the bundle.
+  pub fn write_file(
+    &mut self,
+    path: impl Into<String>,
+    filepath: impl AsRef<Path>,
+    content_type: impl Into<String>,
+    headers: Option<HeaderMap>,
+  ) -> crate::Result<()> {
+    let file = std::fs::File::open(filepath)?;
+    self.write_entry(path, file, content_type, headers)
+  }
+
+  /// Writes the index and the header, and returns the descriptor of the written bundle.
+  pub fn finish(mut self) -> crate::Result<BundleDescriptor> {
+    self.state.check_poisoned()?;
+    let index_len = IndexWriter::new_with_options(&mut self.w, self.state.index_writer_options())
+      .write(&self.state.index)?;
+    let header = self.state.header(index_len);
+    let end = self.w.stream_position()?;
+    self.w.seek(SeekFrom::Start(self.start))?;
+    HeaderWriter::new_with_options(&mut self.w, self.state.options.header).write(&header)?;
+    self.w.seek(SeekFrom::Start(end))?;
+    self.w.flush()?;
+    Ok(BundleDescriptor::new(header, self.state.index))
+  }
+}
+
+/// Async version of [`BundleStreamWriter`].
+#[cfg(feature = "async")]
+pub struct AsyncBundleStreamWriter<W: AsyncWrite + AsyncSeek + Unpin> {
+  w: W,
+  start: u64,
+  state: StreamState,
+}
+
+#[cfg(feature = "async")]
+impl<W: AsyncWrite + AsyncSeek + Unpin> AsyncBundleStreamWriter<W> {
+  pub async fn new(w: W) -> crate::Result<Self> {
+    Self::new_with_options(w, Default::default()).await
+  }
+
+  pub async fn new_with_options(mut w: W, options: BundleBuilderOptions) -> crate::Result<Self> {
+    let start = w.stream_position().await?;
+    w.write_all(&[0; Header::V2_END_OFFSET as usize]).await?;
+    Ok(Self {
+      w,
+      start,
+      state: StreamState::new(options),
+    })
+  }
+
+  /// Sets the bundle-level metadata stored in the index.
+  pub fn set_metadata(&mut self, metadata: BundleMetadata) -> &mut Self {
+    self.state.index.metadata = Some(metadata);
+    self
+  }
+
+  /// Streams an entry from the reader into the bundle.
+  pub async fn write_entry<R: AsyncRead + Unpin>(
+    &mut self,
+    path: impl Into<String>,
+    mut r: R,
+    content_type: impl Into<String>,
+    headers: Option<HeaderMap>,
+  ) -> crate::Result<()> {
+    let path = path.into();
+    let content_type = content_type.into();
+    let mut stream = self.state.begin_entry(&path, &content_type)?;
+    let mut buf = vec![0; CHUNK_SIZE];
+    loop {
+      let n = r.read(&mut buf).await?;
+      if n == 0 {
+        break;
+      }
+      self.w.write_all(&stream.update(&buf[..n])?).await?;
+    }
+    self.w.write_all(&stream.finish()?).await?;
+    let variants = stream.finish_variants()?;
+    self
+      .w
+      .write_all(&self.state.variants_bytes(&variants))
+      .await?;
+    self
+      .state
+      .end_entry(path, stream, &variants, content_type, headers)
+  }
+
+  /// Streams the file at `filepath` into the bundle.
+  pub async fn write_file(
+    &mut self,
+    path: impl Into<String>,
+    filepath: impl AsRef<Path>,
+    content_type: impl Into<String>,
+    headers: Option<HeaderMap>,
+  ) -> crate::Result<()> {
+    let file = tokio::fs::File::open(filepath).await?;
+    self.write_entry(path, file, content_type, headers).await
+  }
+
+  /// Writes the index and the header, and returns the descriptor of the written bundle.
+  pub async fn finish(mut self) -> crate::Result<BundleDescriptor> {
+    self.state.check_poisoned()?;
+    let index_len =
+      AsyncIndexWriter::new_with_options(&mut self.w, self.state.index_writer_options())
+        .write(&self.state.index)
+        .await?;
+    let header = self.state.header(index_len);
+    let end = self.w.stream_position().await?;
+    self.w.seek(SeekFrom::Start(self.start)).await?;
+    AsyncHeaderWriter::new_with_options(&mut self.w, self.state.options.header)
+      .write(&header)
+      .await?;
+    self.w.seek(SeekFrom::Start(end)).await?;
+    self.w.flush().await?;
+    Ok(BundleDescriptor::new(header, self.state.index))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::block::DEFAULT_BLOCK_SIZE;
+  use crate::{Bundle, BundleReader, Codec, Reader};
+  use std::io::Cursor;
+
+  const INDEX_HTML: &[u8] = br#"<html><body><script src="/app.js"></script></body></html>"#;
+  #[cfg(feature = "codec-zstd")]
+  const APP_JS: &[u8] = br#"function hello() { console.log('Hello World'); } hello(); hello();"#;
+
+  #[cfg(feature = "codec-zstd")]
+  fn options() -> BundleBuilderOptions {
+    let mut options = BundleBuilderOptions::new();
+    options
+      .data_checksum_seed(7)
+      .content_type_codec("text/javascript", Codec::Zstd)
+      .content_type_codec("image/*", Codec::None);
+    options
+  }
+
+  #[cfg(feature = "codec-zstd")]
+  #[test]
+  fn write_and_read() {
+    let large = (0..(CHUNK_SIZE * 3))
+      .map(|x| (x % 251) as u8)
+      .collect::<Vec<_>>();
+    let mut file = Cursor::new(Vec::new());
+    let mut writer = BundleStreamWriter::new_with_options(&mut file, options()).unwrap();
+    writer
+      .write_entry("/index.html", INDEX_HTML, "text/html", None)
+      .unwrap();
+    writer
+      .write_entry("/app.js", APP_JS, "text/javascript", None)
+      .unwrap();
+    writer
+      .write_entry("/image.png", &large[..], "image/png", None)
+      .unwrap();
+    let descriptor = writer.finish().unwrap();
+    assert_eq!(descriptor.header().version(), Version::V2);
+
+    file.set_position(0);
+    let bundle: Bundle = BundleReader::new(&mut file).read().unwrap();
+    assert_eq!(bundle.descriptor(), &descriptor);
+    assert_eq!(bundle.get_data("/index.html").unwrap().unwrap(), INDEX_HTML);
+    assert_eq!(bundle.get_data("/app.js").unwrap().unwrap(), APP_JS);
+    assert_eq!(bundle.get_data("/image.png").unwrap().unwrap(), large);
+    let index = bundle.descriptor().index();
+    assert_eq!(index.get_entry("/app.js").unwrap().codec(), Codec::Zstd);
+    assert_eq!(index.get_entry("/image.png").unwrap().codec(), Codec::None);
+  }
+
+  #[cfg(all(
+    feature = "header-rules",
+    feature = "codec-zstd",
+    feature = "codec-brotli",
+    feature = "digest",
+    feature = "precompress"
+  ))]
+  #[test]
+  fn same_as_builder() {
+    use crate::{BundleBuilder, BundleEntry};
+
+    let mut cache = HeaderMap::new();
+    cache.insert("cache-control", "max-age=31536000".parse().unwrap());
+    let mut rules = crate::HeaderRules::new();
+    rules.add(crate::HeaderRule::new("*.js", cache).unwrap());
+    let mut options = BundleBuilderOptions::new();
+    options
+      .header_rules(rules)
+      .data_checksum_seed(7)
+      .content_type_codec("text/javascript", Codec::Brotli)
+      .content_type_codec("text/html", Codec::Zstd)
+      .digest(crate::DigestAlgorithm::Sha3_256)
+      .precompress(ContentEncoding::Brotli)
+      .precompress(ContentEncoding::Gzip);
+    let mut builder = BundleBuilder::new_with_options(options.clone());
+    builder
+      .set_version(Version::V2)
+      .set_metadata(BundleMetadata::new("app", "1.0.0"));
+    builder.insert_entry(
+      "/index.html",
+      BundleEntry::new(INDEX_HTML, "text/html", None),
+    );
+    builder.insert_entry("/app.js", BundleEntry::new(APP_JS, "text/javascript", None));
+    let built = builder.build().unwrap();
+
+    let mut file = Cursor::new(Vec::new());
+    let mut writer = BundleStreamWriter::new_with_options(&mut file, options).unwrap();
+    writer.set_metadata(BundleMetadata::new("app", "1.0.0"));
+    writer
+      .write_entry("/app.js", APP_JS, "text/javascript", None)
+      .unwrap();
+    writer
+      .write_entry("/index.html", INDEX_HTML, "text/html", None)
+      .unwrap();
+    writer.finish().unwrap();
+
+    file.set_position(0);
+    let bundle: Bundle = BundleReader::new(&mut file).read().unwrap();
+    assert_eq!(bundle.descriptor(), built.descriptor());
+    assert_eq!(bundle, built);
+    let app_js = bundle.descriptor().index().get_entry("/app.js").unwrap();
+    assert!(app_js.variant(ContentEncoding::Brotli).is_some());
+    assert_eq!(app_js.headers()["cache-control"], "max-age=31536000");
+  }
+
+  #[cfg(feature = "codec-zstd")]
+  #[test]
+  fn blocks_same_as_builder() {
+    use crate::{BundleBuilder, BundleEntry};
+
+    let video = (0..10_000u32).map(|x| (x % 251) as u8).collect::<Vec<_>>();
+    let mut options = BundleBuilderOptions::new();
+    options
+      .codec(Codec::Zstd)
+      .content_type_codec("image/*", Codec::None)
+      .block_size(1024);
+    let mut builder = BundleBuilder::new_with_options(options.clone());
+    builder.set_version(Version::V2);
+    builder.insert_entry("/video.mp4", BundleEntry::new(&video, "video/mp4", None));
+    builder.insert_entry("/image.png", BundleEntry::new(&video, "image/png", None));
+    builder.insert_entry(
+      "/block.bin",
+      BundleEntry::new(&video[..1024], "video/mp4", None),
+    );
+    builder.insert_entry("/empty.txt", BundleEntry::new(b"", "text/plain", None));
+    let built = builder.build().unwrap();
+
+    let mut file = Cursor::new(Vec::new());
+    let mut writer = BundleStreamWriter::new_with_options(&mut file, options).unwrap();
+    // entries are written in the order of paths, like the builder lays them out
+    writer
+      .write_entry("/block.bin", &video[..1024], "video/mp4", None)
+      .unwrap();
+    writer
+      .write_entry("/empty.txt", &b""[..], "text/plain", None)
+      .unwrap();
+    writer
+      .write_entry("/image.png", &video[..], "image/png", None)
+      .unwrap();
+    writer
+      .write_entry("/video.mp4", &video[..], "video/mp4", None)
+      .unwrap();
+    writer.finish().unwrap();
+
+    file.set_position(0);
+    let bundle: Bundle = BundleReader::new(&mut file).read().unwrap();
+    assert_eq!(bundle, built);
+    let index = bundle.descriptor().index();
+    assert_eq!(
+      index
+        .get_entry("/video.mp4")
+        .unwrap()
+        .blocks()
+        .unwrap()
+        .len(),
+      10
+    );
+    assert_eq!(
+      index
+        .get_entry("/image.png")
+        .unwrap()
+        .blocks()
+        .unwrap()
+        .len(),
+      10
+    );
+    assert!(index.get_entry("/block.bin").unwrap().blocks().is_none());
+
+    // chunks which do not line up with blocks
+    let mut stream = StreamState::new(bundle_options_with_blocks())
+      .begin_entry("/video.mp4", "video/mp4")
+      .unwrap();
+    let mut bytes = vec![];
+    for chunk in video.chunks(700) {
+      bytes.extend(stream.update(chunk).unwrap());
+    }
+    bytes.extend(stream.finish().unwrap());
+    let entry = index.get_entry("/video.mp4").unwrap();
+    assert_eq!(stream.blocks().as_ref(), entry.blocks());
+    assert_eq!(bytes.len() as u64, entry.len() + CHECKSUM_LEN as u64);
+  }
+
+  #[cfg(feature = "codec-zstd")]
+  fn bundle_options_with_blocks() -> BundleBuilderOptions {
+    let mut options = BundleBuilderOptions::new();
+    options.codec(Codec::Zstd).block_size(1024);
+    options
+  }
+
+  #[test]
+  fn start_at_offset() {
+    let mut file = Cursor::new(b"prefix".to_vec());
+    file.set_position(6);
+    let mut writer = BundleStreamWriter::new(&mut file).unwrap();
+    writer
+      .write_entry("/index.html", INDEX_HTML, "text/html", None)
+      .unwrap();
+    writer.finish().unwrap();
+    assert_eq!(file.position(), file.get_ref().len() as u64);
+
+    let bytes = file.into_inner();
+    assert_eq!(&bytes[..6], b"prefix");
+    let bundle: Bundle = BundleReader::new(Cursor::new(&bytes[6..])).read().unwrap();
+    assert_eq!(bundle.get_data("/index.html").unwrap().unwrap(), INDEX_HTML);
+  }
+
+  #[test]
+  fn duplicate_entry() {
+    let mut file = Cursor::new(Vec::new());
+    let mut writer = BundleStreamWriter::new(&mut file).unwrap();
+    writer
+      .write_entry("/index.html", INDEX_HTML, "text/html", None)
+      .unwrap();
+    assert!(matches!(
+      writer.write_entry("/index.html", INDEX_HTML, "text/html", None),
+      Err(crate::Error::EntryAlreadyExists(path)) if path == "/index.html"
+    ));
+  }
+
+  struct FailingReader(usize);
+
+  impl Read for FailingReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+      if self.0 == 0 {
+        return Err(std::io::Error::other("failed"));
+      }
+      self.0 -= 1;
+      buf.fill(b'a');
+      Ok(buf.len())
+    }
+  }
+
+  #[test]
+  fn poisoned_after_failed_entry() {
+    let mut options = BundleBuilderOptions::new();
+    options.codec(Codec::None);
+    let mut file = Cursor::new(Vec::new());
+    let mut writer = BundleStreamWriter::new_with_options(&mut file, options).unwrap();
+    writer
+      .write_entry("/index.html", INDEX_HTML, "text/html", None)
+      .unwrap();
+    // the entry is written partially before the reader fails
+    assert!(matches!(
+      writer.write_entry(
+        "/large.bin",
+        FailingReader(2),
+        "application/octet-stream",
+        None
+      ),
+      Err(crate::Error::Io(_))
+    ));
+    assert!(matches!(
+      writer.write_entry("/index.html", INDEX_HTML, "text/html", None),
+      Err(crate::Error::StreamWriterPoisoned)
+    ));
+    assert!(matches!(
+      writer.finish(),
+      Err(crate::Error::StreamWriterPoisoned)
+    ));
+  }
+
+  #[test]
+  fn lz4_blocks() {
+    let large = (0..(DEFAULT_BLOCK_SIZE as usize * 2 + 100))
+      .map(|x| (x % 251) as u8)
+      .collect::<Vec<_>>();
+    let mut file = Cursor::new(Vec::new());
+    let mut writer = BundleStreamWriter::new(&mut file).unwrap();
+    writer
+      .write_entry("/large.bin", &large[..], "application/octet-stream", None)
+      .unwrap();
+    writer
+      .write_entry("/index.html", INDEX_HTML, "text/html", None)
+      .unwrap();
+    let descriptor = writer.finish().unwrap();
+    let index = descriptor.index();
+    let entry = index.get_entry("/large.bin").unwrap();
+    assert_eq!(entry.codec(), Codec::Lz4);
+    assert_eq!(entry.blocks().unwrap().len(), 3);
+    assert!(index.get_entry("/index.html").unwrap().blocks().is_none());
+
+    file.set_position(0);
+    let bundle: Bundle = BundleReader::new(&mut file).read().unwrap();
+    assert_eq!(bundle.get_data("/large.bin").unwrap().unwrap(), large);
+  }
+
+  #[cfg(all(feature = "async", feature = "codec-zstd"))]
+  #[tokio::test]
+  async fn async_write_and_read() {
+    let mut file = Cursor::new(Vec::new());
+    let mut writer = AsyncBundleStreamWriter::new_with_options(&mut file, options())
+      .await
+      .unwrap();
+    writer
+      .write_entry("/index.html", INDEX_HTML, "text/html", None)
+      .await
+      .unwrap();
+    writer
+      .write_entry("/app.js", APP_JS, "text/javascript", None)
+      .await
+      .unwrap();
+    let descriptor = writer.finish().await.unwrap();
+
+    file.set_position(0);
+    let bundle: Bundle = BundleReader::new(&mut file).read().unwrap();
+    assert_eq!(bundle.descriptor(), &descriptor);
+    assert_eq!(bundle.get_data("/index.html").unwrap().unwrap(), INDEX_HTML);
+    assert_eq!(bundle.get_data("/app.js").unwrap().unwrap(), APP_JS);
+  }
+}
@@ -1,3 +1,15 @@
 mod fixtures;
+mod temp;
 
 pub use fixtures::*;
+pub use temp::*;
+
+/// Codecs enabled in this build.
+pub const CODECS: &[crate::Codec] = &[
+  crate::Codec::None,
+  crate::Codec::Lz4,
+  #[cfg(feature = "codec-zstd")]
+  crate::Codec::Zstd,
+  #[cfg(feature = "codec-brotli")]
+  crate::Codec::Brotli,
+];
@@ -0,0 +1,29 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A temporary directory which is removed when dropped.
+pub struct TempDir {
+  path: PathBuf,
+}
+
+impl TempDir {
+  pub fn new(prefix: &str) -> Self {
+    let id = COUNTER.fetch_add(1, Ordering::SeqCst);
+    let path = std::env::temp_dir().join(format!("wvb-{prefix}-{}-{id}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+    Self { path }
+  }
+
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+}
+
+impl Drop for TempDir {
+  fn drop(&mut self) {
+    let _ = std::fs::remove_dir_all(&self.path);
+  }
+}
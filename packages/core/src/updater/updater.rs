@@ -3,9 +3,12 @@ use crate::integrity::{IntegrityChecker, IntegrityPolicy};
 use crate::remote::{ListRemoteBundleInfo, Remote, RemoteBundleInfo};
 #[cfg(feature = "signature")]
 use crate::signature::SignatureVerifier;
-use crate::source::{BundleManifestMetadata, BundleSource};
+use crate::source::{check_metadata, BundleManifestMetadata, BundleSource};
+use crate::{Bundle, BundleReader, Reader};
 use serde::{Deserialize, Serialize};
+use std::io::Cursor;
 use std::sync::Arc;
+use tokio::io::AsyncReadExt;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -48,6 +51,7 @@ impl From<&RemoteBundleInfo> for BundleManifestMetadata {
 #[non_exhaustive]
 pub struct UpdaterConfig {
   pub(crate) channel: Option<String>,
+  pub(crate) patch: bool,
   #[cfg(feature = "integrity")]
   pub(crate) integrity_checker: IntegrityChecker,
   #[cfg(feature = "integrity")]
@@ -66,6 +70,16 @@ impl UpdaterConfig {
     self
   }
 
+  /// Sets whether updates are downloaded as patches from the installed version when possible.
+  ///
+  /// The patch is applied to the installed bundle, and the patched bundle goes through the same
+  /// integrity and signature checks as a full download. Falls back to downloading the full
+  /// bundle if the remote has no such patch or the installed bundle doesn't match it.
+  pub fn patch(mut self, patch: bool) -> Self {
+    self.patch = patch;
+    self
+  }
+
   #[cfg(feature = "integrity")]
   pub fn integrity_checker(mut self, checker: IntegrityChecker) -> Self {
     self.integrity_checker = checker;
@@ -125,20 +139,22 @@ impl Updater {
     bundle_name: impl Into<String>,
     version: Option<impl Into<String>>,
   ) -> crate::Result<RemoteBundleInfo> {
-    let (info, bundle, data) = match version {
-      Some(ver) => {
+    let bundle_name = bundle_name.into();
+    let version = version.map(Into::into);
+    let patched = self
+      .download_patched(&bundle_name, version.as_deref())
+      .await?;
+    #[cfg_attr(not(feature = "integrity"), allow(unused_variables))]
+    let (info, bundle, data) = match (patched, version) {
+      (Some(patched), _) => patched,
+      (None, Some(ver)) => self.remote.download_version(&bundle_name, &ver).await?,
+      (None, None) => {
         self
           .remote
-          .download_version(&bundle_name.into(), &ver.into())
-          .await
+          .download(&bundle_name, self.config.channel.as_ref())
+          .await?
       }
-      None => {
-        self
-          .remote
-          .download(&bundle_name.into(), self.config.channel.as_ref())
-          .await
-      }
-    }?;
+    };
     #[cfg(feature = "integrity")]
     {
       match self.config.integrity_policy {
@@ -178,6 +194,7 @@ impl Updater {
         }
       }
     }
+    check_metadata(bundle.descriptor(), &info.name, &info.version)?;
     self
       .source
       .write_remote_bundle(
@@ -190,13 +207,69 @@ impl Updater {
     Ok(info)
   }
 
+  /// Downloads a patch from the installed version and applies it, if patches are enabled.
+  ///
+  /// Returns `None` if the bundle should be downloaded in full instead.
+  async fn download_patched(
+    &self,
+    bundle_name: &str,
+    version: Option<&str>,
+  ) -> crate::Result<Option<(RemoteBundleInfo, Bundle, Vec<u8>)>> {
+    if !self.config.patch {
+      return Ok(None);
+    }
+    let Some(local) = self.source.load_version(bundle_name).await? else {
+      return Ok(None);
+    };
+    // there is no patch from a version to itself
+    if version == Some(local.version.as_str()) {
+      return Ok(None);
+    }
+    let downloaded = match version {
+      Some(ver) => {
+        self
+          .remote
+          .download_version_patch(bundle_name, ver, &local.version)
+          .await
+      }
+      None => {
+        self
+          .remote
+          .download_patch(bundle_name, &local.version, self.config.channel.as_ref())
+          .await
+      }
+    };
+    let (info, patch) = match downloaded {
+      Ok(x) => x,
+      Err(crate::Error::RemoteBundleNotFound) => return Ok(None),
+      Err(e) => return Err(e),
+    };
+    let mut old = vec![];
+    self
+      .source
+      .reader(bundle_name)
+      .await?
+      .read_to_end(&mut old)
+      .await?;
+    let data = match patch.apply(&old) {
+      Ok(data) => data,
+      Err(crate::Error::PatchSourceMismatch) => return Ok(None),
+      Err(e) => return Err(e),
+    };
+    let bundle = BundleReader::new(Cursor::new(&data)).read()?;
+    Ok(Some((info, bundle, data)))
+  }
+
   async fn to_update_info(&self, info: RemoteBundleInfo) -> crate::Result<BundleUpdateInfo> {
     let local_version = self.source.load_version(&info.name).await?;
-    let is_available = if let Some(ref local_ver) = local_version {
-      local_ver.version != info.version
-    } else {
-      true
-    };
+    // a version rolled back from isn't offered again
+    let is_available = match local_version {
+      Some(ref local_ver) => local_ver.version != info.version,
+      None => true,
+    } && !self
+      .source
+      .is_failed_version(&info.name, &info.version)
+      .await?;
     Ok(BundleUpdateInfo {
       name: info.name,
       version: info.version,
@@ -0,0 +1,669 @@
+use crate::block::decompress_entry;
+use crate::checksum::{make_checksum, parse_checksum, CHECKSUM_LEN};
+use crate::codec::Codec;
+use crate::digest::{verify_digest, EntryDigest};
+use crate::encoding::ContentEncoding;
+use crate::header::{HeaderReader, HeaderReaderOptions};
+use crate::index::{Index, IndexEntry, IndexReader, IndexReaderOptions};
+use crate::limits::read_exact_vec;
+use crate::reader::Reader;
+use crate::version::Version;
+use crate::{Bundle, BundleDescriptor};
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Seek, SeekFrom};
+
+#[cfg(feature = "async")]
+use crate::limits::async_read_exact_vec;
+#[cfg(feature = "async")]
+use crate::{AsyncHeaderReader, AsyncIndexReader, AsyncReader};
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, AsyncSeek, AsyncSeekExt};
+
+/// Result of verifying a whole bundle.
+///
+/// Verification does not stop at the first problem: every failing check is collected as a
+/// [`VerifyIssue`], so a single run lists every corrupt entry of a bundle.
+///
+/// # Example
+///
+/// ```no_run
+/// # use wvb::{BundleDescriptor, BundleReader, Reader};
+/// let mut file = std::fs::File::open("app.wvb").unwrap();
+/// let descriptor: BundleDescriptor = BundleReader::new(&mut file).read().unwrap();
+/// let report = descriptor.verify(&mut file).unwrap();
+/// for issue in report.issues() {
+///     eprintln!("{issue}");
+/// }
+/// assert!(report.is_ok());
+/// ```
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct VerifyReport {
+  entries: usize,
+  issues: Vec<VerifyIssue>,
+}
+
+impl VerifyReport {
+  /// Returns the number of entries checked.
+  pub fn entries(&self) -> usize {
+    self.entries
+  }
+
+  /// Returns every failing check.
+  pub fn issues(&self) -> &[VerifyIssue] {
+    &self.issues
+  }
+
+  /// Returns `true` if every check passed.
+  pub fn is_ok(&self) -> bool {
+    self.issues.is_empty()
+  }
+
+  /// Returns the paths of entries with at least one issue, in order.
+  pub fn failed_paths(&self) -> Vec<&str> {
+    let mut paths = self
+      .issues
+      .iter()
+      .filter_map(|x| x.path())
+      .collect::<Vec<_>>();
+    paths.dedup();
+    paths
+  }
+}
+
+/// A failing check found while verifying a bundle.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct VerifyIssue {
+  path: Option<String>,
+  encoding: Option<ContentEncoding>,
+  kind: VerifyIssueKind,
+}
+
+impl VerifyIssue {
+  fn bundle(kind: VerifyIssueKind) -> Self {
+    Self {
+      path: None,
+      encoding: None,
+      kind,
+    }
+  }
+
+  fn entry(path: &str, encoding: Option<ContentEncoding>, kind: VerifyIssueKind) -> Self {
+    Self {
+      path: Some(path.to_string()),
+      encoding,
+      kind,
+    }
+  }
+
+  /// Returns the path of the failing entry, or `None` for issues of the header or index.
+  pub fn path(&self) -> Option<&str> {
+    self.path.as_deref()
+  }
+
+  /// Returns the encoding of the failing precompressed variant, if the issue concerns one.
+  pub fn encoding(&self) -> Option<ContentEncoding> {
+    self.encoding
+  }
+
+  /// Returns the reason of the issue.
+  pub fn kind(&self) -> &VerifyIssueKind {
+    &self.kind
+  }
+}
+
+impl Display for VerifyIssue {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match (&self.path, self.encoding) {
+      (Some(path), Some(encoding)) => write!(f, "{path} ({encoding}): {}", self.kind),
+      (Some(path), None) => write!(f, "{path}: {}", self.kind),
+      (None, _) => write!(f, "{}", self.kind),
+    }
+  }
+}
+
+/// Reason of a [`VerifyIssue`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum VerifyIssueKind {
+  /// The header checksum doesn't match.
+  InvalidHeaderChecksum,
+  /// The index checksum doesn't match.
+  InvalidIndexChecksum,
+  /// The index entry fails to decode.
+  InvalidEntry,
+  /// The stored data lies outside the data section.
+  OutOfBounds,
+  /// The stored data overlaps the data of another entry.
+  Overlap(String),
+  /// The xxHash-32 checksum of the stored data doesn't match.
+  ChecksumMismatch,
+  /// The stored data fails to decompress, or decompresses to another length.
+  Decompress(String),
+  /// The decompressed data, or the encoded data of a variant, doesn't match the digest in the
+  /// index.
+  DigestMismatch,
+}
+
+impl Display for VerifyIssueKind {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::InvalidHeaderChecksum => f.write_str("invalid header checksum"),
+      Self::InvalidIndexChecksum => f.write_str("invalid index checksum"),
+      Self::InvalidEntry => f.write_str("invalid index entry"),
+      Self::OutOfBounds => f.write_str("data out of bounds"),
+      Self::Overlap(path) => write!(f, "data overlaps {path}"),
+      Self::ChecksumMismatch => f.write_str("checksum mismatch"),
+      Self::Decompress(reason) => write!(f, "fail to decompress: {reason}"),
+      Self::DigestMismatch => f.write_str("digest mismatch"),
+    }
+  }
+}
+
+/// Stored data of an entry or of one of its variants, followed by its checksum.
+struct Region<'a> {
+  path: &'a str,
+  entry: &'a IndexEntry,
+  encoding: Option<ContentEncoding>,
+  offset: u64,
+  len: u64,
+}
+
+impl Region<'_> {
+  fn end(&self) -> u64 {
+    self.offset.saturating_add(self.len)
+  }
+
+  fn issue(&self, kind: VerifyIssueKind) -> VerifyIssue {
+    VerifyIssue::entry(self.path, self.encoding, kind)
+  }
+}
+
+/// Checks of a bundle which don't depend on how its data section is read.
+struct Verifier<'a> {
+  report: VerifyReport,
+  regions: Vec<Region<'a>>,
+}
+
+impl<'a> Verifier<'a> {
+  /// Lays out every entry of the index, reporting data out of bounds or overlapping.
+  fn new(index: &'a Index, data_size: u64) -> Self {
+    let mut entries = index.entries().iter().collect::<Vec<_>>();
+    entries.sort_unstable_by_key(|(path, _)| path.as_str());
+    let mut report = VerifyReport {
+      entries: index.len(),
+      issues: vec![],
+    };
+    for path in index.invalid_paths() {
+      let issue = VerifyIssue::entry(path, None, VerifyIssueKind::InvalidEntry);
+      report.issues.push(issue);
+    }
+    let mut regions = vec![];
+    for (path, entry) in entries {
+      regions.push(Region {
+        path,
+        entry,
+        encoding: None,
+        offset: entry.offset(),
+        len: entry.len().saturating_add(CHECKSUM_LEN as u64),
+      });
+      for variant in entry.variants() {
+        regions.push(Region {
+          path,
+          entry,
+          encoding: Some(variant.encoding()),
+          offset: variant.offset(),
+          len: variant.len().saturating_add(CHECKSUM_LEN as u64),
+        });
+      }
+    }
+    regions.sort_by_key(|x| x.offset);
+    let mut last: Option<&Region> = None;
+    for region in &regions {
+      if region.end() > data_size {
+        report
+          .issues
+          .push(region.issue(VerifyIssueKind::OutOfBounds));
+      }
+      match last {
+        // identical ranges are data shared by deduplicated entries
+        Some(prev) if prev.offset == region.offset && prev.len == region.len => {}
+        Some(prev) if prev.end() > region.offset => {
+          let overlap = VerifyIssueKind::Overlap(prev.path.to_string());
+          report.issues.push(region.issue(overlap));
+          if region.end() > prev.end() {
+            last = Some(region);
+          }
+        }
+        _ => last = Some(region),
+      }
+    }
+    regions.retain(|x| x.end() <= data_size);
+    regions.sort_by_key(|x| (x.path, x.encoding));
+    Self { report, regions }
+  }
+
+  /// Checks the stored data of a region, read with its checksum.
+  fn check(&mut self, region: &Region, buf: &[u8]) {
+    let (bytes, checksum) = buf.split_at(buf.len() - CHECKSUM_LEN);
+    if make_checksum(0, bytes) != parse_checksum(checksum) {
+      self.push(region, VerifyIssueKind::ChecksumMismatch);
+      return;
+    }
+    let entry = region.entry;
+    // variants are sent as stored, so their encoded data is checked against their digest
+    if let Some(encoding) = region.encoding {
+      let variant = entry.variant(encoding);
+      if digest_mismatch(variant.and_then(|x| x.digest()), bytes) {
+        self.push(region, VerifyIssueKind::DigestMismatch);
+      }
+      return;
+    }
+    let data = match entry.codec() {
+      Codec::None => bytes.to_vec(),
+      _ => match decompress_entry(entry, bytes) {
+        Ok(data) => data,
+        Err(e) => {
+          self.push(region, VerifyIssueKind::Decompress(e.to_string()));
+          return;
+        }
+      },
+    };
+    if data.len() as u64 != entry.content_length() {
+      let reason = format!(
+        "expected {} bytes, got {}",
+        entry.content_length(),
+        data.len()
+      );
+      self.push(region, VerifyIssueKind::Decompress(reason));
+      return;
+    }
+    if digest_mismatch(entry.digest(), &data) {
+      self.push(region, VerifyIssueKind::DigestMismatch);
+    }
+  }
+
+  fn push(&mut self, region: &Region, kind: VerifyIssueKind) {
+    self.report.issues.push(region.issue(kind));
+  }
+
+  fn finish(mut self) -> VerifyReport {
+    // header and index issues first, then entries in the order of paths
+    self
+      .report
+      .issues
+      .sort_by(|a, b| (&a.path, a.encoding).cmp(&(&b.path, b.encoding)));
+    self.report
+  }
+}
+
+/// Checks the header and index checksums by reading them again from the reader.
+fn verify_sections<R: Read + Seek>(reader: &mut R) -> crate::Result<Vec<VerifyIssue>> {
+  let mut issues = vec![];
+  let options = HeaderReaderOptions::new().verify_checksum(true);
+  if let Err(e) = HeaderReader::new_with_options(&mut *reader, options).read() {
+    match e {
+      crate::Error::InvalidHeaderChecksum => {
+        issues.push(VerifyIssue::bundle(VerifyIssueKind::InvalidHeaderChecksum));
+      }
+      e => return Err(e),
+    }
+  }
+  let header = HeaderReader::new(&mut *reader).read()?;
+  let options = IndexReaderOptions::new().verify_checksum(true);
+  if let Err(e) = IndexReader::new_with_options(&mut *reader, header, options).read() {
+    match e {
+      crate::Error::InvalidIndexChecksum => {
+        issues.push(VerifyIssue::bundle(VerifyIssueKind::InvalidIndexChecksum));
+      }
+      e => return Err(e),
+    }
+  }
+  Ok(issues)
+}
+
+#[cfg(feature = "async")]
+async fn async_verify_sections<R: AsyncRead + AsyncSeek + Unpin>(
+  reader: &mut R,
+) -> crate::Result<Vec<VerifyIssue>> {
+  let mut issues = vec![];
+  let options = HeaderReaderOptions::new().verify_checksum(true);
+  if let Err(e) = AsyncHeaderReader::new_with_options(&mut *reader, options)
+    .read()
+    .await
+  {
+    match e {
+      crate::Error::InvalidHeaderChecksum => {
+        issues.push(VerifyIssue::bundle(VerifyIssueKind::InvalidHeaderChecksum));
+      }
+      e => return Err(e),
+    }
+  }
+  let header = AsyncHeaderReader::new(&mut *reader).read().await?;
+  let options = IndexReaderOptions::new().verify_checksum(true);
+  if let Err(e) = AsyncIndexReader::new_with_options(&mut *reader, header, options)
+    .read()
+    .await
+  {
+    match e {
+      crate::Error::InvalidIndexChecksum => {
+        issues.push(VerifyIssue::bundle(VerifyIssueKind::InvalidIndexChecksum));
+      }
+      e => return Err(e),
+    }
+  }
+  Ok(issues)
+}
+
+impl BundleDescriptor {
+  /// Verifies the whole bundle from the provided reader.
+  ///
+  /// Checks the header and index checksums, that every index entry decodes, that the data of
+  /// every entry and variant lies within the data section without overlapping another, and
+  /// that it matches its checksum.
+  /// Entry data is also decompressed and checked against the content length and the digest in
+  /// the index, if any. Digests are only checked with the `digest` feature.
+  ///
+  /// Corrupt data is reported in the returned [`VerifyReport`] instead of failing. Errors are
+  /// only returned if the reader fails or the bundle can't be parsed at all.
+  pub fn verify<R: Read + Seek>(&self, mut reader: R) -> crate::Result<VerifyReport> {
+    let issues = verify_sections(&mut reader)?;
+    let data_offset = self.header.data_offset();
+    let data_size = match self.header.data_size() {
+      Some(size) => size,
+      None => reader.seek(SeekFrom::End(0))?.saturating_sub(data_offset),
+    };
+    let mut verifier = Verifier::new(&self.index, data_size);
+    verifier.report.issues.extend(issues);
+    let regions = std::mem::take(&mut verifier.regions);
+    for region in &regions {
+      reader.seek(SeekFrom::Start(data_offset + region.offset))?;
+      let buf = read_exact_vec(&mut reader, region.len)?;
+      verifier.check(region, &buf);
+    }
+    Ok(verifier.finish())
+  }
+
+  /// Verifies the whole bundle from the provided async reader.
+  ///
+  /// See [`BundleDescriptor::verify`].
+  #[cfg(feature = "async")]
+  pub async fn async_verify<R: AsyncRead + AsyncSeek + Unpin>(
+    &self,
+    mut reader: R,
+  ) -> crate::Result<VerifyReport> {
+    let issues = async_verify_sections(&mut reader).await?;
+    let data_offset = self.header.data_offset();
+    let data_size = match self.header.data_size() {
+      Some(size) => size,
+      None => reader
+        .seek(SeekFrom::End(0))
+        .await?
+        .saturating_sub(data_offset),
+    };
+    let mut verifier = Verifier::new(&self.index, data_size);
+    verifier.report.issues.extend(issues);
+    let regions = std::mem::take(&mut verifier.regions);
+    for region in &regions {
+      reader
+        .seek(SeekFrom::Start(data_offset + region.offset))
+        .await?;
+      let buf = async_read_exact_vec(&mut reader, region.len).await?;
+      verifier.check(region, &buf);
+    }
+    Ok(verifier.finish())
+  }
+}
+
+impl Bundle {
+  /// Verifies every entry of the bundle.
+  ///
+  /// Runs the entry checks of [`BundleDescriptor::verify`] against the data in memory. The
+  /// header and index are held decoded by a `Bundle`, so their checksums can only be checked
+  /// from the bundle file with [`BundleDescriptor::verify`].
+  pub fn verify(&self) -> VerifyReport {
+    let data_size = match self.descriptor.header.version() {
+      Version::V1 => self.data.len() as u64,
+      _ => self
+        .descriptor
+        .header
+        .data_size()
+        .unwrap_or_default()
+        .min(self.data.len() as u64),
+    };
+    let mut verifier = Verifier::new(&self.descriptor.index, data_size);
+    let regions = std::mem::take(&mut verifier.regions);
+    for region in &regions {
+      let buf = &self.data[region.offset as usize..region.end() as usize];
+      verifier.check(region, buf);
+    }
+    verifier.finish()
+  }
+}
+
+/// Returns whether data doesn't match a digest.
+///
+/// Without the `digest` feature, digests can't be computed and never mismatch.
+fn digest_mismatch(digest: Option<&EntryDigest>, data: &[u8]) -> bool {
+  matches!(
+    verify_digest(digest, data),
+    Err(crate::Error::DigestMismatch)
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    BundleBuilder, BundleBuilderOptions, BundleEntry, BundleReader, BundleWriter, Header, Writer,
+  };
+  use std::io::Cursor;
+
+  const INDEX_HTML: &str = r#"<html><body><h1>Hello World</h1></body></html>"#;
+  const INDEX_JS: &str = r#"console.log('Hello World'); console.log('Hello World');"#;
+
+  fn bundle(version: Version) -> Bundle {
+    #[cfg_attr(
+      not(any(feature = "digest", feature = "precompress")),
+      allow(unused_mut)
+    )]
+    let mut options = BundleBuilderOptions::new();
+    if version == Version::V2 {
+      #[cfg(feature = "digest")]
+      options.digest(crate::DigestAlgorithm::Sha3_256);
+      #[cfg(feature = "precompress")]
+      options.precompress(ContentEncoding::Brotli);
+    }
+    let mut builder = BundleBuilder::new_with_options(options);
+    builder.set_version(version);
+    builder.insert_entry(
+      "/index.html",
+      BundleEntry::new(INDEX_HTML.repeat(8).as_bytes(), "text/html", None),
+    );
+    builder.insert_entry(
+      "/index.js",
+      BundleEntry::new(INDEX_JS.as_bytes(), "text/javascript", None),
+    );
+    builder.build().unwrap()
+  }
+
+  fn write(bundle: &Bundle) -> Vec<u8> {
+    let mut buf = vec![];
+    BundleWriter::new(Cursor::new(&mut buf))
+      .write(bundle)
+      .unwrap();
+    buf
+  }
+
+  #[test]
+  fn verify() {
+    for version in [Version::V1, Version::V2] {
+      let bundle = bundle(version);
+      let report = bundle.verify();
+      assert!(report.is_ok(), "{version}: {:?}", report.issues());
+      assert_eq!(report.entries(), 2);
+
+      let buf = write(&bundle);
+      let descriptor: BundleDescriptor = BundleReader::new(Cursor::new(&buf)).read().unwrap();
+      let report = descriptor.verify(Cursor::new(&buf)).unwrap();
+      assert!(report.is_ok(), "{version}: {:?}", report.issues());
+    }
+  }
+
+  #[cfg(feature = "async")]
+  #[tokio::test]
+  async fn async_verify() {
+    let buf = write(&bundle(Version::V2));
+    let descriptor: BundleDescriptor = BundleReader::new(Cursor::new(&buf)).read().unwrap();
+    let report = descriptor.async_verify(Cursor::new(&buf)).await.unwrap();
+    assert!(report.is_ok());
+    assert_eq!(report.entries(), 2);
+  }
+
+  fn flip_byte(bundle: &mut Bundle, offset: u64) {
+    let mut data = bundle.data.to_vec();
+    data[offset as usize] ^= 0xff;
+    bundle.data = data.into();
+  }
+
+  #[test]
+  fn corrupt_entry() {
+    let mut bundle = bundle(Version::V2);
+    let entry = bundle
+      .descriptor()
+      .index()
+      .get_entry("/index.js")
+      .unwrap()
+      .clone();
+    flip_byte(&mut bundle, entry.offset());
+    let report = bundle.verify();
+    assert_eq!(report.failed_paths(), ["/index.js"]);
+    assert_eq!(
+      report.issues()[0].kind(),
+      &VerifyIssueKind::ChecksumMismatch
+    );
+  }
+
+  #[cfg(feature = "precompress")]
+  #[test]
+  fn corrupt_variant() {
+    // a corrupt variant is reported on its own
+    let mut bundle = bundle(Version::V2);
+    let variant = bundle
+      .descriptor()
+      .index()
+      .get_entry("/index.html")
+      .unwrap()
+      .variant(ContentEncoding::Brotli)
+      .cloned()
+      .unwrap();
+    flip_byte(&mut bundle, variant.offset());
+    let report = bundle.verify();
+    assert_eq!(report.issues().len(), 1);
+    assert_eq!(report.issues()[0].path(), Some("/index.html"));
+    assert_eq!(report.issues()[0].encoding(), Some(ContentEncoding::Brotli));
+  }
+
+  #[test]
+  fn corrupt_sections() {
+    let bundle = bundle(Version::V2);
+    let mut buf = write(&bundle);
+    let descriptor: BundleDescriptor = BundleReader::new(Cursor::new(&buf)).read().unwrap();
+    let index_offset = descriptor.header().index_offset() as usize;
+    // header checksum
+    buf[Header::V2_END_OFFSET as usize - 1] ^= 0xff;
+    // a byte of the index checksum
+    let checksum_offset = index_offset + descriptor.header().index_size() as usize;
+    buf[checksum_offset] ^= 0xff;
+    let report = descriptor.verify(Cursor::new(&buf)).unwrap();
+    let kinds = report
+      .issues()
+      .iter()
+      .map(|x| x.kind().clone())
+      .collect::<Vec<_>>();
+    assert_eq!(
+      kinds,
+      [
+        VerifyIssueKind::InvalidHeaderChecksum,
+        VerifyIssueKind::InvalidIndexChecksum
+      ]
+    );
+  }
+
+  #[test]
+  fn undecodable_entry() {
+    let mut buf = write(&bundle(Version::V2));
+    let descriptor: BundleDescriptor = BundleReader::new(Cursor::new(&buf)).read().unwrap();
+    let index_offset = descriptor.header().index_offset() as usize;
+    // the entry of the first path ends where it starts
+    let end = index_offset + 4 + 2 * 4;
+    buf[end..end + 4].copy_from_slice(&0u32.to_be_bytes());
+    let descriptor: BundleDescriptor = BundleReader::new(Cursor::new(&buf)).read().unwrap();
+    let report = descriptor.verify(Cursor::new(&buf)).unwrap();
+    assert_eq!(report.entries(), 2);
+    let issue = report
+      .issues()
+      .iter()
+      .find(|x| x.kind() == &VerifyIssueKind::InvalidEntry)
+      .unwrap();
+    assert_eq!(issue.path(), Some("/index.html"));
+  }
+
+  #[test]
+  fn invalid_layout() {
+    let mut bundle = bundle(Version::V2);
+    let js = bundle
+      .descriptor()
+      .index()
+      .get_entry("/index.js")
+      .unwrap()
+      .clone();
+    // shared data is legitimate, a partial overlap is not
+    let shared = IndexEntry::new(
+      js.offset(),
+      js.len(),
+      "text/javascript",
+      js.content_length(),
+    );
+    bundle.descriptor.index.insert_entry("/shared.js", shared);
+    let copy = IndexEntry::new(
+      js.offset() + 1,
+      js.len() - 1,
+      "text/javascript",
+      js.content_length(),
+    );
+    bundle.descriptor.index.insert_entry("/copy.js", copy);
+    let far = IndexEntry::new(
+      bundle.data.len() as u64,
+      js.len(),
+      "text/javascript",
+      js.content_length(),
+    );
+    bundle.descriptor.index.insert_entry("/far.js", far);
+    let report = bundle.verify();
+    let issues = report
+      .issues()
+      .iter()
+      .map(|x| (x.path().unwrap(), x.kind().clone()))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      issues,
+      [
+        (
+          "/copy.js",
+          VerifyIssueKind::Overlap("/index.js".to_string())
+        ),
+        ("/copy.js", VerifyIssueKind::ChecksumMismatch),
+        ("/far.js", VerifyIssueKind::OutOfBounds),
+      ]
+    );
+    assert_eq!(report.entries(), 5);
+  }
+
+  #[test]
+  fn fixtures() {
+    let path = crate::testing::Fixtures::bundles().get_path("builtin/app/app_1.0.0.wvb");
+    let buf = std::fs::read(path).unwrap();
+    let descriptor: BundleDescriptor = BundleReader::new(Cursor::new(&buf)).read().unwrap();
+    let report = descriptor.verify(Cursor::new(&buf)).unwrap();
+    assert!(report.is_ok(), "{:?}", report.issues());
+  }
+}
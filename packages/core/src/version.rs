@@ -8,12 +8,17 @@ pub enum Version {
   /// Version 1
   #[default]
   V1,
+  /// Version 2
+  ///
+  /// Records a compression codec for each entry in the index.
+  V2,
 }
 
 impl Version {
   pub const fn bytes(&self) -> [u8; VERSION_LEN] {
     match self {
       Version::V1 => [0x01],
+      Version::V2 => [0x02],
     }
   }
 }
@@ -22,6 +27,7 @@ impl Display for Version {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
     let s = match self {
       Self::V1 => "v1",
+      Self::V2 => "v2",
     };
     f.write_str(s)
   }
@@ -39,10 +45,12 @@ mod tests {
   #[test]
   fn bytes() {
     assert_eq!(Version::V1.bytes(), [0x01]);
+    assert_eq!(Version::V2.bytes(), [0x02]);
   }
 
   #[test]
   fn display() {
     assert_eq!(format!("{}", Version::V1), "v1");
+    assert_eq!(format!("{}", Version::V2), "v2");
   }
 }
@@ -369,6 +369,54 @@ export declare class BundleSource {
    * ```
    */
   updateVersion(bundleName: string, version: string): Promise<void>
+  /**
+   * Rolls a bundle back to its previous version.
+   *
+   * Switches from the current remote version to the version which was current
+   * before it, or to the builtin version if there is no such remote version.
+   *
+   * @param {string} bundleName - Name of the bundle
+   * @returns {Promise<BundleSourceVersion | null>} Version in use after the rollback
+   *
+   * @example
+   * ```typescript
+   * const version = await source.rollback("app");
+   * console.log(`Rolled back to ${version?.version} (${version?.type})`);
+   * ```
+   */
+  rollback(bundleName: string): Promise<BundleSourceVersion | null>
+  /**
+   * Launches a bundle, to be called once when the app starts using it.
+   *
+   * When `confirmationLaunches` is set, counts a launch of the current version
+   * if it is pending confirmation, and rolls it back if it is still pending
+   * after that many launches. Only the first call for a bundle counts a launch.
+   *
+   * @param {string} bundleName - Name of the bundle
+   * @returns {Promise<BundleSourceVersion | null>} Version in use after the launch
+   *
+   * @example
+   * ```typescript
+   * const version = await source.launch("app");
+   * ```
+   */
+  launch(bundleName: string): Promise<BundleSourceVersion | null>
+  /**
+   * Confirms that the current version of a bundle is healthy.
+   *
+   * When `confirmationLaunches` is set, a newly activated version which isn't
+   * marked healthy within that many launches is rolled back automatically.
+   *
+   * @param {string} bundleName - Name of the bundle
+   * @returns {Promise<boolean>} Whether the version was pending confirmation
+   *
+   * @example
+   * ```typescript
+   * // once the frontend has booted successfully
+   * await source.markHealthy("app");
+   * ```
+   */
+  markHealthy(bundleName: string): Promise<boolean>
   /**
    * Gets the file path for a bundle.
    *
@@ -436,6 +484,28 @@ export declare class BundleSource {
    * ```
    */
   writeRemoteBundle(bundleName: string, version: string, bundle: Bundle, metadata: BundleManifestMetadata): Promise<void>
+  /**
+   * Removes old remote bundle versions and orphaned bundle files.
+   *
+   * Removes versions not kept by the retention policy along with their files,
+   * and `.wvb` files in the remote directory which aren't in the manifest.
+   * The current version of a bundle, and the previous version a rollback
+   * restores, are never removed.
+   *
+   * @returns {Promise<PruneReport>} Removed versions and files
+   *
+   * @example
+   * ```typescript
+   * const source = new BundleSource({
+   *   builtinDir: "./builtin",
+   *   remoteDir: "./remote",
+   *   retention: { previousVersions: 1 }
+   * });
+   * const report = await source.prune();
+   * console.log(`Freed ${report.freedBytes} bytes`);
+   * ```
+   */
+  prune(): Promise<PruneReport>
 }
 
 /**
@@ -527,6 +597,14 @@ export declare class Index {
    * ```
    */
   containsPath(path: string): boolean
+  /**
+   * Returns the size of stored data shared by more than one entry.
+   *
+   * This is how much building the bundle with `dedup` saved.
+   *
+   * @returns {bigint} Saved size in bytes
+   */
+  deduplicatedSize(): bigint
 }
 
 /**
@@ -851,11 +929,15 @@ export interface BuildIndexOptions {
  * @property {BuildHeaderOptions} [header] - Header generation options
  * @property {BuildIndexOptions} [index] - Index generation options
  * @property {number} [dataChecksumSeed] - Seed for data checksums (for testing)
+ * @property {boolean} [dedup] - Store the data of identical files once
+ * @property {number} [threads] - Threads compressing files, or 0 for the available parallelism
  */
 export interface BuildOptions {
   header?: BuildHeaderOptions
   index?: BuildIndexOptions
   dataChecksumSeed?: number
+  dedup?: boolean
+  threads?: number
 }
 
 /**
@@ -877,11 +959,17 @@ export interface BundleManifestData {
  * Contains all versions and the current active version.
  *
  * @property {Record<string, BundleManifestMetadata>} versions - Available versions
- * @property {string} currentVersion - Currently active version
+ * @property {string} currentVersion - Currently active version, empty when the builtin version is used
+ * @property {string} [previousVersion] - Version restored by a rollback
+ * @property {BundleManifestPending} [pending] - Set while the current version is pending confirmation
+ * @property {string[]} [failedVersions] - Versions rolled back from, not activated again when written
  */
 export interface BundleManifestEntry {
   versions: Record<string, BundleManifestMetadata>
   currentVersion: string
+  previousVersion?: string
+  pending?: BundleManifestPending
+  failedVersions?: Array<string>
 }
 
 /**
@@ -901,6 +989,15 @@ export interface BundleManifestMetadata {
   lastModified?: string
 }
 
+/**
+ * Confirmation state of a newly activated version.
+ *
+ * @property {number} launches - Number of launches since the version was activated
+ */
+export interface BundleManifestPending {
+  launches: number
+}
+
 /**
  * Manifest format version.
  *
@@ -917,6 +1014,8 @@ export declare enum BundleManifestVersion {
  * @property {string} remoteDir - Directory containing remote bundles
  * @property {string} [builtinManifestFilepath] - Custom manifest path for builtin
  * @property {string} [remoteManifestFilepath] - Custom manifest path for remote
+ * @property {RetentionPolicy} [retention] - Retention policy of remote bundle versions
+ * @property {number} [confirmationLaunches] - Launches within which a newly activated version must be marked healthy
  *
  * @example
  * ```typescript
@@ -932,6 +1031,8 @@ export interface BundleSourceConfig {
   remoteDir: string
   builtinManifestFilepath?: string
   remoteManifestFilepath?: string
+  retention?: RetentionPolicy
+  confirmationLaunches?: number
 }
 
 /**
@@ -1115,6 +1216,30 @@ export interface ListRemoteBundleInfo {
   version: string
 }
 
+/**
+ * A bundle version removed by `prune()`.
+ *
+ * @property {string} name - Bundle name
+ * @property {string} version - Version string
+ */
+export interface PrunedVersion {
+  name: string
+  version: string
+}
+
+/**
+ * Result of `prune()`.
+ *
+ * @property {PrunedVersion[]} versions - Versions removed from the manifest along with their files
+ * @property {string[]} orphans - Bundle files removed because they were not in the manifest
+ * @property {number} freedBytes - Total size of the removed files in bytes
+ */
+export interface PruneReport {
+  versions: Array<PrunedVersion>
+  orphans: Array<string>
+  freedBytes: number
+}
+
 /**
  * Reads a bundle from a file asynchronously.
  *
@@ -1202,6 +1327,20 @@ export interface RemoteOptions {
   onDownload?: (data: RemoteOnDownloadData) => void
 }
 
+/**
+ * Retention policy of remote bundle versions, applied by `prune()`.
+ *
+ * The current version of a bundle is always kept. Other versions are ranked from
+ * the newest by semver, and kept as long as they fit the policy.
+ *
+ * @property {number} [previousVersions] - Maximum number of versions kept besides the current version, including the previous version which is always kept
+ * @property {number} [maxBytes] - Maximum total size of the files of a bundle in bytes
+ */
+export interface RetentionPolicy {
+  previousVersions?: number
+  maxBytes?: number
+}
+
 /**
  * Digital signature algorithm for bundle verification.
  *
@@ -1291,6 +1430,7 @@ export interface SignatureVerifyingKeyOptions {
  * Configuration options for the updater.
  *
  * @property {string} [channel] - Update channel (e.g., "stable", "beta")
+ * @property {boolean} [patch] - Download updates as patches from the installed version when possible
  * @property {IntegrityPolicy} [integrityPolicy] - Policy for integrity verification
  * @property {Function} [integrityChecker] - Custom integrity verification function
  * @property {SignatureVerifierOptions | Function} [signatureVerifier] - Signature verification config or custom function
@@ -1327,6 +1467,7 @@ export interface SignatureVerifyingKeyOptions {
  */
 export interface UpdaterOptions {
   channel?: string
+  patch?: boolean
   integrityPolicy?: IntegrityPolicy
   integrityChecker?: (data: Uint8Array, integrity: string) => Promise<boolean>
   signatureVerifier?: SignatureVerifierOptions | ((data: Uint8Array, signature: string) => Promise<boolean>)
@@ -1,5 +1,4 @@
 use crate::http::HttpHeaders;
-use crate::mime::MimeType;
 use crate::version::Version;
 use napi::bindgen_prelude::*;
 use napi_derive::napi;
@@ -157,10 +156,20 @@ impl Index {
   pub fn contains_path(&self, path: String) -> bool {
     self.inner.contains_path(&path)
   }
+
+  /// Returns the size of stored data shared by more than one entry.
+  ///
+  /// This is how much building the bundle with `dedup` saved.
+  ///
+  /// @returns {bigint} Saved size in bytes
+  #[napi]
+  pub fn deduplicated_size(&self) -> u64 {
+    self.inner.deduplicated_size()
+  }
 }
 
 pub(crate) enum BundleDescriptorInner {
-  Owned(wvb::BundleDescriptor),
+  Owned(Box<wvb::BundleDescriptor>),
   Bundle(SharedReference<Bundle, &'static wvb::BundleDescriptor>),
 }
 
@@ -373,11 +382,15 @@ pub fn write_bundle_into_buffer(bundle: &Bundle) -> crate::Result<Buffer> {
 /// @property {BuildHeaderOptions} [header] - Header generation options
 /// @property {BuildIndexOptions} [index] - Index generation options
 /// @property {number} [dataChecksumSeed] - Seed for data checksums (for testing)
+/// @property {boolean} [dedup] - Store the data of identical files once
+/// @property {number} [threads] - Threads compressing files, or 0 for the available parallelism
 #[napi(object)]
 pub struct BuildOptions {
   pub header: Option<BuildHeaderOptions>,
   pub index: Option<BuildIndexOptions>,
   pub data_checksum_seed: Option<u32>,
+  pub dedup: Option<bool>,
+  pub threads: Option<u32>,
 }
 
 impl From<BuildOptions> for BundleBuilderOptions {
@@ -392,6 +405,12 @@ impl From<BuildOptions> for BundleBuilderOptions {
     if let Some(seed) = value.data_checksum_seed {
       options.data_checksum_seed(seed);
     }
+    if let Some(dedup) = value.dedup {
+      options.dedup(dedup);
+    }
+    if let Some(threads) = value.threads {
+      options.threads(threads as usize);
+    }
     options
   }
 }
@@ -468,10 +487,10 @@ impl BundleBuilder {
   /// ```
   #[napi(constructor)]
   pub fn new(version: Option<Version>) -> BundleBuilder {
-    Self {
-      version: version.unwrap_or(Version::V1),
-      inner: wvb::BundleBuilder::new(),
-    }
+    let version = version.unwrap_or(Version::V1);
+    let mut inner = wvb::BundleBuilder::new();
+    inner.set_version(version.into());
+    Self { version, inner }
   }
 
   /// Gets the bundle format version.
@@ -534,8 +553,10 @@ impl BundleBuilder {
       None
     };
     let content_type = content_type.unwrap_or_else(|| {
-      let mime = MimeType::parse_with_fallback(data.as_ref(), &path, MimeType::OctetStream);
-      mime.to_string()
+      self
+        .inner
+        .options()
+        .resolve_content_type(&path, data.as_ref())
     });
     Ok(
       self
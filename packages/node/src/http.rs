@@ -1,6 +1,5 @@
 use napi::bindgen_prelude::Buffer;
 use napi_derive::napi;
-use std::borrow::Cow;
 use std::collections::HashMap;
 use std::ops::Deref;
 use wvb::http;
@@ -84,8 +83,8 @@ pub struct HttpResponse {
   pub body: Buffer,
 }
 
-impl From<http::Response<Cow<'static, [u8]>>> for HttpResponse {
-  fn from(value: http::Response<Cow<'static, [u8]>>) -> Self {
+impl From<http::Response<wvb::bytes::Bytes>> for HttpResponse {
+  fn from(value: http::Response<wvb::bytes::Bytes>) -> Self {
     let status = value.status().as_u16();
     let headers = HttpHeaders::from(value.headers()).0;
     let body = Buffer::from(value.body().as_ref());
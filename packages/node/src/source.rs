@@ -99,11 +99,25 @@ pub enum BundleManifestVersion {
 /// Contains all versions and the current active version.
 ///
 /// @property {Record<string, BundleManifestMetadata>} versions - Available versions
-/// @property {string} currentVersion - Currently active version
+/// @property {string} currentVersion - Currently active version, empty when the builtin version is used
+/// @property {string} [previousVersion] - Version restored by a rollback
+/// @property {BundleManifestPending} [pending] - Set while the current version is pending confirmation
+/// @property {string[]} [failedVersions] - Versions rolled back from, not activated again when written
 #[napi(object)]
 pub struct BundleManifestEntry {
   pub versions: HashMap<String, BundleManifestMetadata>,
   pub current_version: String,
+  pub previous_version: Option<String>,
+  pub pending: Option<BundleManifestPending>,
+  pub failed_versions: Option<Vec<String>>,
+}
+
+/// Confirmation state of a newly activated version.
+///
+/// @property {number} launches - Number of launches since the version was activated
+#[napi(object)]
+pub struct BundleManifestPending {
+  pub launches: u32,
 }
 
 /// Complete manifest data structure.
@@ -148,12 +162,80 @@ impl From<source::ListBundleItem> for ListBundleItem {
   }
 }
 
+/// Retention policy of remote bundle versions, applied by `prune()`.
+///
+/// The current version of a bundle is always kept. Other versions are ranked from
+/// the newest by semver, and kept as long as they fit the policy.
+///
+/// @property {number} [previousVersions] - Maximum number of versions kept besides the current version, including the previous version which is always kept
+/// @property {number} [maxBytes] - Maximum total size of the files of a bundle in bytes
+#[napi(object)]
+pub struct RetentionPolicy {
+  pub previous_versions: Option<u32>,
+  pub max_bytes: Option<i64>,
+}
+
+impl From<RetentionPolicy> for source::RetentionPolicy {
+  fn from(value: RetentionPolicy) -> Self {
+    let mut policy = source::RetentionPolicy::new();
+    if let Some(count) = value.previous_versions {
+      policy = policy.previous_versions(count as usize);
+    }
+    if let Some(bytes) = value.max_bytes {
+      policy = policy.max_bytes(bytes.max(0) as u64);
+    }
+    policy
+  }
+}
+
+/// A bundle version removed by `prune()`.
+///
+/// @property {string} name - Bundle name
+/// @property {string} version - Version string
+#[napi(object)]
+pub struct PrunedVersion {
+  pub name: String,
+  pub version: String,
+}
+
+/// Result of `prune()`.
+///
+/// @property {PrunedVersion[]} versions - Versions removed from the manifest along with their files
+/// @property {string[]} orphans - Bundle files removed because they were not in the manifest
+/// @property {number} freedBytes - Total size of the removed files in bytes
+#[napi(object)]
+pub struct PruneReport {
+  pub versions: Vec<PrunedVersion>,
+  pub orphans: Vec<String>,
+  pub freed_bytes: i64,
+}
+
+impl From<source::PruneReport> for PruneReport {
+  fn from(value: source::PruneReport) -> Self {
+    Self {
+      versions: value
+        .versions
+        .into_iter()
+        .map(|(name, version)| PrunedVersion { name, version })
+        .collect(),
+      orphans: value
+        .orphans
+        .into_iter()
+        .map(|x| x.to_string_lossy().to_string())
+        .collect(),
+      freed_bytes: value.freed_bytes as i64,
+    }
+  }
+}
+
 /// Configuration for creating a bundle source.
 ///
 /// @property {string} builtinDir - Directory containing builtin bundles
 /// @property {string} remoteDir - Directory containing remote bundles
 /// @property {string} [builtinManifestFilepath] - Custom manifest path for builtin
 /// @property {string} [remoteManifestFilepath] - Custom manifest path for remote
+/// @property {RetentionPolicy} [retention] - Retention policy of remote bundle versions
+/// @property {number} [confirmationLaunches] - Launches within which a newly activated version must be marked healthy
 ///
 /// @example
 /// ```typescript
@@ -169,6 +251,8 @@ pub struct BundleSourceConfig {
   pub remote_dir: String,
   pub builtin_manifest_filepath: Option<String>,
   pub remote_manifest_filepath: Option<String>,
+  pub retention: Option<RetentionPolicy>,
+  pub confirmation_launches: Option<u32>,
 }
 
 /// Bundle source for managing multiple bundle versions.
@@ -225,6 +309,12 @@ impl BundleSource {
     if let Some(remote_manifest) = config.remote_manifest_filepath {
       builder = builder.remote_manifest_filepath(remote_manifest);
     }
+    if let Some(retention) = config.retention {
+      builder = builder.retention(retention.into());
+    }
+    if let Some(launches) = config.confirmation_launches {
+      builder = builder.confirmation_launches(launches);
+    }
     let source = builder.build();
     BundleSource {
       inner: Arc::new(source),
@@ -297,6 +387,63 @@ impl BundleSource {
     Ok(())
   }
 
+  /// Rolls a bundle back to its previous version.
+  ///
+  /// Switches from the current remote version to the version which was current
+  /// before it, or to the builtin version if there is no such remote version.
+  ///
+  /// @param {string} bundleName - Name of the bundle
+  /// @returns {Promise<BundleSourceVersion | null>} Version in use after the rollback
+  ///
+  /// @example
+  /// ```typescript
+  /// const version = await source.rollback("app");
+  /// console.log(`Rolled back to ${version?.version} (${version?.type})`);
+  /// ```
+  #[napi]
+  pub async fn rollback(&self, bundle_name: String) -> crate::Result<Option<BundleSourceVersion>> {
+    let version = self.inner.rollback(&bundle_name).await?;
+    Ok(version.map(Into::into))
+  }
+
+  /// Launches a bundle, to be called once when the app starts using it.
+  ///
+  /// When `confirmationLaunches` is set, counts a launch of the current version
+  /// if it is pending confirmation, and rolls it back if it is still pending
+  /// after that many launches. Only the first call for a bundle counts a launch.
+  ///
+  /// @param {string} bundleName - Name of the bundle
+  /// @returns {Promise<BundleSourceVersion | null>} Version in use after the launch
+  ///
+  /// @example
+  /// ```typescript
+  /// const version = await source.launch("app");
+  /// ```
+  #[napi]
+  pub async fn launch(&self, bundle_name: String) -> crate::Result<Option<BundleSourceVersion>> {
+    let version = self.inner.launch(&bundle_name).await?;
+    Ok(version.map(Into::into))
+  }
+
+  /// Confirms that the current version of a bundle is healthy.
+  ///
+  /// When `confirmationLaunches` is set, a newly activated version which isn't
+  /// marked healthy within that many launches is rolled back automatically.
+  ///
+  /// @param {string} bundleName - Name of the bundle
+  /// @returns {Promise<boolean>} Whether the version was pending confirmation
+  ///
+  /// @example
+  /// ```typescript
+  /// // once the frontend has booted successfully
+  /// await source.markHealthy("app");
+  /// ```
+  #[napi]
+  pub async fn mark_healthy(&self, bundle_name: String) -> crate::Result<bool> {
+    let marked = self.inner.mark_healthy(&bundle_name).await?;
+    Ok(marked)
+  }
+
   /// Gets the file path for a bundle.
   ///
   /// Returns the path to the `.wvb` file for the current version,
@@ -351,7 +498,7 @@ impl BundleSource {
   pub async fn fetch_descriptor(&self, bundle_name: String) -> crate::Result<BundleDescriptor> {
     let inner = self.inner.fetch_descriptor(&bundle_name).await?;
     Ok(BundleDescriptor {
-      inner: BundleDescriptorInner::Owned(inner),
+      inner: BundleDescriptorInner::Owned(Box::new(inner)),
     })
   }
 
@@ -386,4 +533,29 @@ impl BundleSource {
       .await?;
     Ok(())
   }
+
+  /// Removes old remote bundle versions and orphaned bundle files.
+  ///
+  /// Removes versions not kept by the retention policy along with their files,
+  /// and `.wvb` files in the remote directory which aren't in the manifest.
+  /// The current version of a bundle, and the previous version a rollback
+  /// restores, are never removed.
+  ///
+  /// @returns {Promise<PruneReport>} Removed versions and files
+  ///
+  /// @example
+  /// ```typescript
+  /// const source = new BundleSource({
+  ///   builtinDir: "./builtin",
+  ///   remoteDir: "./remote",
+  ///   retention: { previousVersions: 1 }
+  /// });
+  /// const report = await source.prune();
+  /// console.log(`Freed ${report.freedBytes} bytes`);
+  /// ```
+  #[napi]
+  pub async fn prune(&self) -> crate::Result<PruneReport> {
+    let report = self.inner.prune().await?;
+    Ok(report.into())
+  }
 }
@@ -75,6 +75,7 @@ pub(crate) type UpdateIntegrityChecker = JsCallback<FnArgs<(Buffer, String)>, Pr
 /// Configuration options for the updater.
 ///
 /// @property {string} [channel] - Update channel (e.g., "stable", "beta")
+/// @property {boolean} [patch] - Download updates as patches from the installed version when possible
 /// @property {IntegrityPolicy} [integrityPolicy] - Policy for integrity verification
 /// @property {Function} [integrityChecker] - Custom integrity verification function
 /// @property {SignatureVerifierOptions | Function} [signatureVerifier] - Signature verification config or custom function
@@ -111,6 +112,7 @@ pub(crate) type UpdateIntegrityChecker = JsCallback<FnArgs<(Buffer, String)>, Pr
 #[napi(object, object_to_js = false)]
 pub struct UpdaterOptions {
   pub channel: Option<String>,
+  pub patch: Option<bool>,
   pub integrity_policy: Option<IntegrityPolicy>,
   #[napi(ts_type = "(data: Uint8Array, integrity: string) => Promise<boolean>")]
   pub integrity_checker: Option<UpdateIntegrityChecker>,
@@ -126,6 +128,9 @@ impl From<UpdaterOptions> for updater::UpdaterConfig {
     if let Some(channel) = value.channel {
       config = config.channel(channel);
     }
+    if let Some(patch) = value.patch {
+      config = config.patch(patch);
+    }
     if let Some(policy) = value.integrity_policy {
       config = config.integrity_policy(policy.into());
     }
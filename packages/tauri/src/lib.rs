@@ -55,7 +55,8 @@ pub fn init<R: Runtime>(config: Config<R>) -> TauriPlugin<R> {
           .clone();
         tauri::async_runtime::spawn(async move {
           match protocol.handle(req).await {
-            Ok(resp) => res.respond(resp),
+            // tauri takes bodies convertible to `Cow<'static, [u8]>`, which `Bytes` is not
+            Ok(resp) => res.respond(resp.map(Vec::from)),
             Err(e) => {
               let resp = http::Response::builder()
                 .status(http::StatusCode::INTERNAL_SERVER_ERROR)